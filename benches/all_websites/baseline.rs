@@ -0,0 +1,83 @@
+//! Baseline files: each website's median cycles per benchmark, saved from one
+//! run with `--save-baseline` and compared against by a later one with
+//! `--compare`, to fail CI when matching gets slower.
+//!
+//! ```json
+//! {"commit_hash": "...", "websites": {"example.com": {"before_preprocessing": 123456, "after_preprocessing": 98765}}}
+//! ```
+//!
+//! Cycles are TSC cycles, so baselines only compare with runs on the same
+//! machine.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::CommitHash;
+
+/// Median cycles by benchmark (`before_preprocessing`, `indexing`, ...)
+pub(crate) type WebsiteMedians = BTreeMap<String, u64>;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub(crate) struct BaselineJson {
+    pub(crate) commit_hash: Option<CommitHash>,
+    pub(crate) websites: BTreeMap<String, WebsiteMedians>,
+}
+
+impl BaselineJson {
+    pub(crate) fn read(path: &Path) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|err| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("Invalid baseline {}: {err}", path.display()))
+        })
+    }
+
+    pub(crate) fn write(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self).unwrap())
+    }
+}
+
+/// A benchmark whose median grew by more than the threshold.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Regression {
+    pub(crate) website: String,
+    pub(crate) benchmark: String,
+    pub(crate) baseline_cycles: u64,
+    pub(crate) current_cycles: u64,
+    /// `current / baseline - 1`
+    pub(crate) slowdown: f64,
+}
+
+/// The benchmarks in both `current` and `baseline` that got slower by more
+/// than `threshold`, a fraction (0.1 for 10%). Websites and benchmarks only
+/// in one of them are skipped.
+pub(crate) fn compare(current: &BaselineJson, baseline: &BaselineJson, threshold: f64) -> Vec<Regression> {
+    let mut regressions = Vec::new();
+    for (website, medians) in &current.websites {
+        let Some(baseline_medians) = baseline.websites.get(website) else {
+            continue;
+        };
+        for (benchmark, &current_cycles) in medians {
+            let Some(&baseline_cycles) = baseline_medians.get(benchmark) else {
+                continue;
+            };
+            if baseline_cycles == 0 {
+                continue;
+            }
+            let slowdown = current_cycles as f64 / baseline_cycles as f64 - 1.0;
+            if slowdown > threshold {
+                regressions.push(Regression {
+                    website: website.clone(),
+                    benchmark: benchmark.clone(),
+                    baseline_cycles,
+                    current_cycles,
+                    slowdown,
+                });
+            }
+        }
+    }
+    regressions
+}
@@ -0,0 +1,171 @@
+//! Benchmark configuration from the command line and environment.
+//!
+//! `cargo bench --bench all_websites -- [OPTIONS] [WEBSITE...]`, where options
+//! take the form `--name=value` and each falls back to an environment variable:
+//!
+//! - `--variants=before,preprocessing,after` (`MACH6_BENCH_VARIANTS`): which
+//!   parts of the report to benchmark. The report is only written when all of
+//!   them are. `construction` additionally benchmarks building the selector
+//!   map, and `arena` naive matching with and without the `arena` feature's
+//!   bump allocation; neither is in the report or benchmarked by default.
+//!   Nor is `per-selector`, which times naive matching of each selector on
+//!   its own and prints the slowest.
+//! - `--samples=N` (`MACH6_BENCH_SAMPLES`): samples per benchmark.
+//! - `--measurement-time=SECS` (`MACH6_BENCH_MEASUREMENT_TIME`): stop taking
+//!   samples once this much time has passed, even if fewer than `--samples`
+//!   were taken.
+//! - `--warm-up-time=SECS` (`MACH6_BENCH_WARM_UP_TIME`)
+//! - `--save-baseline=PATH` (`MACH6_BENCH_SAVE_BASELINE`): write each
+//!   website's median cycles per benchmark to this baseline file.
+//! - `--compare=PATH` (`MACH6_BENCH_COMPARE`): compare the medians with this
+//!   baseline file and exit with status 1 if any grew by more than
+//!   `--threshold`.
+//! - `--threshold=FRACTION` (`MACH6_BENCH_THRESHOLD`): the slowdown
+//!   `--compare` fails on, e.g. 0.1 (the default) for 10%.
+//! - `--slowest=N` (`MACH6_BENCH_SLOWEST`): how many selectors `per-selector`
+//!   prints per website, 10 by default.
+//!
+//! Websites can also be given comma-separated in `MACH6_BENCH_WEBSITES`.
+//! Unknown `--` arguments (like the `--bench` cargo passes) are ignored.
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Variant {
+    /// Matching with the website's own selectors
+    Before,
+    /// Indexing, :is() conversion and distribution
+    Preprocessing,
+    /// Matching with the preprocessed selectors
+    After,
+    /// Building the Stylist, its SelectorMap alone, and AncestorHashes alone
+    Construction,
+    /// Naive matching with results in the heap and in a bump arena
+    Arena,
+    /// Naive matching of each selector alone
+    PerSelector,
+}
+
+impl Variant {
+    /// The variants in the report
+    const REPORT: [Variant; 3] = [Variant::Before, Variant::Preprocessing, Variant::After];
+
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "before" => Ok(Variant::Before),
+            "preprocessing" => Ok(Variant::Preprocessing),
+            "after" => Ok(Variant::After),
+            "construction" => Ok(Variant::Construction),
+            "arena" => Ok(Variant::Arena),
+            "per-selector" => Ok(Variant::PerSelector),
+            _ => Err(format!(
+                "unknown variant {s:?}, expected before, preprocessing, after, construction, arena or per-selector"
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Sampling {
+    pub(crate) samples: u64,
+    pub(crate) measurement_time: Option<Duration>,
+    pub(crate) warm_up_time: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct BenchConfig {
+    /// Empty means every website
+    pub(crate) websites: Vec<String>,
+    pub(crate) variants: BTreeSet<Variant>,
+    pub(crate) sampling: Sampling,
+    pub(crate) save_baseline: Option<PathBuf>,
+    pub(crate) compare: Option<PathBuf>,
+    pub(crate) threshold: f64,
+    /// How many of the slowest selectors `Variant::PerSelector` prints
+    pub(crate) slowest: usize,
+}
+
+impl BenchConfig {
+    pub(crate) fn from_args_and_env() -> Result<Self, String> {
+        let mut websites = Vec::new();
+        let mut options = Vec::new();
+        for arg in std::env::args().skip(1) { // the executable name
+            match arg.strip_prefix("--") {
+                Some(option) => {
+                    if let Some((name, value)) = option.split_once('=') {
+                        options.push((name.to_owned(), value.to_owned()));
+                    }
+                }
+                None => websites.push(arg),
+            }
+        }
+        let option = |name: &str, env: &str| {
+            options
+                .iter()
+                .rev()
+                .find(|(n, _)| n == name)
+                .map(|(_, value)| value.clone())
+                .or_else(|| std::env::var(env).ok())
+        };
+        if websites.is_empty() && let Ok(env) = std::env::var("MACH6_BENCH_WEBSITES") {
+            websites = env.split(',').filter(|w| !w.is_empty()).map(str::to_owned).collect();
+        }
+        let variants = match option("variants", "MACH6_BENCH_VARIANTS") {
+            Some(variants) => variants.split(',').map(Variant::parse).collect::<Result<_, _>>()?,
+            None => Variant::REPORT.into_iter().collect(),
+        };
+        let samples = match option("samples", "MACH6_BENCH_SAMPLES") {
+            Some(samples) => match samples.parse() {
+                Ok(0) | Err(_) => return Err(format!("invalid sample count {samples:?}")),
+                Ok(samples) => samples,
+            },
+            None => DEFAULT_SAMPLES,
+        };
+        let measurement_time = option("measurement-time", "MACH6_BENCH_MEASUREMENT_TIME")
+            .map(|secs| parse_seconds(&secs))
+            .transpose()?;
+        let warm_up_time = option("warm-up-time", "MACH6_BENCH_WARM_UP_TIME")
+            .map(|secs| parse_seconds(&secs))
+            .transpose()?
+            .unwrap_or(DEFAULT_WARM_UP_TIME);
+        let threshold = match option("threshold", "MACH6_BENCH_THRESHOLD") {
+            Some(threshold) => match threshold.parse::<f64>() {
+                Ok(threshold) if threshold.is_finite() && threshold >= 0.0 => threshold,
+                _ => return Err(format!("invalid threshold {threshold:?}")),
+            },
+            None => DEFAULT_THRESHOLD,
+        };
+        let slowest = match option("slowest", "MACH6_BENCH_SLOWEST") {
+            Some(slowest) => slowest.parse().map_err(|_| format!("invalid selector count {slowest:?}"))?,
+            None => DEFAULT_SLOWEST,
+        };
+        Ok(Self {
+            websites,
+            variants,
+            sampling: Sampling { samples, measurement_time, warm_up_time },
+            save_baseline: option("save-baseline", "MACH6_BENCH_SAVE_BASELINE").map(PathBuf::from),
+            compare: option("compare", "MACH6_BENCH_COMPARE").map(PathBuf::from),
+            threshold,
+            slowest,
+        })
+    }
+
+    /// Whether every variant in the report is benchmarked, which it needs.
+    pub(crate) fn is_complete(&self) -> bool {
+        Variant::REPORT.iter().all(|variant| self.variants.contains(variant))
+    }
+}
+
+const DEFAULT_SAMPLES: u64 = 25;
+const DEFAULT_WARM_UP_TIME: Duration = Duration::from_millis(500);
+const DEFAULT_THRESHOLD: f64 = 0.1;
+const DEFAULT_SLOWEST: usize = 10;
+
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    s.parse::<f64>()
+        .ok()
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .ok_or_else(|| format!("invalid number of seconds {s:?}"))
+}
@@ -209,11 +209,12 @@ mod overall_summary {
 }
 
 mod selector_summary {
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap};
 
+    use mach_6::analysis::complexity::SelectorKind;
     use serde::{Deserialize, Serialize};
 
-    use crate::WebsiteResult;
+    use crate::{MatchBenchResult, WebsiteResult};
 
     use super::{SelectorSlowRejectSamples, SelectorString};
 
@@ -226,8 +227,8 @@ mod selector_summary {
     impl From<&WebsiteResult> for SelectorsSummaryJson {
         fn from(value: &WebsiteResult) -> Self {
             Self {
-                before_preprocessing: SelectorStatsJson::from(value.before_preprocessing.selector_slow_reject_times.as_slice()),
-                after_preprocessing: SelectorStatsJson::from(value.after_preprocessing.selector_slow_reject_times.as_slice())
+                before_preprocessing: SelectorStatsJson::from(&value.before_preprocessing),
+                after_preprocessing: SelectorStatsJson::from(&value.after_preprocessing)
             }
         }
     }
@@ -236,19 +237,41 @@ mod selector_summary {
     pub(crate) struct SelectorStatsJson {
         pub(crate) means_cycles: HashMap<SelectorString, u64>,
         pub(crate) stddevs_cycles: HashMap<SelectorString, u64>,
+        /// Absent from reports written before it was added
+        #[serde(default)]
+        pub(crate) kinds: BTreeMap<SelectorKind, SelectorKindJson>,
+    }
+
+    #[derive(Clone, Serialize, Deserialize)]
+    pub(crate) struct SelectorKindJson {
+        pub(crate) attempts: usize,
+        pub(crate) mean_slow_reject_cycles: u64,
+        pub(crate) stddev_slow_reject_cycles: u64,
     }
 
-    impl From<&[SelectorSlowRejectSamples]> for SelectorStatsJson {
-        fn from(value: &[SelectorSlowRejectSamples]) -> Self {
+    impl From<&MatchBenchResult> for SelectorStatsJson {
+        fn from(value: &MatchBenchResult) -> Self {
+            let rows: &[SelectorSlowRejectSamples] = &value.selector_slow_reject_times;
             Self {
-                means_cycles: value
+                means_cycles: rows
                     .iter()
                     .map(|row| (row.selector.clone(), row.aggregate_durations.mean().cycles()))
                     .collect(),
-                stddevs_cycles: value
+                stddevs_cycles: rows
                     .iter()
                     .map(|row| (row.selector.clone(), row.aggregate_durations.stddev().cycles()))
                     .collect(),
+                kinds: value
+                    .kind_costs
+                    .iter()
+                    .map(|(kind, cost)| {
+                        (*kind, SelectorKindJson {
+                            attempts: cost.attempts,
+                            mean_slow_reject_cycles: cost.slow_reject_durations.mean().cycles(),
+                            stddev_slow_reject_cycles: cost.slow_reject_durations.stddev().cycles(),
+                        })
+                    })
+                    .collect(),
             }
         }
     }
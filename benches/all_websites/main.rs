@@ -1,12 +1,16 @@
 use log::{error, warn};
-use mach_6::{self, MatchingContext, Optimizations, get_all_documents_and_selectors, stylesheet_from_selectors};
-use mach_6::parse::{ParsedWebsite, get_document_and_selectors, websites_path};
+use mach_6::{self, MatchingContext, Optimizations, stylesheet_from_selectors};
+use mach_6::analysis::complexity::SelectorKind;
+use mach_6::parse::{ParsedWebsite, get_all_documents_and_selectors, get_document_and_selectors, websites_path};
 use mach_6::preprocessing::{self, concretize, distribute};
 use mach_6::structs::Selector;
 use scraper::Html;
-use selectors::matching::{CountingStats, SelectorStats, Statistics, TimingStats};
+use selectors::matching::{CountingStats, QuirksMode, SelectorStats, Statistics, TimingStats};
+use selectors::parser::{AncestorHashes, SelectorIter};
+use style::selector_map::{SelectorMap, SelectorMapEntry};
+use style::selector_parser::SelectorImpl;
 use smallvec::SmallVec;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::cmp::Reverse;
 use std::fs;
 use std::io;
@@ -15,15 +19,21 @@ use std::process::Command;
 use cssparser::ToCss as _;
 use time::OffsetDateTime;
 
+use crate::baseline::{BaselineJson, WebsiteMedians};
+use crate::config::{BenchConfig, Sampling, Variant};
 use crate::json::{ReportJson, ReportMetadataJson, ReportSourceJson, WebsiteJson};
 use crate::stats::Samples;
 
+mod baseline;
+mod config;
 mod json;
 mod stats;
 
 struct TimedResults<R> {
     total_duration: tsc_timer::Duration,
     samples: Samples<R>,
+    /// How long each sample took
+    sample_durations: Samples<tsc_timer::Duration>,
 }
 
 impl<R> TimedResults<R> {
@@ -31,6 +41,10 @@ impl<R> TimedResults<R> {
         assert!(self.samples.len() != 0, "tried to compute overall mean on result with no samples");
         self.total_duration / u64::try_from(self.samples.len()).unwrap()
     }
+
+    fn median(&self) -> tsc_timer::Duration {
+        self.sample_durations.median()
+    }
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
@@ -50,6 +64,16 @@ struct SelectorSlowRejectSamples {
     aggregate_durations: Samples<tsc_timer::Duration>,
 }
 
+/// What the selectors of one `SelectorKind` cost, over every element
+#[derive(Clone, Debug)]
+struct SelectorKindCost {
+    /// How many times a selector of the kind was matched against an
+    /// element, in one sample
+    attempts: usize,
+    /// The kind's aggregate slow-reject duration in each sample
+    slow_reject_durations: Samples<tsc_timer::Duration>,
+}
+
 /// Aggregated data for one matching variant in the website report.
 ///
 /// A "variant" here means one of the two selector-matching configurations we
@@ -60,6 +84,8 @@ struct SelectorSlowRejectSamples {
 struct MatchBenchResult {
     /// The total duration of the benched website
     total_duration: tsc_timer::Duration,
+    /// The median duration of a sample
+    median_duration: tsc_timer::Duration,
     /// Counting stats of one sample (should be the same accross all samples)
     counting_stats: CountingStats,
     /// Per-sample timing stats
@@ -67,6 +93,8 @@ struct MatchBenchResult {
     /// All slow-rejecting selectors and their aggregate slow-reject durations
     /// for each sample. Sorted in descending order by mean.
     selector_slow_reject_times: Vec<SelectorSlowRejectSamples>,
+    /// The same matches, by the kind of selector
+    kind_costs: BTreeMap<SelectorKind, SelectorKindCost>,
 }
 
 impl MatchBenchResult {
@@ -87,7 +115,11 @@ impl MatchBenchResult {
             .map(|stats| stats.times)
             .collect();
 
+        let sample_count = per_match_stats.samples.len();
         let mut map: HashMap<SelectorString, Vec<tsc_timer::Duration>> = HashMap::new();
+        let mut kinds: HashMap<SelectorString, SelectorKind> = HashMap::new();
+        let mut kind_attempts: BTreeMap<SelectorKind, usize> = BTreeMap::new();
+        let mut kind_durations: BTreeMap<SelectorKind, Vec<tsc_timer::Duration>> = BTreeMap::new();
         for (i, per_match_stats) in per_match_stats.samples.into_iter().enumerate() {
             for (selector, selector_stats) in per_match_stats {
                 let slow_reject_duration = match selector_stats {
@@ -96,7 +128,14 @@ impl MatchBenchResult {
                     SelectorStats::ScopeProximity(sp) =>
                         sp.time_slow_rejecting,
                 };
-                let samples = map.entry(SelectorString::from(selector)).or_default();
+                let selector_string = SelectorString::from(selector);
+                let kind = *kinds.entry(selector_string.clone()).or_insert_with(|| SelectorKind::of(selector));
+                if i == 0 {
+                    *kind_attempts.entry(kind).or_default() += 1;
+                }
+                kind_durations.entry(kind).or_insert_with(|| vec![Default::default(); sample_count])[i] +=
+                    slow_reject_duration;
+                let samples = map.entry(selector_string).or_default();
                 // If this is the first time we have touched the vector at this
                 // selector for this sample (samples.len() == i), push a new
                 // Duration onto the end. Otherwise, samples.len() == i + 1,
@@ -114,11 +153,20 @@ impl MatchBenchResult {
             SelectorSlowRejectSamples { selector, aggregate_durations: Samples::from_vec(durations) }
         ).collect();
         sorted.sort_unstable_by_key(|sel| Reverse(sel.aggregate_durations.mean()));
+        let kind_costs = kind_durations
+            .into_iter()
+            .map(|(kind, durations)| {
+                let attempts = kind_attempts.get(&kind).copied().unwrap_or_default();
+                (kind, SelectorKindCost { attempts, slow_reject_durations: Samples::from_vec(durations) })
+            })
+            .collect();
         MatchBenchResult {
             total_duration: stats.total_duration,
+            median_duration: stats.median(),
             counting_stats,
             timing_stats: Samples::from_vec(timing_stats),
             selector_slow_reject_times: sorted,
+            kind_costs,
         }
     }
 
@@ -168,8 +216,6 @@ struct WebsiteResult {
     after_preprocessing: MatchBenchResult,
 }
 
-const NUM_SAMPLES: u64 = 25;
-
 fn main() {
     env_logger::Builder::new().filter_level(log::LevelFilter::Warn).init();
     let git_metadata = match collect_report_git_metadata() {
@@ -180,31 +226,133 @@ fn main() {
         },
     };
     let time_start = OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc());
-    let website_filter: Vec<String> = std::env::args()
-        .skip(1) // the executable name
-        .filter(|a| !a.starts_with("--"))
-        .collect();
-    let websites = get_documents(website_filter.iter().map(String::as_str));
-    let results = websites.map(|w| {
-        let matching_context = w.get_matcher();
-        let before_preprocessing = bench_website(
-            &format!("{} before preprocessing", w.name),
-            w.document(),
-            &matching_context,
+    let config = match BenchConfig::from_args_and_env() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+    };
+    let websites = get_documents(config.websites.iter().map(String::as_str));
+    let mut baseline = BaselineJson {
+        commit_hash: git_metadata.as_ref().map(|git| git.commit_hash.clone()),
+        ..BaselineJson::default()
+    };
+    let mut websites_json = Vec::new();
+    for w in websites {
+        let name = w.name.clone();
+        let (medians, result) = bench_variants(w, &config);
+        baseline.websites.insert(name, medians);
+        websites_json.extend(result.as_ref().map(WebsiteJson::from));
+    }
+    let regressed = save_and_compare_baseline(&baseline, &config);
+
+    if !config.is_complete() {
+        eprintln!("Not writing a report because only some variants were benchmarked.");
+        if regressed {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let time_end = OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc());
+    let metadata = ReportMetadataJson::new(report_source_from_env(), git_metadata, time_start, time_end);
+
+    let report_json = ReportJson {
+        metadata,
+        websites: websites_json,
+    };
+
+    match fs::create_dir_all(&report_dir()) {
+        Ok(()) => (),
+        Err(e) => {
+            error!("Failed to create report directory: {e}");
+            return
+        },
+    };
+    let report_json_result = write_report_json(&report_json);
+    let html_result = copy_html_js();
+    match report_json_result.and(html_result)
+    {
+        Ok(()) => eprintln!("Wrote report to {}", report_dir().display()),
+        Err(e) => error!("{e}"),
+    };
+    if regressed {
+        std::process::exit(1);
+    }
+}
+
+/// Writes `current` to `--save-baseline` and compares it with `--compare`,
+/// printing the regressions. Returns whether there were any.
+fn save_and_compare_baseline(current: &BaselineJson, config: &BenchConfig) -> bool {
+    if let Some(path) = &config.save_baseline {
+        match current.write(path) {
+            Ok(()) => eprintln!("Wrote baseline to {}", path.display()),
+            Err(e) => error!("Failed to write baseline {}: {e}", path.display()),
+        }
+    }
+    let Some(path) = &config.compare else {
+        return false;
+    };
+    let baseline = match BaselineJson::read(path) {
+        Ok(baseline) => baseline,
+        Err(e) => {
+            error!("Failed to read baseline {}: {e}", path.display());
+            std::process::exit(2);
+        }
+    };
+    let regressions = baseline::compare(current, &baseline, config.threshold);
+    for regression in &regressions {
+        eprintln!(
+            "{} {}: {:.1}% slower ({} -> {} median cycles)",
+            regression.website,
+            regression.benchmark,
+            regression.slowdown * 100.0,
+            regression.baseline_cycles,
+            regression.current_cycles,
         );
-        let selectors = matching_context.get_selectors();
+    }
+    if regressions.is_empty() {
+        eprintln!("No benchmark regressed by more than {}% from {}", config.threshold * 100.0, path.display());
+    }
+    !regressions.is_empty()
+}
+
+/// Benchmarks the configured variants of a website, returning the median of
+/// each benchmark and the website's report entry. The entry is `None` unless
+/// every variant was benchmarked, since the report needs all of them.
+fn bench_variants(w: ParsedWebsite, config: &BenchConfig) -> (WebsiteMedians, Option<WebsiteResult>) {
+    let sampling = &config.sampling;
+    if config.variants.contains(&Variant::Construction) {
+        bench_construction(&w, sampling);
+    }
+    if config.variants.contains(&Variant::Arena) {
+        bench_arena(&w, sampling);
+    }
+    if config.variants.contains(&Variant::PerSelector) {
+        bench_per_selector(&w, sampling, config.slowest);
+    }
+    let matching_context = w.get_matcher();
+    let before_preprocessing = config.variants.contains(&Variant::Before).then(|| bench_website(
+        &format!("{} before preprocessing", w.name),
+        w.document(),
+        &matching_context,
+        sampling,
+    ));
+    let selectors = matching_context.get_selectors();
+    let preprocessing = config.variants.contains(&Variant::Preprocessing).then(|| {
         let substrings =
           concretize::substrings_from_selectors(selectors.iter());
         let indexing_results = bench_function(
           &format!("{} indexing", w.name),
           || { concretize::build_substr_selector_index(w.document(), substrings.clone()); },
-          NUM_SAMPLES,
+          sampling,
         );
         drop(substrings); // Why doesn't the compiler do this automatically? I don't know.
         let overall_is_conversion_results = bench_function(
           &format!("{} :is() conversion", w.name),
           || { concretize::convert_to_is_selectors(w.document(), &selectors); },
-          NUM_SAMPLES,
+          sampling,
         );
         let is = concretize::convert_to_is_selectors(w.document(), &selectors);
         let distribute = || {
@@ -216,8 +364,15 @@ fn main() {
         let distributing_results = bench_function(
             &format!("{} :is() distribution", w.name),
             distribute,
-            NUM_SAMPLES,
+            sampling,
         );
+        PreprocessingResult::new(
+            indexing_results,
+            overall_is_conversion_results,
+            distributing_results,
+        )
+    });
+    let after_preprocessing = config.variants.contains(&Variant::After).then(|| {
         let preprocessed_selectors = preprocessing::preprocess(w.document(), &selectors);
         let (preprocessed_stylesheet, preprocessed_lock) =
             stylesheet_from_selectors(preprocessed_selectors.iter());
@@ -225,55 +380,149 @@ fn main() {
             std::iter::once(&preprocessed_stylesheet),
             preprocessed_lock,
         );
-        let after_preprocessing = bench_website(
+        bench_website(
             &format!("{} after preprocessing", w.name),
             w.document(),
             &preprocessed_context,
-        );
-        let result = WebsiteResult {
+            sampling,
+        )
+    });
+    let mut medians = WebsiteMedians::new();
+    if let Some(before) = &before_preprocessing {
+        medians.insert("before_preprocessing".to_owned(), before.median_duration.cycles());
+    }
+    if let Some(preprocessing) = &preprocessing {
+        medians.insert("indexing".to_owned(), preprocessing.indexing.median().cycles());
+        medians.insert("is_conversion".to_owned(), preprocessing.overall_is_conversion.median().cycles());
+        medians.insert("distribution".to_owned(), preprocessing.distribution.median().cycles());
+    }
+    if let Some(after) = &after_preprocessing {
+        medians.insert("after_preprocessing".to_owned(), after.median_duration.cycles());
+    }
+    let result = match (before_preprocessing, preprocessing, after_preprocessing) {
+        (Some(before_preprocessing), Some(preprocessing), Some(after_preprocessing)) => Some(WebsiteResult {
             website: w.name,
             before_preprocessing,
-            preprocessing: PreprocessingResult::new(
-                indexing_results,
-                overall_is_conversion_results,
-                distributing_results,
-            ),
+            preprocessing,
             after_preprocessing,
-        };
-        result
-    });
-    let websites_json = results
-        .map(|res| WebsiteJson::from(&res))
-        .collect::<Vec<_>>();
+        }),
+        _ => None,
+    };
+    (medians, result)
+}
 
-    let time_end = OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc());
-    let metadata = ReportMetadataJson::new(report_source_from_env(), git_metadata, time_start, time_end);
+/// A selector on its own, to build a `SelectorMap` without the rest of a
+/// Stylist's `Rule`s.
+#[derive(Clone)]
+struct BareSelector(Selector);
 
-    let report_json = ReportJson {
-        metadata,
-        websites: websites_json,
-    };
+impl SelectorMapEntry for BareSelector {
+    fn selector(&self) -> SelectorIter<'_, SelectorImpl> {
+        self.0.iter()
+    }
+}
 
-    match fs::create_dir_all(&report_dir()) {
-        Ok(()) => (),
-        Err(e) => {
-            error!("Failed to create report directory: {e}");
-            return
+/// Benchmarks building a website's selector map three ways: the whole
+/// Stylist, as `get_matcher` does, which includes computing AncestorHashes
+/// for each rule; a `SelectorMap` of its selectors alone; and the
+/// AncestorHashes of its selectors alone. The build cost only matters
+/// relative to matching for small documents, so these are printed next to
+/// the matching times rather than added to the report.
+fn bench_construction(w: &ParsedWebsite, sampling: &Sampling) {
+    bench_function(
+        &format!("{} stylist construction", w.name),
+        || w.get_matcher(),
+        sampling,
+    );
+    let selectors = w.get_matcher().get_selectors();
+    bench_function(
+        &format!("{} selector map construction ({} selectors)", w.name, selectors.len()),
+        || {
+            let mut map = SelectorMap::<BareSelector>::new();
+            for selector in &selectors {
+                map.insert(BareSelector(selector.clone()), QuirksMode::NoQuirks)
+                    .expect("out of memory building the selector map");
+            }
+            map
         },
-    };
-    let report_json_result = write_report_json(&report_json);
-    let html_result = copy_html_js();
-    match report_json_result.and(html_result)
+        sampling,
+    );
+    bench_function(
+        &format!("{} ancestor hashes", w.name),
+        || {
+            selectors
+                .iter()
+                .map(|selector| AncestorHashes::new(selector, QuirksMode::NoQuirks))
+                .collect::<Vec<_>>()
+        },
+        sampling,
+    );
+}
+
+/// Benchmarks naive matching into heap-allocated results, then into a bump
+/// arena that is reset between samples. Both include dropping the results.
+fn bench_arena(w: &ParsedWebsite, sampling: &Sampling) {
+    let selectors = w.get_matcher().get_selectors();
+    bench_function(
+        &format!("{} naive matching", w.name),
+        || mach_6::match_selectors(w.document(), &selectors).0.len(),
+        sampling,
+    );
+    #[cfg(feature = "arena")]
     {
-        Ok(()) => eprintln!("Wrote report to {}", report_dir().display()),
-        Err(e) => error!("{e}"),
-    };
+        let bump = std::cell::RefCell::new(bumpalo::Bump::new());
+        bench_function(
+            &format!("{} naive matching in an arena", w.name),
+            || {
+                let mut bump = bump.borrow_mut();
+                bump.reset();
+                let matches = mach_6::match_selectors_in_arena(&bump, w.document(), &selectors);
+                matches.0.len()
+            },
+            sampling,
+        );
+    }
+    #[cfg(not(feature = "arena"))]
+    warn!("not benchmarking matching in an arena without the arena feature");
+}
+
+/// Times naive matching of each of a website's selectors on its own against
+/// the whole document, and prints the `slowest` by median, to find the
+/// pathological selectors that whole-document timings hide. Each selector is
+/// matched once to warm up and then `sampling.samples` times; the
+/// measurement and warm-up times are per website, not per selector, so
+/// they're ignored.
+fn bench_per_selector(w: &ParsedWebsite, sampling: &Sampling, slowest: usize) {
+    let selectors = w.get_matcher().get_selectors();
+    eprint!("Benchmarking {} per selector ({} selectors)...", w.name, selectors.len());
+    let mut medians: Vec<_> = selectors
+        .iter()
+        .map(|selector| {
+            let selector = std::slice::from_ref(selector);
+            mach_6::match_selectors(w.document(), selector);
+            let durations = (0..sampling.samples)
+                .map(|_| {
+                    let start = tsc_timer::Start::now();
+                    mach_6::match_selectors(w.document(), selector);
+                    start.elapsed()
+                })
+                .collect();
+            (Samples::from_vec(durations).median(), &selector[0])
+        })
+        .collect();
+    eprintln!("done.");
+    medians.sort_unstable_by_key(|(median, _)| Reverse(median.cycles()));
+    println!("Slowest selectors of {} (median per document):", w.name);
+    for (median, selector) in medians.iter().take(slowest) {
+        println!("  {:>16}  {}", format_duration(*median), selector.to_css_string());
+    }
 }
 
 fn bench_website(
     benchmark_name: &str,
     document: &Html,
     matching_context: &MatchingContext,
+    sampling: &Sampling,
 ) -> MatchBenchResult {
     let overall_stats = bench_function(
         benchmark_name,
@@ -287,7 +536,7 @@ fn bench_website(
                 );
             overall_stats
         },
-        NUM_SAMPLES,
+        sampling,
     );
     print!("Getting selector stats for {benchmark_name}...");
     let mut per_match_stats = SmallVec::new();
@@ -301,8 +550,21 @@ fn bench_website(
     let results = TimedResults {
         total_duration: tsc_timer::Duration::from_cycles(0), // whatever
         samples: Samples::from_vec(vec![per_match_stats]),
+        sample_durations: Samples::from_vec(Vec::new()),
     };
-    MatchBenchResult::new(overall_stats, results)
+    let result = MatchBenchResult::new(overall_stats, results);
+    println!("Matching cost by selector kind for {benchmark_name}:");
+    let mut kind_costs: Vec<_> = result.kind_costs.iter().collect();
+    kind_costs.sort_by_key(|(_, cost)| Reverse(cost.slow_reject_durations.mean()));
+    for (kind, cost) in kind_costs {
+        println!(
+            "  {:<16} {:>10} attempts  {:>16} slow-rejecting",
+            format!("{kind:?}"),
+            cost.attempts,
+            format_duration(cost.slow_reject_durations.mean()),
+        );
+    }
+    result
 }
 
 fn get_documents<'a>(website_filter: impl Iterator<Item = &'a str> + 'a) -> Box<dyn Iterator<Item = ParsedWebsite> + 'a> {
@@ -345,25 +607,34 @@ fn get_documents<'a>(website_filter: impl Iterator<Item = &'a str> + 'a) -> Box<
     }
 }
 
-fn bench_function<F, R>(name: &str, func: F, num_samples: u64) -> TimedResults<R>
+fn bench_function<F, R>(name: &str, func: F, sampling: &Sampling) -> TimedResults<R>
 where
     F: Fn() -> R,
 {
     const WARM_UP_ITERATIONS: usize = 100;
-    const WARM_UP_TIME: std::time::Duration = std::time::Duration::from_millis(500);
+    let Sampling { samples: num_samples, measurement_time, warm_up_time: warm_up } = *sampling;
     let mut samples_vec = Vec::with_capacity(num_samples as usize);
-    eprint!("Benchmarking {name}...warming up for {} seconds...", WARM_UP_TIME.as_secs_f32());
-    warm_up_time(&WARM_UP_TIME, &func);
+    let mut sample_durations = Vec::with_capacity(num_samples as usize);
+    eprint!("Benchmarking {name}...warming up for {} seconds...", warm_up.as_secs_f32());
+    warm_up_time(&warm_up, &func);
     eprint!("measuring {num_samples} samples...");
+    let wall_clock_start = std::time::Instant::now();
     let start = tsc_timer::Start::now();
     for _ in 0..num_samples {
+      let sample_start = tsc_timer::Start::now();
       samples_vec.push(func());
+      sample_durations.push(sample_start.elapsed());
+      if measurement_time.is_some_and(|time| wall_clock_start.elapsed() >= time) {
+          break;
+      }
     }
     let total_duration = start.elapsed();
+    let num_samples = samples_vec.len() as u64;
     eprintln!("done. ({}, {} total)", format_duration(total_duration / num_samples), format_duration(total_duration));
     TimedResults {
         total_duration,
         samples: Samples::from_vec(samples_vec),
+        sample_durations: Samples::from_vec(sample_durations),
     }
 }
 
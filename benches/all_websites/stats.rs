@@ -45,6 +45,16 @@ impl<T> Samples<T> {
 
 }
 
+impl Samples<tsc_timer::Duration> {
+    /// The lower middle sample, for an even number of them
+    pub fn median(&self) -> tsc_timer::Duration {
+        assert!(!self.0.is_empty(), "tried to compute median of empty sample set");
+        let mut cycles: Vec<u64> = self.0.iter().map(|duration| duration.cycles()).collect();
+        cycles.sort_unstable();
+        tsc_timer::Duration::from_cycles(cycles[(cycles.len() - 1) / 2])
+    }
+}
+
 impl<T> From<Vec<T>> for Samples<T> {
     fn from(value: Vec<T>) -> Samples<T> {
         Samples(value)
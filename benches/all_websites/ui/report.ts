@@ -100,6 +100,14 @@ interface SelectorsSummaryJson {
 interface SelectorStatsJson {
   means_cycles: Record<string, number>;
   stddevs_cycles: Record<string, number>;
+  // Absent from reports written before it was added
+  kinds?: Record<string, SelectorKindJson>;
+}
+
+interface SelectorKindJson {
+  attempts: number;
+  mean_slow_reject_cycles: number;
+  stddev_slow_reject_cycles: number;
 }
 
 interface SegmentInfo {
@@ -951,9 +959,22 @@ function aggregateSelectorStats(stats: SelectorStatsJson[]): SelectorStatsJson {
     stddevsCycles[key] = combineStddevs(stats.map((entry) => entry.stddevs_cycles[key] ?? 0));
   }
 
+  const kinds: Record<string, SelectorKindJson> = {};
+  for (const entry of stats) {
+    for (const [kind, cost] of Object.entries(entry.kinds ?? {})) {
+      const sum = kinds[kind];
+      kinds[kind] = sum === undefined ? { ...cost } : {
+        attempts: sum.attempts + cost.attempts,
+        mean_slow_reject_cycles: sum.mean_slow_reject_cycles + cost.mean_slow_reject_cycles,
+        stddev_slow_reject_cycles: combineStddevs([sum.stddev_slow_reject_cycles, cost.stddev_slow_reject_cycles])
+      };
+    }
+  }
+
   return {
     means_cycles: sumRecordValues(stats.map((entry) => entry.means_cycles)),
-    stddevs_cycles: stddevsCycles
+    stddevs_cycles: stddevsCycles,
+    kinds
   };
 }
 
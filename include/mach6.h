@@ -0,0 +1,35 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+/* The C interface of mach-6, built with `make capi`. See src/capi.rs. */
+#ifndef MACH6_H
+#define MACH6_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Matches a document against its stylesheets and returns the result as a
+ * NUL-terminated JSON string, {"matches": ..., "stats": ...} or
+ * {"error": ...}, to free with mach6_free_string.
+ *
+ * html is html_len bytes of UTF-8. hrefs[i] is the NUL-terminated href the
+ * document links stylesheets[i] by, which is stylesheet_lens[i] bytes of CSS.
+ * algorithm is a name as on the command line (e.g. "with-style-sharing"), or
+ * NULL for "naive". */
+char *mach6_match(const char *html, size_t html_len, const char *const *hrefs, const char *const *stylesheets,
+                  const size_t *stylesheet_lens, size_t stylesheet_count, const char *algorithm);
+
+/* Frees a string from mach6_match. Does nothing for NULL. */
+void mach6_free_string(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -3,11 +3,32 @@ use selectors::{
     bloom::BloomFilter,
     matching, Element, OpaqueElement,
 };
+use style::servo::selector_parser::NonTSPseudoClass;
 use style::values::AtomIdent;
+use stylo_dom::ElementState;
 
+use super::state::{is_link_element, ATTRIBUTE_DERIVED_STATES};
 use super::ElementRef;
 
-/// Note: will never match against non-tree-structure pseudo-classes.
+/// Whether `match_non_ts_pseudo_class` can match `pc` on any element. It
+/// can't for pseudo-classes that depend on user interaction or other browser
+/// state, like `:hover` or `:fullscreen`.
+pub fn can_match_non_ts_pseudo_class(pc: &NonTSPseudoClass) -> bool {
+    match *pc {
+        NonTSPseudoClass::AnyLink
+        | NonTSPseudoClass::Link
+        | NonTSPseudoClass::Visited
+        | NonTSPseudoClass::Lang(_)
+        | NonTSPseudoClass::ReadOnly => true,
+        _ => {
+            let flag = pc.state_flag();
+            !flag.is_empty() && ATTRIBUTE_DERIVED_STATES.contains(flag)
+        }
+    }
+}
+
+/// Note: only matches non-tree-structural pseudo-classes whose state can be
+/// derived from the document alone (see `ElementRef::attribute_state`).
 impl Element for ElementRef<'_> {
     type Impl = style::selector_parser::SelectorImpl;
 
@@ -31,16 +52,29 @@ impl Element for ElementRef<'_> {
         false
     }
 
-    fn is_part(&self, _name: &AtomIdent) -> bool {
-        false
+    fn is_part(&self, name: &AtomIdent) -> bool {
+        self.value()
+            .attr("part")
+            .is_some_and(|parts| parts.split_ascii_whitespace().any(|part| part == &*name.0))
     }
 
     fn is_same_type(&self, other: &Self) -> bool {
         self.value().name == other.value().name
     }
 
-    fn imported_part(&self, _: &AtomIdent) -> Option<AtomIdent> {
-        None
+    /// Maps an outer part name to the inner part name it was exported from,
+    /// using the `exportparts` attribute (`inner` or `inner:outer`, comma
+    /// separated). Note that `::part()` still only crosses shadow boundaries
+    /// we know about, and this DOM has no shadow roots yet.
+    fn imported_part(&self, name: &AtomIdent) -> Option<AtomIdent> {
+        let exportparts = self.value().attr("exportparts")?;
+        exportparts.split(',').find_map(|mapping| {
+            let (inner, outer) = match mapping.split_once(':') {
+                Some((inner, outer)) => (inner.trim(), outer.trim()),
+                None => (mapping.trim(), mapping.trim()),
+            };
+            (!inner.is_empty() && outer == &*name.0).then(|| AtomIdent::from(inner))
+        })
     }
 
     fn prev_sibling_element(&self) -> Option<Self> {
@@ -62,7 +96,10 @@ impl Element for ElementRef<'_> {
     }
 
     fn is_html_element_in_html_document(&self) -> bool {
-        // FIXME: Is there more to this?
+        // Documents are always parsed as HTML, so this only depends on the
+        // element: foreign content (inline `<svg>` and `<math>`) gets its own
+        // namespace from the parser, and with it case-sensitive type and
+        // attribute selectors.
         self.value().name.ns == ns!(html)
     }
 
@@ -74,6 +111,11 @@ impl Element for ElementRef<'_> {
         *self.value().name.ns == *namespace
     }
 
+    /// `operation` already has the case sensitivity to compare values with:
+    /// the `i` and `s` flags, or for attributes on HTML's list of
+    /// case-insensitive ones (`type`, `lang`, ...), case-insensitive on HTML
+    /// elements only, which is what `is_html_element_in_html_document` is
+    /// asked for.
     fn attr_matches(
         &self,
         ns: &NamespaceConstraint<&style::Namespace>,
@@ -89,10 +131,27 @@ impl Element for ElementRef<'_> {
 
     fn match_non_ts_pseudo_class(
         &self,
-        _pc: &style::servo::selector_parser::NonTSPseudoClass,
+        pc: &NonTSPseudoClass,
         _context: &mut matching::MatchingContext<'_, Self::Impl>,
     ) -> bool {
-        false
+        match *pc {
+            NonTSPseudoClass::AnyLink => self.is_link(),
+            NonTSPseudoClass::Link => {
+                self.is_link() && self.value().link_state().contains(ElementState::UNVISITED)
+            }
+            NonTSPseudoClass::Visited => {
+                self.is_link() && self.value().link_state().contains(ElementState::VISITED)
+            }
+            NonTSPseudoClass::Lang(ref lang) => self.matches_lang(lang),
+            // :read-only is the complement of :read-write.
+            NonTSPseudoClass::ReadOnly => !self.attribute_state().contains(pc.state_flag()),
+            _ => {
+                let flag = pc.state_flag();
+                !flag.is_empty()
+                    && ATTRIBUTE_DERIVED_STATES.contains(flag)
+                    && self.attribute_state().contains(flag)
+            }
+        }
     }
 
     fn match_pseudo_element(
@@ -104,11 +163,21 @@ impl Element for ElementRef<'_> {
     }
 
     fn is_link(&self) -> bool {
-        self.value().name() == "link"
+        let name = &self.value().name;
+        if name.ns == ns!(svg) {
+            // SVG 2 links take `href`, older ones `xlink:href`
+            return *name.local == *"a"
+                && self.value().attrs_atom().any(|(attr, _)| {
+                    *attr.local == *"href" && (attr.ns == ns!() || attr.ns == ns!(xlink))
+                });
+        }
+        name.ns == ns!(html)
+            && is_link_element(self.value().name())
+            && self.value().attr("href").is_some()
     }
 
     fn is_html_slot_element(&self) -> bool {
-        true
+        self.value().name.ns == ns!(html) && self.value().name() == "slot"
     }
 
     fn has_id(&self, id: &AtomIdent, case_sensitivity: CaseSensitivity) -> bool {
@@ -137,20 +206,53 @@ impl Element for ElementRef<'_> {
             .is_some_and(|parent| parent.value().is_document())
     }
 
-    fn apply_selector_flags(&self, _flags: matching::ElementSelectorFlags) {}
+    fn apply_selector_flags(&self, flags: matching::ElementSelectorFlags) {
+        let self_flags = flags.for_self();
+        if !self_flags.is_empty() {
+            self.value().insert_selector_flags(self_flags);
+        }
+        let parent_flags = flags.for_parent();
+        if !parent_flags.is_empty() {
+            if let Some(parent) = self.parent_element() {
+                parent.value().insert_selector_flags(parent_flags);
+            }
+        }
+    }
 
-    fn add_element_unique_hashes(&self, _filter: &mut BloomFilter) -> bool {
-        // FIXME: Do we want to add `self.node.id()` here?
-        false
+    fn add_element_unique_hashes(&self, filter: &mut BloomFilter) -> bool {
+        // Same hashes as Servo's `each_relevant_element_hash`.
+        let element = self.value();
+        filter.insert_hash(element.name.local.get_hash());
+        filter.insert_hash(element.name.ns.get_hash());
+        if let Some(id) = element.id_atom() {
+            filter.insert_hash(id.get_hash());
+        }
+        for class in element.classes_atom() {
+            filter.insert_hash(class.get_hash());
+        }
+        for (attr, _) in element.attrs_atom() {
+            if !is_attr_name_excluded_from_filter(&attr.local) {
+                filter.insert_hash(attr.local.get_hash());
+            }
+        }
+        true
     }
 }
 
+/// `id` and `class` are already hashed by value, and `style` is too common to
+/// be worth a slot in the filter.
+fn is_attr_name_excluded_from_filter(name: &web_atoms::LocalName) -> bool {
+    *name == local_name!("class") || *name == local_name!("id") || *name == local_name!("style")
+}
+
 #[cfg(test)]
 mod tests {
     use crate::html::Html;
     use crate::selector::Selector;
     use style::values::AtomIdent;
     use selectors::attr::CaseSensitivity;
+    use selectors::bloom::BloomFilter;
+    use selectors::matching::ElementSelectorFlags;
     use selectors::Element;
 
     #[test]
@@ -187,6 +289,33 @@ mod tests {
         let sel = Selector::parse("p").unwrap();
         let element = fragment.select(&sel).next().unwrap();
         assert!(!element.is_link());
+
+        let html = "<a>no href</a>";
+        let fragment = Html::parse_fragment(html);
+        let sel = Selector::parse("a").unwrap();
+        let element = fragment.select(&sel).next().unwrap();
+        assert!(!element.is_link());
+    }
+
+    #[test]
+    fn test_foreign_content_namespaces() {
+        let html = "<svg viewBox='0 0 1 1'><a xlink:href='#x'><linearGradient/></a></svg><math><mi>x</mi></math><slot></slot>";
+        let document = Html::parse_document(html);
+        let element = |name: &str| document.select(&Selector::parse(name).unwrap()).next().unwrap();
+        let svg = element("svg");
+        assert!(!svg.is_html_element_in_html_document());
+        assert!(svg.has_namespace(&web_atoms::Namespace::from("http://www.w3.org/2000/svg")));
+        // the parser restores the case of SVG names
+        assert!(svg.attr_matches(
+            &selectors::attr::NamespaceConstraint::Any,
+            &style::LocalName::from("viewBox"),
+            &selectors::attr::AttrSelectorOperation::Exists,
+        ));
+        assert!(element("a").is_link());
+        assert!(!element("a").is_html_element_in_html_document());
+        assert!(element("mi").has_namespace(&web_atoms::Namespace::from("http://www.w3.org/1998/Math/MathML")));
+        assert!(element("slot").is_html_slot_element());
+        assert!(!svg.is_html_slot_element());
     }
 
     #[test]
@@ -209,4 +338,85 @@ mod tests {
             CaseSensitivity::CaseSensitive
         ));
     }
+
+    #[test]
+    fn test_add_element_unique_hashes() {
+        let html = "<p id='my_id' class='my_class' data-foo='bar'>hey there</p>";
+        let fragment = Html::parse_fragment(html);
+        let sel = Selector::parse("p").unwrap();
+        let element = fragment.select(&sel).next().unwrap();
+        let mut filter = BloomFilter::new();
+        assert!(element.add_element_unique_hashes(&mut filter));
+        for hash in [
+            web_atoms::LocalName::from("p").get_hash(),
+            web_atoms::Namespace::from("http://www.w3.org/1999/xhtml").get_hash(),
+            style::Atom::from("my_id").get_hash(),
+            style::Atom::from("my_class").get_hash(),
+            web_atoms::LocalName::from("data-foo").get_hash(),
+        ] {
+            assert!(filter.might_contain_hash(hash));
+        }
+    }
+
+    #[test]
+    fn test_apply_selector_flags() {
+        let html = "<div><p>hey there</p></div>";
+        let fragment = Html::parse_fragment(html);
+        let div = fragment.select(&Selector::parse("div").unwrap()).next().unwrap();
+        let p = fragment.select(&Selector::parse("p").unwrap()).next().unwrap();
+        p.apply_selector_flags(
+            ElementSelectorFlags::HAS_EMPTY_SELECTOR | ElementSelectorFlags::HAS_EDGE_CHILD_SELECTOR,
+        );
+        assert!(p.value().selector_flags().contains(ElementSelectorFlags::HAS_EMPTY_SELECTOR));
+        assert!(!p.value().selector_flags().contains(ElementSelectorFlags::HAS_EDGE_CHILD_SELECTOR));
+        assert!(div.value().selector_flags().contains(ElementSelectorFlags::HAS_EDGE_CHILD_SELECTOR));
+        p.value().clear_selector_flags();
+        assert!(p.value().selector_flags().is_empty());
+    }
+
+    #[test]
+    fn test_attr_case_sensitivity() {
+        let document = Html::parse_document(
+            "<input type='checkbox' data-x='abc' lang='en-US'><svg><a type='checkbox'></a></svg>",
+        );
+        let matches = |selector: &str| -> Vec<String> {
+            document
+                .select(&Selector::parse(selector).unwrap())
+                .map(|element| element.value().name().to_owned())
+                .collect()
+        };
+        // `type` and `lang` are on the list, `data-x` isn't
+        assert_eq!(matches("[type=CHECKBOX]"), ["input"]);
+        assert_eq!(matches("[lang|=EN]"), ["input"]);
+        assert!(matches("[data-x=ABC]").is_empty());
+        assert!(matches("[data-x^=A]").is_empty());
+        // `i` and `s` override the list, on any element
+        assert_eq!(matches("[data-x=ABC i]"), ["input"]);
+        assert_eq!(matches("[data-x$=BC i]"), ["input"]);
+        assert!(matches("[type=CHECKBOX s]").is_empty());
+        assert_eq!(matches("[type=CHECKBOX i]"), ["input", "a"]);
+        assert_eq!(matches("[type=checkbox s]"), ["input", "a"]);
+    }
+
+    #[test]
+    fn test_is_part() {
+        let html = "<p part='label  title'>hey there</p>";
+        let fragment = Html::parse_fragment(html);
+        let sel = Selector::parse("p").unwrap();
+        let element = fragment.select(&sel).next().unwrap();
+        assert!(element.is_part(&AtomIdent::from("label")));
+        assert!(element.is_part(&AtomIdent::from("title")));
+        assert!(!element.is_part(&AtomIdent::from("lab")));
+    }
+
+    #[test]
+    fn test_imported_part() {
+        let html = "<div exportparts='label, inner-title: title'>hey there</div>";
+        let fragment = Html::parse_fragment(html);
+        let sel = Selector::parse("div").unwrap();
+        let element = fragment.select(&sel).next().unwrap();
+        assert_eq!(element.imported_part(&AtomIdent::from("label")), Some(AtomIdent::from("label")));
+        assert_eq!(element.imported_part(&AtomIdent::from("title")), Some(AtomIdent::from("inner-title")));
+        assert_eq!(element.imported_part(&AtomIdent::from("inner-title")), None);
+    }
 }
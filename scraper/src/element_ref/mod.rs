@@ -211,6 +211,9 @@ mod selector_map;
 mod bloom_filter;
 mod style_sharing;
 mod serializable;
+mod state;
+
+pub use element::can_match_non_ts_pseudo_class;
 
 #[cfg(test)]
 mod tests {
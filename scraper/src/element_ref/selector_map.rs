@@ -19,7 +19,7 @@ impl SelectorMapElement for ElementRef<'_> {
     where
         F: FnMut(&style::LocalName) {
         for (attr, _) in self.value().attrs_atom() {
-            callback(&style::values::GenericAtomIdent(attr.local.clone()))
+            callback(style::values::GenericAtomIdent::cast(&attr.local))
         }
     }
 
@@ -28,7 +28,7 @@ impl SelectorMapElement for ElementRef<'_> {
     }
 
     fn state(&self) -> stylo_dom::ElementState {
-        stylo_dom::ElementState::from_bits(0).unwrap() // TODO: This is probably right?
+        self.attribute_state()
     }
 
     fn namespace(&self) -> &web_atoms::Namespace {
@@ -38,7 +38,7 @@ impl SelectorMapElement for ElementRef<'_> {
     fn traversal_parent(&self) -> Option<Self> {
         let parent_node = self.node.parent()?;
         match parent_node.value() {
-            Node::Document => None,
+            Node::Document | Node::Fragment => None,
             Node::Element(_) => Some(ElementRef::new(parent_node)),
             other => panic!("Did not expect parent of element to be {:?}.", other),
         }
@@ -57,3 +57,37 @@ impl SelectorMapElement for ElementRef<'_> {
         euclid::Size2D::new(None, None) 
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::html::Html;
+    use crate::selector::Selector;
+    use style::selector_map::SelectorMapElement;
+
+    #[test]
+    fn traversal_parent_stops_at_fragment_root() {
+        let fragment = Html::parse_fragment("<p>hey there</p>");
+        let root = fragment.root_element();
+        assert_eq!(SelectorMapElement::traversal_parent(&root), None);
+        let p = fragment.select(&Selector::parse("p").unwrap()).next().unwrap();
+        assert_eq!(SelectorMapElement::traversal_parent(&p), Some(root));
+    }
+
+    #[test]
+    fn each_attr_name_visits_every_attribute() {
+        let fragment = Html::parse_fragment("<p data-foo='1' title='hey'>hey there</p>");
+        let p = fragment.select(&Selector::parse("p").unwrap()).next().unwrap();
+        let mut names = Vec::new();
+        p.each_attr_name(|name| names.push(name.to_string()));
+        names.sort();
+        assert_eq!(names, ["data-foo", "title"]);
+    }
+
+    #[test]
+    fn namespace_and_local_name_come_from_element_name() {
+        let fragment = Html::parse_fragment("<svg><circle></circle></svg>");
+        let circle = fragment.select(&Selector::parse("circle").unwrap()).next().unwrap();
+        assert_eq!(&**SelectorMapElement::local_name(&circle), "circle");
+        assert_eq!(&**SelectorMapElement::namespace(&circle), "http://www.w3.org/2000/svg");
+    }
+}
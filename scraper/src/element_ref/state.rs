@@ -0,0 +1,291 @@
+//! Element states that can be derived from the document alone.
+
+use stylo_dom::ElementState;
+
+use crate::{ElementRef, Node};
+
+/// The states `ElementRef::attribute_state` knows how to derive. Pseudo-classes
+/// depending on any other state never match.
+pub(crate) const ATTRIBUTE_DERIVED_STATES: ElementState = ElementState::UNVISITED
+    .union(ElementState::VISITED)
+    .union(ElementState::URLTARGET)
+    .union(ElementState::DISABLED)
+    .union(ElementState::ENABLED)
+    .union(ElementState::CHECKED)
+    .union(ElementState::REQUIRED)
+    .union(ElementState::OPTIONAL)
+    .union(ElementState::READWRITE)
+    .union(ElementState::LTR)
+    .union(ElementState::RTL);
+
+impl<'a> ElementRef<'a> {
+    /// Returns the states of this element that are knowable from its attributes
+    /// and ancestors, e.g. `:checked` or `:disabled`, or that were set on it
+    /// (`Element::set_link_state` and `Element::set_target`). User-interaction
+    /// states like `:hover` or `:focus` are never set.
+    pub fn attribute_state(&self) -> ElementState {
+        self.value().state_or_init(|| self.compute_attribute_state())
+    }
+
+    fn compute_attribute_state(&self) -> ElementState {
+        let element = self.value();
+        let mut state = ElementState::empty();
+        if element.is_target() {
+            state.insert(ElementState::URLTARGET);
+        }
+        if element.name.ns != ns!(html) {
+            return state;
+        }
+        if is_link_element(element.name()) && element.attr("href").is_some() {
+            state.insert(element.link_state());
+        }
+        let can_be_disabled = matches!(
+            element.name(),
+            "button" | "input" | "select" | "textarea" | "optgroup" | "option" | "fieldset"
+        );
+        let disabled = can_be_disabled && self.is_actually_disabled();
+        if can_be_disabled {
+            state.insert(if disabled { ElementState::DISABLED } else { ElementState::ENABLED });
+        }
+        let mut read_write = false;
+        match element.name() {
+            "input" => {
+                let input_type = element
+                    .attr("type")
+                    .map(str::to_ascii_lowercase)
+                    .unwrap_or_else(|| "text".to_owned());
+                if matches!(input_type.as_str(), "checkbox" | "radio") && element.attr("checked").is_some() {
+                    state.insert(ElementState::CHECKED);
+                }
+                let text_like = matches!(
+                    input_type.as_str(),
+                    "text" | "search" | "url" | "tel" | "email" | "password" | "date" | "month"
+                        | "week" | "time" | "datetime-local" | "number"
+                );
+                let requirable = text_like || matches!(input_type.as_str(), "checkbox" | "radio" | "file");
+                if requirable {
+                    state.insert(required_or_optional(element.attr("required").is_some()));
+                }
+                read_write = text_like && !disabled && element.attr("readonly").is_none();
+            }
+            "textarea" => {
+                state.insert(required_or_optional(element.attr("required").is_some()));
+                read_write = !disabled && element.attr("readonly").is_none();
+            }
+            "select" => {
+                state.insert(required_or_optional(element.attr("required").is_some()));
+            }
+            "option" => {
+                if element.attr("selected").is_some() {
+                    state.insert(ElementState::CHECKED);
+                }
+            }
+            _ => (),
+        }
+        if matches!(element.attr("contenteditable"), Some("" | "true" | "plaintext-only")) {
+            read_write = true;
+        }
+        if read_write {
+            state.insert(ElementState::READWRITE);
+        }
+        state.insert(self.directionality());
+        state
+    }
+
+    /// Returns the language of this element from the nearest `lang` attribute
+    /// on it or an ancestor. An empty `lang` means the language is unknown.
+    pub fn language(&self) -> Option<&'a str> {
+        std::iter::once(*self)
+            .chain(self.ancestors().filter_map(ElementRef::wrap))
+            .find_map(|element| element.value().attr("lang"))
+            .filter(|lang| !lang.is_empty())
+    }
+
+    /// Returns true if this element's language matches the `:lang()` range,
+    /// using RFC 4647 prefix matching.
+    pub fn matches_lang(&self, range: &str) -> bool {
+        let Some(lang) = self.language() else {
+            return false;
+        };
+        if range == "*" {
+            return true;
+        }
+        lang.get(..range.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(range))
+            && matches!(lang.as_bytes().get(range.len()), None | Some(b'-'))
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/dom.html#the-directionality>
+    fn directionality(&self) -> ElementState {
+        match self.value().attr("dir").map(str::to_ascii_lowercase).as_deref() {
+            Some("ltr") => ElementState::LTR,
+            Some("rtl") => ElementState::RTL,
+            Some("auto") => self.auto_directionality(),
+            _ if self.value().name() == "bdi" => self.auto_directionality(),
+            _ => self
+                .parent()
+                .and_then(ElementRef::wrap)
+                .map_or(ElementState::LTR, |parent| {
+                    parent.attribute_state() & (ElementState::LTR | ElementState::RTL)
+                }),
+        }
+    }
+
+    /// Resolves `dir=auto` from the first strongly directional character in
+    /// the element's text.
+    fn auto_directionality(&self) -> ElementState {
+        let text = self.descendants().filter_map(|node| match node.value() {
+            Node::Text(text) => Some(&**text),
+            _ => None,
+        });
+        for c in text.flat_map(str::chars) {
+            if is_strong_rtl(c) {
+                return ElementState::RTL;
+            }
+            if c.is_alphabetic() {
+                return ElementState::LTR;
+            }
+        }
+        ElementState::LTR
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/semantics-other.html#concept-element-disabled>
+    fn is_actually_disabled(&self) -> bool {
+        let element = self.value();
+        if element.attr("disabled").is_some() {
+            return true;
+        }
+        if element.name() == "option" {
+            return self
+                .parent()
+                .and_then(ElementRef::wrap)
+                .is_some_and(|parent| parent.value().name() == "optgroup" && parent.value().attr("disabled").is_some());
+        }
+        if element.name() == "optgroup" {
+            return false;
+        }
+        // Form controls inside a disabled fieldset are disabled, unless they
+        // are inside that fieldset's first legend.
+        let mut child = *self;
+        for ancestor in self.ancestors().filter_map(ElementRef::wrap) {
+            if ancestor.value().name() == "fieldset" && ancestor.value().attr("disabled").is_some() {
+                let first_legend = ancestor
+                    .child_elements()
+                    .find(|c| c.value().name() == "legend");
+                if first_legend != Some(child) {
+                    return true;
+                }
+            }
+            child = ancestor;
+        }
+        false
+    }
+}
+
+pub(crate) fn is_link_element(name: &str) -> bool {
+    matches!(name, "a" | "area" | "link")
+}
+
+/// Hebrew, Arabic, Syriac, Thaana, N'Ko and related blocks, plus the Hebrew and
+/// Arabic presentation forms.
+fn is_strong_rtl(c: char) -> bool {
+    matches!(c, '\u{0590}'..='\u{08FF}' | '\u{FB1D}'..='\u{FDFF}' | '\u{FE70}'..='\u{FEFF}')
+}
+
+fn required_or_optional(required: bool) -> ElementState {
+    if required {
+        ElementState::REQUIRED
+    } else {
+        ElementState::OPTIONAL
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::html::Html;
+    use crate::selector::Selector;
+    use stylo_dom::ElementState;
+
+    fn state_of(html: &str, selector: &str) -> ElementState {
+        let fragment = Html::parse_fragment(html);
+        let sel = Selector::parse(selector).unwrap();
+        let element = fragment.select(&sel).next().unwrap();
+        element.attribute_state()
+    }
+
+    #[test]
+    fn checked_inputs_and_selected_options() {
+        assert!(state_of("<input type='checkbox' checked>", "input").contains(ElementState::CHECKED));
+        assert!(!state_of("<input type='checkbox'>", "input").contains(ElementState::CHECKED));
+        assert!(!state_of("<input type='text' checked>", "input").contains(ElementState::CHECKED));
+        assert!(state_of("<select><option selected>a</option></select>", "option").contains(ElementState::CHECKED));
+    }
+
+    #[test]
+    fn disabled_and_enabled() {
+        assert!(state_of("<button disabled>a</button>", "button").contains(ElementState::DISABLED));
+        assert!(state_of("<button>a</button>", "button").contains(ElementState::ENABLED));
+        assert!(state_of("<fieldset disabled><input></fieldset>", "input").contains(ElementState::DISABLED));
+        assert!(state_of("<fieldset disabled><legend><input></legend></fieldset>", "input").contains(ElementState::ENABLED));
+        let p = state_of("<p disabled>a</p>", "p");
+        assert!(!p.intersects(ElementState::DISABLED | ElementState::ENABLED));
+    }
+
+    #[test]
+    fn required_and_read_write() {
+        assert!(state_of("<input required>", "input").contains(ElementState::REQUIRED));
+        assert!(state_of("<textarea></textarea>", "textarea").contains(ElementState::OPTIONAL | ElementState::READWRITE));
+        assert!(!state_of("<input readonly>", "input").contains(ElementState::READWRITE));
+        assert!(!state_of("<input type='checkbox'>", "input").contains(ElementState::READWRITE));
+        assert!(state_of("<div contenteditable>a</div>", "div").contains(ElementState::READWRITE));
+    }
+
+    #[test]
+    fn links() {
+        assert!(state_of("<a href='#'>a</a>", "a").contains(ElementState::UNVISITED));
+        assert!(!state_of("<a>a</a>", "a").contains(ElementState::UNVISITED));
+        let mut fragment = Html::parse_fragment("<a href='#'>a</a>");
+        let id = fragment.select(&Selector::parse("a").unwrap()).next().unwrap().id();
+        let mut a = fragment.tree.get_mut(id).unwrap();
+        let crate::Node::Element(element) = a.value() else { unreachable!() };
+        element.set_link_state(ElementState::VISITED);
+        let a = fragment.select(&Selector::parse("a").unwrap()).next().unwrap();
+        assert!(a.attribute_state().contains(ElementState::VISITED));
+        assert!(!a.attribute_state().contains(ElementState::UNVISITED));
+    }
+
+    #[test]
+    fn target() {
+        let mut fragment = Html::parse_fragment("<p id='x'>a</p>");
+        assert!(!state_of("<p id='x'>a</p>", "p").contains(ElementState::URLTARGET));
+        let id = fragment.select(&Selector::parse("p").unwrap()).next().unwrap().id();
+        let mut p = fragment.tree.get_mut(id).unwrap();
+        let crate::Node::Element(element) = p.value() else { unreachable!() };
+        element.set_target(true);
+        let p = fragment.select(&Selector::parse("p").unwrap()).next().unwrap();
+        assert!(p.attribute_state().contains(ElementState::URLTARGET));
+    }
+
+    #[test]
+    fn directionality() {
+        assert!(state_of("<p>a</p>", "p").contains(ElementState::LTR));
+        assert!(state_of("<div dir='rtl'><p>a</p></div>", "p").contains(ElementState::RTL));
+        assert!(state_of("<div dir='rtl'><p dir='ltr'>a</p></div>", "p").contains(ElementState::LTR));
+        assert!(state_of("<p dir='auto'>\u{05D0}bc</p>", "p").contains(ElementState::RTL));
+        assert!(state_of("<bdi>abc \u{05D0}</bdi>", "bdi").contains(ElementState::LTR));
+    }
+
+    #[test]
+    fn lang() {
+        let fragment = Html::parse_fragment("<div lang='en-US'><p>a</p></div><div lang=''><span>b</span></div>");
+        let p = fragment.select(&Selector::parse("p").unwrap()).next().unwrap();
+        assert_eq!(p.language(), Some("en-US"));
+        assert!(p.matches_lang("en"));
+        assert!(p.matches_lang("EN-us"));
+        assert!(p.matches_lang("*"));
+        assert!(!p.matches_lang("e"));
+        assert!(!p.matches_lang("fr"));
+        let span = fragment.select(&Selector::parse("span").unwrap()).next().unwrap();
+        assert_eq!(span.language(), None);
+        assert!(!span.matches_lang("*"));
+    }
+}
@@ -79,7 +79,9 @@ impl Html {
     pub fn parse_document(document: &str) -> Self {
         let parser =
             driver::parse_document(HtmlTreeSink::new(Self::new_document()), Default::default());
-        parser.one(document)
+        let mut html = parser.one(document);
+        source_columns::record_source_columns(&mut html, document);
+        html
     }
 
     /// Parses a string of HTML as a fragment.
@@ -91,7 +93,9 @@ impl Html {
             Vec::new(),
             false,
         );
-        parser.one(fragment)
+        let mut html = parser.one(fragment);
+        source_columns::record_source_columns(&mut html, fragment);
+        html
     }
 
     /// Returns an iterator over elements matching a selector.
@@ -199,6 +203,7 @@ impl DoubleEndedIterator for Select<'_, '_> {
 impl FusedIterator for Select<'_, '_> {}
 
 mod serializable;
+mod source_columns;
 mod tree_sink;
 
 #[cfg(test)]
@@ -261,6 +266,35 @@ mod tests {
         assert_eq!(upper, Some(10));
     }
 
+    #[test]
+    fn elements_record_source_lines() {
+        let html = Html::parse_document("<html>\n<body>\n<p>a</p>\n\n<div>b</div></body></html>");
+        let line_of = |selector: &str| {
+            html.select(&Selector::parse(selector).unwrap())
+                .next()
+                .unwrap()
+                .value()
+                .source_line()
+        };
+        assert_eq!(line_of("html"), Some(1));
+        assert_eq!(line_of("p"), Some(3));
+        assert_eq!(line_of("div"), Some(5));
+    }
+
+    #[test]
+    fn elements_record_source_columns() {
+        let html = Html::parse_document(
+            "<!-- <p> -->\n  <p title='a>b'>a</p><P>b</P>\n<script>'<p>'</script>\t<p\nid=c>",
+        );
+        let columns: Vec<_> = html
+            .select(&Selector::parse("p").unwrap())
+            .map(|element| element.value().source_column())
+            .collect();
+        assert_eq!(columns, [Some(3), Some(23), Some(24)]);
+        // Implied by the parser
+        assert_eq!(html.root_element().value().source_column(), None);
+    }
+
     #[cfg(feature = "atomic")]
     #[test]
     fn html_is_send() {
@@ -0,0 +1,145 @@
+//! Columns of start tags, which html5ever's `TreeSink` doesn't report. The
+//! source is scanned for start tags, and each element html5ever created is
+//! given the next unclaimed tag of its name around its line, in the order the
+//! elements were created.
+
+use std::collections::{HashMap, VecDeque};
+
+use super::Html;
+use crate::Node;
+
+/// Elements whose contents are text, so a `<` in them starts no tag
+const RAW_TEXT: &[&str] = &[
+    "script", "style", "textarea", "title", "xmp", "iframe", "noembed", "noframes",
+];
+
+struct StartTag {
+    /// Of the `<`
+    line: u64,
+    /// Of the `>`
+    end_line: u64,
+    column: u64,
+}
+
+/// Sets the column of each element with a source line to that of its start
+/// tag in `source`, the text it was parsed from. Elements html5ever made up
+/// (an implied `<tbody>`, say) are left without one.
+pub(super) fn record_source_columns(html: &mut Html, source: &str) {
+    let mut tags = start_tags(source);
+    let ids: Vec<_> = html.tree.nodes().map(|node| node.id()).collect();
+    for id in ids {
+        let mut node = html.tree.get_mut(id).unwrap();
+        let Node::Element(element) = node.value() else {
+            continue;
+        };
+        let (Some(line), Some(tags)) = (
+            element.source_line,
+            tags.get_mut(&element.name().to_ascii_lowercase()),
+        ) else {
+            continue;
+        };
+        // Tags html5ever made no element for, like a second `<body>`
+        while tags.front().is_some_and(|tag| tag.end_line < line) {
+            tags.pop_front();
+        }
+        if tags.front().is_some_and(|tag| tag.line <= line) {
+            element.source_column = tags.pop_front().map(|tag| tag.column);
+        }
+    }
+}
+
+/// The start tags in `source` by lowercased name, in source order
+fn start_tags(source: &str) -> HashMap<String, VecDeque<StartTag>> {
+    let mut tags: HashMap<String, VecDeque<StartTag>> = HashMap::new();
+    let mut cursor = Cursor {
+        rest: source,
+        line: 1,
+        column: 1,
+    };
+    while !cursor.rest.is_empty() {
+        if cursor.starts_with("<!--") {
+            cursor.skip_past("-->");
+            continue;
+        }
+        let mut chars = cursor.rest.chars();
+        if !(chars.next() == Some('<') && chars.next().is_some_and(|c| c.is_ascii_alphabetic())) {
+            cursor.bump();
+            continue;
+        }
+        let (line, column) = (cursor.line, cursor.column);
+        cursor.bump();
+        let end = cursor
+            .rest
+            .find(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>')
+            .unwrap_or(cursor.rest.len());
+        let name = cursor.rest[..end].to_ascii_lowercase();
+        // To the end of the tag, where a `>` in a quoted attribute value
+        // doesn't count
+        let mut quote = None;
+        let mut after_equals = false;
+        while let Some(c) = cursor.bump() {
+            match quote {
+                Some(q) if c == q => quote = None,
+                Some(_) => (),
+                None if c == '>' => break,
+                None if c.is_ascii_whitespace() => (),
+                None => {
+                    if after_equals && (c == '"' || c == '\'') {
+                        quote = Some(c);
+                    }
+                    after_equals = c == '=';
+                }
+            }
+        }
+        let end_line = cursor.line;
+        if RAW_TEXT.contains(&name.as_str()) {
+            cursor.skip_past(&format!("</{name}"));
+        }
+        tags.entry(name).or_default().push_back(StartTag {
+            line,
+            end_line,
+            column,
+        });
+    }
+    tags
+}
+
+/// A position in the source, counting lines the way html5ever does
+struct Cursor<'a> {
+    rest: &'a str,
+    line: u64,
+    /// In characters
+    column: u64,
+}
+
+impl Cursor<'_> {
+    fn starts_with(&self, pattern: &str) -> bool {
+        self.rest
+            .get(..pattern.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(pattern))
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.rest.chars().next()?;
+        self.rest = &self.rest[c.len_utf8()..];
+        // `\r\n` is one line break, and so is a lone `\r`
+        if c == '\n' || (c == '\r' && !self.rest.starts_with('\n')) {
+            self.line += 1;
+            self.column = 1;
+        } else if c != '\r' {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    /// Advances past the next (ASCII case-insensitive) `pattern`, or to the
+    /// end
+    fn skip_past(&mut self, pattern: &str) {
+        while !self.rest.is_empty() && !self.starts_with(pattern) {
+            self.bump();
+        }
+        for _ in pattern.chars() {
+            self.bump();
+        }
+    }
+}
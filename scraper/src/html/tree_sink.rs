@@ -7,17 +7,17 @@ use html5ever::tree_builder::{ElementFlags, NodeOrText, QuirksMode, TreeSink};
 use html5ever::Attribute;
 use html5ever::QualName;
 use std::borrow::Cow;
-use std::cell::{Ref, RefCell};
+use std::cell::{Cell, Ref, RefCell};
 use style::Atom;
 
 /// Wraps `Html` instances as sinks to drive parsing
 #[derive(Debug)]
-pub struct HtmlTreeSink(pub RefCell<Html>);
+pub struct HtmlTreeSink(pub RefCell<Html>, Cell<u64>);
 
 impl HtmlTreeSink {
     /// Wrap a `Html`instance as a sink to drive parsing
     pub fn new(html: Html) -> Self {
-        Self(RefCell::new(html))
+        Self(RefCell::new(html), Cell::new(1))
     }
 }
 
@@ -83,8 +83,10 @@ impl TreeSink for HtmlTreeSink {
     ) -> Self::Handle {
         let fragment = name.expanded() == expanded_name!(html "template");
 
+        let mut element = Element::new(name, attrs);
+        element.source_line = Some(self.1.get());
         let mut this = self.0.borrow_mut();
-        let mut node = this.tree.orphan(Node::Element(Element::new(name, attrs)));
+        let mut node = this.tree.orphan(Node::Element(element));
 
         if fragment {
             node.append(Node::Fragment);
@@ -257,6 +259,11 @@ impl TreeSink for HtmlTreeSink {
             .id()
     }
 
+    // Called whenever the line number changes.
+    fn set_current_line(&self, line_number: u64) {
+        self.1.set(line_number);
+    }
+
     // Mark a HTML <script> element as "already started".
     fn mark_script_already_started(&self, _node: &Self::Handle) {}
 
@@ -7,6 +7,8 @@ use std::slice::Iter as SliceIter;
 use crate::{CaseSensitivity, StrTendril};
 use atomic_refcell::{AtomicRef, AtomicRefCell, AtomicRefMut};
 use html5ever::{Attribute, LocalName, QualName};
+use selectors::matching::ElementSelectorFlags;
+use stylo_dom::ElementState;
 use style::data::{ElementData, ElementDataFlags, ElementStyles};
 use style::properties::ComputedValues;
 use style::properties::style_structs::Font;
@@ -15,6 +17,7 @@ use style::style_resolver::{PrimaryStyle, ResolvedStyle};
 use style::{Atom, values::GenericAtomIdent};
 use std::cell::OnceCell;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Mutex, OnceLock};
 use style::properties::declaration_block::parse_style_attribute;
 use style::context::QuirksMode;
@@ -25,7 +28,7 @@ use style::shared_lock::{Locked, SharedRwLock};
 // `Element` is usally the most common variant and hence boxing it
 // will most likely not improve performance overall.
 #[allow(variant_size_differences)]
-#[derive(PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq)]
 pub enum Node {
     /// The document root.
     Document,
@@ -249,6 +252,18 @@ pub struct Element {
     id: OnceCell<Option<Atom>>,
 
     classes: OnceCell<Box<[style::values::AtomIdent]>>,
+
+    selector_flags: AtomicUsize,
+
+    state: OnceCell<ElementState>,
+
+    link_state: ElementState,
+
+    target: bool,
+
+    pub(crate) source_line: Option<u64>,
+
+    pub(crate) source_column: Option<u64>,
 }
 
 struct InternedStyleBlock {
@@ -286,6 +301,28 @@ fn intern_style_block(style_attr: &str) -> (SharedRwLock, Arc<Locked<style::prop
     (lock, block)
 }
 
+/// Copies the element's name and attributes; the copy starts with none of the
+/// cached state, selector flags or style data.
+impl Clone for Element {
+    fn clone(&self) -> Self {
+        Element {
+            name: self.name.clone(),
+            attrs: self.attrs.clone(),
+            style_block: self.style_block.clone(),
+            style_block_lock: self.style_block_lock.clone(),
+            element_data: OnceCell::new(),
+            id: OnceCell::new(),
+            classes: OnceCell::new(),
+            selector_flags: AtomicUsize::new(0),
+            state: OnceCell::new(),
+            link_state: self.link_state,
+            target: self.target,
+            source_line: self.source_line,
+            source_column: self.source_column,
+        }
+    }
+}
+
 impl PartialEq for Element {
     fn eq(&self, other: &Self) -> bool {
         self.name == other.name && self.attrs == other.attrs && self.id == other.id && self.classes == other.classes
@@ -318,9 +355,36 @@ impl Element {
             element_data: OnceCell::new(),
             id: OnceCell::new(),
             classes: OnceCell::new(),
+            selector_flags: AtomicUsize::new(0),
+            state: OnceCell::new(),
+            link_state: ElementState::UNVISITED,
+            target: false,
+            source_line: None,
+            source_column: None,
         }
     }
 
+    /// Returns the (1-based) line of the element's start tag in the parsed
+    /// source, if known.
+    pub fn source_line(&self) -> Option<u64> {
+        self.source_line
+    }
+
+    /// Returns the (1-based) column, in characters, of the `<` of the
+    /// element's start tag in the parsed source, if known. Elements the
+    /// parser implied have none.
+    pub fn source_column(&self) -> Option<u64> {
+        self.source_column
+    }
+
+    /// Returns how many declarations the element's `style` attribute has,
+    /// with shorthands expanded to their longhands, or `None` if the element
+    /// has no `style` attribute.
+    pub fn inline_style_declarations(&self) -> Option<usize> {
+        self.attr("style")?;
+        Some(self.style_block.read_with(&self.style_block_lock.read()).len())
+    }
+
     /// Returns the element name.
     pub fn name(&self) -> &str {
         self.name.local.deref()
@@ -413,17 +477,64 @@ impl Element {
 
     /// Returns this Element's ElementData. Initializes it if it hasn't been
     /// initialized yet.
-    /// TODO: I hope this is correct.
     pub fn borrow_data(&self) -> AtomicRef<'_, ElementData> {
         self.element_data.get_or_init(|| AtomicRefCell::new(default_data())).borrow()
     }
 
     /// Returns a mutable reference to this Element's ElementData. Initializes
     /// it if it hasn't been initialized yet.
-    /// TODO: I hope this is correct.
     pub fn mutate_data(&self) -> AtomicRefMut<'_, ElementData> {
         self.element_data.get_or_init(|| AtomicRefCell::new(default_data())).borrow_mut()
     }
+
+    /// Returns the selector flags set on this Element by matching with
+    /// `NeedsSelectorFlags::Yes`.
+    pub fn selector_flags(&self) -> ElementSelectorFlags {
+        ElementSelectorFlags::from_bits_truncate(self.selector_flags.load(Ordering::Relaxed))
+    }
+
+    /// Adds the given selector flags to this Element.
+    pub fn insert_selector_flags(&self, flags: ElementSelectorFlags) {
+        self.selector_flags.fetch_or(flags.bits(), Ordering::Relaxed);
+    }
+
+    /// Clears all selector flags on this Element.
+    pub fn clear_selector_flags(&self) {
+        self.selector_flags.store(0, Ordering::Relaxed);
+    }
+
+    /// Whether `:link` (`UNVISITED`) and `:visited` (`VISITED`) match this
+    /// element, if it is a link. Links are unvisited unless set otherwise.
+    pub fn link_state(&self) -> ElementState {
+        self.link_state
+    }
+
+    /// Sets `link_state`. Only `VISITED` and `UNVISITED` are kept; either,
+    /// both or neither may be set.
+    pub fn set_link_state(&mut self, state: ElementState) {
+        self.link_state = state & (ElementState::VISITED | ElementState::UNVISITED);
+        self.state = OnceCell::new();
+    }
+
+    /// Whether this is the document's target element, which `:target`
+    /// matches.
+    pub fn is_target(&self) -> bool {
+        self.target
+    }
+
+    pub fn set_target(&mut self, target: bool) {
+        self.target = target;
+        self.state = OnceCell::new();
+    }
+
+    pub(crate) fn state_or_init(&self, f: impl FnOnce() -> ElementState) -> ElementState {
+        *self.state.get_or_init(f)
+    }
+
+    /// Returns true if this Element's ElementData has been initialized.
+    pub fn has_data(&self) -> bool {
+        self.element_data.get().is_some()
+    }
 }
 
 fn default_data() -> ElementData {
@@ -536,3 +647,29 @@ impl Deref for ProcessingInstruction {
 }
 
 pub(crate) mod serializable;
+
+#[cfg(test)]
+mod tests {
+    use crate::html::Html;
+    use crate::selector::Selector;
+    use style::data::ElementDataFlags;
+
+    #[test]
+    fn element_data_is_allocated_lazily() {
+        let fragment = Html::parse_fragment("<p>hey there</p>");
+        let p = fragment.select(&Selector::parse("p").unwrap()).next().unwrap();
+        assert!(!p.value().has_data());
+        assert!(p.value().borrow_data().has_styles());
+        assert!(p.value().has_data());
+    }
+
+    #[test]
+    fn element_data_is_per_element() {
+        let fragment = Html::parse_fragment("<p id='a'>a</p><p id='b'>b</p>");
+        let a = fragment.select(&Selector::parse("#a").unwrap()).next().unwrap();
+        let b = fragment.select(&Selector::parse("#b").unwrap()).next().unwrap();
+        a.value().mutate_data().flags.insert(ElementDataFlags::WAS_RESTYLED);
+        assert!(a.value().borrow_data().flags.contains(ElementDataFlags::WAS_RESTYLED));
+        assert!(!b.value().borrow_data().flags.contains(ElementDataFlags::WAS_RESTYLED));
+    }
+}
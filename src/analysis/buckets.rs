@@ -0,0 +1,144 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Where the rules of a built `SelectorMap` landed. A rule goes into one
+//! bucket, chosen from its rightmost compound, and an element is only matched
+//! against the buckets for its id, classes, attributes, local name and so on,
+//! plus the catch-all list. So a corpus whose rules mostly land in the
+//! catch-all list, or in a few huge buckets, gets little from the map.
+
+use std::ops::AddAssign;
+
+use serde::Serialize;
+use style::selector_map::SelectorMap;
+use style::stylist::Rule;
+
+/// Rules in one kind of bucket, e.g. the class buckets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct BucketStats {
+    pub rules: usize,
+    /// Distinct keys (e.g. class names), or 1 for a single list
+    pub buckets: usize,
+    /// Rules in the largest bucket
+    pub largest_bucket: usize,
+}
+
+impl BucketStats {
+    fn from_buckets(bucket_sizes: impl Iterator<Item = usize>) -> Self {
+        bucket_sizes.fold(Self::default(), |stats, size| Self {
+            rules: stats.rules + size,
+            buckets: stats.buckets + 1,
+            largest_bucket: stats.largest_bucket.max(size),
+        })
+    }
+
+    fn from_list(size: usize) -> Self {
+        Self::from_buckets((size > 0).then_some(size).into_iter())
+    }
+}
+
+impl AddAssign for BucketStats {
+    /// Adds up rules and buckets. The largest bucket is the largest of either.
+    fn add_assign(&mut self, other: Self) {
+        self.rules += other.rules;
+        self.buckets += other.buckets;
+        self.largest_bucket = self.largest_bucket.max(other.largest_bucket);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SelectorMapBuckets {
+    /// Rules keyed on `:root`
+    pub root: BucketStats,
+    pub id: BucketStats,
+    pub class: BucketStats,
+    pub attribute: BucketStats,
+    pub local_name: BucketStats,
+    pub namespace: BucketStats,
+    pub common_pseudo_classes: BucketStats,
+    pub rare_pseudo_classes: BucketStats,
+    /// The catch-all list, tried for every element
+    pub other: BucketStats,
+}
+
+impl SelectorMapBuckets {
+    pub fn new(map: &SelectorMap<Rule>) -> Self {
+        Self {
+            root: BucketStats::from_list(map.root.len()),
+            id: BucketStats::from_buckets(map.id_hash.iter().map(|(_, bucket)| bucket.len())),
+            class: BucketStats::from_buckets(map.class_hash.iter().map(|(_, bucket)| bucket.len())),
+            attribute: BucketStats::from_buckets(map.attribute_hash.values().map(|bucket| bucket.len())),
+            local_name: BucketStats::from_buckets(map.local_name_hash.values().map(|bucket| bucket.len())),
+            namespace: BucketStats::from_buckets(map.namespace_hash.values().map(|bucket| bucket.len())),
+            common_pseudo_classes: BucketStats::from_list(map.common_pseudo_classes.len()),
+            rare_pseudo_classes: BucketStats::from_list(map.rare_pseudo_classes.len()),
+            other: BucketStats::from_list(map.other.len()),
+        }
+    }
+
+    /// Rules in every bucket
+    pub fn rules(&self) -> usize {
+        self.kinds().iter().map(|stats| stats.rules).sum()
+    }
+
+    fn kinds(&self) -> [BucketStats; 9] {
+        [
+            self.root,
+            self.id,
+            self.class,
+            self.attribute,
+            self.local_name,
+            self.namespace,
+            self.common_pseudo_classes,
+            self.rare_pseudo_classes,
+            self.other,
+        ]
+    }
+}
+
+impl AddAssign for SelectorMapBuckets {
+    fn add_assign(&mut self, other: Self) {
+        self.root += other.root;
+        self.id += other.id;
+        self.class += other.class;
+        self.attribute += other.attribute;
+        self.local_name += other.local_name;
+        self.namespace += other.namespace;
+        self.common_pseudo_classes += other.common_pseudo_classes;
+        self.rare_pseudo_classes += other.rare_pseudo_classes;
+        self.other += other.other;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BucketStats, SelectorMapBuckets};
+    use crate::MatchingContext;
+    use crate::parse::parse_stylesheet;
+    use style::shared_lock::SharedRwLock;
+    use style::stylesheets::UrlExtraData;
+    use test_log::test;
+
+    #[test]
+    fn counts_rules_per_bucket() {
+        let lock = SharedRwLock::new();
+        let url_data = UrlExtraData::from(url::Url::parse("about:blank").unwrap());
+        let css = "#a {} .b {} .b.c {} div .c {} p {} [title] {} * {} td > * {}";
+        let stylesheet = parse_stylesheet(css, url_data, &lock).unwrap();
+        let context = MatchingContext::new(std::iter::once(&stylesheet), lock);
+        let buckets = SelectorMapBuckets::new(context.selector_map().unwrap());
+        assert_eq!(buckets.id, BucketStats { rules: 1, buckets: 1, largest_bucket: 1 });
+        // `.b.c` goes only in one of its classes' buckets
+        assert_eq!(buckets.class.rules, 3);
+        assert_eq!(buckets.local_name, BucketStats { rules: 1, buckets: 1, largest_bucket: 1 });
+        assert_eq!(buckets.attribute.rules, 1);
+        assert_eq!(buckets.other, BucketStats { rules: 2, buckets: 1, largest_bucket: 2 });
+        assert_eq!(buckets.rules(), 8);
+        let mut total = buckets;
+        total += buckets;
+        assert_eq!(total.other, BucketStats { rules: 4, buckets: 2, largest_bucket: 2 });
+    }
+}
@@ -0,0 +1,308 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Structural metrics for each selector: how many components and combinators
+//! it has, whether it uses `:has()` or `:not()`, its specificity, and what
+//! kind of simple selector its rightmost compound is keyed on.
+//! `SelectorKind` sorts selectors into coarser categories, as the bench's
+//! matching cost breakdown does.
+
+use std::collections::BTreeMap;
+
+use cssparser::ToCss as _;
+use selectors::parser::{Combinator, Component};
+use serde::{Deserialize, Serialize};
+use style::selector_parser::SelectorImpl;
+
+use crate::structs::Selector;
+
+/// The most selective simple selector in a selector's rightmost compound, in
+/// the order the SelectorMap picks buckets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RightmostKind {
+    Id,
+    Class,
+    Attribute,
+    Type,
+    PseudoClass,
+    Universal,
+}
+
+/// A selector's category: combinator-heavy if it has at least
+/// `SelectorKind::COMBINATOR_HEAVY` combinators (not counting the one before
+/// a pseudo-element), and otherwise by its `RightmostKind`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectorKind {
+    Id,
+    Class,
+    Type,
+    Attribute,
+    CombinatorHeavy,
+    /// Keyed on a pseudo-class, or on nothing but a pseudo-element
+    Pseudo,
+    Universal,
+}
+
+impl SelectorKind {
+    pub const COMBINATOR_HEAVY: usize = 3;
+
+    pub fn of(selector: &Selector) -> Self {
+        let combinators = selector
+            .iter_raw_match_order()
+            .filter_map(Component::as_combinator)
+            .filter(|combinator| *combinator != Combinator::PseudoElement)
+            .count();
+        if combinators >= Self::COMBINATOR_HEAVY {
+            return Self::CombinatorHeavy;
+        }
+        match rightmost_kind(selector) {
+            RightmostKind::Id => Self::Id,
+            RightmostKind::Class => Self::Class,
+            RightmostKind::Attribute => Self::Attribute,
+            RightmostKind::Type => Self::Type,
+            RightmostKind::PseudoClass => Self::Pseudo,
+            RightmostKind::Universal => Self::Universal,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SelectorComplexity {
+    pub selector: String,
+    /// Simple selectors, not counting those nested in `:is()`, `:not()`, etc.
+    pub components: usize,
+    pub combinators: usize,
+    /// Combinators by kind, e.g. `descendant` or `child`
+    pub combinator_kinds: BTreeMap<&'static str, usize>,
+    /// Whether `:has()` appears anywhere, including nested
+    pub has_has: bool,
+    /// Whether `:not()` appears anywhere, including nested
+    pub has_not: bool,
+    /// (ids, classes, types)
+    pub specificity: [u32; 3],
+    pub rightmost: RightmostKind,
+}
+
+impl SelectorComplexity {
+    pub fn new(selector: &Selector) -> Self {
+        let mut components = 0;
+        let mut combinator_kinds = BTreeMap::new();
+        for component in selector.iter_raw_match_order() {
+            match component.as_combinator() {
+                Some(combinator) => *combinator_kinds.entry(combinator_name(combinator)).or_default() += 1,
+                None => components += 1,
+            }
+        }
+        let mut has_has = false;
+        let mut has_not = false;
+        visit_nested(selector, &mut |component| match component {
+            Component::Has(_) => has_has = true,
+            Component::Negation(_) => has_not = true,
+            _ => (),
+        });
+        Self {
+            selector: selector.to_css_string(),
+            components,
+            combinators: combinator_kinds.values().sum(),
+            combinator_kinds,
+            has_has,
+            has_not,
+            specificity: decode_specificity(selector.specificity()),
+            rightmost: rightmost_kind(selector),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ComplexitySummary {
+    pub selector_count: usize,
+    pub mean_components: f64,
+    pub max_components: usize,
+    pub mean_combinators: f64,
+    pub max_combinators: usize,
+    pub combinator_kinds: BTreeMap<&'static str, usize>,
+    pub with_has: usize,
+    pub with_not: usize,
+    pub max_specificity: [u32; 3],
+    pub rightmost: BTreeMap<RightmostKind, usize>,
+}
+
+impl<'a> FromIterator<&'a SelectorComplexity> for ComplexitySummary {
+    fn from_iter<T: IntoIterator<Item = &'a SelectorComplexity>>(iter: T) -> Self {
+        let mut summary = Self::default();
+        let mut total_components = 0;
+        let mut total_combinators = 0;
+        for complexity in iter {
+            summary.selector_count += 1;
+            total_components += complexity.components;
+            total_combinators += complexity.combinators;
+            summary.max_components = summary.max_components.max(complexity.components);
+            summary.max_combinators = summary.max_combinators.max(complexity.combinators);
+            for (kind, count) in &complexity.combinator_kinds {
+                *summary.combinator_kinds.entry(kind).or_default() += count;
+            }
+            summary.with_has += usize::from(complexity.has_has);
+            summary.with_not += usize::from(complexity.has_not);
+            summary.max_specificity = summary.max_specificity.max(complexity.specificity);
+            *summary.rightmost.entry(complexity.rightmost).or_default() += 1;
+        }
+        if summary.selector_count > 0 {
+            summary.mean_components = total_components as f64 / summary.selector_count as f64;
+            summary.mean_combinators = total_combinators as f64 / summary.selector_count as f64;
+        }
+        summary
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ComplexityReport {
+    pub summary: ComplexitySummary,
+    pub selectors: Vec<SelectorComplexity>,
+}
+
+impl ComplexityReport {
+    pub fn new(selectors: &[Selector]) -> Self {
+        let selectors: Vec<_> = selectors.iter().map(SelectorComplexity::new).collect();
+        Self {
+            summary: selectors.iter().collect(),
+            selectors,
+        }
+    }
+}
+
+fn combinator_name(combinator: Combinator) -> &'static str {
+    match combinator {
+        Combinator::Child => "child",
+        Combinator::Descendant => "descendant",
+        Combinator::NextSibling => "next_sibling",
+        Combinator::LaterSibling => "later_sibling",
+        Combinator::PseudoElement => "pseudo_element",
+        Combinator::SlotAssignment => "slot_assignment",
+        Combinator::Part => "part",
+    }
+}
+
+/// Calls `f` with every component of `selector`, including those in nested
+/// selectors.
+fn visit_nested(selector: &Selector, f: &mut impl FnMut(&Component<SelectorImpl>)) {
+    for component in selector.iter_raw_match_order() {
+        f(component);
+        match component {
+            Component::Negation(list) | Component::Is(list) | Component::Where(list) => {
+                for nested in list.slice() {
+                    visit_nested(nested, f);
+                }
+            }
+            Component::Has(relative_selectors) => {
+                for relative in relative_selectors.iter() {
+                    visit_nested(&relative.selector, f);
+                }
+            }
+            Component::NthOf(nth_of) => {
+                for nested in nth_of.selectors() {
+                    visit_nested(nested, f);
+                }
+            }
+            Component::Slotted(nested) | Component::Host(Some(nested)) => visit_nested(nested, f),
+            _ => (),
+        }
+    }
+}
+
+/// Selectors packs specificity into 10 bits each of ids, classes and types.
+fn decode_specificity(specificity: u32) -> [u32; 3] {
+    const MAX_10BIT: u32 = (1 << 10) - 1;
+    [specificity >> 20, (specificity >> 10) & MAX_10BIT, specificity & MAX_10BIT]
+}
+
+pub(crate) fn rightmost_kind(selector: &Selector) -> RightmostKind {
+    let mut kind = RightmostKind::Universal;
+    // `iter()` stops at the first combinator
+    for component in selector.iter() {
+        let component_kind = match component {
+            Component::ID(_) => RightmostKind::Id,
+            Component::Class(_) => RightmostKind::Class,
+            Component::AttributeInNoNamespaceExists { .. }
+            | Component::AttributeInNoNamespace { .. }
+            | Component::AttributeOther(_) => RightmostKind::Attribute,
+            Component::LocalName(_) => RightmostKind::Type,
+            Component::ExplicitUniversalType
+            | Component::ExplicitAnyNamespace
+            | Component::ExplicitNoNamespace
+            | Component::DefaultNamespace(_)
+            | Component::Namespace(..) => RightmostKind::Universal,
+            _ => RightmostKind::PseudoClass,
+        };
+        kind = kind.min(component_kind);
+    }
+    kind
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ComplexityReport, RightmostKind, SelectorComplexity, SelectorKind};
+    use crate::structs::Selector;
+    use style::selector_parser::SelectorParser;
+    use style::stylesheets::UrlExtraData;
+    use test_log::test;
+
+    fn parse(selector: &str) -> Selector {
+        let url_data = UrlExtraData::from(url::Url::parse("about:blank").unwrap());
+        SelectorParser::parse_author_origin_no_namespace(selector, &url_data).unwrap().slice()[0].clone()
+    }
+
+    #[test]
+    fn measures_selectors() {
+        let complexity = SelectorComplexity::new(&parse("div > ul li.item:is(.a, :not(.b))"));
+        assert_eq!(complexity.components, 5);
+        assert_eq!(complexity.combinators, 2);
+        assert_eq!(complexity.combinator_kinds.get("child"), Some(&1));
+        assert_eq!(complexity.combinator_kinds.get("descendant"), Some(&1));
+        assert!(complexity.has_not);
+        assert!(!complexity.has_has);
+        assert_eq!(complexity.specificity, [0, 2, 3]);
+        assert_eq!(complexity.rightmost, RightmostKind::Class);
+
+        let complexity = SelectorComplexity::new(&parse("section:has(> #x) *"));
+        assert!(complexity.has_has);
+        assert_eq!(complexity.specificity, [1, 0, 1]);
+        assert_eq!(complexity.rightmost, RightmostKind::Universal);
+        assert_eq!(SelectorComplexity::new(&parse("a:hover")).rightmost, RightmostKind::Type);
+        assert_eq!(SelectorComplexity::new(&parse(":hover")).rightmost, RightmostKind::PseudoClass);
+    }
+
+    #[test]
+    fn categorizes_selectors() {
+        let kinds: Vec<_> = ["#x", "div .a", "a[href]", "ul li", "a b c d", "a b c::before", ":hover", "*"]
+            .into_iter()
+            .map(|selector| SelectorKind::of(&parse(selector)))
+            .collect();
+        assert_eq!(kinds, [
+            SelectorKind::Id,
+            SelectorKind::Class,
+            SelectorKind::Attribute,
+            SelectorKind::Type,
+            SelectorKind::CombinatorHeavy,
+            SelectorKind::Pseudo,
+            SelectorKind::Pseudo,
+            SelectorKind::Universal,
+        ]);
+    }
+
+    #[test]
+    fn summarizes() {
+        let report = ComplexityReport::new(&[parse("a b"), parse("#x"), parse("p:not(.a)")]);
+        let summary = &report.summary;
+        assert_eq!(summary.selector_count, 3);
+        assert_eq!(summary.max_components, 2);
+        assert_eq!(summary.max_combinators, 1);
+        assert_eq!(summary.with_not, 1);
+        assert_eq!(summary.max_specificity, [1, 0, 0]);
+        assert_eq!(summary.rightmost.get(&RightmostKind::Type), Some(&2));
+    }
+}
@@ -0,0 +1,264 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! What a corpus is made of, from parsing alone: document sizes and element
+//! counts, stylesheet counts and sizes, selectors by the kind of their
+//! rightmost compound, the `@font-face` families and `@keyframes` names each
+//! stylesheet defines, and how often websites and stylesheets fail to parse.
+//! Nothing is matched, so this is quick even on a corpus that takes hours to
+//! match.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+use serde::Serialize;
+use style::stylesheets::{AllRules, CssRule, StylesheetInDocument as _};
+
+use crate::analysis::complexity::{RightmostKind, rightmost_kind};
+use crate::parse::{self, ParseOptions, ParsedWebsite};
+use crate::result::{ErrorKind, Result};
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct WebsiteStats {
+    /// Of the main html file
+    pub html_bytes: u64,
+    /// Including `<html>`
+    pub elements: usize,
+    /// `<style>` elements and linked stylesheets that could be read
+    pub stylesheets: usize,
+    pub stylesheet_bytes: usize,
+    /// Linked stylesheets that couldn't be read
+    pub unreadable_stylesheets: usize,
+    /// Stylesheets with rules or declarations that didn't parse
+    pub stylesheets_with_errors: usize,
+    /// Selectors of style rules, including nested ones and those in `@media`
+    /// rules that don't apply
+    pub selectors: usize,
+    pub selectors_by_kind: BTreeMap<RightmostKind, usize>,
+    /// See `responsive::media_breakpoints`
+    pub breakpoints: Vec<f32>,
+    /// For each stylesheet defining any fonts or animations, in order
+    pub at_rules: Vec<StylesheetAtRules>,
+}
+
+/// What a stylesheet's `@font-face` and `@keyframes` rules define, including
+/// those in `@media` rules that don't apply
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct StylesheetAtRules {
+    /// See `ParsedWebsite::stylesheet_names`
+    pub stylesheet: String,
+    pub font_families: BTreeSet<String>,
+    pub keyframes: BTreeSet<String>,
+}
+
+impl WebsiteStats {
+    /// `html_bytes` is left 0, since the website doesn't know its files.
+    pub fn new(website: &ParsedWebsite) -> Self {
+        let root = website.document().root_element();
+        let mut stats = Self {
+            elements: 1 + root.descendent_elements().count(),
+            stylesheets: website.stylesheets().len(),
+            stylesheet_bytes: website.stylesheet_sizes().iter().flatten().sum(),
+            breakpoints: crate::responsive::media_breakpoints(website),
+            ..Self::default()
+        };
+        for error in website.css_errors() {
+            match error.error {
+                ErrorKind::CssParse { .. } => stats.stylesheets_with_errors += 1,
+                _ => stats.unreadable_stylesheets += 1,
+            }
+        }
+        let device = website.device().stylo_device();
+        let guard = website.stylesheet_lock().read();
+        for (name, stylesheet) in website.stylesheet_names().iter().zip(website.stylesheets()) {
+            let mut at_rules = StylesheetAtRules { stylesheet: name.clone(), ..Default::default() };
+            for rule in stylesheet.iter_rules::<AllRules>(&device, &guard) {
+                match rule {
+                    CssRule::Style(style_rule) => {
+                        for selector in style_rule.read_with(&guard).selectors.slice() {
+                            stats.selectors += 1;
+                            *stats.selectors_by_kind.entry(rightmost_kind(selector)).or_default() += 1;
+                        }
+                    }
+                    CssRule::FontFace(font_face_rule) => {
+                        // Without a family the rule is invalid, and never used
+                        if let Some(family) = &font_face_rule.read_with(&guard).family {
+                            at_rules.font_families.insert(family.name.to_string());
+                        }
+                    }
+                    CssRule::Keyframes(keyframes_rule) => {
+                        at_rules.keyframes.insert(keyframes_rule.read_with(&guard).name.as_atom().to_string());
+                    }
+                    _ => (),
+                }
+            }
+            if !(at_rules.font_families.is_empty() && at_rules.keyframes.is_empty()) {
+                stats.at_rules.push(at_rules);
+            }
+        }
+        stats
+    }
+}
+
+/// Total, mean and largest of one statistic over the parsed websites
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Distribution {
+    pub total: u64,
+    pub mean: Option<f64>,
+    pub max: u64,
+}
+
+impl FromIterator<u64> for Distribution {
+    fn from_iter<T: IntoIterator<Item = u64>>(iter: T) -> Self {
+        let (mut distribution, mut count) = (Self::default(), 0);
+        for value in iter {
+            distribution.total += value;
+            distribution.max = distribution.max.max(value);
+            count += 1;
+        }
+        distribution.mean = (count > 0).then(|| distribution.total as f64 / count as f64);
+        distribution
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct CorpusSummary {
+    /// Folders that were websites, whether or not they parsed
+    pub websites: usize,
+    pub failed_websites: usize,
+    /// `failed_websites / websites`
+    pub website_failure_rate: Option<f64>,
+    pub html_bytes: Distribution,
+    pub elements: Distribution,
+    pub stylesheets: Distribution,
+    pub stylesheet_bytes: Distribution,
+    /// Stylesheets that couldn't be read or had parse errors, out of all
+    /// stylesheets including unreadable ones
+    pub stylesheet_failure_rate: Option<f64>,
+    pub selectors: Distribution,
+    pub selectors_by_kind: BTreeMap<RightmostKind, usize>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct CorpusStats {
+    pub websites: BTreeMap<String, WebsiteStats>,
+    /// The error each website that didn't parse failed with, by path
+    pub failed: BTreeMap<PathBuf, String>,
+    pub aggregate: CorpusSummary,
+}
+
+impl CorpusStats {
+    /// Parses each of `website_paths` with `options`. A website that fails
+    /// to parse is recorded in `failed` rather than stopping the rest;
+    /// failing to list the websites still does.
+    pub fn new(website_paths: impl Iterator<Item = Result<PathBuf>>, options: &ParseOptions) -> Result<Self> {
+        let mut websites = BTreeMap::new();
+        let mut failed = BTreeMap::new();
+        for path in website_paths {
+            let path = path?;
+            let parsed = parse::get_document_and_selectors_with_options(&path, options).and_then(|website| {
+                let Some(website) = website else {
+                    return Ok(None);
+                };
+                let html_bytes = match parse::main_html_file(&path, options)? {
+                    Some(html_file) => std::fs::metadata(&html_file.0).map_or(0, |metadata| metadata.len()),
+                    None => 0,
+                };
+                Ok(Some((website.name.clone(), WebsiteStats { html_bytes, ..WebsiteStats::new(&website) })))
+            });
+            match parsed {
+                Ok(Some((name, stats))) => {
+                    websites.insert(name, stats);
+                }
+                Ok(None) => (),
+                Err(e) => {
+                    failed.insert(path, e.to_string());
+                }
+            }
+        }
+        let aggregate = CorpusSummary::new(&websites, failed.len());
+        Ok(Self { websites, failed, aggregate })
+    }
+}
+
+impl CorpusSummary {
+    fn new(websites: &BTreeMap<String, WebsiteStats>, failed_websites: usize) -> Self {
+        let stats = || websites.values();
+        let mut selectors_by_kind = BTreeMap::new();
+        for website in stats() {
+            for (&kind, &count) in &website.selectors_by_kind {
+                *selectors_by_kind.entry(kind).or_default() += count;
+            }
+        }
+        let all_stylesheets: usize = stats().map(|website| website.stylesheets + website.unreadable_stylesheets).sum();
+        let failed_stylesheets: usize = stats()
+            .map(|website| website.stylesheets_with_errors + website.unreadable_stylesheets)
+            .sum();
+        let total = websites.len() + failed_websites;
+        Self {
+            websites: total,
+            failed_websites,
+            website_failure_rate: (total > 0).then(|| failed_websites as f64 / total as f64),
+            html_bytes: stats().map(|website| website.html_bytes).collect(),
+            elements: stats().map(|website| website.elements as u64).collect(),
+            stylesheets: stats().map(|website| website.stylesheets as u64).collect(),
+            stylesheet_bytes: stats().map(|website| website.stylesheet_bytes as u64).collect(),
+            stylesheet_failure_rate: (all_stylesheets > 0)
+                .then(|| failed_stylesheets as f64 / all_stylesheets as f64),
+            selectors: stats().map(|website| website.selectors as u64).collect(),
+            selectors_by_kind,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::CorpusStats;
+    use crate::analysis::complexity::RightmostKind;
+    use crate::parse::{ParseOptions, get_websites_dirs};
+    use test_log::test;
+
+    #[test]
+    fn counts_without_matching() -> crate::result::Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let good = dir.path().join("good");
+        fs::create_dir(&good).unwrap();
+        fs::write(
+            good.join("index.html"),
+            "<link rel='stylesheet' href='a.css'><link rel='stylesheet' href='missing.css'><style>p {}</style><p></p>",
+        ).unwrap();
+        let a_css = ".a, #b > p {} p { color: } * {} \
+                     @font-face { font-family: Inter; src: url(inter.woff2) } \
+                     @media print { @font-face { font-family: 'Print Serif'; src: url(a.woff) } } \
+                     @keyframes spin { to { rotate: 1turn } }";
+        fs::write(good.join("a.css"), a_css).unwrap();
+        let bad = dir.path().join("bad");
+        fs::create_dir(&bad).unwrap();
+        fs::write(bad.join("one.html"), "").unwrap();
+        fs::write(bad.join("two.html"), "").unwrap();
+
+        let stats = CorpusStats::new(get_websites_dirs(dir.path())?, &ParseOptions::default())?;
+        let good = &stats.websites["good"];
+        // html, head, link, link, style, body, p
+        assert_eq!(good.elements, 7);
+        assert_eq!((good.stylesheets, good.unreadable_stylesheets, good.stylesheets_with_errors), (2, 1, 1));
+        assert_eq!(good.stylesheet_bytes, "p {}".len() + a_css.len());
+        assert_eq!(good.selectors, 5);
+        assert_eq!(good.selectors_by_kind[&RightmostKind::Type], 3);
+        assert_eq!(good.selectors_by_kind[&RightmostKind::Universal], 1);
+        assert!(good.html_bytes > 0);
+        assert_eq!(good.at_rules.len(), 1);
+        assert_eq!(good.at_rules[0].font_families, ["Inter".to_owned(), "Print Serif".to_owned()].into());
+        assert_eq!(good.at_rules[0].keyframes, ["spin".to_owned()].into());
+        assert_eq!(stats.failed.len(), 1);
+        assert_eq!(stats.aggregate.websites, 2);
+        assert_eq!(stats.aggregate.website_failure_rate, Some(0.5));
+        assert_eq!(stats.aggregate.stylesheet_failure_rate, Some(2.0 / 3.0));
+        Ok(())
+    }
+}
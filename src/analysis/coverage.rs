@@ -0,0 +1,143 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! How much of each stylesheet is used: the fraction of its style rules with
+//! a selector that matched at least one element. Rules inside `@media` rules
+//! that don't apply to the device count as unused.
+
+use std::collections::HashSet;
+
+use cssparser::ToCss as _;
+use serde::Serialize;
+use style::stylesheets::{AllRules, CssRule, EffectiveRules, StylesheetInDocument as _};
+
+use crate::parse::ParsedWebsite;
+use crate::structs::set::SetDocumentMatches;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StylesheetCoverage {
+    pub stylesheet: String,
+    /// Style rules, including nested ones
+    pub rules: usize,
+    pub used_rules: usize,
+    /// `used_rules / rules`, or `None` for a stylesheet without style rules
+    pub coverage: Option<f64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct CoverageReport {
+    pub stylesheets: Vec<StylesheetCoverage>,
+    /// Stylesheets with style rules, none of which were used
+    pub dead_stylesheets: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct CoverageSummary {
+    pub stylesheets: usize,
+    pub dead_stylesheets: usize,
+    pub rules: usize,
+    pub used_rules: usize,
+    pub coverage: Option<f64>,
+}
+
+impl CoverageReport {
+    /// Computes coverage of `website`'s stylesheets from `matches`, the
+    /// result of matching it.
+    pub fn new(website: &ParsedWebsite, matches: &SetDocumentMatches) -> Self {
+        let matched: HashSet<&str> = matches
+            .0
+            .keys()
+            .flat_map(|&id| matches.find_selectors(id))
+            .map(|selector| &**selector)
+            .collect();
+        let device = website.device().stylo_device();
+        let guard = website.stylesheet_lock().read();
+        let mut report = Self::default();
+        for (name, stylesheet) in website.stylesheet_names().iter().zip(website.stylesheets()) {
+            let rules = stylesheet
+                .iter_rules::<AllRules>(&device, &guard)
+                .filter(|rule| matches!(rule, CssRule::Style(_)))
+                .count();
+            let mut used_rules = 0;
+            // Matches of the same selector text elsewhere don't count for a rule
+            // that doesn't apply
+            for rule in stylesheet.iter_rules::<EffectiveRules>(&device, &guard) {
+                let CssRule::Style(style_rule) = rule else {
+                    continue;
+                };
+                let used = style_rule
+                    .read_with(&guard)
+                    .selectors
+                    .slice()
+                    .iter()
+                    .any(|selector| matched.contains(selector.to_css_string().as_str()));
+                used_rules += usize::from(used);
+            }
+            if rules > 0 && used_rules == 0 {
+                report.dead_stylesheets.push(name.clone());
+            }
+            report.stylesheets.push(StylesheetCoverage {
+                stylesheet: name.clone(),
+                rules,
+                used_rules,
+                coverage: fraction(used_rules, rules),
+            });
+        }
+        report
+    }
+}
+
+impl<'a> FromIterator<&'a CoverageReport> for CoverageSummary {
+    fn from_iter<T: IntoIterator<Item = &'a CoverageReport>>(iter: T) -> Self {
+        let mut summary = Self::default();
+        for report in iter {
+            summary.stylesheets += report.stylesheets.len();
+            summary.dead_stylesheets += report.dead_stylesheets.len();
+            for stylesheet in &report.stylesheets {
+                summary.rules += stylesheet.rules;
+                summary.used_rules += stylesheet.used_rules;
+            }
+        }
+        summary.coverage = fraction(summary.used_rules, summary.rules);
+        summary
+    }
+}
+
+fn fraction(numerator: usize, denominator: usize) -> Option<f64> {
+    (denominator > 0).then(|| numerator as f64 / denominator as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CoverageReport, CoverageSummary};
+    use crate::parse::{ParsedWebsite, parse_stylesheet};
+    use crate::{Algorithm, do_website};
+    use scraper::Html;
+    use style::shared_lock::SharedRwLock;
+    use style::stylesheets::UrlExtraData;
+    use test_log::test;
+
+    #[test]
+    fn counts_used_rules_per_stylesheet() {
+        let lock = SharedRwLock::new();
+        let url_data = || UrlExtraData::from(url::Url::parse("about:blank").unwrap());
+        let used = parse_stylesheet("p {} .missing, div {} span {} @media print { p {} }", url_data(), &lock).unwrap();
+        let dead = parse_stylesheet("table {} .nope {}", url_data(), &lock).unwrap();
+        let empty = parse_stylesheet("@font-face { font-family: x; src: url(x.woff) }", url_data(), &lock).unwrap();
+        let document = Html::parse_document("<div><p></p></div>");
+        let website = ParsedWebsite::new("test".to_owned(), document, vec![used, dead, empty], lock)
+            .with_stylesheet_names(vec!["used.css".to_owned(), "dead.css".to_owned(), "fonts.css".to_owned()]);
+        let (_, matches, _) = do_website(&website, Algorithm::Naive, None);
+        let report = CoverageReport::new(&website, &matches);
+        let rules: Vec<_> = report.stylesheets.iter().map(|s| (s.rules, s.used_rules)).collect();
+        // `p` matched, but the print rule doesn't apply
+        assert_eq!(rules, [(4, 2), (2, 0), (0, 0)]);
+        assert_eq!(report.stylesheets[2].coverage, None);
+        assert_eq!(report.dead_stylesheets, ["dead.css"]);
+        let summary: CoverageSummary = [&report].into_iter().collect();
+        assert_eq!((summary.rules, summary.used_rules), (6, 2));
+    }
+}
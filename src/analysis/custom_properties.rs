@@ -0,0 +1,191 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Custom properties (`--name: value`): where the style rules of a website's
+//! stylesheets define them, where they reference them with `var()`, and
+//! which references have no definition. Rules in `@media` rules that don't
+//! apply count too, as definitions that only apply sometimes are still
+//! definitions.
+//!
+//! Definitions are the custom declarations of each rule's declaration block,
+//! and references the `var()`s in its unparsed values, so `var()`s in
+//! shorthands and in other custom properties are found too. Inline `style`
+//! attributes aren't looked at, so a reference can be
+//! `undefined` here and still get a value from one, or from its fallback.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use cssparser::{ParseError, Parser, ParserInput, Token};
+use serde::Serialize;
+use style::properties::{CustomDeclarationValue, PropertyDeclaration};
+use style::stylesheets::{AllRules, CssRule, StylesheetInDocument as _};
+
+use crate::parse::ParsedWebsite;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct RuleLocation {
+    pub stylesheet: String,
+    /// Of the rule, 1-based
+    pub line: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct CustomPropertiesReport {
+    /// By name, in stylesheet order
+    pub defined: BTreeMap<String, Vec<RuleLocation>>,
+    /// By name, in stylesheet order. A rule referencing a property more than
+    /// once is listed once.
+    pub referenced: BTreeMap<String, Vec<RuleLocation>>,
+    /// Referenced, but defined in no rule
+    pub undefined: BTreeSet<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct CustomPropertiesSummary {
+    /// Distinct within each website, summed
+    pub defined: usize,
+    pub referenced: usize,
+    pub undefined: usize,
+    pub websites_with_undefined: usize,
+}
+
+impl CustomPropertiesReport {
+    pub fn new(website: &ParsedWebsite) -> Self {
+        // `AllRules` doesn't evaluate media queries, so the device only has to
+        // be there
+        let device = website.device().stylo_device();
+        let guard = website.stylesheet_lock().read();
+        let mut report = Self::default();
+        for (name, stylesheet) in website.stylesheet_names().iter().zip(website.stylesheets()) {
+            for rule in stylesheet.iter_rules::<AllRules>(&device, &guard) {
+                let CssRule::Style(style_rule) = rule else {
+                    continue;
+                };
+                let style_rule = style_rule.read_with(&guard);
+                // stylo's lines are 0-based
+                let line = style_rule.source_location.line + 1;
+                let location = || RuleLocation { stylesheet: name.clone(), line };
+                let mut references = BTreeSet::new();
+                for declaration in style_rule.block.read_with(&guard).declarations() {
+                    if let PropertyDeclaration::Custom(custom) = declaration {
+                        report.defined.entry(format!("--{}", custom.name)).or_default().push(location());
+                    }
+                    if let Some(css) = unparsed_value(declaration) {
+                        let mut input = ParserInput::new(&css);
+                        find_references(&mut Parser::new(&mut input), &mut references);
+                    }
+                }
+                for property in references {
+                    report.referenced.entry(property).or_default().push(location());
+                }
+            }
+        }
+        report.undefined = report
+            .referenced
+            .keys()
+            .filter(|property| !report.defined.contains_key(*property))
+            .cloned()
+            .collect();
+        report
+    }
+}
+
+impl<'a> FromIterator<&'a CustomPropertiesReport> for CustomPropertiesSummary {
+    fn from_iter<T: IntoIterator<Item = &'a CustomPropertiesReport>>(iter: T) -> Self {
+        let mut summary = Self::default();
+        for report in iter {
+            summary.defined += report.defined.len();
+            summary.referenced += report.referenced.len();
+            summary.undefined += report.undefined.len();
+            summary.websites_with_undefined += usize::from(!report.undefined.is_empty());
+        }
+        summary
+    }
+}
+
+/// The value of `declaration` as written, if it may reference custom
+/// properties
+fn unparsed_value(declaration: &PropertyDeclaration) -> Option<String> {
+    match declaration {
+        PropertyDeclaration::Custom(custom) => match &custom.value {
+            CustomDeclarationValue::Unparsed(value) => Some(value.css.clone()),
+            _ => None,
+        },
+        PropertyDeclaration::WithVariables(variables) => {
+            // A longhand expanded from a shorthand has no value of its own:
+            // the shorthand's is on each of its longhands
+            if let Some(css) = variables.id.shorthands().find_map(|shorthand| declaration.with_variables_from_shorthand(shorthand)) {
+                return Some(css.to_owned());
+            }
+            let mut css = String::new();
+            declaration.to_css(&mut css).ok()?;
+            Some(css)
+        }
+        _ => None,
+    }
+}
+
+/// Adds the custom properties referenced with `var()` in `parser`, at any
+/// depth, to `references`
+fn find_references(parser: &mut Parser, references: &mut BTreeSet<String>) {
+    loop {
+        match parser.next().cloned() {
+            Ok(Token::Function(name)) if name.eq_ignore_ascii_case("var") => {
+                let _ = parser.parse_nested_block(|block| {
+                    if let Ok(Token::Ident(property)) = block.next().cloned()
+                        && property.starts_with("--")
+                    {
+                        references.insert(property.to_string());
+                    }
+                    // The fallback may reference others
+                    find_references(block, references);
+                    Ok::<_, ParseError<()>>(())
+                });
+            }
+            Ok(Token::CurlyBracketBlock | Token::ParenthesisBlock | Token::SquareBracketBlock | Token::Function(_)) => {
+                let _ = parser.parse_nested_block(|block| {
+                    find_references(block, references);
+                    Ok::<_, ParseError<()>>(())
+                });
+            }
+            Ok(_) => (),
+            Err(_) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{CustomPropertiesReport, CustomPropertiesSummary};
+    use crate::parse::get_document_and_selectors;
+    use test_log::test;
+
+    #[test]
+    fn finds_definitions_and_references() -> crate::result::Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("index.html"),
+            "<link rel='stylesheet' href='a.css'><style>p { margin: var(--gap) VAR( --missing, 1px) }</style>",
+        ).unwrap();
+        fs::write(
+            dir.path().join("a.css"),
+            ":root { --gap: 4px; --accent: var(--brand, red) }\n@media print { :root { --gap: 0 } }\n.a { color: var(--accent); border: 1px solid var(--accent) }",
+        ).unwrap();
+        let website = get_document_and_selectors(dir.path())?.unwrap();
+        let report = CustomPropertiesReport::new(&website);
+        let lines = |locations: &[super::RuleLocation]| -> Vec<u32> { locations.iter().map(|l| l.line).collect() };
+        assert_eq!(report.defined.keys().collect::<Vec<_>>(), ["--accent", "--gap"]);
+        assert_eq!(lines(&report.defined["--gap"]), [1, 2]);
+        assert_eq!(report.referenced.keys().collect::<Vec<_>>(), ["--accent", "--brand", "--gap", "--missing"]);
+        assert_eq!(lines(&report.referenced["--accent"]), [3]);
+        assert_eq!(report.undefined, ["--brand".to_owned(), "--missing".to_owned()].into());
+        let summary: CustomPropertiesSummary = [&report, &report].into_iter().collect();
+        assert_eq!((summary.defined, summary.undefined, summary.websites_with_undefined), (4, 4, 2));
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Selectors written more than once, as when a website includes the same
+//! vendor CSS twice or a framework repeats a reset. Selectors are the same
+//! when they serialize the same, and `--dedup-selectors` (see
+//! `ParsedWebsite::with_dedup_selectors`) matches each of them once.
+//!
+//! Whole rules are compared too: a rule whose selectors and declarations
+//! serialize the same as a rule of another stylesheet of the website is
+//! duplicated, as bundlers do when two entry points include the same CSS.
+//!
+//! Rules inside `@media` rules that don't apply to the device are left out,
+//! as in `provenance`.
+
+use std::collections::BTreeMap;
+
+use cssparser::ToCss as _;
+use serde::Serialize;
+use style::shared_lock::ToCssWithGuard as _;
+use style::stylesheets::{CssRule, EffectiveRules, StylesheetInDocument as _};
+
+use crate::parse::ParsedWebsite;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SelectorLocation {
+    pub stylesheet: String,
+    /// Of the selector's rule, 1-based
+    pub line: u32,
+    /// Of the selector's rule, 1-based
+    pub column: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DuplicatedSelector {
+    pub selector: String,
+    /// How many times it was written, `provenance.len()`
+    pub multiplicity: usize,
+    /// In stylesheet order
+    pub provenance: Vec<SelectorLocation>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DuplicatedRule {
+    /// As stylo serializes it
+    pub rule: String,
+    /// The stylesheets it is in
+    pub stylesheets: usize,
+    /// Each time it was written, in stylesheet order
+    pub provenance: Vec<SelectorLocation>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct DedupReport {
+    /// Selectors of style rules, counting each time one is written
+    pub selectors: usize,
+    pub unique: usize,
+    /// `unique / selectors`, or `None` without selectors
+    pub ratio: Option<f64>,
+    /// The selectors written more than once, most written first
+    pub duplicated: Vec<DuplicatedSelector>,
+    /// The rules in more than one stylesheet, in most stylesheets first
+    pub duplicated_rules: Vec<DuplicatedRule>,
+    /// The serialized size of the duplicated rules beyond their first time
+    pub duplicated_rule_bytes: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct DedupSummary {
+    pub selectors: usize,
+    /// Unique within each website, summed
+    pub unique: usize,
+    pub ratio: Option<f64>,
+    pub duplicated_rules: usize,
+    pub duplicated_rule_bytes: usize,
+}
+
+impl DedupReport {
+    pub fn new(website: &ParsedWebsite) -> Self {
+        let device = website.device().stylo_device();
+        let guard = website.stylesheet_lock().read();
+        let mut selectors = 0;
+        // In order of first appearance, for a stable sort
+        let mut locations: Vec<(String, Vec<SelectorLocation>)> = Vec::new();
+        let mut indices = BTreeMap::new();
+        // The same for whole rules, by their serialization, with the
+        // stylesheet each location is in
+        let mut rule_locations: Vec<(String, Vec<(usize, SelectorLocation)>)> = Vec::new();
+        let mut rule_indices = BTreeMap::new();
+        for (stylesheet_index, (name, stylesheet)) in website.stylesheet_names().iter().zip(website.stylesheets()).enumerate() {
+            for rule in stylesheet.iter_rules::<EffectiveRules>(&device, &guard) {
+                let CssRule::Style(style_rule) = rule else {
+                    continue;
+                };
+                let style_rule = style_rule.read_with(&guard);
+                // stylo's lines are 0-based
+                let (line, column) = (style_rule.source_location.line + 1, style_rule.source_location.column);
+                let css = rule.to_css_string(&guard).to_string();
+                let i = *rule_indices.entry(css.clone()).or_insert_with(|| {
+                    rule_locations.push((css, Vec::new()));
+                    rule_locations.len() - 1
+                });
+                rule_locations[i].1.push((stylesheet_index, SelectorLocation { stylesheet: name.clone(), line, column }));
+                for selector in style_rule.selectors.slice() {
+                    selectors += 1;
+                    let selector = selector.to_css_string();
+                    let i = *indices.entry(selector.clone()).or_insert_with(|| {
+                        locations.push((selector, Vec::new()));
+                        locations.len() - 1
+                    });
+                    locations[i].1.push(SelectorLocation { stylesheet: name.clone(), line, column });
+                }
+            }
+        }
+        let unique = locations.len();
+        let mut duplicated: Vec<_> = locations
+            .into_iter()
+            .filter(|(_, provenance)| provenance.len() > 1)
+            .map(|(selector, provenance)| DuplicatedSelector { selector, multiplicity: provenance.len(), provenance })
+            .collect();
+        duplicated.sort_by_key(|duplicated| std::cmp::Reverse(duplicated.multiplicity));
+        let mut duplicated_rule_bytes = 0;
+        let mut duplicated_rules: Vec<_> = rule_locations
+            .into_iter()
+            .filter_map(|(rule, locations)| {
+                let mut stylesheets: Vec<_> = locations.iter().map(|(stylesheet, _)| *stylesheet).collect();
+                stylesheets.dedup();
+                if stylesheets.len() < 2 {
+                    return None;
+                }
+                duplicated_rule_bytes += rule.len() * (locations.len() - 1);
+                let provenance = locations.into_iter().map(|(_, location)| location).collect();
+                Some(DuplicatedRule { rule, stylesheets: stylesheets.len(), provenance })
+            })
+            .collect();
+        duplicated_rules.sort_by_key(|duplicated| std::cmp::Reverse(duplicated.stylesheets));
+        Self {
+            selectors,
+            unique,
+            ratio: ratio(unique, selectors),
+            duplicated,
+            duplicated_rules,
+            duplicated_rule_bytes,
+        }
+    }
+}
+
+fn ratio(unique: usize, selectors: usize) -> Option<f64> {
+    (selectors > 0).then(|| unique as f64 / selectors as f64)
+}
+
+impl<'a> FromIterator<&'a DedupReport> for DedupSummary {
+    fn from_iter<T: IntoIterator<Item = &'a DedupReport>>(iter: T) -> Self {
+        let mut summary = Self::default();
+        for report in iter {
+            summary.selectors += report.selectors;
+            summary.unique += report.unique;
+            summary.duplicated_rules += report.duplicated_rules.len();
+            summary.duplicated_rule_bytes += report.duplicated_rule_bytes;
+        }
+        summary.ratio = ratio(summary.unique, summary.selectors);
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{DedupReport, DedupSummary};
+    use crate::parse::get_document_and_selectors;
+    use crate::{Algorithm, do_website};
+    use test_log::test;
+
+    #[test]
+    fn counts_selectors_written_more_than_once() -> crate::result::Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("index.html"),
+            "<link rel='stylesheet' href='a.css'><link rel='stylesheet' href='b.css'><p class='a'></p>",
+        ).unwrap();
+        fs::write(dir.path().join("a.css"), ".a, p {}\n.a { color: red }\n@media print { .a {} }").unwrap();
+        fs::write(dir.path().join("b.css"), "p {}\n\n.a { color:red; }\ndiv {}").unwrap();
+        let website = get_document_and_selectors(dir.path())?.unwrap();
+        let report = DedupReport::new(&website);
+        assert_eq!((report.selectors, report.unique), (6, 3));
+        assert_eq!(report.ratio, Some(0.5));
+        let duplicated: Vec<_> = report
+            .duplicated
+            .iter()
+            .map(|duplicated| {
+                let provenance: Vec<_> = duplicated.provenance.iter().map(|l| (l.stylesheet.as_str(), l.line)).collect();
+                (duplicated.selector.as_str(), duplicated.multiplicity, provenance)
+            })
+            .collect();
+        assert_eq!(duplicated, [
+            (".a", 3, vec![("a.css", 1), ("a.css", 2), ("b.css", 3)]),
+            ("p", 2, vec![("a.css", 1), ("b.css", 1)]),
+        ]);
+        let rules: Vec<_> = report
+            .duplicated_rules
+            .iter()
+            .map(|duplicated| {
+                let provenance: Vec<_> = duplicated.provenance.iter().map(|l| (l.stylesheet.as_str(), l.line)).collect();
+                (duplicated.rule.as_str(), duplicated.stylesheets, provenance)
+            })
+            .collect();
+        assert_eq!(rules, [(".a { color: red; }", 2, vec![("a.css", 2), ("b.css", 3)])]);
+        assert_eq!(report.duplicated_rule_bytes, ".a { color: red; }".len());
+        let summary: DedupSummary = [&report, &report].into_iter().collect();
+        assert_eq!((summary.selectors, summary.unique, summary.ratio), (12, 6, Some(0.5)));
+        assert_eq!(summary.duplicated_rules, 2);
+
+        let deduplicated = get_document_and_selectors(dir.path())?.unwrap().with_dedup_selectors(true);
+        for algorithm in [Algorithm::Naive, Algorithm::WithStyleSharing, Algorithm::WithDistribution] {
+            assert_eq!(do_website(&deduplicated, algorithm, None).1, do_website(&website, algorithm, None).1);
+        }
+        Ok(())
+    }
+}
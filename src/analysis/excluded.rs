@@ -0,0 +1,151 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Selectors that can never match, because a compound of theirs needs a
+//! pseudo-class that depends on state the document doesn't have (`:hover`,
+//! `:focus`, ...; see `scraper::element_ref::can_match_non_ts_pseudo_class`).
+//!
+//! They still take part in matching, and are what every algorithm spends on
+//! CSS that can't apply. Pseudo-classes under `:not()`, `:is()` and the like
+//! don't exclude a selector, since they can still match around one that
+//! never does.
+
+use std::collections::BTreeMap;
+
+use cssparser::ToCss as _;
+use selectors::parser::Component;
+use serde::Serialize;
+use style::stylesheets::{AllRules, CssRule, StylesheetInDocument as _};
+
+use crate::parse::ParsedWebsite;
+use crate::structs::Selector;
+
+/// How many excluded selectors each stylesheet lists
+pub const SAMPLE_SIZE: usize = 5;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct StylesheetExclusions {
+    pub stylesheet: String,
+    /// Selectors of style rules, including nested ones
+    pub selectors: usize,
+    pub excluded: usize,
+    /// Excluded selectors by the pseudo-classes that excluded them. A
+    /// selector counts toward each one it has.
+    pub by_pseudo_class: BTreeMap<String, usize>,
+    /// The first few excluded selectors
+    pub sample: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ExcludedReport {
+    pub stylesheets: Vec<StylesheetExclusions>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ExcludedSummary {
+    pub selectors: usize,
+    pub excluded: usize,
+    /// `excluded / selectors`, or `None` without selectors
+    pub fraction: Option<f64>,
+    pub by_pseudo_class: BTreeMap<String, usize>,
+}
+
+impl ExcludedReport {
+    pub fn new(website: &ParsedWebsite) -> Self {
+        let device = website.device().stylo_device();
+        let guard = website.stylesheet_lock().read();
+        let mut report = Self::default();
+        for (name, stylesheet) in website.stylesheet_names().iter().zip(website.stylesheets()) {
+            let mut exclusions = StylesheetExclusions { stylesheet: name.clone(), ..Default::default() };
+            for rule in stylesheet.iter_rules::<AllRules>(&device, &guard) {
+                let CssRule::Style(style_rule) = rule else {
+                    continue;
+                };
+                for selector in style_rule.read_with(&guard).selectors.slice() {
+                    exclusions.selectors += 1;
+                    let pseudo_classes = excluding_pseudo_classes(selector);
+                    if pseudo_classes.is_empty() {
+                        continue;
+                    }
+                    exclusions.excluded += 1;
+                    for pseudo_class in pseudo_classes {
+                        *exclusions.by_pseudo_class.entry(pseudo_class).or_default() += 1;
+                    }
+                    if exclusions.sample.len() < SAMPLE_SIZE {
+                        exclusions.sample.push(selector.to_css_string());
+                    }
+                }
+            }
+            report.stylesheets.push(exclusions);
+        }
+        report
+    }
+}
+
+/// The pseudo-classes in `selector`'s own compounds that never match, e.g.
+/// `:hover`, each once
+pub fn excluding_pseudo_classes(selector: &Selector) -> Vec<String> {
+    let mut pseudo_classes = Vec::new();
+    for component in selector.iter_raw_match_order() {
+        if let Component::NonTSPseudoClass(pc) = component
+            && !scraper::element_ref::can_match_non_ts_pseudo_class(pc)
+        {
+            let name = pc.to_css_string();
+            if !pseudo_classes.contains(&name) {
+                pseudo_classes.push(name);
+            }
+        }
+    }
+    pseudo_classes
+}
+
+impl<'a> FromIterator<&'a ExcludedReport> for ExcludedSummary {
+    fn from_iter<T: IntoIterator<Item = &'a ExcludedReport>>(iter: T) -> Self {
+        let mut summary = Self::default();
+        for stylesheet in iter.into_iter().flat_map(|report| &report.stylesheets) {
+            summary.selectors += stylesheet.selectors;
+            summary.excluded += stylesheet.excluded;
+            for (pseudo_class, count) in &stylesheet.by_pseudo_class {
+                *summary.by_pseudo_class.entry(pseudo_class.clone()).or_default() += count;
+            }
+        }
+        summary.fraction = (summary.selectors > 0).then(|| summary.excluded as f64 / summary.selectors as f64);
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExcludedReport, ExcludedSummary};
+    use crate::parse::{ParsedWebsite, parse_stylesheet};
+    use scraper::Html;
+    use style::shared_lock::SharedRwLock;
+    use style::stylesheets::UrlExtraData;
+    use test_log::test;
+
+    #[test]
+    fn counts_excluded_selectors_per_stylesheet() {
+        let lock = SharedRwLock::new();
+        let url_data = || UrlExtraData::from(url::Url::parse("about:blank").unwrap());
+        let a = parse_stylesheet(
+            "a:hover, a:focus:hover {} p:not(:hover) {} input:checked {} @media print { b:active {} }",
+            url_data(),
+            &lock,
+        ).unwrap();
+        let b = parse_stylesheet("div {}", url_data(), &lock).unwrap();
+        let website = ParsedWebsite::new("test".to_owned(), Html::parse_document(""), vec![a, b], lock)
+            .with_stylesheet_names(vec!["a.css".to_owned(), "b.css".to_owned()]);
+        let report = ExcludedReport::new(&website);
+        let a = &report.stylesheets[0];
+        assert_eq!((a.selectors, a.excluded), (5, 3));
+        assert_eq!(a.by_pseudo_class.get(":hover"), Some(&2));
+        assert_eq!(a.by_pseudo_class.get(":active"), Some(&1));
+        assert_eq!(a.sample, ["a:hover", "a:focus:hover", "b:active"]);
+        assert_eq!(report.stylesheets[1].excluded, 0);
+        let summary: ExcludedSummary = [&report].into_iter().collect();
+        assert_eq!((summary.selectors, summary.excluded), (6, 3));
+    }
+}
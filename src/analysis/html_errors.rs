@@ -0,0 +1,72 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The errors html5ever recovered from while parsing each document. Matches
+//! against a document with many of them come from a DOM the parser had to
+//! guess at, which a browser may have guessed differently.
+
+use serde::Serialize;
+use scraper::Html;
+
+/// How many of a document's error messages are kept
+pub const FIRST_ERRORS: usize = 5;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct HtmlErrorReport {
+    pub count: usize,
+    /// The first `FIRST_ERRORS` messages, in document order
+    pub first: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct HtmlErrorSummary {
+    pub websites: usize,
+    pub websites_with_errors: usize,
+    pub errors: usize,
+}
+
+impl HtmlErrorReport {
+    pub fn new(document: &Html) -> Self {
+        Self {
+            count: document.errors.len(),
+            first: document.errors.iter().take(FIRST_ERRORS).map(|error| error.to_string()).collect(),
+        }
+    }
+}
+
+impl<'a> FromIterator<&'a HtmlErrorReport> for HtmlErrorSummary {
+    fn from_iter<T: IntoIterator<Item = &'a HtmlErrorReport>>(iter: T) -> Self {
+        let mut summary = Self::default();
+        for report in iter {
+            summary.websites += 1;
+            if report.count > 0 {
+                summary.websites_with_errors += 1;
+            }
+            summary.errors += report.count;
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FIRST_ERRORS, HtmlErrorReport, HtmlErrorSummary};
+    use scraper::Html;
+    use test_log::test;
+
+    #[test]
+    fn counts_recovered_errors() {
+        let clean = HtmlErrorReport::new(&Html::parse_document("<!DOCTYPE html><html><head></head><body><p>x</p></body></html>"));
+        assert_eq!(clean, HtmlErrorReport::default());
+        let messy = HtmlErrorReport::new(&Html::parse_document(
+            &"<table><p></i></b><tr></table></div>".repeat(4),
+        ));
+        assert!(messy.count > FIRST_ERRORS);
+        assert_eq!(messy.first.len(), FIRST_ERRORS);
+        let summary: HtmlErrorSummary = [&clean, &messy].into_iter().collect();
+        assert_eq!(summary, HtmlErrorSummary { websites: 2, websites_with_errors: 1, errors: messy.count });
+    }
+}
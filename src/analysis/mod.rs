@@ -0,0 +1,19 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Reports characterizing a corpus's selectors and matches.
+
+pub mod buckets;
+pub mod complexity;
+pub mod corpus;
+pub mod coverage;
+pub mod custom_properties;
+pub mod dedup;
+pub mod excluded;
+pub mod html_errors;
+pub mod provenance;
+pub mod reach;
+pub mod unmatched;
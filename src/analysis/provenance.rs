@@ -0,0 +1,226 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Where each matched selector was written: its stylesheet and the line of
+//! its rule and, when the stylesheet has a `sourceMappingURL` comment and the
+//! map is present locally, the SCSS or LESS file and line it was compiled
+//! from.
+//!
+//! Positions are those of the CSS Stylo parsed, which has the same lines as
+//! the file but can have different columns on lines that `--media-feature`
+//! or `--forgiving-selector-lists` rewrote. Rules inside `@media` rules that
+//! don't apply to the device are left out, as in `coverage`.
+
+use std::collections::HashSet;
+
+use cssparser::ToCss as _;
+use log::warn;
+use serde::Serialize;
+use style::stylesheets::{CssRule, EffectiveRules, StylesheetInDocument as _};
+
+use crate::parse::{ParsedWebsite, StylesheetKind};
+use crate::selector_normalization;
+use crate::source_map::{OriginalLocation, SourceMap};
+use crate::structs::set::SetDocumentMatches;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SelectorProvenance {
+    /// As in the matches, so canonical with
+    /// `ParsedWebsite::with_normalized_selectors`
+    pub selector: String,
+    /// The selector as the stylesheet has it (as Stylo serializes it), when
+    /// that isn't `selector`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub written: Option<String>,
+    pub stylesheet: String,
+    /// How the document includes the stylesheet, so preloaded and alternate
+    /// stylesheets can be told apart
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<StylesheetKind>,
+    /// Of the selector's rule, 1-based
+    pub line: u32,
+    /// Of the selector's rule, 1-based
+    pub column: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original: Option<OriginalLocation>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ProvenanceReport {
+    /// In stylesheet order. A selector is listed once for each rule it
+    /// matched in.
+    pub selectors: Vec<SelectorProvenance>,
+    /// Stylesheets whose source map was read
+    pub source_mapped_stylesheets: Vec<String>,
+    /// Stylesheets with a `sourceMappingURL` to a local map that isn't there
+    /// or couldn't be read
+    pub missing_source_maps: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ProvenanceSummary {
+    pub selectors: usize,
+    /// Selectors resolved to an original source
+    pub resolved: usize,
+    pub source_mapped_stylesheets: usize,
+    pub missing_source_maps: usize,
+}
+
+impl ProvenanceReport {
+    /// Finds where the selectors in `matches`, the result of matching
+    /// `website`, came from.
+    pub fn new(website: &ParsedWebsite, matches: &SetDocumentMatches) -> Self {
+        let matched: HashSet<&str> = matches
+            .0
+            .keys()
+            .flat_map(|&id| matches.find_selectors(id))
+            .map(|selector| &**selector)
+            .collect();
+        let device = website.device().stylo_device();
+        let guard = website.stylesheet_lock().read();
+        let mut report = Self::default();
+        let stylesheets = website
+            .stylesheet_names()
+            .iter()
+            .zip(website.stylesheets())
+            .zip(website.source_map_paths())
+            .zip(website.stylesheet_kinds());
+        for (((name, stylesheet), source_map_path), &kind) in stylesheets {
+            let source_map = source_map_path.as_ref().and_then(|path| {
+                if !path.is_file() {
+                    report.missing_source_maps.push(name.clone());
+                    return None;
+                }
+                match SourceMap::read(path) {
+                    Ok(source_map) => {
+                        report.source_mapped_stylesheets.push(name.clone());
+                        Some(source_map)
+                    }
+                    Err(e) => {
+                        warn!("error reading the source map of {name}: {e}. Skipping.");
+                        report.missing_source_maps.push(name.clone());
+                        None
+                    }
+                }
+            });
+            for rule in stylesheet.iter_rules::<EffectiveRules>(&device, &guard) {
+                let CssRule::Style(style_rule) = rule else {
+                    continue;
+                };
+                let style_rule = style_rule.read_with(&guard);
+                // stylo's lines are 0-based
+                let (line, column) = (style_rule.source_location.line + 1, style_rule.source_location.column);
+                for selector in style_rule.selectors.slice() {
+                    let written = selector.to_css_string();
+                    let (selector, written) = if website.normalize_selectors() {
+                        let canonical = selector_normalization::canonical_css(selector);
+                        let written = (written != canonical).then_some(written);
+                        (canonical, written)
+                    } else {
+                        (written, None)
+                    };
+                    if !matched.contains(selector.as_str()) {
+                        continue;
+                    }
+                    report.selectors.push(SelectorProvenance {
+                        selector,
+                        written,
+                        stylesheet: name.clone(),
+                        kind,
+                        line,
+                        column,
+                        original: source_map.as_ref().and_then(|source_map| source_map.lookup(line, column)),
+                    });
+                }
+            }
+        }
+        report
+    }
+}
+
+impl<'a> FromIterator<&'a ProvenanceReport> for ProvenanceSummary {
+    fn from_iter<T: IntoIterator<Item = &'a ProvenanceReport>>(iter: T) -> Self {
+        let mut summary = Self::default();
+        for report in iter {
+            summary.selectors += report.selectors.len();
+            summary.resolved += report.selectors.iter().filter(|selector| selector.original.is_some()).count();
+            summary.source_mapped_stylesheets += report.source_mapped_stylesheets.len();
+            summary.missing_source_maps += report.missing_source_maps.len();
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{ProvenanceReport, ProvenanceSummary};
+    use crate::parse::{StylesheetKind, get_document_and_selectors};
+    use crate::source_map::OriginalLocation;
+    use crate::{Algorithm, do_website};
+    use test_log::test;
+
+    #[test]
+    fn resolves_through_local_source_maps() -> crate::result::Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let website = dir.path().join("site");
+        fs::create_dir_all(website.join("css")).unwrap();
+        fs::write(
+            website.join("index.html"),
+            "<link rel='stylesheet' href='css/main.css'><link rel='preload' as='style' href='other.css'><p class='a'></p>",
+        ).unwrap();
+        // `.a` from main.scss line 3, `p` from line 7
+        fs::write(website.join("css/main.css"), ".a {}\np {}\n.unused {}\n/*# sourceMappingURL=main.css.map */\n").unwrap();
+        fs::write(
+            website.join("css/main.css.map"),
+            r#"{"version": 3, "sources": ["../scss/main.scss"], "mappings": "AAEA;AAIA"}"#,
+        ).unwrap();
+        fs::write(website.join("other.css"), "p {}\n/*# sourceMappingURL=other.css.map */").unwrap();
+        let parsed = get_document_and_selectors(&website)?.unwrap();
+        let (_, matches, _) = do_website(&parsed, Algorithm::Naive, None);
+        let report = ProvenanceReport::new(&parsed, &matches);
+        let selectors: Vec<_> = report
+            .selectors
+            .iter()
+            .map(|s| (s.selector.as_str(), s.stylesheet.as_str(), s.line, s.original.as_ref().map(|o| o.line)))
+            .collect();
+        assert_eq!(selectors, [
+            (".a", "css/main.css", 1, Some(3)),
+            ("p", "css/main.css", 2, Some(7)),
+            ("p", "other.css", 1, None),
+        ]);
+        assert_eq!(
+            report.selectors[0].original,
+            Some(OriginalLocation { source: "../scss/main.scss".to_owned(), line: 3, column: 1 }),
+        );
+        assert_eq!(report.selectors[2].kind, Some(StylesheetKind::Preload));
+        assert_eq!(report.source_mapped_stylesheets, ["css/main.css"]);
+        assert_eq!(report.missing_source_maps, ["other.css"]);
+        let summary: ProvenanceSummary = [&report].into_iter().collect();
+        assert_eq!((summary.selectors, summary.resolved), (3, 2));
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_selectors_as_written_when_normalizing() -> crate::result::Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("index.html"), "<link rel='stylesheet' href='a.css'><p title='t' class='a'></p>").unwrap();
+        fs::write(dir.path().join("a.css"), "P[title][class] {}
+p[class][title] {}
+.a {}").unwrap();
+        let parsed = get_document_and_selectors(dir.path())?.unwrap().with_normalized_selectors(true);
+        let (_, matches, _) = do_website(&parsed, Algorithm::Naive, None);
+        let report = ProvenanceReport::new(&parsed, &matches);
+        let selectors: Vec<_> = report.selectors.iter().map(|s| (s.selector.as_str(), s.written.as_deref(), s.line)).collect();
+        assert_eq!(selectors, [
+            ("p[class][title]", Some("P[title][class]"), 1),
+            ("p[class][title]", None, 2),
+            (".a", None, 3),
+        ]);
+        Ok(())
+    }
+}
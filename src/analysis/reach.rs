@@ -0,0 +1,107 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Selectors that match a large fraction of a document's elements, like `*`,
+//! `div` or `body *`. They dominate both the output and matching time, so
+//! they are often worth excluding or studying on their own.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+
+use crate::structs::set::SetDocumentMatches;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct WideSelector {
+    pub selector: String,
+    pub elements: usize,
+    /// `elements` over the document's element count
+    pub fraction: f64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ReachReport {
+    pub element_count: usize,
+    /// Widest first
+    pub selectors: Vec<WideSelector>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ReachSummary {
+    pub websites: usize,
+    /// Each wide selector, with the number of websites it was wide in
+    pub selectors: BTreeMap<String, usize>,
+}
+
+impl ReachReport {
+    /// Finds the selectors matching more than `threshold` (a fraction
+    /// between 0 and 1) of the elements in `matches`.
+    pub fn new(matches: &SetDocumentMatches, threshold: f64) -> Self {
+        let element_count = matches.0.len();
+        let mut elements_per_selector: HashMap<&str, usize> = HashMap::new();
+        for &id in matches.0.keys() {
+            for selector in matches.find_selectors(id) {
+                *elements_per_selector.entry(selector).or_default() += 1;
+            }
+        }
+        let mut selectors: Vec<_> = elements_per_selector
+            .into_iter()
+            .map(|(selector, elements)| WideSelector {
+                selector: selector.to_owned(),
+                elements,
+                fraction: elements as f64 / element_count as f64,
+            })
+            .filter(|wide| wide.fraction > threshold)
+            .collect();
+        selectors.sort_by(|a, b| b.elements.cmp(&a.elements).then_with(|| a.selector.cmp(&b.selector)));
+        Self { element_count, selectors }
+    }
+}
+
+impl<'a> FromIterator<&'a ReachReport> for ReachSummary {
+    fn from_iter<T: IntoIterator<Item = &'a ReachReport>>(iter: T) -> Self {
+        let mut summary = Self::default();
+        for report in iter {
+            summary.websites += 1;
+            for wide in &report.selectors {
+                *summary.selectors.entry(wide.selector.clone()).or_default() += 1;
+            }
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ReachReport, ReachSummary};
+    use crate::match_selectors;
+    use crate::structs::Selector;
+    use crate::structs::owned::OwnedDocumentMatches;
+    use crate::structs::set::SetDocumentMatches;
+    use scraper::Html;
+    use style::selector_parser::SelectorParser;
+    use style::stylesheets::UrlExtraData;
+    use test_log::test;
+
+    #[test]
+    fn finds_wide_selectors() {
+        let document = Html::parse_document("<div><p></p><p></p></div><div><span></span></div>");
+        let url_data = UrlExtraData::from(url::Url::parse("about:blank").unwrap());
+        let selectors: Vec<Selector> = ["*", "body *", "p", "span"]
+            .iter()
+            .map(|s| SelectorParser::parse_author_origin_no_namespace(s, &url_data).unwrap().slice()[0].clone())
+            .collect();
+        let matches = SetDocumentMatches::from(OwnedDocumentMatches::from(&match_selectors(&document, &selectors)));
+        // html, head, body, 2 divs, 2 ps and a span
+        let report = ReachReport::new(&matches, 0.5);
+        assert_eq!(report.element_count, 8);
+        let wide: Vec<_> = report.selectors.iter().map(|wide| (wide.selector.as_str(), wide.elements)).collect();
+        assert_eq!(wide, [("*", 8), ("body *", 5)]);
+        let summary: ReachSummary = [&report, &ReachReport::new(&matches, 0.9)].into_iter().collect();
+        assert_eq!(summary.websites, 2);
+        assert_eq!(summary.selectors, [("*".to_owned(), 2), ("body *".to_owned(), 1)].into());
+    }
+}
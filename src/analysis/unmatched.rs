@@ -0,0 +1,126 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Elements no selector matched, author or user-agent: the complement of
+//! `coverage`'s unused rules. Elements in `<head>` (`<meta>`, `<link>` and
+//! the like) rarely have styles, so they can be left out.
+
+use serde::Serialize;
+
+use crate::structs::set::SetDocumentMatches;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct UnmatchedElement {
+    /// See `ElementPath`
+    pub path: String,
+    pub html: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct UnmatchedReport {
+    /// Elements considered, so without those in `<head>` if they were left
+    /// out
+    pub elements: usize,
+    pub unmatched: usize,
+    /// `unmatched / elements`, or `None` without elements
+    pub fraction: Option<f64>,
+    /// In document order
+    pub unmatched_elements: Vec<UnmatchedElement>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct UnmatchedSummary {
+    pub elements: usize,
+    pub unmatched: usize,
+    pub fraction: Option<f64>,
+}
+
+impl UnmatchedReport {
+    /// Finds the elements of `matches` without matched selectors, leaving
+    /// out `<head>` and its descendants if `exclude_head`.
+    pub fn new(matches: &SetDocumentMatches, exclude_head: bool) -> Self {
+        let mut report = Self::default();
+        let mut unmatched = Vec::new();
+        for (&id, element_matches) in &matches.0 {
+            let element = &element_matches.element;
+            if exclude_head && element.path.0.iter().any(|step| step.name == "head") {
+                continue;
+            }
+            report.elements += 1;
+            if matches.find_selectors(id).is_empty() && element_matches.ua_selectors.is_empty() {
+                unmatched.push(element);
+            }
+        }
+        unmatched.sort_by(|a, b| a.path.cmp(&b.path));
+        report.unmatched = unmatched.len();
+        report.fraction = fraction(report.unmatched, report.elements);
+        report.unmatched_elements = unmatched
+            .into_iter()
+            .map(|element| UnmatchedElement {
+                path: element.path.to_string(),
+                html: element.html().to_owned(),
+                line: element.source_line,
+                column: element.source_column,
+            })
+            .collect();
+        report
+    }
+}
+
+impl<'a> FromIterator<&'a UnmatchedReport> for UnmatchedSummary {
+    fn from_iter<T: IntoIterator<Item = &'a UnmatchedReport>>(iter: T) -> Self {
+        let mut summary = Self::default();
+        for report in iter {
+            summary.elements += report.elements;
+            summary.unmatched += report.unmatched;
+        }
+        summary.fraction = fraction(summary.unmatched, summary.elements);
+        summary
+    }
+}
+
+fn fraction(numerator: usize, denominator: usize) -> Option<f64> {
+    (denominator > 0).then(|| numerator as f64 / denominator as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{UnmatchedReport, UnmatchedSummary};
+    use crate::match_selectors;
+    use crate::parse_selector_list;
+    use crate::structs::owned::OwnedDocumentMatches;
+    use crate::structs::set::SetDocumentMatches;
+    use scraper::Html;
+    use test_log::test;
+
+    #[test]
+    fn finds_elements_without_matches() {
+        let document = Html::parse_document("<meta charset='utf-8'><div><p></p><p class='a'></p></div><span></span>");
+        let selectors = parse_selector_list("div, .a, html").unwrap().slice().to_vec();
+        let matches = SetDocumentMatches::from(OwnedDocumentMatches::from(&match_selectors(&document, &selectors)));
+        let unmatched = |report: &UnmatchedReport| -> Vec<String> {
+            report.unmatched_elements.iter().map(|element| element.path.clone()).collect()
+        };
+        // html, head, meta, body, div, 2 ps and a span
+        let report = UnmatchedReport::new(&matches, false);
+        assert_eq!((report.elements, report.unmatched), (8, 5));
+        assert_eq!(unmatched(&report), [
+            "html[0]/head[0]",
+            "html[0]/head[0]/meta[0]",
+            "html[0]/body[1]",
+            "html[0]/body[1]/div[0]/p[0]",
+            "html[0]/body[1]/span[1]",
+        ]);
+        let without_head = UnmatchedReport::new(&matches, true);
+        assert_eq!((without_head.elements, without_head.unmatched), (6, 3));
+        let summary: UnmatchedSummary = [&report, &without_head].into_iter().collect();
+        assert_eq!((summary.elements, summary.unmatched, summary.fraction), (14, 8, Some(8.0 / 14.0)));
+    }
+}
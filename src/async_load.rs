@@ -0,0 +1,246 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Loading websites on a tokio runtime while the caller parses and matches
+//! the ones already loaded. `load_websites` reads each website's document
+//! and linked stylesheets from an `AsyncCorpusSource` on a background thread,
+//! up to `concurrency` websites at a time and in order, and the returned
+//! iterator parses them on the caller's thread as it is advanced.
+//!
+//! Parsing stays on the caller's thread because `ParsedWebsite` isn't `Send`.
+//! To know which stylesheets to read, the loader parses each document once
+//! more on a blocking task, so this only pays off when reading is slow, as it
+//! is over a network.
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::io;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, mpsc};
+
+use scraper::Html;
+
+use crate::corpus_source::{CorpusSource, FsSource};
+use crate::parse::{self, CssFile, HtmlFile, ParseOptions, ParsedWebsite, StylesheetLinks};
+use crate::result::{Error, ErrorKind, IntoResultExt as _, Result};
+
+/// Like `CorpusSource`, for sources whose reads are async
+pub trait AsyncCorpusSource: Send + Sync + 'static {
+    fn websites(&self) -> impl Future<Output = Result<Vec<String>>> + Send;
+
+    fn open_document(&self, website: &str, main_file: Option<&Path>) -> impl Future<Output = Result<Option<String>>> + Send;
+
+    fn open_stylesheet(&self, website: &str, href: &str) -> impl Future<Output = Result<String>> + Send;
+
+    fn website_dir(&self, _website: &str) -> Option<PathBuf> {
+        None
+    }
+}
+
+impl AsyncCorpusSource for FsSource {
+    async fn websites(&self) -> Result<Vec<String>> {
+        CorpusSource::websites(self)
+    }
+
+    async fn open_document(&self, website: &str, main_file: Option<&Path>) -> Result<Option<String>> {
+        let path = self.path(website)?.to_path_buf();
+        if !tokio::fs::metadata(&path).await.is_ok_and(|metadata| metadata.is_dir()) {
+            log::warn!("ignoring {} because it is not a directory", path.display());
+            return Ok(None);
+        }
+        let main_file = main_file.map(Path::to_path_buf);
+        let html_file = tokio::task::spawn_blocking(move || parse::main_html_file_with(&path, main_file.as_deref()))
+            .await
+            .map_err(join_error)??;
+        let Some(HtmlFile(html_path)) = html_file else {
+            return Ok(None);
+        };
+        tokio::fs::read_to_string(&html_path).await.into_result(Some(html_path)).map(Some)
+    }
+
+    async fn open_stylesheet(&self, website: &str, href: &str) -> Result<String> {
+        let path = self.path(website)?.join(href);
+        tokio::fs::read_to_string(&path).await.into_result(Some(path))
+    }
+
+    fn website_dir(&self, website: &str) -> Option<PathBuf> {
+        CorpusSource::website_dir(self, website)
+    }
+}
+
+/// A website's document and linked stylesheets, read ahead of parsing
+#[derive(Debug)]
+struct LoadedWebsite {
+    name: String,
+    dir: Option<PathBuf>,
+    document: Option<String>,
+    /// By href, as the document links them
+    stylesheets: HashMap<String, Result<String>>,
+}
+
+impl CorpusSource for LoadedWebsite {
+    fn websites(&self) -> Result<Vec<String>> {
+        Ok(vec![self.name.clone()])
+    }
+
+    /// `main_file` was already used when loading
+    fn open_document(&self, _website: &str, _main_file: Option<&Path>) -> Result<Option<String>> {
+        Ok(self.document.clone())
+    }
+
+    fn open_stylesheet(&self, website: &str, href: &str) -> Result<String> {
+        match self.stylesheets.get(href) {
+            Some(Ok(css)) => Ok(css.clone()),
+            // Errors aren't `Clone`, and the stylesheet may be opened more
+            // than once, e.g. by `DuplicateDocuments`
+            Some(Err(e)) => Err(Error {
+                path: e.path.clone(),
+                error: match &e.error {
+                    ErrorKind::Io(io) => ErrorKind::Io(io::Error::new(io.kind(), io.to_string())),
+                    _ => ErrorKind::Other(e.to_string()),
+                },
+            }),
+            None => Err(Error {
+                path: Some(Path::new(website).join(href)),
+                error: ErrorKind::Io(io::Error::from(io::ErrorKind::NotFound)),
+            }),
+        }
+    }
+
+    fn website_dir(&self, _website: &str) -> Option<PathBuf> {
+        self.dir.clone()
+    }
+}
+
+/// Parses every website of `source` with `options`, like
+/// `get_all_documents_and_selectors_with_options`, reading up to
+/// `concurrency` websites ahead of the one being parsed. Each website's
+/// stylesheets are read one after another. Websites that aren't websites are
+/// skipped, and `options.selection` is ignored.
+pub fn load_websites<S: AsyncCorpusSource>(
+    source: S,
+    options: ParseOptions,
+    concurrency: NonZeroUsize,
+) -> impl Iterator<Item = Result<ParsedWebsite>> {
+    // Bounded, so the loader waits for the parser instead of reading the
+    // whole corpus into memory
+    let (sender, receiver) = mpsc::sync_channel(concurrency.get());
+    let (main_file, links) = (options.main_file.clone(), options.stylesheet_links);
+    std::thread::spawn(move || {
+        let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                let _ = sender.send(Err(Error { path: None, error: ErrorKind::Io(e) }));
+                return;
+            }
+        };
+        runtime.block_on(load(Arc::new(source), main_file, links, concurrency, sender));
+    });
+    receiver.into_iter().filter_map(move |loaded: Result<LoadedWebsite>| {
+        loaded.and_then(|website| parse::parse_website_from_source(&website, &website.name, &options)).transpose()
+    })
+}
+
+async fn load<S: AsyncCorpusSource>(
+    source: Arc<S>,
+    main_file: Option<PathBuf>,
+    links: StylesheetLinks,
+    concurrency: NonZeroUsize,
+    sender: mpsc::SyncSender<Result<LoadedWebsite>>,
+) {
+    let websites = match source.websites().await {
+        Ok(websites) => websites,
+        Err(e) => {
+            let _ = sender.send(Err(e));
+            return;
+        }
+    };
+    let mut in_flight = VecDeque::new();
+    let mut websites = websites.into_iter();
+    loop {
+        while in_flight.len() < concurrency.get()
+            && let Some(website) = websites.next()
+        {
+            in_flight.push_back(tokio::spawn(load_website(source.clone(), website, main_file.clone(), links)));
+        }
+        let Some(next) = in_flight.pop_front() else {
+            return;
+        };
+        let loaded = next.await.map_err(join_error).and_then(|loaded| loaded);
+        // `send` blocks the runtime while the channel is full, which is what
+        // bounds it. An error means the iterator was dropped.
+        if sender.send(loaded).is_err() {
+            return;
+        }
+    }
+}
+
+async fn load_website<S: AsyncCorpusSource>(
+    source: Arc<S>,
+    website: String,
+    main_file: Option<PathBuf>,
+    links: StylesheetLinks,
+) -> Result<LoadedWebsite> {
+    let document = source.open_document(&website, main_file.as_deref()).await?;
+    let mut stylesheets = HashMap::new();
+    if let Some(html) = document.clone() {
+        let hrefs = tokio::task::spawn_blocking(move || parse::get_stylesheet_paths(&Html::parse_document(&html), links))
+            .await
+            .map_err(join_error)?;
+        for (CssFile(href), _) in hrefs {
+            let href = href.to_string_lossy().into_owned();
+            let css = source.open_stylesheet(&website, &href).await;
+            stylesheets.insert(href, css);
+        }
+    }
+    Ok(LoadedWebsite { dir: source.website_dir(&website), name: website, document, stylesheets })
+}
+
+fn join_error(e: tokio::task::JoinError) -> Error {
+    Error::other(format!("website loading task failed: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::num::NonZeroUsize;
+
+    use super::load_websites;
+    use crate::corpus_source::FsSource;
+    use crate::parse::{ParseOptions, get_all_documents_and_selectors};
+    use test_log::test;
+
+    #[test]
+    fn loads_like_parsing_directly() -> crate::result::Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..5 {
+            let website = dir.path().join(format!("site-{i}"));
+            fs::create_dir(&website).unwrap();
+            fs::write(
+                website.join("index.html"),
+                format!("<link rel='stylesheet' href='a.css'><link rel='stylesheet' href='missing.css'><p class='c{i}'></p>"),
+            ).unwrap();
+            fs::write(website.join("a.css"), format!(".c{i} {{}} p {{}}")).unwrap();
+        }
+        fs::write(dir.path().join("not-a-website.txt"), "").unwrap();
+        fs::create_dir(dir.path().join("empty")).unwrap();
+
+        let summarize = |website: crate::parse::ParsedWebsite| {
+            (website.name.clone(), website.stylesheet_names().to_vec(), website.css_errors().len(), website.document().html())
+        };
+        let mut expected = get_all_documents_and_selectors(dir.path())?
+            .map(|website| website.map(summarize))
+            .collect::<crate::result::Result<Vec<_>>>()?;
+        expected.sort();
+        let loaded = load_websites(FsSource::open(dir.path())?, ParseOptions::default(), NonZeroUsize::new(2).unwrap())
+            .map(|website| website.map(summarize))
+            .collect::<crate::result::Result<Vec<_>>>()?;
+        assert_eq!(loaded.len(), 5);
+        assert_eq!(loaded, expected);
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! A self-check of the ancestor bloom filter, with the `bloom_self_check`
+//! feature.
+//!
+//! `match_selectors_with_style_sharing` looks up every candidate rule the
+//! selector map gives an element. Candidates the bloom filter rejects are
+//! matched again in full, without the filter, and must not match; candidates
+//! it lets through are matched in full anyway. The bloom filter is only an
+//! optimization as long as the first kind never match.
+
+use std::cell::Cell;
+
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct BloomCheckCounts {
+    /// Candidates the bloom filter rejected, each confirmed not to match
+    pub filtered: usize,
+    /// Candidates the bloom filter let through to full matching
+    pub verified: usize,
+}
+
+thread_local! {
+    static COUNTS: Cell<BloomCheckCounts> = Cell::default();
+}
+
+pub(crate) fn record(filtered: bool) {
+    COUNTS.with(|counts| {
+        let mut c = counts.get();
+        if filtered {
+            c.filtered += 1;
+        } else {
+            c.verified += 1;
+        }
+        counts.set(c);
+    });
+}
+
+/// Returns this thread's counts since the last call, and starts over.
+pub fn take_counts() -> BloomCheckCounts {
+    COUNTS.take()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::take_counts;
+    use crate::parse::{get_document_and_selectors, websites_path};
+    use crate::result::Result;
+    use crate::{Algorithm, do_website};
+    use test_log::test;
+
+    #[test]
+    fn checks_every_candidate() -> Result<()> {
+        let website = get_document_and_selectors(&websites_path().join("is_conversion_test"))?.unwrap();
+        take_counts();
+        do_website(&website, Algorithm::WithStyleSharing, None);
+        let counts = take_counts();
+        assert!(counts.filtered + counts.verified > 0);
+        do_website(&website, Algorithm::Naive, None);
+        assert_eq!(take_counts(), Default::default());
+        Ok(())
+    }
+}
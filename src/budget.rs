@@ -0,0 +1,264 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Limits on what one website may take, so a pathological website (say, one
+//! with a 40MB stylesheet) fails with `ErrorKind::BudgetExceeded` and the rest
+//! of the run goes on.
+//!
+//! The selector limit is checked once a website's stylesheets are parsed,
+//! before its selector maps are built. The timeout covers parsing and
+//! matching: `do_websites` runs each website on a thread of its own and stops
+//! waiting for it when time runs out. Rust can't stop a thread, so the thread
+//! is told to stop instead: parsing checks between stylesheets and matching
+//! between elements (`stop_if_cancelled`), and unwinds, dropping what it
+//! built. Until it gets there it keeps running in the background, so at most
+//! `MAX_ABANDONED` such threads are left running; past that, websites fail
+//! without being started.
+
+use std::cell::OnceCell;
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use style::stylesheets::{AllRules, CssRule, StylesheetInDocument as _};
+
+use crate::instrument;
+use crate::parse::ParsedWebsite;
+use crate::result::{Error, ErrorKind, Result};
+
+/// How many threads of websites that ran out of time may still be running
+/// before `WebsiteBudget::run` refuses to start another
+pub const MAX_ABANDONED: usize = 4;
+
+/// The threads of websites that ran out of time, which may not have stopped
+/// yet
+static ABANDONED: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+
+thread_local! {
+    /// Set on the thread `WebsiteBudget::run` runs a website on, once it stops
+    /// waiting for it
+    static CANCELLED: OnceCell<Arc<AtomicBool>> = const { OnceCell::new() };
+}
+
+/// What a website's thread unwinds with when it is cancelled
+struct Cancelled;
+
+/// Stops the website being run on this thread if `WebsiteBudget::run` stopped
+/// waiting for it, unwinding so that what it built so far is dropped. Does
+/// nothing on other threads.
+pub(crate) fn stop_if_cancelled() {
+    let cancelled = CANCELLED.with(|cancelled| cancelled.get().is_some_and(|cancelled| cancelled.load(Ordering::Relaxed)));
+    if cancelled {
+        // Unlike `panic!`, doesn't print a panic message
+        std::panic::resume_unwind(Box::new(Cancelled));
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WebsiteBudget {
+    /// How long parsing and matching a website may take
+    pub timeout: Option<Duration>,
+    /// How many selectors a website's style rules may have, counting each
+    /// time one is written, including rules in `@media` rules that don't
+    /// apply
+    pub max_selectors: Option<usize>,
+}
+
+/// Which limit of a `WebsiteBudget` a website went over
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetExceeded {
+    Timeout(Duration),
+    Selectors {
+        selectors: usize,
+        max: usize,
+    },
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BudgetExceeded::Timeout(timeout) => write!(f, "took longer than {timeout:?}"),
+            BudgetExceeded::Selectors { selectors, max } => write!(f, "has {selectors} selectors, more than {max}"),
+        }
+    }
+}
+
+impl WebsiteBudget {
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Fails if `website` has more than `max_selectors` selectors.
+    pub fn check_selectors(&self, website: &ParsedWebsite, path: Option<&Path>) -> Result<()> {
+        let Some(max) = self.max_selectors else {
+            return Ok(());
+        };
+        let selectors = count_selectors(website);
+        if selectors > max {
+            return Err(exceeded(path, BudgetExceeded::Selectors { selectors, max }));
+        }
+        Ok(())
+    }
+
+    /// Runs `f`, the processing of the website at `path`, failing if it
+    /// takes longer than `timeout`. With a timeout, `f` runs on its own
+    /// thread, and what it records for `instrument` is added to this
+    /// thread's report when it finishes in time. If it doesn't, the thread is
+    /// cancelled (see `stop_if_cancelled`). Fails without running `f` while
+    /// `MAX_ABANDONED` cancelled threads are still running.
+    pub fn run<T: Send + 'static>(&self, path: &Path, f: impl FnOnce() -> Result<T> + Send + 'static) -> Result<T> {
+        let Some(timeout) = self.timeout else {
+            return f();
+        };
+        let running = abandoned_running();
+        if running >= MAX_ABANDONED {
+            let message = format!("not started, {running} websites that ran out of time are still running");
+            return Err(Error { path: Some(path.to_owned()), error: ErrorKind::Other(message) });
+        }
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name(format!("website {}", path.display()))
+            .spawn({
+                let cancelled = cancelled.clone();
+                move || {
+                    CANCELLED.with(|cell| cell.set(cancelled)).expect("a new thread isn't cancellable yet");
+                    let result = f();
+                    // An error means the caller stopped waiting
+                    let _ = sender.send((result, instrument::take_report()));
+                }
+            })
+            .map_err(|e| Error { path: Some(path.to_owned()), error: ErrorKind::Io(e) })?;
+        match receiver.recv_timeout(timeout) {
+            Ok((result, report)) => {
+                instrument::add_report(report);
+                result
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                cancelled.store(true, Ordering::Relaxed);
+                ABANDONED.lock().unwrap_or_else(|e| e.into_inner()).push(thread);
+                Err(exceeded(Some(path), BudgetExceeded::Timeout(timeout)))
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                Err(Error { path: Some(path.to_owned()), error: ErrorKind::Other("website thread panicked".to_owned()) })
+            }
+        }
+    }
+}
+
+/// How many threads of websites that ran out of time haven't stopped yet
+fn abandoned_running() -> usize {
+    let mut abandoned = ABANDONED.lock().unwrap_or_else(|e| e.into_inner());
+    abandoned.retain(|thread| !thread.is_finished());
+    abandoned.len()
+}
+
+fn exceeded(path: Option<&Path>, budget: BudgetExceeded) -> Error {
+    Error { path: path.map(Path::to_owned), error: ErrorKind::BudgetExceeded(budget) }
+}
+
+/// The selectors of every style rule of `website`'s stylesheets
+fn count_selectors(website: &ParsedWebsite) -> usize {
+    // `AllRules` doesn't evaluate media queries, so the device only has to
+    // be there
+    let device = website.device().stylo_device();
+    let guard = website.stylesheet_lock().read();
+    website
+        .stylesheets()
+        .iter()
+        .flat_map(|stylesheet| stylesheet.iter_rules::<AllRules>(&device, &guard))
+        .map(|rule| match rule {
+            CssRule::Style(style_rule) => style_rule.read_with(&guard).selectors.slice().len(),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Parses a duration like `60s`, `500ms`, `2m` or `1h`. A number alone is
+/// seconds.
+pub fn parse_duration(s: &str) -> std::result::Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number.parse().map_err(|_| format!("expected a duration like 60s, got {s:?}"))?;
+    let seconds = match unit.trim() {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        unit => return Err(format!("unknown duration unit {unit:?} in {s:?}; expected ms, s, m or h")),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|e| format!("invalid duration {s:?}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::{Duration, Instant};
+
+    use super::{BudgetExceeded, WebsiteBudget, abandoned_running, parse_duration, stop_if_cancelled};
+    use crate::parse::ParseOptions;
+    use crate::result::ErrorKind;
+    use crate::Algorithm;
+    use test_log::test;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("60s"), Ok(Duration::from_secs(60)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("1.5m"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2"), Ok(Duration::from_secs(2)));
+        assert!(parse_duration("10 days").is_err());
+        assert!(parse_duration("s").is_err());
+    }
+
+    #[test]
+    fn skips_websites_over_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        for (website, css) in [("big", "a, b, c {} d {}"), ("small", "a {} @media print { b {} }")] {
+            fs::create_dir(dir.path().join(website)).unwrap();
+            fs::write(dir.path().join(website).join("index.html"), "<link rel='stylesheet' href='a.css'><a></a>").unwrap();
+            fs::write(dir.path().join(website).join("a.css"), css).unwrap();
+        }
+        let budget = WebsiteBudget { max_selectors: Some(2), timeout: Some(Duration::from_secs(60)) };
+        let options = ParseOptions { budget, ..Default::default() };
+        let mut results: Vec<_> = crate::do_all_websites_with_options(dir.path(), Algorithm::Naive, options).unwrap().collect();
+        results.sort_by_key(|result| result.is_ok());
+        let [Err(error), Ok((name, _, _))] = &results[..] else {
+            panic!("expected one failure and one success");
+        };
+        assert_eq!(name, "small");
+        assert!(matches!(error.error, ErrorKind::BudgetExceeded(BudgetExceeded::Selectors { selectors: 4, max: 2 })));
+        assert!(error.path.as_ref().unwrap().ends_with("big"));
+
+        let budget = WebsiteBudget { timeout: Some(Duration::ZERO), ..Default::default() };
+        let error = budget.run(dir.path(), || {
+            std::thread::sleep(Duration::from_millis(100));
+            Ok(())
+        });
+        assert!(matches!(error.unwrap_err().error, ErrorKind::BudgetExceeded(BudgetExceeded::Timeout(_))));
+    }
+
+    #[test]
+    fn stops_websites_that_run_out_of_time() {
+        let budget = WebsiteBudget { timeout: Some(Duration::from_millis(10)), ..Default::default() };
+        let error = budget.run(std::path::Path::new("forever"), || -> crate::result::Result<()> {
+            loop {
+                stop_if_cancelled();
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        });
+        assert!(matches!(error.unwrap_err().error, ErrorKind::BudgetExceeded(BudgetExceeded::Timeout(_))));
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while abandoned_running() > 0 {
+            assert!(Instant::now() < deadline, "the website's thread didn't stop");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
@@ -0,0 +1,241 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! An experimental matching backend that compiles selectors to a flat
+//! bytecode, run by a small interpreter instead of `matches_selector`.
+//!
+//! Only type, id and class selectors, `*`, and the four tree combinators
+//! compile. A selector with anything else (attributes, pseudo-classes,
+//! namespaces, pseudo-elements) is left to Stylo, so the results are always
+//! the same as naive matching; `Compiled::compiled_count` says how much of a
+//! selector list the interpreter actually ran.
+
+use scraper::{ElementRef, Html};
+use selectors::Element as _;
+use selectors::context::SelectorCaches;
+use selectors::matching;
+use selectors::parser::{Combinator, Component};
+use smallvec::SmallVec;
+
+use crate::ElementOrder;
+use crate::structs::Selector;
+use crate::structs::borrowed::{DocumentMatches, ElementMatches, SelectorsOrSharedStyles};
+
+/// One instruction, in the selector's match order: from the rightmost
+/// compound to the left, starting at the element being matched.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Op {
+    /// Fails unless the current element has this local name; `lower_name`
+    /// is used for HTML elements in HTML documents.
+    MatchLocalName { name: String, lower_name: String },
+    MatchId(String),
+    MatchClass(String),
+    /// Moves to the parent element (`>`)
+    Ascend,
+    /// Moves to an ancestor (` `), trying each in turn if the rest fails
+    AscendAny,
+    /// Moves to the previous sibling element (`+`)
+    PreviousSibling,
+    /// Moves to a previous sibling element (`~`), trying each in turn if the
+    /// rest fails
+    PreviousSiblingAny,
+    /// The selector matched
+    Accept,
+}
+
+/// A selector's program, or `None` if it has something the bytecode can't
+/// express.
+pub fn compile(selector: &Selector) -> Option<Vec<Op>> {
+    let mut ops = Vec::new();
+    for component in selector.iter_raw_match_order() {
+        let op = match component {
+            Component::LocalName(local_name) => Op::MatchLocalName {
+                name: local_name.name.to_string(),
+                lower_name: local_name.lower_name.to_string(),
+            },
+            Component::ID(id) => Op::MatchId(id.0.to_string()),
+            Component::Class(class) => Op::MatchClass(class.0.to_string()),
+            Component::ExplicitUniversalType => continue,
+            Component::Combinator(Combinator::Child) => Op::Ascend,
+            Component::Combinator(Combinator::Descendant) => Op::AscendAny,
+            Component::Combinator(Combinator::NextSibling) => Op::PreviousSibling,
+            Component::Combinator(Combinator::LaterSibling) => Op::PreviousSiblingAny,
+            _ => return None,
+        };
+        ops.push(op);
+    }
+    ops.push(Op::Accept);
+    Some(ops)
+}
+
+/// Runs `ops` from `element`. Backtracks to the most recent `AscendAny` or
+/// `PreviousSiblingAny` on failure.
+pub fn run(ops: &[Op], element: ElementRef) -> bool {
+    let mut backtrack: SmallVec<[(usize, ElementRef); 4]> = SmallVec::new();
+    let (mut pc, mut current) = (0, element);
+    loop {
+        let ok = match &ops[pc] {
+            Op::Accept => return true,
+            Op::MatchLocalName { name, lower_name } => {
+                let name = if current.is_html_element_in_html_document() { lower_name } else { name };
+                current.value().name() == name
+            }
+            Op::MatchId(id) => current.value().id() == Some(id.as_str()),
+            Op::MatchClass(class) => current
+                .value()
+                .has_class(class, selectors::attr::CaseSensitivity::CaseSensitive),
+            Op::Ascend => current.parent_element().map(|parent| current = parent).is_some(),
+            Op::PreviousSibling => current.prev_sibling_element().map(|sibling| current = sibling).is_some(),
+            Op::AscendAny | Op::PreviousSiblingAny => {
+                let next = if ops[pc] == Op::AscendAny {
+                    current.parent_element()
+                } else {
+                    current.prev_sibling_element()
+                };
+                next.map(|next| {
+                    // Retrying this instruction from `next` moves one further
+                    backtrack.push((pc, next));
+                    current = next;
+                }).is_some()
+            }
+        };
+        if ok {
+            pc += 1;
+        } else if let Some((retry_pc, retry_element)) = backtrack.pop() {
+            (pc, current) = (retry_pc, retry_element);
+        } else {
+            return false;
+        }
+    }
+}
+
+/// A selector list with a program for every selector that compiles.
+pub struct Compiled<'a> {
+    selectors: &'a [Selector],
+    programs: Vec<Option<Vec<Op>>>,
+}
+
+impl<'a> Compiled<'a> {
+    pub fn new(selectors: &'a [Selector]) -> Self {
+        Self { selectors, programs: selectors.iter().map(compile).collect() }
+    }
+
+    /// How many of the selectors the interpreter runs, rather than Stylo
+    pub fn compiled_count(&self) -> usize {
+        self.programs.iter().filter(|program| program.is_some()).count()
+    }
+}
+
+/// Like `match_selectors`, running compiled selectors with `run`.
+pub fn match_selectors_with_bytecode<'a>(document: &'a Html, selectors: &'a [Selector]) -> DocumentMatches<'a> {
+    let compiled = Compiled::new(selectors);
+    log::debug!("compiled {} of {} selectors to bytecode", compiled.compiled_count(), selectors.len());
+    let mut caches: SelectorCaches = Default::default();
+    let mut result = Vec::new();
+    bytecode_preorder_traversal(document.root_element(), &compiled, &mut result, &mut caches);
+    DocumentMatches(result)
+}
+
+/// Like `match_selectors_with_bytecode`, matching the elements in `order`.
+pub fn match_selectors_with_bytecode_in_order<'a>(
+    document: &'a Html,
+    selectors: &'a [Selector],
+    order: ElementOrder,
+) -> DocumentMatches<'a> {
+    if order == ElementOrder::Document {
+        return match_selectors_with_bytecode(document, selectors);
+    }
+    let compiled = Compiled::new(selectors);
+    crate::match_in_element_order(document, order, |element, caches| bytecode_match_element(element, &compiled, caches))
+}
+
+fn bytecode_preorder_traversal<'a>(
+    element: ElementRef<'a>,
+    compiled: &Compiled<'a>,
+    matches: &mut Vec<ElementMatches<'a>>,
+    caches: &mut SelectorCaches,
+) {
+    matches.push(bytecode_match_element(element, compiled, caches));
+    for child in element.child_elements() {
+        bytecode_preorder_traversal(child, compiled, matches, caches);
+    }
+}
+
+fn bytecode_match_element<'a>(element: ElementRef<'a>, compiled: &Compiled<'a>, caches: &mut SelectorCaches) -> ElementMatches<'a> {
+    crate::budget::stop_if_cancelled();
+    let mut context = matching::MatchingContext::new(
+        matching::MatchingMode::Normal,
+        None,
+        caches,
+        matching::QuirksMode::NoQuirks,
+        matching::NeedsSelectorFlags::No,
+        matching::MatchingForInvalidation::No,
+    );
+    let matched_selectors = compiled
+        .selectors
+        .iter()
+        .zip(&compiled.programs)
+        .filter(|(s, program)| match program {
+            Some(ops) => run(ops, element),
+            None => matching::matches_selector(s, 0, None, &element, &mut context).0,
+        })
+        .map(|(s, _)| s)
+        .collect();
+    ElementMatches{ element, selectors: SelectorsOrSharedStyles::Selectors(matched_selectors) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Compiled, Op, compile, match_selectors_with_bytecode};
+    use crate::match_selectors;
+    use crate::structs::Selector;
+    use crate::structs::borrowed::{DocumentMatches, SelectorsOrSharedStyles};
+    use cssparser::ToCss as _;
+    use style::selector_parser::SelectorParser;
+    use style::stylesheets::UrlExtraData;
+    use test_log::test;
+
+    fn parse(selectors: &str) -> Vec<Selector> {
+        let url_data = UrlExtraData::from(url::Url::parse("about:blank").unwrap());
+        SelectorParser::parse_author_origin_no_namespace(selectors, &url_data).unwrap().slice().to_vec()
+    }
+
+    fn to_css(matches: &DocumentMatches) -> Vec<Vec<String>> {
+        matches.0.iter().map(|element_matches| match &element_matches.selectors {
+            SelectorsOrSharedStyles::Selectors(selectors) => selectors.iter().map(|s| s.to_css_string()).collect(),
+            SelectorsOrSharedStyles::SharedWithElement(_) => unreachable!(),
+        }).collect()
+    }
+
+    #[test]
+    fn compiles_in_match_order() {
+        let selector = &parse("div > .a #b")[0];
+        assert_eq!(compile(selector), Some(vec![
+            Op::MatchId("b".to_owned()),
+            Op::AscendAny,
+            Op::MatchClass("a".to_owned()),
+            Op::Ascend,
+            Op::MatchLocalName { name: "div".to_owned(), lower_name: "div".to_owned() },
+            Op::Accept,
+        ]));
+        assert_eq!(compile(&parse("a:hover")[0]), None);
+    }
+
+    #[test]
+    fn matches_like_naive() {
+        // `.a > .b .c` needs backtracking past the inner .b
+        let document = scraper::Html::parse_document(
+            "<div class='a'><div class='b'><div class='b'><p class='c'></p></div></div></div><h1></h1><h2></h2><P></P>",
+        );
+        let selectors = parse(".a > .b .c, h1 + h2, h1 ~ p, div p, :not(.a), *, .a > p");
+        let compiled = Compiled::new(&selectors);
+        assert_eq!(compiled.compiled_count(), 6);
+        let bytecode = match_selectors_with_bytecode(&document, &selectors);
+        assert_eq!(to_css(&bytecode), to_css(&match_selectors(&document, &selectors)));
+        // the inner p
+        assert!(to_css(&bytecode).iter().any(|selectors| selectors.contains(&".a > .b .c".to_owned())));
+    }
+}
@@ -0,0 +1,160 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! A C interface, with the `capi` feature, declared in `include/mach6.h`.
+//! `make capi` builds it as a shared library:
+//!
+//! ```sh
+//! cargo rustc --release --lib --features capi --crate-type cdylib
+//! ```
+//!
+//! Results are JSON, shaped like the `serve` responses: `{"matches": ...,
+//! "stats": ...}`, or `{"error": ...}`.
+
+use std::ffi::{CStr, CString, c_char};
+use std::panic::{self, AssertUnwindSafe};
+
+use clap::ValueEnum as _;
+
+use crate::Algorithm;
+use crate::parse::ParseOptions;
+use crate::result::{Error, Result};
+use crate::structs::{ser::SerDocumentMatches, stats::DocumentStats};
+
+/// Matches a document against its stylesheets.
+///
+/// `html` is `html_len` bytes of UTF-8. The `stylesheet_count` stylesheets are
+/// what the document's `<link>`s refer to: `hrefs[i]` is a NUL-terminated
+/// href, and `stylesheets[i]` is `stylesheet_lens[i]` bytes of CSS. The arrays
+/// may be null when `stylesheet_count` is 0. `algorithm` is a NUL-terminated
+/// name as on the command line, or null for `naive`.
+///
+/// Returns a NUL-terminated JSON string to free with `mach6_free_string`.
+///
+/// # Safety
+///
+/// Every pointer must be valid for the lengths given.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mach6_match(
+    html: *const c_char,
+    html_len: usize,
+    hrefs: *const *const c_char,
+    stylesheets: *const *const c_char,
+    stylesheet_lens: *const usize,
+    stylesheet_count: usize,
+    algorithm: *const c_char,
+) -> *mut c_char {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        // SAFETY: the caller's, as documented
+        unsafe { match_buffers(html, html_len, hrefs, stylesheets, stylesheet_lens, stylesheet_count, algorithm) }
+    }));
+    let json = match result {
+        Ok(Ok(json)) => json,
+        Ok(Err(e)) => serde_json::json!({ "error": e.to_string() }),
+        Err(_) => serde_json::json!({ "error": "mach-6 panicked" }),
+    };
+    // JSON escapes NULs in strings, so there are none to fail on
+    CString::new(json.to_string()).unwrap().into_raw()
+}
+
+/// Frees a string returned by `mach6_match`. Does nothing when `s` is null.
+///
+/// # Safety
+///
+/// `s` must be null or a string from `mach6_match` that hasn't been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mach6_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        // SAFETY: `s` came from `CString::into_raw` in `mach6_match`
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+unsafe fn match_buffers(
+    html: *const c_char,
+    html_len: usize,
+    hrefs: *const *const c_char,
+    stylesheets: *const *const c_char,
+    stylesheet_lens: *const usize,
+    stylesheet_count: usize,
+    algorithm: *const c_char,
+) -> Result<serde_json::Value> {
+    let utf8 = |bytes: &[u8], what: &str| {
+        std::str::from_utf8(bytes).map(str::to_owned).map_err(|e| Error::other(format!("{what} is not UTF-8: {e}")))
+    };
+    // SAFETY: the caller's, as documented for `mach6_match`
+    let (html, files, algorithm) = unsafe {
+        let html = utf8(bytes(html, html_len), "the html")?;
+        let mut files = Vec::with_capacity(stylesheet_count);
+        for i in 0..stylesheet_count {
+            let href = utf8(CStr::from_ptr(*hrefs.add(i)).to_bytes(), "an href")?;
+            let css = utf8(bytes(*stylesheets.add(i), *stylesheet_lens.add(i)), &format!("stylesheet {href}"))?;
+            files.push((href, css));
+        }
+        let algorithm = if algorithm.is_null() {
+            Algorithm::Naive
+        } else {
+            let name = CStr::from_ptr(algorithm).to_string_lossy();
+            Algorithm::from_str(&name, true).map_err(|e| Error::other(format!("invalid algorithm {name:?}: {e}")))?
+        };
+        (html, files, algorithm)
+    };
+    let files: Vec<_> = files.iter().map(|(href, css)| (href.as_str(), css.as_str())).collect();
+    let matches = crate::match_in_memory(&html, &files, algorithm, &ParseOptions::default())?;
+    Ok(serde_json::json!({
+        "matches": SerDocumentMatches::from(&matches),
+        "stats": DocumentStats::from(&matches),
+    }))
+}
+
+/// # Safety
+///
+/// `pointer` must be valid for `len` bytes, or `len` must be 0.
+unsafe fn bytes<'a>(pointer: *const c_char, len: usize) -> &'a [u8] {
+    match len {
+        0 => &[],
+        // SAFETY: the caller's
+        _ => unsafe { std::slice::from_raw_parts(pointer.cast(), len) },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{CStr, c_char};
+
+    use super::{mach6_free_string, mach6_match};
+    use test_log::test;
+
+    fn call(html: &str, stylesheets: &[(&CStr, &str)], algorithm: Option<&CStr>) -> serde_json::Value {
+        let hrefs: Vec<_> = stylesheets.iter().map(|(href, _)| href.as_ptr()).collect();
+        let css: Vec<*const c_char> = stylesheets.iter().map(|(_, css)| css.as_ptr().cast()).collect();
+        let lens: Vec<_> = stylesheets.iter().map(|(_, css)| css.len()).collect();
+        // SAFETY: every pointer is to a live buffer of the given length
+        unsafe {
+            let result = mach6_match(
+                html.as_ptr().cast(),
+                html.len(),
+                hrefs.as_ptr(),
+                css.as_ptr(),
+                lens.as_ptr(),
+                stylesheets.len(),
+                algorithm.map_or(std::ptr::null(), CStr::as_ptr),
+            );
+            let json = serde_json::from_slice(CStr::from_ptr(result).to_bytes()).unwrap();
+            mach6_free_string(result);
+            json
+        }
+    }
+
+    #[test]
+    fn matches_through_the_c_interface() {
+        let result = call("<link rel=stylesheet href=a.css><p class=a></p>", &[(c"a.css", ".a {}")], Some(c"with-style-sharing"));
+        assert_eq!(result["stats"]["matched_pairs"], 1);
+        assert!(result["error"].is_null());
+        let result = call("", &[], Some(c"fastest"));
+        assert!(result["error"].as_str().unwrap().contains("invalid algorithm"));
+    }
+}
@@ -0,0 +1,219 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Resuming long corpus runs.
+//!
+//! A manifest file records each website whose results were written, by a
+//! hash of its folder's contents, one `<hash>\t<name>` line per website.
+//! A re-run with the same manifest skips them. A website whose files changed
+//! since it was recorded has a new hash, so it is processed again.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use sha2::{Digest as _, Sha256};
+
+use crate::result::{Error, ErrorKind, IntoResultExt as _, Result};
+
+pub struct Checkpoint {
+    manifest_path: PathBuf,
+    manifest: File,
+    completed: HashSet<String>,
+    /// Hashes of websites let through by `skip_completed`, by name
+    pending: HashMap<String, String>,
+}
+
+impl Checkpoint {
+    /// Opens the manifest at `manifest_path`, creating it if it doesn't exist.
+    pub fn open(manifest_path: &Path) -> Result<Self> {
+        let io_error = |e| Error { path: Some(manifest_path.to_path_buf()), error: ErrorKind::Io(e) };
+        let completed = match fs::read_to_string(manifest_path) {
+            Ok(contents) => contents
+                .lines()
+                .filter_map(|line| line.split_once('\t'))
+                .map(|(hash, _name)| hash.to_owned())
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashSet::new(),
+            Err(e) => return Err(io_error(e)),
+        };
+        let manifest = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(manifest_path)
+            .map_err(io_error)?;
+        Ok(Self {
+            manifest_path: manifest_path.to_path_buf(),
+            manifest,
+            completed,
+            pending: HashMap::new(),
+        })
+    }
+
+    pub fn is_completed(&self, hash: &str) -> bool {
+        self.completed.contains(hash)
+    }
+
+    /// Records the website `name`, previously let through by
+    /// `skip_completed`, as completed. Does nothing for other names.
+    pub fn complete(&mut self, name: &str) -> Result<()> {
+        let Some(hash) = self.pending.remove(name) else {
+            return Ok(());
+        };
+        writeln!(self.manifest, "{hash}\t{name}")
+            .and_then(|()| self.manifest.flush())
+            .into_result(Some(self.manifest_path.clone()))?;
+        self.completed.insert(hash);
+        Ok(())
+    }
+
+    /// Filters out websites the manifest records as completed, unless
+    /// `force`.
+    pub fn skip_completed(
+        checkpoint: Rc<RefCell<Self>>,
+        website_paths: impl Iterator<Item = Result<PathBuf>>,
+        force: bool,
+    ) -> impl Iterator<Item = Result<PathBuf>> {
+        website_paths.filter_map(move |path| {
+            path.and_then(|path| {
+                let hash = website_hash(&path)?;
+                let mut checkpoint = checkpoint.borrow_mut();
+                if !force && checkpoint.is_completed(&hash) {
+                    log::info!("skipping {}, already completed", path.display());
+                    return Ok(None);
+                }
+                let name = crate::parse::website_name(&path);
+                checkpoint.pending.insert(name, hash);
+                Ok(Some(path))
+            })
+            .transpose()
+        })
+    }
+
+    /// Records each website of `results` as completed once the one after it
+    /// is asked for, i.e. once the caller has finished writing it, and the
+    /// last one when `results` runs out. A website whose writing failed is
+    /// never asked past, so it isn't recorded.
+    pub fn record_completed<T>(
+        checkpoint: Rc<RefCell<Self>>,
+        mut results: impl Iterator<Item = Result<(String, T)>>,
+    ) -> impl Iterator<Item = Result<(String, T)>> {
+        let mut previous: Option<String> = None;
+        std::iter::from_fn(move || {
+            if let Some(name) = previous.take()
+                && let Err(e) = checkpoint.borrow_mut().complete(&name)
+            {
+                return Some(Err(e));
+            }
+            let result = results.next()?;
+            if let Ok((name, _)) = &result {
+                previous = Some(name.clone());
+            }
+            Some(result)
+        })
+    }
+}
+
+/// A SHA-256 of every file in the website folder, with their paths relative
+/// to it, in path order.
+pub fn website_hash(website: &Path) -> Result<String> {
+    fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+        for entry in fs::read_dir(dir).into_result(Some(dir.to_path_buf()))? {
+            let path = entry.into_result(Some(dir.to_path_buf()))?.path();
+            if path.is_dir() {
+                collect_files(&path, files)?;
+            } else {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+    let mut files = Vec::new();
+    collect_files(website, &mut files)?;
+    files.sort();
+    let mut hasher = Sha256::new();
+    for file in files {
+        let contents = fs::read(&file).into_result(Some(file.clone()))?;
+        let relative = file.strip_prefix(website).unwrap_or(&file);
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update((contents.len() as u64).to_le_bytes());
+        hasher.update(&contents);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::fs;
+    use std::rc::Rc;
+
+    use super::{Checkpoint, website_hash};
+    use crate::result::{IntoResultExt as _, Result};
+    use test_log::test;
+
+    #[test]
+    fn skips_completed_websites() -> Result<()> {
+        let websites_dir = tempfile::tempdir().into_result(None)?;
+        let paths: Vec<_> = ["a", "b", "c"]
+            .into_iter()
+            .map(|name| {
+                let path = websites_dir.path().join(name);
+                fs::create_dir(&path).into_result(Some(path.clone()))?;
+                fs::write(path.join("index.html"), name).into_result(Some(path.clone()))?;
+                Ok(path)
+            })
+            .collect::<Result<_>>()?;
+        let manifest = websites_dir.path().join("manifest.tsv");
+        let run = |force: bool| -> Result<Vec<String>> {
+            let checkpoint = Rc::new(RefCell::new(Checkpoint::open(&manifest)?));
+            let paths = Checkpoint::skip_completed(checkpoint.clone(), paths.clone().into_iter().map(Ok), force);
+            let results = paths.map(|path| {
+                path.map(|path| (path.file_name().unwrap().to_str().unwrap().to_owned(), ()))
+            });
+            // Stop after writing the first website, as if the run crashed
+            let mut results = Checkpoint::record_completed(checkpoint, results);
+            let first = results.next().transpose()?;
+            drop(results);
+            Ok(first.into_iter().map(|(name, ())| name).collect())
+        };
+        assert_eq!(run(false)?, ["a"]);
+        // `a` was not recorded yet: only asking for `b` records it
+        assert_eq!(run(false)?, ["a"]);
+
+        let checkpoint = Rc::new(RefCell::new(Checkpoint::open(&manifest)?));
+        let names: Vec<_> = Checkpoint::skip_completed(checkpoint.clone(), paths.clone().into_iter().map(Ok), false)
+            .map(|path| path.map(|path| (path.file_name().unwrap().to_str().unwrap().to_owned(), ())))
+            .collect::<Vec<_>>();
+        let names: Vec<_> = Checkpoint::record_completed(checkpoint, names.into_iter())
+            .map(|result| result.map(|(name, ())| name))
+            .collect::<Result<_>>()?;
+        assert_eq!(names, ["a", "b", "c"]);
+        assert_eq!(run(false)?, Vec::<String>::new());
+        assert_eq!(run(true)?, ["a"]);
+
+        // Changing a website's files makes it pending again
+        fs::write(paths[1].join("index.html"), "changed").into_result(None)?;
+        assert_eq!(run(false)?, ["b"]);
+        Ok(())
+    }
+
+    #[test]
+    fn hashes_names_and_contents() -> Result<()> {
+        let dir = tempfile::tempdir().into_result(None)?;
+        let write = |name: &str, contents: &str| fs::write(dir.path().join(name), contents).into_result(None);
+        write("a.html", "x")?;
+        let one = website_hash(dir.path())?;
+        assert_eq!(website_hash(dir.path())?, one);
+        fs::rename(dir.path().join("a.html"), dir.path().join("b.html")).into_result(None)?;
+        assert_ne!(website_hash(dir.path())?, one);
+        Ok(())
+    }
+}
@@ -0,0 +1,309 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Where websites are read from. `parse::parse_website_from_source` turns a
+//! website of any `CorpusSource` into a `ParsedWebsite`; the usual website
+//! folders are `FsSource`, and other layouts (archives, captures, fetching)
+//! only need to list websites and hand over their files.
+//!
+//! Websites are named by strings, and files by their path relative to the
+//! website, as an `href` in its document gives them.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::parse::{self, HtmlFile, WEBSITE_CONFIG_FILE, WebsiteConfig};
+use crate::result::{Error, ErrorKind, IntoResultExt as _, Result};
+
+pub trait CorpusSource {
+    /// The websites' names, in the order to process them
+    fn websites(&self) -> Result<Vec<String>>;
+
+    /// The main document of `website`: `main_file` if given (relative to the
+    /// website), or else whatever the source's layout makes it. `None` if the
+    /// website has no document, and so isn't a website.
+    fn open_document(&self, website: &str, main_file: Option<&Path>) -> Result<Option<String>>;
+
+    /// The CSS of the stylesheet `href`, relative to the website, links to
+    fn open_stylesheet(&self, website: &str, href: &str) -> Result<String>;
+
+    /// The folder `website`'s files are in, for sources that have one. It is
+    /// used to resolve source maps and for stylesheets' URLs.
+    fn website_dir(&self, _website: &str) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// Website folders on disk: each folder of the websites directory is a
+/// website, with one html file or a main file chosen by its `mach6.toml`.
+#[derive(Clone, Debug, Default)]
+pub struct FsSource {
+    /// By name, as given by `parse::website_name`
+    websites: BTreeMap<String, PathBuf>,
+}
+
+impl FsSource {
+    /// Every entry of the websites directory `websites_path`
+    pub fn open(websites_path: &Path) -> Result<Self> {
+        let mut source = Self::default();
+        for path in parse::get_websites_dirs(websites_path)? {
+            source.insert(path?);
+        }
+        Ok(source)
+    }
+
+    /// Just one website folder
+    pub fn single(website_path: &Path) -> Self {
+        let mut source = Self::default();
+        source.insert(website_path.to_path_buf());
+        source
+    }
+
+    fn insert(&mut self, path: PathBuf) {
+        let name = parse::website_name(&path);
+        if let Some(previous) = self.websites.insert(name.clone(), path) {
+            log::warn!("only keeping one of the websites named {name}, not {}", previous.display());
+        }
+    }
+
+    pub(crate) fn path(&self, website: &str) -> Result<&Path> {
+        self.websites
+            .get(website)
+            .map(PathBuf::as_path)
+            .ok_or_else(|| Error::other(format!("no website named {website}")))
+    }
+}
+
+impl CorpusSource for FsSource {
+    fn websites(&self) -> Result<Vec<String>> {
+        Ok(self.websites.keys().cloned().collect())
+    }
+
+    fn open_document(&self, website: &str, main_file: Option<&Path>) -> Result<Option<String>> {
+        let path = self.path(website)?;
+        if !path.is_dir() {
+            log::warn!("ignoring {} because it is not a directory", path.display());
+            return Ok(None);
+        }
+        let Some(HtmlFile(html_path)) = parse::main_html_file_with(path, main_file)? else {
+            return Ok(None);
+        };
+        std::fs::read_to_string(&html_path).into_result(Some(html_path)).map(Some)
+    }
+
+    fn open_stylesheet(&self, website: &str, href: &str) -> Result<String> {
+        let path = self.path(website)?.join(href);
+        std::fs::read_to_string(&path).into_result(Some(path))
+    }
+
+    fn website_dir(&self, website: &str) -> Option<PathBuf> {
+        self.websites.get(website).cloned()
+    }
+}
+
+/// Websites held in memory, as each website's files by their path relative
+/// to it. Without a `main_file`, the main document is the website's only
+/// top-level `.html` file, or the one its `mach6.toml` names.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemorySource {
+    websites: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl MemorySource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `path`, relative to `website`, adding the website if it's new.
+    pub fn insert_file(&mut self, website: &str, path: &str, contents: String) {
+        self.websites.entry(website.to_owned()).or_default().insert(normalize(path), contents);
+    }
+
+    /// The websites in an uncompressed tar archive, where each top-level
+    /// directory is a website. Files are decoded as UTF-8 lossily. Long
+    /// names from GNU or pax extension headers aren't supported; entries
+    /// using them are skipped.
+    pub fn from_tar(archive: &[u8]) -> Result<Self> {
+        let mut source = Self::new();
+        for (path, contents) in tar_entries(archive)? {
+            let path = path.strip_prefix("./").unwrap_or(&path);
+            let Some((website, file)) = path.split_once('/') else {
+                continue;
+            };
+            if file.is_empty() {
+                continue;
+            }
+            source.insert_file(website, file, String::from_utf8_lossy(contents).into_owned());
+        }
+        Ok(source)
+    }
+
+    pub fn read_tar(path: &Path) -> Result<Self> {
+        let archive = std::fs::read(path).into_result(Some(path.to_path_buf()))?;
+        Self::from_tar(&archive).map_err(|e| Error { path: Some(path.to_path_buf()), ..e })
+    }
+
+    fn files(&self, website: &str) -> Result<&BTreeMap<String, String>> {
+        self.websites.get(website).ok_or_else(|| Error::other(format!("no website named {website}")))
+    }
+}
+
+impl CorpusSource for MemorySource {
+    fn websites(&self) -> Result<Vec<String>> {
+        Ok(self.websites.keys().cloned().collect())
+    }
+
+    fn open_document(&self, website: &str, main_file: Option<&Path>) -> Result<Option<String>> {
+        let files = self.files(website)?;
+        let main_file = match main_file {
+            Some(main_file) => Some(main_file.to_path_buf()),
+            None => match files.get(WEBSITE_CONFIG_FILE) {
+                Some(config) => toml::from_str::<WebsiteConfig>(config)
+                    .map_err(|e| Error {
+                        path: Some(Path::new(website).join(WEBSITE_CONFIG_FILE)),
+                        error: ErrorKind::InvalidConfig(e),
+                    })?
+                    .main_file,
+                None => None,
+            },
+        };
+        if let Some(main_file) = main_file {
+            return self.open_stylesheet(website, &main_file.to_string_lossy()).map(Some);
+        }
+        let mut html_files = files.iter().filter(|(path, _)| !path.contains('/') && path.ends_with(".html"));
+        match (html_files.next(), html_files.next()) {
+            (None, _) => Ok(None),
+            (Some((_, html)), None) => Ok(Some(html.clone())),
+            (Some(_), Some(_)) => Err(Error {
+                path: Some(PathBuf::from(website)),
+                error: ErrorKind::MultipleHtmlFiles(
+                    files
+                        .keys()
+                        .filter(|path| !path.contains('/') && path.ends_with(".html"))
+                        .map(|path| HtmlFile(PathBuf::from(website).join(path)))
+                        .collect(),
+                ),
+            }),
+        }
+    }
+
+    fn open_stylesheet(&self, website: &str, href: &str) -> Result<String> {
+        self.files(website)?.get(&normalize(href)).cloned().ok_or_else(|| Error {
+            path: Some(Path::new(website).join(href)),
+            error: ErrorKind::Io(std::io::Error::from(std::io::ErrorKind::NotFound)),
+        })
+    }
+}
+
+/// `path` without `.` segments, with `..` applied, and without a leading `/`
+fn normalize(path: &str) -> String {
+    let mut segments = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => (),
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+/// The regular files of a tar archive, by path
+fn tar_entries(archive: &[u8]) -> Result<Vec<(String, &[u8])>> {
+    const BLOCK: usize = 512;
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    while offset + BLOCK <= archive.len() {
+        let header = &archive[offset..offset + BLOCK];
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        let field = |start: usize, len: usize| {
+            let field = &header[start..start + len];
+            &field[..field.iter().position(|&b| b == 0).unwrap_or(len)]
+        };
+        let size = std::str::from_utf8(field(124, 12))
+            .ok()
+            .and_then(|size| usize::from_str_radix(size.trim_matches(|c: char| c == ' ' || c == '\0'), 8).ok())
+            .ok_or_else(|| Error::other(format!("invalid tar header at byte {offset}")))?;
+        let start = offset + BLOCK;
+        let end = start
+            .checked_add(size)
+            .filter(|&end| end <= archive.len())
+            .ok_or_else(|| Error::other(format!("truncated tar entry at byte {offset}")))?;
+        // Regular files; everything else, including extension headers, is
+        // skipped along with its data
+        if matches!(header[156], b'0' | 0) {
+            let name = String::from_utf8_lossy(field(0, 100));
+            let path = match &header[257..262] {
+                b"ustar" if !field(345, 155).is_empty() => {
+                    format!("{}/{name}", String::from_utf8_lossy(field(345, 155)))
+                }
+                _ => name.into_owned(),
+            };
+            entries.push((path, &archive[start..end]));
+        }
+        offset = start + size.div_ceil(BLOCK) * BLOCK;
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CorpusSource as _, MemorySource};
+    use crate::result::{ErrorKind, Result};
+    use test_log::test;
+
+    /// A ustar archive of `files`
+    fn tar(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut archive = Vec::new();
+        for (path, contents) in files {
+            let mut header = [0u8; 512];
+            header[..path.len()].copy_from_slice(path.as_bytes());
+            header[124..135].copy_from_slice(format!("{:011o}", contents.len()).as_bytes());
+            header[156] = b'0';
+            header[257..262].copy_from_slice(b"ustar");
+            archive.extend_from_slice(&header);
+            archive.extend_from_slice(contents.as_bytes());
+            archive.resize(archive.len().div_ceil(512) * 512, 0);
+        }
+        archive.resize(archive.len() + 1024, 0);
+        archive
+    }
+
+    #[test]
+    fn reads_websites_from_tar() -> Result<()> {
+        let source = MemorySource::from_tar(&tar(&[
+            ("./a/index.html", "<link rel=stylesheet href='css/a.css'>"),
+            ("a/css/a.css", "p {}"),
+            ("b/one.html", ""),
+            ("b/two.html", ""),
+            ("top-level.txt", ""),
+        ]))?;
+        assert_eq!(source.websites()?, ["a", "b"]);
+        assert_eq!(source.open_document("a", None)?.as_deref(), Some("<link rel=stylesheet href='css/a.css'>"));
+        assert_eq!(source.open_stylesheet("a", "./css/../css/a.css")?, "p {}");
+        assert!(source.open_stylesheet("a", "missing.css").is_err());
+        let error = source.open_document("b", None).unwrap_err();
+        assert!(matches!(error.error, ErrorKind::MultipleHtmlFiles(files) if files.len() == 2));
+        assert_eq!(source.open_document("b", Some("two.html".as_ref()))?.as_deref(), Some(""));
+        Ok(())
+    }
+
+    #[test]
+    fn parses_websites_from_memory() -> Result<()> {
+        let mut source = MemorySource::new();
+        source.insert_file("site", "index.html", "<link rel='stylesheet' href='s.css'><p class='a'></p>".to_owned());
+        source.insert_file("site", "s.css", ".a {}".to_owned());
+        let website = crate::parse::parse_website_from_source(&source, "site", &Default::default())?.unwrap();
+        assert_eq!(website.name, "site");
+        assert_eq!(website.stylesheet_names(), ["s.css"]);
+        assert_eq!(website.get_matcher().get_selectors().len(), 1);
+        Ok(())
+    }
+}
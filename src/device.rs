@@ -0,0 +1,410 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The device that selectors are matched for. It decides which `@media` rules
+//! end up in the selector maps.
+//!
+//! Stylo's servo `Device` only evaluates `prefers-color-scheme` among the
+//! user-preference and interaction media features; a query using any of the
+//! others never matches. So `prefers-reduced-motion`, `forced-colors`,
+//! `hover`, `pointer`, `any-hover` and `any-pointer` are resolved before the
+//! CSS is parsed (`resolve_media_features`), by replacing each one in an
+//! `@media` prelude with a condition that is always true or never true.
+//!
+//! `DeviceOptions` (de)serializes, with the same value names as the command
+//! line, so library consumers can keep device configurations in a file. A
+//! consumer needing something `DeviceOptions` can't say can build stylo's
+//! `Device` itself and pass it to `MatchingContext::with_stylo_device`.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use cssparser::{ParseError, Parser, ParserInput, Token};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MediaType {
+    #[default]
+    Screen,
+    Print,
+}
+
+/// A viewport size in CSS pixels, written `WIDTHxHEIGHT`, e.g. `375x667`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Viewport {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl FromStr for Viewport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |n: &str| n.trim().parse::<f32>().ok().filter(|n| n.is_finite() && *n > 0.0);
+        s.split_once(['x', 'X'])
+            .and_then(|(width, height)| Some(Viewport { width: parse(width)?, height: parse(height)? }))
+            .ok_or_else(|| format!("expected a viewport like 375x667, got {s:?}"))
+    }
+}
+
+impl fmt::Display for Viewport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+impl From<Viewport> for String {
+    fn from(viewport: Viewport) -> Self {
+        viewport.to_string()
+    }
+}
+
+impl TryFrom<String> for Viewport {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorScheme {
+    #[default]
+    Light,
+    Dark,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReducedMotion {
+    #[default]
+    NoPreference,
+    Reduce,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ForcedColors {
+    #[default]
+    None,
+    Active,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Hover {
+    None,
+    #[default]
+    Hover,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Pointer {
+    None,
+    Coarse,
+    #[default]
+    Fine,
+}
+
+/// User preferences and input capabilities, as seen by media queries. The
+/// defaults are a desktop with a mouse and no preferences.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::Args, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct MediaFeatures {
+    /// The `prefers-color-scheme` media queries are evaluated against
+    #[arg(long, value_enum, default_value_t)]
+    pub prefers_color_scheme: ColorScheme,
+    /// The `prefers-reduced-motion` media queries are evaluated against
+    #[arg(long, value_enum, default_value_t)]
+    pub prefers_reduced_motion: ReducedMotion,
+    /// The `forced-colors` media queries are evaluated against
+    #[arg(long, value_enum, default_value_t)]
+    pub forced_colors: ForcedColors,
+    /// The `hover` (and `any-hover`) media queries are evaluated against
+    #[arg(long, value_enum, default_value_t)]
+    pub hover: Hover,
+    /// The `pointer` (and `any-pointer`) media queries are evaluated against
+    #[arg(long, value_enum, default_value_t)]
+    pub pointer: Pointer,
+}
+
+impl MediaFeatures {
+    /// Evaluates a media feature written `name: value` or just `name` (the
+    /// inside of its parentheses), or returns `None` if it isn't one of the
+    /// features resolved here or its value is unknown.
+    fn evaluate(&self, feature: &str) -> Option<bool> {
+        let (name, value) = match feature.split_once(':') {
+            Some((name, value)) => (name.trim(), Some(value.trim())),
+            None => (feature.trim(), None),
+        };
+        match name {
+            "prefers-reduced-motion" => keyword(self.prefers_reduced_motion, value, ReducedMotion::NoPreference),
+            "forced-colors" => keyword(self.forced_colors, value, ForcedColors::None),
+            "hover" | "any-hover" => keyword(self.hover, value, Hover::None),
+            "pointer" | "any-pointer" => keyword(self.pointer, value, Pointer::None),
+            _ => None,
+        }
+    }
+}
+
+/// Evaluates a keyword media feature whose value is `actual`. In a boolean
+/// context (no value), it is true unless `actual` is `falsy`.
+fn keyword<T: ValueEnum + PartialEq>(actual: T, value: Option<&str>, falsy: T) -> Option<bool> {
+    match value {
+        Some(value) => T::from_str(value, true).ok().map(|value| value == actual),
+        None => Some(actual != falsy),
+    }
+}
+
+/// Media conditions that are always and never true, in any device
+const ALWAYS: &str = "(min-width: 0px)";
+const NEVER: &str = "(not (min-width: 0px))";
+
+/// Replaces the media features in `@media` preludes that stylo can't evaluate
+/// with conditions that are always true or never true for `features`. The
+/// CSS is tokenized, so text in comments and strings is left alone.
+pub(crate) fn resolve_media_features<'a>(css: &'a str, features: &MediaFeatures) -> Cow<'a, str> {
+    let mut input = ParserInput::new(css);
+    let mut parser = Parser::new(&mut input);
+    let mut replacements = Vec::new();
+    find_media_rules(&mut parser, features, &mut replacements);
+    if replacements.is_empty() {
+        return Cow::Borrowed(css);
+    }
+    let mut resolved = String::with_capacity(css.len());
+    let mut copied = 0;
+    for (range, replacement) in replacements {
+        resolved.push_str(&css[copied..range.start]);
+        resolved.push_str(replacement);
+        copied = range.end;
+    }
+    resolved.push_str(&css[copied..]);
+    Cow::Owned(resolved)
+}
+
+/// Byte ranges of `css` to replace, in order
+type Replacements = Vec<(Range<usize>, &'static str)>;
+
+/// Resolves the preludes of the `@media` rules in `parser`, at any depth
+fn find_media_rules(parser: &mut Parser, features: &MediaFeatures, replacements: &mut Replacements) {
+    loop {
+        match parser.next().cloned() {
+            Ok(Token::AtKeyword(name)) if name.eq_ignore_ascii_case("media") => {
+                resolve_prelude(parser, features, replacements);
+            }
+            Ok(Token::CurlyBracketBlock | Token::ParenthesisBlock | Token::SquareBracketBlock | Token::Function(_)) => {
+                let _ = parser.parse_nested_block(|block| {
+                    find_media_rules(block, features, replacements);
+                    Ok::<_, ParseError<()>>(())
+                });
+            }
+            Ok(_) => (),
+            Err(_) => return,
+        }
+    }
+}
+
+/// Resolves the features of the `@media` prelude `parser` is at, and then the
+/// rule's block
+fn resolve_prelude(parser: &mut Parser, features: &MediaFeatures, replacements: &mut Replacements) {
+    loop {
+        parser.skip_whitespace();
+        let start = parser.position();
+        match parser.next().cloned() {
+            Ok(Token::ParenthesisBlock) => resolve_condition(parser, start, features, replacements),
+            Ok(Token::CurlyBracketBlock) => {
+                let _ = parser.parse_nested_block(|block| {
+                    find_media_rules(block, features, replacements);
+                    Ok::<_, ParseError<()>>(())
+                });
+                return;
+            }
+            Ok(Token::Semicolon) | Err(_) => return,
+            Ok(_) => (),
+        }
+    }
+}
+
+/// Resolves the parenthesized condition `parser` just read, starting at
+/// `start`. Only features, the innermost parentheses, are replaced.
+fn resolve_condition(
+    parser: &mut Parser,
+    start: cssparser::SourcePosition,
+    features: &MediaFeatures,
+    replacements: &mut Replacements,
+) {
+    let mut nested = false;
+    let _ = parser.parse_nested_block(|block| {
+        loop {
+            block.skip_whitespace();
+            let start = block.position();
+            match block.next().cloned() {
+                Ok(Token::ParenthesisBlock) => {
+                    nested = true;
+                    resolve_condition(block, start, features, replacements);
+                }
+                Ok(Token::Function(_)) => nested = true,
+                Ok(_) => (),
+                Err(_) => break,
+            }
+        }
+        Ok::<_, ParseError<()>>(())
+    });
+    if nested {
+        return;
+    }
+    let condition = parser.slice_from(start);
+    // An unclosed parenthesis at the end of the CSS has no `)`
+    let Some(feature) = condition.strip_prefix('(').and_then(|condition| condition.strip_suffix(')')) else {
+        return;
+    };
+    if let Some(value) = features.evaluate(&feature.to_ascii_lowercase()) {
+        let start = start.byte_index();
+        replacements.push((start..start + condition.len(), if value { ALWAYS } else { NEVER }));
+    }
+}
+
+/// What selectors are matched for. Any field left out when deserializing
+/// is the default's.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct DeviceOptions {
+    pub viewport: Viewport,
+    /// Device pixels per CSS pixel, for `resolution` media queries
+    pub device_pixel_ratio: f32,
+    pub media_type: MediaType,
+    pub media_features: MediaFeatures,
+}
+
+impl Default for DeviceOptions {
+    fn default() -> Self {
+        Self {
+            viewport: Viewport { width: 1200.0, height: 800.0 },
+            device_pixel_ratio: 1.0,
+            media_type: MediaType::Screen,
+            media_features: MediaFeatures::default(),
+        }
+    }
+}
+
+impl DeviceOptions {
+    /// Stylo's `Device` for these options, which evaluates the media
+    /// queries `resolve_media_features` leaves.
+    pub fn stylo_device(&self) -> style::media_queries::Device {
+        crate::stylo_interface::device(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColorScheme, DeviceOptions, Hover, MediaFeatures, Pointer, ReducedMotion, Viewport, resolve_media_features};
+    use crate::MatchingContext;
+    use crate::parse::parse_stylesheet;
+    use cssparser::ToCss as _;
+    use style::shared_lock::SharedRwLock;
+    use style::stylesheets::UrlExtraData;
+    use test_log::test;
+
+    #[test]
+    fn parses_viewports() {
+        assert_eq!("375x667".parse(), Ok(Viewport { width: 375.0, height: 667.0 }));
+        assert_eq!("1280X720.5".parse(), Ok(Viewport { width: 1280.0, height: 720.5 }));
+        assert!("375".parse::<Viewport>().is_err());
+        assert!("0x10".parse::<Viewport>().is_err());
+    }
+
+    fn selectors_for(css: &str, device: &DeviceOptions) -> Vec<String> {
+        let lock = SharedRwLock::new();
+        let url_data = UrlExtraData::from(url::Url::parse("about:blank").unwrap());
+        let css = resolve_media_features(css, &device.media_features);
+        let stylesheet = parse_stylesheet(&css, url_data, &lock).unwrap();
+        let context = MatchingContext::with_device(std::iter::once(&stylesheet), lock, device);
+        let mut selectors: Vec<_> = context.get_selectors().iter().map(|s| s.to_css_string()).collect();
+        selectors.sort();
+        selectors
+    }
+
+    #[test]
+    fn media_queries_follow_the_device() {
+        let css = "@media (max-width: 500px) { .narrow {} } \
+                   @media (min-resolution: 2dppx) { .retina {} } \
+                   @media print { .print {} } \
+                   .always {}";
+        let selectors_for = |device| selectors_for(css, device);
+        assert_eq!(selectors_for(&DeviceOptions::default()), [".always"]);
+        let phone = DeviceOptions {
+            viewport: Viewport { width: 375.0, height: 667.0 },
+            device_pixel_ratio: 3.0,
+            ..Default::default()
+        };
+        assert_eq!(selectors_for(&phone), [".always", ".narrow", ".retina"]);
+        let print = DeviceOptions { media_type: super::MediaType::Print, ..Default::default() };
+        assert_eq!(selectors_for(&print), [".always", ".print"]);
+    }
+
+    #[test]
+    fn resolves_media_features() {
+        let features = MediaFeatures { hover: Hover::None, ..Default::default() };
+        assert_eq!(
+            resolve_media_features("@media (HOVER: hover) and (min-width: 1px), not ( pointer ) { a {} } p { b: c }", &features),
+            "@media (not (min-width: 0px)) and (min-width: 1px), not (min-width: 0px) { a {} } p { b: c }",
+        );
+        assert_eq!(
+            resolve_media_features("@media ((any-hover) or (pointer: bogus)) {}", &features),
+            "@media ((not (min-width: 0px)) or (pointer: bogus)) {}",
+        );
+        let untouched = "@media (prefers-color-scheme: dark) {} .hover {}";
+        assert!(matches!(resolve_media_features(untouched, &features), std::borrow::Cow::Borrowed(_)));
+        assert_eq!(
+            resolve_media_features("/* @media (hover) {} */ a { content: '@media (hover)' } a { @media (hover) {} }", &features),
+            "/* @media (hover) {} */ a { content: '@media (hover)' } a { @media (not (min-width: 0px)) {} }",
+        );
+    }
+
+    #[test]
+    fn deserializes_partial_device_options() {
+        let phone: DeviceOptions = serde_json::from_str(
+            r#"{"viewport": "375x667", "device-pixel-ratio": 3, "media-features": {"pointer": "coarse"}}"#,
+        ).unwrap();
+        assert_eq!(phone, DeviceOptions {
+            viewport: Viewport { width: 375.0, height: 667.0 },
+            device_pixel_ratio: 3.0,
+            media_features: MediaFeatures { pointer: Pointer::Coarse, ..Default::default() },
+            ..Default::default()
+        });
+        let round_trip: DeviceOptions = serde_json::from_str(&serde_json::to_string(&phone).unwrap()).unwrap();
+        assert_eq!(round_trip, phone);
+        assert!(serde_json::from_str::<DeviceOptions>(r#"{"viewport": "375"}"#).is_err());
+        assert!(serde_json::from_str::<DeviceOptions>(r#"{"pixel-ratio": 2}"#).is_err());
+    }
+
+    #[test]
+    fn media_features_follow_the_overrides() {
+        let css = "@media (prefers-color-scheme: dark) { .dark {} } \
+                   @media (prefers-reduced-motion: reduce) { .still {} } \
+                   @media (hover: hover) and (pointer: fine) { .mouse {} } \
+                   @media (pointer: coarse) { .touch {} }";
+        assert_eq!(selectors_for(css, &DeviceOptions::default()), [".mouse"]);
+        let phone = DeviceOptions {
+            media_features: MediaFeatures {
+                prefers_color_scheme: ColorScheme::Dark,
+                prefers_reduced_motion: ReducedMotion::Reduce,
+                hover: Hover::None,
+                pointer: Pointer::Coarse,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(selectors_for(css, &phone), [".dark", ".still", ".touch"]);
+    }
+}
@@ -0,0 +1,122 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Rendering errors with miette: CSS parse errors and invalid selectors show
+//! the offending line with the error underlined, under the file's path.
+
+use miette::{Diagnostic, NamedSource, SourceSpan};
+
+use crate::result::{Error, ErrorKind};
+
+#[derive(Debug, thiserror::Error, Diagnostic)]
+#[error("{message}")]
+pub struct ErrorDiagnostic {
+    message: String,
+    #[source_code]
+    source_code: Option<NamedSource<String>>,
+    #[label("here")]
+    span: Option<SourceSpan>,
+    #[related]
+    related: Vec<ErrorDiagnostic>,
+}
+
+impl ErrorDiagnostic {
+    fn message(message: String) -> Self {
+        Self { message, source_code: None, span: None, related: Vec::new() }
+    }
+}
+
+impl From<&Error> for ErrorDiagnostic {
+    fn from(error: &Error) -> Self {
+        match &error.error {
+            ErrorKind::CssParse { file, errors, source } => {
+                let name = error.path.as_ref().map_or_else(|| file.clone(), |path| path.display().to_string());
+                let mut diagnostic = Self::message(format!("{} css parse errors in {file}", errors.len()));
+                diagnostic.related = errors
+                    .iter()
+                    .map(|css_error| Self {
+                        message: css_error.message.clone(),
+                        source_code: Some(NamedSource::new(&name, source.to_string())),
+                        span: Some(span(source, css_error.line, css_error.column)),
+                        related: Vec::new(),
+                    })
+                    .collect();
+                diagnostic
+            }
+            ErrorKind::InvalidSelector { selector, line, column, message } => Self {
+                message: format!("invalid selector: {message}"),
+                source_code: Some(NamedSource::new("selector", selector.clone())),
+                span: Some(span(selector, *line, *column)),
+                related: Vec::new(),
+            },
+            ErrorKind::Multiple(errors) => {
+                let mut diagnostic = Self::message(format!("{} errors", errors.len()));
+                diagnostic.related = errors.iter().map(Self::from).collect();
+                diagnostic
+            }
+            _ => Self::message(error.to_string().trim_end().to_owned()),
+        }
+    }
+}
+
+/// A report for `error`, whose `Debug` output is the rendered diagnostic.
+pub fn report(error: &Error) -> miette::Report {
+    miette::Report::new(ErrorDiagnostic::from(error))
+}
+
+/// From the 1-based `line` and `column` (counted in UTF-16 code units, as
+/// cssparser does) to the end of that line of `source`.
+fn span(source: &str, line: u32, column: u32) -> SourceSpan {
+    let mut offset = 0;
+    let mut lines = source.split_inclusive('\n');
+    for _ in 1..line {
+        offset += lines.next().map_or(0, str::len);
+    }
+    let line = lines.next().unwrap_or_default().trim_end_matches(['\n', '\r']);
+    let mut units = 1;
+    let mut start = line.len();
+    for (i, c) in line.char_indices() {
+        if units >= column {
+            start = i;
+            break;
+        }
+        units += c.len_utf16() as u32;
+    }
+    (offset + start, line.len() - start).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{report, span};
+    use crate::result::{CssParseError, Error, ErrorKind};
+    use test_log::test;
+
+    #[test]
+    fn spans_run_to_the_end_of_the_line() {
+        let css = "p {}\n  é { colr: red }\n";
+        assert_eq!(span(css, 1, 1), (0, 4).into());
+        // é is one UTF-16 unit but two bytes
+        assert_eq!(span(css, 2, 7), (5 + 7, 11).into());
+        assert_eq!(span(css, 9, 1), (css.len(), 0).into());
+    }
+
+    #[test]
+    fn renders_sources() {
+        let source = "div { colr: blue; }";
+        let error = Error {
+            path: Some("site/broken.css".into()),
+            error: ErrorKind::CssParse {
+                file: "broken.css".to_owned(),
+                errors: vec![CssParseError { line: 1, column: 7, message: "unsupported property".to_owned() }],
+                source: source.into(),
+            },
+        };
+        let rendered = format!("{:?}", report(&error));
+        assert!(rendered.contains("site/broken.css"), "{rendered}");
+        assert!(rendered.contains(source), "{rendered}");
+        assert!(rendered.contains("unsupported property"), "{rendered}");
+    }
+}
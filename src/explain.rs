@@ -0,0 +1,239 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Tracing why a selector does or doesn't match an element, one compound
+//! selector at a time, right to left, the way matching walks it.
+//!
+//! After each combinator the candidates (ancestors for ` `, the parent for
+//! `>`, previous siblings for `~`, the previous sibling for `+`) are tried
+//! nearest first. The trace follows the first candidate from which the rest
+//! of the selector matches, as matching would; if there is none, it follows
+//! the first candidate matching the compound alone, to show where the rest
+//! fails.
+
+use cssparser::ToCss as _;
+use scraper::{ElementRef, Html};
+use selectors::context::SelectorCaches;
+use selectors::matching;
+use selectors::parser::{Combinator, Component};
+use serde::Serialize;
+use style::selector_parser::SelectorImpl;
+
+use crate::result::Result;
+use crate::structs::{ElementPath, Selector};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Explanation {
+    pub selector: String,
+    pub matched: bool,
+    /// Rightmost compound first
+    pub steps: Vec<ExplainStep>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ExplainStep {
+    pub compound: String,
+    /// How this step's candidates relate to the previous step's element, e.g.
+    /// `descendant`. `None` for the rightmost compound.
+    pub combinator: Option<&'static str>,
+    /// The XPath of the element the compound matched, or `None` if no
+    /// candidate did
+    pub element: Option<String>,
+    /// XPaths of candidates tried before `element`
+    pub rejected: Vec<String>,
+}
+
+/// Finds the element with the given key: its `ElementPath` in `Display` form
+/// (`html[0]/body[1]/p[0]`, as in matches output) or its XPath
+/// (`/html[1]/body[1]/p[1]`).
+pub fn find_element<'a>(document: &'a Html, key: &str) -> Option<ElementRef<'a>> {
+    let root = document.root_element();
+    std::iter::once(root).chain(root.descendent_elements()).find(|element| {
+        let path = ElementPath::from(*element);
+        if key.starts_with('/') { path.to_xpath() == key } else { path.to_string() == key }
+    })
+}
+
+/// Explains each selector in the comma-separated list `selectors` against
+/// `element`.
+pub fn explain(element: ElementRef<'_>, selectors: &str) -> Result<Vec<Explanation>> {
+    let list = crate::parse_selector_list(selectors)?;
+    Ok(list.slice().iter().map(|selector| explain_selector(element, selector)).collect())
+}
+
+pub(crate) fn explain_selector(element: ElementRef<'_>, selector: &Selector) -> Explanation {
+    // Compounds right to left, each in parse order with the combinator to its
+    // left
+    let mut compounds: Vec<(Vec<Component<SelectorImpl>>, Option<Combinator>)> = Vec::new();
+    let mut iter = selector.iter();
+    loop {
+        let mut components: Vec<_> = iter.by_ref().cloned().collect();
+        components.reverse();
+        if components.is_empty() {
+            components.push(Component::ExplicitUniversalType);
+        }
+        let combinator = iter.next_sequence();
+        compounds.push((components, combinator));
+        if combinator.is_none() {
+            break;
+        }
+    }
+    let compound_css = |i: usize| -> String { compounds[i].0.iter().map(|component| component.to_css_string()).collect() };
+    // The part of the selector from compound `i` leftward, as a selector
+    let rest = |i: usize| -> Selector {
+        let components = compounds[i..].iter().rev().flat_map(|(compound, combinator)| {
+            compound.iter().cloned().chain(combinator.map(Component::Combinator))
+        });
+        crate::preprocessing::selector_from_iter(components)
+    };
+    let compound_selector = |i: usize| crate::preprocessing::selector_from_iter(compounds[i].0.iter().cloned());
+    let mut caches = SelectorCaches::default();
+    let mut matches = |selector: &Selector, element: ElementRef<'_>| {
+        let mut context = matching::MatchingContext::new(
+            matching::MatchingMode::Normal,
+            None,
+            &mut caches,
+            matching::QuirksMode::NoQuirks,
+            matching::NeedsSelectorFlags::No,
+            matching::MatchingForInvalidation::No,
+        );
+        matching::matches_selector(selector, 0, None, &element, &mut context).0
+    };
+    let xpath = |element: ElementRef<'_>| ElementPath::from(element).to_xpath();
+
+    let mut explanation = Explanation {
+        selector: selector.to_css_string(),
+        matched: matches(selector, element),
+        steps: Vec::new(),
+    };
+    let first = matches(&compound_selector(0), element);
+    explanation.steps.push(ExplainStep {
+        compound: compound_css(0),
+        combinator: None,
+        element: first.then(|| xpath(element)),
+        rejected: if first { Vec::new() } else { vec![xpath(element)] },
+    });
+    if !first {
+        return explanation;
+    }
+    let mut current = element;
+    for i in 1..compounds.len() {
+        let combinator = compounds[i - 1].1.unwrap();
+        let candidates = candidates(current, combinator);
+        let rest = rest(i);
+        let compound = compound_selector(i);
+        let chosen = candidates
+            .iter()
+            .position(|&candidate| matches(&rest, candidate))
+            .or_else(|| candidates.iter().position(|&candidate| matches(&compound, candidate)));
+        let rejected_count = chosen.unwrap_or(candidates.len());
+        explanation.steps.push(ExplainStep {
+            compound: compound_css(i),
+            combinator: Some(combinator_name(combinator)),
+            element: chosen.map(|i| xpath(candidates[i])),
+            rejected: candidates[..rejected_count].iter().map(|&candidate| xpath(candidate)).collect(),
+        });
+        match chosen {
+            Some(chosen) => current = candidates[chosen],
+            None => break,
+        }
+    }
+    explanation
+}
+
+/// The elements a compound to the left of `combinator` may match, nearest
+/// first, given that the compound to its right matched `element`.
+fn candidates(element: ElementRef<'_>, combinator: Combinator) -> Vec<ElementRef<'_>> {
+    let mut ancestors = element.ancestors().filter_map(ElementRef::wrap);
+    let mut previous_siblings = element.prev_siblings().filter_map(ElementRef::wrap);
+    match combinator {
+        Combinator::Child => ancestors.next().into_iter().collect(),
+        Combinator::Descendant => ancestors.collect(),
+        Combinator::NextSibling => previous_siblings.next().into_iter().collect(),
+        Combinator::LaterSibling => previous_siblings.collect(),
+        // Pseudo-elements, slots and parts: the compound applies to the
+        // element itself
+        Combinator::PseudoElement | Combinator::SlotAssignment | Combinator::Part => vec![element],
+    }
+}
+
+fn combinator_name(combinator: Combinator) -> &'static str {
+    match combinator {
+        Combinator::Child => "child",
+        Combinator::Descendant => "descendant",
+        Combinator::NextSibling => "next_sibling",
+        Combinator::LaterSibling => "later_sibling",
+        Combinator::PseudoElement => "pseudo_element",
+        Combinator::SlotAssignment => "slot_assignment",
+        Combinator::Part => "part",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{explain, find_element};
+    use scraper::Html;
+    use test_log::test;
+
+    const SPAN: &str = "/html[1]/body[1]/div[1]/p[1]/span[1]";
+
+    fn document() -> Html {
+        Html::parse_document(r#"<div class="a"><p><span id="t"></span></p></div><section><span></span></section>"#)
+    }
+
+    #[test]
+    fn finds_elements_by_either_key() {
+        let document = document();
+        let by_xpath = find_element(&document, SPAN).unwrap();
+        let by_display = find_element(&document, "html[0]/body[1]/div[0]/p[0]/span[0]").unwrap();
+        assert_eq!(by_xpath, by_display);
+        assert_eq!(by_xpath.value().id(), Some("t"));
+        assert!(find_element(&document, "/html[1]/body[1]/table[1]").is_none());
+    }
+
+    #[test]
+    fn follows_the_matching_ancestor() {
+        let document = document();
+        let span = find_element(&document, SPAN).unwrap();
+        let explanation = explain(span, "div.a span#t").unwrap().remove(0);
+        assert!(explanation.matched);
+        assert_eq!(explanation.steps.len(), 2);
+        assert_eq!(explanation.steps[0].compound, "span#t");
+        assert_eq!(explanation.steps[1].combinator, Some("descendant"));
+        assert_eq!(explanation.steps[1].element.as_deref(), Some("/html[1]/body[1]/div[1]"));
+        assert_eq!(explanation.steps[1].rejected, ["/html[1]/body[1]/div[1]/p[1]"]);
+    }
+
+    #[test]
+    fn shows_where_matching_fails() {
+        let document = document();
+        let span = find_element(&document, SPAN).unwrap();
+        let explanations = explain(span, ".a > span, em").unwrap();
+        assert!(!explanations[0].matched);
+        assert_eq!(explanations[0].steps[1].element, None);
+        assert_eq!(explanations[0].steps[1].rejected, ["/html[1]/body[1]/div[1]/p[1]"]);
+        assert!(!explanations[1].matched);
+        assert_eq!(explanations[1].steps.len(), 1);
+        assert_eq!(explanations[1].steps[0].element, None);
+        assert!(explain(span, "p..a").is_err());
+    }
+
+    #[test]
+    fn explains_namespaced_selectors() {
+        let lock = style::shared_lock::SharedRwLock::new();
+        let stylesheet = crate::parse::parse_stylesheet(
+            "@namespace h url(http://www.w3.org/1999/xhtml); h|div span {}",
+            style::stylesheets::UrlExtraData::from(url::Url::parse("about:blank").unwrap()),
+            &lock,
+        ).unwrap();
+        let website = crate::parse::ParsedWebsite::new("test".to_owned(), document(), vec![stylesheet], lock);
+        let selectors = website.get_matcher().get_selectors();
+        let span = find_element(website.document(), SPAN).unwrap();
+        let explanation = super::explain_selector(span, &selectors[0]);
+        assert!(explanation.matched);
+        assert_eq!(explanation.steps[1].element.as_deref(), Some("/html[1]/body[1]/div[1]"));
+    }
+}
@@ -0,0 +1,103 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+use std::io;
+
+use crate::structs::ser::SerDocumentMatches;
+use crate::structs::set::SetDocumentMatches;
+
+pub const HEADER: [&str; 4] = ["website", "element_key", "element_html", "selector"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Delimiter {
+    Comma,
+    Tab,
+}
+
+impl Delimiter {
+    fn as_byte(self) -> u8 {
+        match self {
+            Delimiter::Comma => b',',
+            Delimiter::Tab => b'\t',
+        }
+    }
+}
+
+/// Writes one row per (website, element, selector) match, one website at a
+/// time, after a header row. Elements that shared styles get the selectors of
+/// the element they shared with. Rows for a website are in document order,
+/// then selector order, so output is stable across runs.
+pub struct MatchesWriter<W: io::Write>(::csv::Writer<W>);
+
+impl<W: io::Write> MatchesWriter<W> {
+    pub fn new(writer: W, delimiter: Delimiter) -> io::Result<Self> {
+        let mut writer = ::csv::WriterBuilder::new()
+            .delimiter(delimiter.as_byte())
+            .from_writer(writer);
+        writer.write_record(HEADER)?;
+        Ok(Self(writer))
+    }
+
+    pub fn write_website(&mut self, website: &str, matches: &SetDocumentMatches) -> io::Result<()> {
+        let matches = SerDocumentMatches::from(matches);
+        for (key, element_matches) in &matches.0 {
+            let key = key.0.to_string();
+            for selector in &element_matches.selectors {
+                self.0.write_record([website, &key, &element_matches.html, selector])?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Writes all of `results` with a `MatchesWriter`, in the order given.
+pub fn write_matches<'a, W: io::Write>(
+    writer: W,
+    results: impl IntoIterator<Item = (&'a str, &'a SetDocumentMatches)>,
+    delimiter: Delimiter,
+) -> io::Result<()> {
+    let mut writer = MatchesWriter::new(writer, delimiter)?;
+    for (website, matches) in results {
+        writer.write_website(website, matches)?;
+    }
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Delimiter, write_matches};
+    use crate::test_matches as matches;
+    use test_log::test;
+
+    #[test]
+    fn one_row_per_match() {
+        let a = matches("<p class=\"x\">a, b</p>", &["p", ".x", "span"]);
+        let b = matches("<span>b</span>", &["span"]);
+        let mut out = Vec::new();
+        write_matches(&mut out, [("a", &a), ("b", &b)], Delimiter::Comma).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(lines, [
+            "website,element_key,element_html,selector",
+            "a,html[0]/body[1]/p[0],\"<p class=\"\"x\"\">\",.x",
+            "a,html[0]/body[1]/p[0],\"<p class=\"\"x\"\">\",p",
+            "b,html[0]/body[1]/span[0],<span>,span",
+        ]);
+    }
+
+    #[test]
+    fn tab_delimited() {
+        let a = matches("<p>a</p>", &["p"]);
+        let mut out = Vec::new();
+        write_matches(&mut out, [("a", &a)], Delimiter::Tab).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().nth(1), Some("a\thtml[0]/body[1]/p[0]\t<p>\tp"));
+    }
+}
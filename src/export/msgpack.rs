@@ -0,0 +1,103 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! A compact binary form of `SetDocumentMatches`, for feeding results to other
+//! Rust tools without going through YAML. A stream is a sequence of
+//! MessagePack values, one `(website, SetDocumentMatches)` pair per website,
+//! so it can be written and read one website at a time.
+
+use std::io::{self, BufRead, BufReader, Read};
+
+use crate::result::{Error, ErrorKind, IntoResultExt, Result};
+use crate::structs::set::SetDocumentMatches;
+
+pub struct MsgpackWriter<W: io::Write>(W);
+
+impl<W: io::Write> MsgpackWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self(writer)
+    }
+
+    pub fn write_website(&mut self, website: &str, matches: &SetDocumentMatches) -> Result<()> {
+        rmp_serde::encode::write(&mut self.0, &(website, matches)).map_err(|e| Error {
+            path: None,
+            error: ErrorKind::MsgpackEncode(e),
+        })
+    }
+
+    pub fn finish(mut self) -> Result<W> {
+        self.0.flush().into_result(None)?;
+        Ok(self.0)
+    }
+}
+
+/// Reads back what a `MsgpackWriter` wrote, one website at a time.
+pub struct MsgpackReader<R: Read>(BufReader<R>);
+
+impl<R: Read> MsgpackReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self(BufReader::new(reader))
+    }
+}
+
+impl<R: Read> Iterator for MsgpackReader<R> {
+    type Item = Result<(String, SetDocumentMatches)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.0.fill_buf() {
+            Ok([]) => return None,
+            Ok(_) => (),
+            Err(e) => return Some(Err(e).into_result(None)),
+        }
+        Some(rmp_serde::decode::from_read(&mut self.0).map_err(|e| Error {
+            path: None,
+            error: ErrorKind::MsgpackDecode(e),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MsgpackReader, MsgpackWriter};
+    use crate::test_matches as matches;
+    use crate::structs::set::SetDocumentMatches;
+    use test_log::test;
+
+    #[test]
+    fn round_trips() {
+        let a = matches("<p>a</p><div><p>b</p></div>", &["p", "div > p"]);
+        let b = matches("<span>c</span>", &["span"]);
+        let mut writer = MsgpackWriter::new(Vec::new());
+        writer.write_website("a", &a).unwrap();
+        writer.write_website("b", &b).unwrap();
+        let bytes = writer.finish().unwrap();
+        let read: Vec<_> = MsgpackReader::new(bytes.as_slice())
+            .collect::<crate::result::Result<_>>()
+            .unwrap();
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[0].0, "a");
+        assert_eq!(read[1].0, "b");
+        assert!(read[0].1.diff(&a).is_empty());
+        assert!(read[1].1.diff(&b).is_empty());
+        // paths keep their type indices, which their string form leaves out
+        let paths = |m: &SetDocumentMatches| {
+            let mut paths: Vec<_> = m.0.values().map(|e| e.element.path.to_xpath()).collect();
+            paths.sort();
+            paths
+        };
+        assert_eq!(paths(&read[0].1), paths(&a));
+    }
+
+    #[test]
+    fn truncated_input_is_an_error() {
+        let a = matches("<p>a</p>", &["p"]);
+        let mut writer = MsgpackWriter::new(Vec::new());
+        writer.write_website("a", &a).unwrap();
+        let bytes = writer.finish().unwrap();
+        let mut read = MsgpackReader::new(&bytes[..bytes.len() - 1]);
+        assert!(read.next().unwrap().is_err());
+    }
+}
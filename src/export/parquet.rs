@@ -0,0 +1,166 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+use arrow_array::builder::{Float64Builder, StringBuilder, UInt64Builder};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use ::parquet::arrow::ArrowWriter;
+use ::parquet::errors::ParquetError;
+
+use crate::result::{Error, ErrorKind, IntoResultExt, Result};
+use crate::structs::ser::SerDocumentMatches;
+use crate::structs::set::SetDocumentMatches;
+use crate::structs::stats::DocumentStats;
+
+pub const MATCHES_FILE: &str = "matches.parquet";
+pub const STATS_FILE: &str = "stats.parquet";
+
+fn matches_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("website", DataType::Utf8, false),
+        Field::new("element_key", DataType::Utf8, false),
+        Field::new("element_html", DataType::Utf8, false),
+        Field::new("line", DataType::UInt64, true),
+        Field::new("column", DataType::UInt64, true),
+        Field::new("selector", DataType::Utf8, false),
+    ]))
+}
+
+fn stats_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("website", DataType::Utf8, false),
+        Field::new("element_count", DataType::UInt64, false),
+        Field::new("selector_count", DataType::UInt64, false),
+        Field::new("matched_pairs", DataType::UInt64, false),
+        Field::new("elements_with_zero_matches", DataType::UInt64, false),
+        Field::new("max_selectors_on_one_element", DataType::UInt64, false),
+        Field::new("mean_selectors_per_element", DataType::Float64, false),
+        Field::new("elements_sharing_styles", DataType::UInt64, false),
+    ]))
+}
+
+/// Writes `matches.parquet`, with one row per (website, element, selector)
+/// match like the CSV export, and `stats.parquet`, with one row of
+/// `DocumentStats` per website, into `dir`. Each website is written as its
+/// own row group, so memory use doesn't grow with the number of websites.
+pub fn write_parquet<'a>(
+    dir: &Path,
+    results: impl IntoIterator<Item = (&'a str, &'a SetDocumentMatches)>,
+) -> Result<()> {
+    std::fs::create_dir_all(dir).into_result(Some(dir.to_path_buf()))?;
+    let matches_path = dir.join(MATCHES_FILE);
+    let stats_path = dir.join(STATS_FILE);
+    let matches_file = File::create(&matches_path).into_result(Some(matches_path.clone()))?;
+    let stats_file = File::create(&stats_path).into_result(Some(stats_path.clone()))?;
+    let with_path = |path: &Path| {
+        let path = path.to_path_buf();
+        move |e| Error { path: Some(path), error: ErrorKind::Parquet(e) }
+    };
+    let mut matches_writer = ArrowWriter::try_new(matches_file, matches_schema(), None)
+        .map_err(with_path(&matches_path))?;
+    let mut stats_writer = ArrowWriter::try_new(stats_file, stats_schema(), None)
+        .map_err(with_path(&stats_path))?;
+    for (website, matches) in results {
+        matches_writer.write(&matches_batch(website, matches)?).map_err(with_path(&matches_path))?;
+        stats_writer.write(&stats_batch(website, matches)?).map_err(with_path(&stats_path))?;
+    }
+    matches_writer.close().map_err(with_path(&matches_path))?;
+    stats_writer.close().map_err(with_path(&stats_path))?;
+    Ok(())
+}
+
+fn matches_batch(website: &str, matches: &SetDocumentMatches) -> Result<RecordBatch> {
+    let matches = SerDocumentMatches::from(matches);
+    let mut websites = StringBuilder::new();
+    let mut keys = StringBuilder::new();
+    let mut htmls = StringBuilder::new();
+    let mut lines = UInt64Builder::new();
+    let mut source_columns = UInt64Builder::new();
+    let mut selectors = StringBuilder::new();
+    for (key, element_matches) in &matches.0 {
+        let key = key.0.to_string();
+        for selector in &element_matches.selectors {
+            websites.append_value(website);
+            keys.append_value(&key);
+            htmls.append_value(&element_matches.html);
+            lines.append_option(element_matches.line);
+            source_columns.append_option(element_matches.column);
+            selectors.append_value(selector);
+        }
+    }
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(websites.finish()),
+        Arc::new(keys.finish()),
+        Arc::new(htmls.finish()),
+        Arc::new(lines.finish()),
+        Arc::new(source_columns.finish()),
+        Arc::new(selectors.finish()),
+    ];
+    record_batch(matches_schema(), columns)
+}
+
+fn stats_batch(website: &str, matches: &SetDocumentMatches) -> Result<RecordBatch> {
+    let stats = DocumentStats::from(matches);
+    let mut website_column = StringBuilder::new();
+    website_column.append_value(website);
+    let count = |n: usize| -> ArrayRef {
+        let mut builder = UInt64Builder::new();
+        builder.append_value(n as u64);
+        Arc::new(builder.finish())
+    };
+    let mut mean = Float64Builder::new();
+    mean.append_value(stats.mean_selectors_per_element);
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(website_column.finish()),
+        count(stats.element_count),
+        count(stats.selector_count),
+        count(stats.matched_pairs),
+        count(stats.elements_with_zero_matches),
+        count(stats.max_selectors_on_one_element),
+        Arc::new(mean.finish()),
+        count(stats.elements_sharing_styles),
+    ];
+    record_batch(stats_schema(), columns)
+}
+
+fn record_batch(schema: SchemaRef, columns: Vec<ArrayRef>) -> Result<RecordBatch> {
+    RecordBatch::try_new(schema, columns).map_err(|e| Error {
+        path: None,
+        error: ErrorKind::Parquet(ParquetError::from(e)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::{MATCHES_FILE, STATS_FILE, write_parquet};
+    use crate::test_matches as matches;
+    use ::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use test_log::test;
+
+    fn row_count(path: &std::path::Path) -> usize {
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        reader.map(|batch| batch.unwrap().num_rows()).sum()
+    }
+
+    #[test]
+    fn writes_matches_and_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = matches("<p>a</p><p>b</p>", &["p", "body"]);
+        let b = matches("<p>c</p>", &["p"]);
+        write_parquet(dir.path(), [("a", &a), ("b", &b)]).unwrap();
+        assert_eq!(row_count(&dir.path().join(MATCHES_FILE)), 4);
+        assert_eq!(row_count(&dir.path().join(STATS_FILE)), 2);
+    }
+}
@@ -0,0 +1,136 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+use std::collections::HashMap;
+use std::path::Path;
+
+use rusqlite::{Connection, params};
+
+use crate::result::{Error, ErrorKind, Result};
+use crate::structs::ser::SerDocumentMatches;
+use crate::structs::set::SetDocumentMatches;
+
+const SCHEMA: &str = "
+CREATE TABLE websites (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+CREATE TABLE elements (
+    id INTEGER PRIMARY KEY,
+    website_id INTEGER NOT NULL REFERENCES websites(id),
+    path TEXT NOT NULL,
+    html TEXT NOT NULL,
+    css_path TEXT NOT NULL,
+    xpath TEXT NOT NULL,
+    line INTEGER,
+    column INTEGER,
+    UNIQUE (website_id, path)
+);
+CREATE TABLE selectors (
+    id INTEGER PRIMARY KEY,
+    text TEXT NOT NULL UNIQUE
+);
+CREATE TABLE matches (
+    element_id INTEGER NOT NULL REFERENCES elements(id),
+    selector_id INTEGER NOT NULL REFERENCES selectors(id),
+    PRIMARY KEY (element_id, selector_id)
+);
+CREATE INDEX matches_by_selector ON matches(selector_id);
+";
+
+/// Writes every website's results into a new SQLite database at `path`.
+/// Selectors are shared between websites, so e.g. "selectors matching more
+/// than 1000 elements across all websites" is a single `GROUP BY` over
+/// `matches`. Fails if the database already has these tables.
+pub fn write_database<'a>(
+    path: &Path,
+    results: impl IntoIterator<Item = (&'a str, &'a SetDocumentMatches)>,
+) -> Result<()> {
+    write_database_inner(path, results).map_err(|e| Error {
+        path: Some(path.to_path_buf()),
+        error: ErrorKind::Sqlite(e),
+    })
+}
+
+fn write_database_inner<'a>(
+    path: &Path,
+    results: impl IntoIterator<Item = (&'a str, &'a SetDocumentMatches)>,
+) -> rusqlite::Result<()> {
+    let mut connection = Connection::open(path)?;
+    let transaction = connection.transaction()?;
+    transaction.execute_batch(SCHEMA)?;
+    {
+        let mut insert_website = transaction.prepare("INSERT INTO websites (name) VALUES (?1)")?;
+        let mut insert_element = transaction.prepare(
+            "INSERT INTO elements (website_id, path, html, css_path, xpath, line, column) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )?;
+        let mut insert_selector = transaction.prepare("INSERT INTO selectors (text) VALUES (?1)")?;
+        let mut insert_match = transaction.prepare("INSERT INTO matches (element_id, selector_id) VALUES (?1, ?2)")?;
+        let mut selector_ids: HashMap<String, i64> = HashMap::new();
+        for (website, matches) in results {
+            insert_website.execute(params![website])?;
+            let website_id = transaction.last_insert_rowid();
+            let matches = SerDocumentMatches::from(matches);
+            for (key, element_matches) in matches.0 {
+                insert_element.execute(params![
+                    website_id,
+                    key.0.to_string(),
+                    element_matches.html,
+                    element_matches.css_path,
+                    element_matches.xpath,
+                    element_matches.line,
+                    element_matches.column,
+                ])?;
+                let element_id = transaction.last_insert_rowid();
+                for selector in element_matches.selectors {
+                    let selector_id = match selector_ids.get(&selector) {
+                        Some(id) => *id,
+                        None => {
+                            insert_selector.execute(params![selector])?;
+                            let id = transaction.last_insert_rowid();
+                            selector_ids.insert(selector, id);
+                            id
+                        }
+                    };
+                    insert_match.execute(params![element_id, selector_id])?;
+                }
+            }
+        }
+    }
+    transaction.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write_database;
+    use crate::test_matches as matches;
+    use rusqlite::Connection;
+    use test_log::test;
+
+    #[test]
+    fn writes_all_tables() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("results.sqlite");
+        let a = matches("<p>a</p><p>b</p>", &["p", "body"]);
+        let b = matches("<p>c</p>", &["p"]);
+        write_database(&path, [("a", &a), ("b", &b)]).unwrap();
+
+        let connection = Connection::open(&path).unwrap();
+        let count = |sql: &str| -> i64 { connection.query_row(sql, [], |row| row.get(0)).unwrap() };
+        assert_eq!(count("SELECT COUNT(*) FROM websites"), 2);
+        // html, head, body and the paragraphs
+        assert_eq!(count("SELECT COUNT(*) FROM elements"), 9);
+        assert_eq!(count("SELECT COUNT(*) FROM selectors"), 2);
+        assert_eq!(count("SELECT COUNT(*) FROM matches"), 4);
+        let p_matches = count(
+            "SELECT COUNT(*) FROM matches JOIN selectors ON selectors.id = matches.selector_id WHERE text = 'p'",
+        );
+        assert_eq!(p_matches, 3);
+
+        // Refuses to write into an existing results database
+        assert!(write_database(&path, [("a", &a)]).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Font metrics measured from the system's fonts, for resolving `ex`, `ch`,
+//! `cap` and `ic` units like a browser would.
+//!
+//! Generic families map to fontdb's configured families (Times New Roman,
+//! Arial, Courier New, etc. unless the system's fontconfig says otherwise).
+//! When no installed font matches, the metrics are left to stylo's
+//! fallbacks, e.g. 0.5em for `ex`.
+
+use std::sync::OnceLock;
+
+use fontdb::{Database, Family, Query, Weight};
+use rustybuzz::{Face, UnicodeBuffer};
+use style::font_metrics::FontMetrics;
+use style::properties::style_structs::Font;
+use style::servo::media_queries::FontMetricsProvider;
+use style::values::computed::font::{GenericFontFamily, QueryFontMetricsFlags, SingleFontFamily};
+use style::values::computed::{CSSPixelLength, Length};
+
+/// Loading the system's fonts takes a while, so it is done once, the first
+/// time a device is created.
+static SYSTEM_FONTS: OnceLock<Database> = OnceLock::new();
+
+#[derive(Debug)]
+pub(crate) struct SystemFontMetricsProvider {
+    fonts: &'static Database,
+}
+
+impl SystemFontMetricsProvider {
+    pub(crate) fn new() -> Self {
+        let fonts = SYSTEM_FONTS.get_or_init(|| {
+            let mut fonts = Database::new();
+            fonts.load_system_fonts();
+            fonts
+        });
+        Self { fonts }
+    }
+}
+
+impl FontMetricsProvider for SystemFontMetricsProvider {
+    fn query_font_metrics(
+        &self,
+        vertical: bool,
+        font: &Font,
+        base_size: CSSPixelLength,
+        flags: QueryFontMetricsFlags,
+    ) -> FontMetrics {
+        let mut families: Vec<Family> = font
+            .font_family
+            .families
+            .iter()
+            .map(|family| match family {
+                SingleFontFamily::FamilyName(name) => Family::Name(&name.name),
+                SingleFontFamily::Generic(generic) => generic_family(*generic),
+            })
+            .collect();
+        // The last resort, as in browsers
+        families.push(Family::Serif);
+        let query = Query {
+            families: &families,
+            weight: Weight(font.font_weight.value().round().clamp(1.0, 1000.0) as u16),
+            ..Query::default()
+        };
+        let unmeasured = FontMetrics {
+            ascent: base_size,
+            ..FontMetrics::default()
+        };
+        let Some(id) = self.fonts.query(&query) else {
+            return unmeasured;
+        };
+        self.fonts
+            .with_face_data(id, |data, index| {
+                let face = Face::from_slice(data, index)?;
+                Some(measure(&face, vertical, base_size, flags))
+            })
+            .flatten()
+            .unwrap_or(unmeasured)
+    }
+
+    fn base_size_for_generic(&self, generic: GenericFontFamily) -> Length {
+        // Browsers' default font sizes
+        match generic {
+            GenericFontFamily::Monospace => CSSPixelLength::new(13.0),
+            _ => CSSPixelLength::new(16.0),
+        }
+    }
+}
+
+fn generic_family(generic: GenericFontFamily) -> Family<'static> {
+    match generic {
+        GenericFontFamily::Serif => Family::Serif,
+        GenericFontFamily::Monospace => Family::Monospace,
+        GenericFontFamily::Cursive => Family::Cursive,
+        GenericFontFamily::Fantasy => Family::Fantasy,
+        _ => Family::SansSerif,
+    }
+}
+
+fn measure(face: &Face, vertical: bool, size: CSSPixelLength, flags: QueryFontMetricsFlags) -> FontMetrics {
+    let scale = size.px() / f32::from(face.units_per_em());
+    let px = |units: f32| CSSPixelLength::new(units * scale);
+    FontMetrics {
+        x_height: face.x_height().map(|units| px(units.into())),
+        zero_advance_measure: flags
+            .contains(QueryFontMetricsFlags::NEEDS_CH)
+            .then(|| advance(face, '0', vertical))
+            .flatten()
+            .map(px),
+        cap_height: face.capital_height().map(|units| px(units.into())),
+        ic_width: flags
+            .contains(QueryFontMetricsFlags::NEEDS_IC)
+            .then(|| advance(face, '\u{6C34}', vertical)) // 水, as the spec says
+            .flatten()
+            .map(px),
+        ascent: px(face.ascender().into()),
+        script_percent_scale_down: None,
+        script_script_percent_scale_down: None,
+    }
+}
+
+/// The advance of `c` when shaped on its own, in font units, or `None` if
+/// the font has no glyph for it.
+fn advance(face: &Face, c: char, vertical: bool) -> Option<f32> {
+    face.glyph_index(c)?;
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(c.encode_utf8(&mut [0; 4]));
+    if vertical {
+        buffer.set_direction(rustybuzz::Direction::TopToBottom);
+    }
+    let shaped = rustybuzz::shape(face, &[], buffer);
+    let position = shaped.glyph_positions().first()?;
+    let advance = if vertical { -position.y_advance } else { position.x_advance };
+    Some(advance as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SystemFontMetricsProvider;
+    use style::properties::style_structs::Font;
+    use style::servo::media_queries::FontMetricsProvider as _;
+    use style::values::computed::CSSPixelLength;
+    use style::values::computed::font::{GenericFontFamily, QueryFontMetricsFlags};
+    use test_log::test;
+
+    #[test]
+    fn metrics_scale_with_font_size() {
+        let provider = SystemFontMetricsProvider::new();
+        let font = Font::initial_values();
+        let flags = QueryFontMetricsFlags::NEEDS_CH;
+        let small = provider.query_font_metrics(false, &font, CSSPixelLength::new(10.0), flags);
+        let large = provider.query_font_metrics(false, &font, CSSPixelLength::new(20.0), flags);
+        assert_eq!(large.ascent.px(), small.ascent.px() * 2.0);
+        // Only with fonts installed
+        if let (Some(small), Some(large)) = (small.zero_advance_measure, large.zero_advance_measure) {
+            assert!(small.px() > 0.0);
+            assert!((large.px() - small.px() * 2.0).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn default_font_sizes() {
+        let provider = SystemFontMetricsProvider::new();
+        assert_eq!(provider.base_size_for_generic(GenericFontFamily::Monospace).px(), 13.0);
+        assert_eq!(provider.base_size_for_generic(GenericFontFamily::Serif).px(), 16.0);
+    }
+}
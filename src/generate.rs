@@ -0,0 +1,642 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Random documents and selectors for property tests, with the `generate`
+//! feature.
+//!
+//! Documents only use elements the html parser keeps where they were written,
+//! and selectors only use type, id, class and attribute selectors with the
+//! descendant and child combinators, so `GenCase::expected_matches` can work
+//! out what should match without going through Stylo.
+//!
+//! `FixtureConfig` makes the same kind of case from a seed instead, with
+//! exact sizes, for synthetic websites to benchmark scaling on (the
+//! `generate` subcommand).
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::path::Path;
+
+use proptest::collection::{btree_map, btree_set, vec};
+use proptest::prelude::*;
+use proptest::sample::select;
+use proptest::test_runner::{RngAlgorithm, TestRng};
+use scraper::Html;
+use style::shared_lock::SharedRwLock;
+use style::stylesheets::UrlExtraData;
+
+use crate::parse::{ParsedWebsite, parse_stylesheet};
+use crate::result::{IntoResultExt as _, Result};
+
+const NAMES: &[&str] = &["div", "span", "section", "article", "aside", "nav"];
+const ATTR_NAMES: &[&str] = &["data-a", "data-b", "title"];
+const ATTR_VALUES: &[&str] = &["x", "y", "z"];
+
+/// Controls the shape of what is generated. Small pools make many elements
+/// and selectors share names, which crowds a few `SelectorMap` buckets.
+#[derive(Clone, Debug)]
+pub struct GenConfig {
+    pub max_depth: u32,
+    pub max_children: usize,
+    pub max_selectors: usize,
+    /// Compounds per selector
+    pub max_compounds: usize,
+    pub class_pool: usize,
+    pub id_pool: usize,
+}
+
+impl Default for GenConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: 5,
+            max_children: 4,
+            max_selectors: 16,
+            max_compounds: 3,
+            class_pool: 8,
+            id_pool: 8,
+        }
+    }
+}
+
+impl GenConfig {
+    /// Many selectors keyed on very few classes and ids.
+    pub fn crowded_buckets() -> Self {
+        Self {
+            max_selectors: 128,
+            class_pool: 2,
+            id_pool: 1,
+            ..Self::default()
+        }
+    }
+
+    fn class(&self) -> impl Strategy<Value = String> + use<> {
+        (0..self.class_pool).prop_map(|i| format!("c{i}"))
+    }
+
+    fn id(&self) -> impl Strategy<Value = String> + use<> {
+        (0..self.id_pool).prop_map(|i| format!("i{i}"))
+    }
+
+    pub fn element(&self) -> impl Strategy<Value = GenElement> + use<> {
+        let childless = (
+            select(NAMES),
+            proptest::option::of(self.id()),
+            btree_set(self.class(), 0..=3),
+            btree_map(select(ATTR_NAMES), select(ATTR_VALUES), 0..=2),
+        )
+            .prop_map(|(name, id, classes, attrs)| GenElement {
+                name,
+                id,
+                classes,
+                attrs,
+                children: Vec::new(),
+            })
+            .boxed();
+        let max_children = self.max_children;
+        childless.clone().prop_recursive(self.max_depth, 256, max_children as u32, move |inner| {
+            (childless.clone(), vec(inner, 0..=max_children)).prop_map(|(element, children)| GenElement {
+                children,
+                ..element
+            })
+        })
+    }
+
+    pub fn document(&self) -> impl Strategy<Value = GenDocument> + use<> {
+        vec(self.element(), 1..=self.max_children).prop_map(|body| GenDocument { body })
+    }
+
+    fn compound(&self) -> impl Strategy<Value = GenCompound> + use<> {
+        let attr = (select(ATTR_NAMES), proptest::option::of(select(ATTR_VALUES)));
+        (
+            proptest::option::of(select(NAMES)),
+            proptest::option::weighted(0.2, self.id()),
+            btree_set(self.class(), 0..=2),
+            vec(attr, 0..=1),
+        )
+            .prop_map(|(name, id, classes, attrs)| {
+                let mut compound = GenCompound { name, id, classes, attrs };
+                // An empty compound would be `*`, which also matches <html> and
+                // <body>
+                if compound.is_empty() {
+                    compound.name = Some(NAMES[0]);
+                }
+                compound
+            })
+    }
+
+    pub fn selector(&self) -> impl Strategy<Value = GenSelector> + use<> {
+        vec((self.compound(), any::<bool>()), 1..=self.max_compounds).prop_map(|parts| {
+            let mut combinators = Vec::new();
+            let mut compounds = Vec::new();
+            for (i, (compound, child)) in parts.into_iter().enumerate() {
+                if i != 0 {
+                    combinators.push(if child { Combinator::Child } else { Combinator::Descendant });
+                }
+                compounds.push(compound);
+            }
+            GenSelector { compounds, combinators }
+        })
+    }
+
+    pub fn case(&self) -> impl Strategy<Value = GenCase> + use<> {
+        (self.document(), vec(self.selector(), 1..=self.max_selectors))
+            .prop_map(|(document, selectors)| GenCase { document, selectors })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GenElement {
+    pub name: &'static str,
+    pub id: Option<String>,
+    pub classes: BTreeSet<String>,
+    pub attrs: BTreeMap<&'static str, &'static str>,
+    pub children: Vec<GenElement>,
+}
+
+impl GenElement {
+    fn write_html(&self, out: &mut String) {
+        write!(out, "<{}", self.name).unwrap();
+        if let Some(id) = &self.id {
+            write!(out, " id=\"{id}\"").unwrap();
+        }
+        if !self.classes.is_empty() {
+            let classes: Vec<&str> = self.classes.iter().map(String::as_str).collect();
+            write!(out, " class=\"{}\"", classes.join(" ")).unwrap();
+        }
+        for (name, value) in &self.attrs {
+            write!(out, " {name}=\"{value}\"").unwrap();
+        }
+        out.push('>');
+        for child in &self.children {
+            child.write_html(out);
+        }
+        write!(out, "</{}>", self.name).unwrap();
+    }
+}
+
+/// The children of `<body>`.
+#[derive(Clone, Debug)]
+pub struct GenDocument {
+    pub body: Vec<GenElement>,
+}
+
+impl GenDocument {
+    pub fn to_html(&self) -> String {
+        self.to_html_with_head("")
+    }
+
+    /// With `head` as the contents of `<head>`
+    pub fn to_html_with_head(&self, head: &str) -> String {
+        let mut out = format!("<!DOCTYPE html><html><head>{head}</head><body>");
+        for element in &self.body {
+            element.write_html(&mut out);
+        }
+        out.push_str("</body></html>");
+        out
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Combinator {
+    Descendant,
+    Child,
+}
+
+#[derive(Clone, Debug)]
+pub struct GenCompound {
+    pub name: Option<&'static str>,
+    pub id: Option<String>,
+    pub classes: BTreeSet<String>,
+    /// Attribute names, and the value they must equal if any
+    pub attrs: Vec<(&'static str, Option<&'static str>)>,
+}
+
+impl GenCompound {
+    fn is_empty(&self) -> bool {
+        self.name.is_none() && self.id.is_none() && self.classes.is_empty() && self.attrs.is_empty()
+    }
+
+    fn matches(&self, element: &FlatElement<'_>) -> bool {
+        let Some(gen_element) = element.element else {
+            // <html> and <body>, which have no attributes and a name outside
+            // of `NAMES`
+            return false;
+        };
+        self.name.is_none_or(|name| name == gen_element.name)
+            && self.id.as_ref().is_none_or(|id| gen_element.id.as_ref() == Some(id))
+            && self.classes.is_subset(&gen_element.classes)
+            && self.attrs.iter().all(|(name, value)| match gen_element.attrs.get(name) {
+                Some(actual) => value.is_none_or(|value| value == *actual),
+                None => false,
+            })
+    }
+
+    fn write_css(&self, out: &mut String) {
+        if let Some(name) = self.name {
+            out.push_str(name);
+        }
+        if let Some(id) = &self.id {
+            write!(out, "#{id}").unwrap();
+        }
+        for class in &self.classes {
+            write!(out, ".{class}").unwrap();
+        }
+        for (name, value) in &self.attrs {
+            match value {
+                Some(value) => write!(out, "[{name}=\"{value}\"]").unwrap(),
+                None => write!(out, "[{name}]").unwrap(),
+            }
+        }
+    }
+}
+
+/// Compounds from left to right. `combinators[i]` joins `compounds[i]` and
+/// `compounds[i + 1]`.
+#[derive(Clone, Debug)]
+pub struct GenSelector {
+    pub compounds: Vec<GenCompound>,
+    pub combinators: Vec<Combinator>,
+}
+
+impl GenSelector {
+    pub fn to_css(&self) -> String {
+        let mut out = String::new();
+        for (i, compound) in self.compounds.iter().enumerate() {
+            if i != 0 {
+                out.push_str(match self.combinators[i - 1] {
+                    Combinator::Descendant => " ",
+                    Combinator::Child => " > ",
+                });
+            }
+            compound.write_css(&mut out);
+        }
+        out
+    }
+
+    /// Whether the selector, up to and including `compounds[last]`, matches
+    /// `elements[element]`.
+    fn matches(&self, elements: &[FlatElement<'_>], element: usize, last: usize) -> bool {
+        if !self.compounds[last].matches(&elements[element]) {
+            return false;
+        }
+        if last == 0 {
+            return true;
+        }
+        let parent = elements[element].parent;
+        match self.combinators[last - 1] {
+            Combinator::Child => parent.is_some_and(|parent| self.matches(elements, parent, last - 1)),
+            Combinator::Descendant => std::iter::successors(parent, |&ancestor| elements[ancestor].parent)
+                .any(|ancestor| self.matches(elements, ancestor, last - 1)),
+        }
+    }
+}
+
+struct FlatElement<'a> {
+    /// `None` for `<html>` and `<body>`
+    element: Option<&'a GenElement>,
+    parent: Option<usize>,
+    xpath: String,
+}
+
+/// A document and the selectors to match against it.
+#[derive(Clone, Debug)]
+pub struct GenCase {
+    pub document: GenDocument,
+    pub selectors: Vec<GenSelector>,
+}
+
+impl GenCase {
+    /// One rule per selector.
+    pub fn to_css(&self) -> String {
+        let mut out = String::new();
+        for selector in &self.selectors {
+            writeln!(out, "{} {{ color: red }}", selector.to_css()).unwrap();
+        }
+        out
+    }
+
+    pub fn to_website(&self, name: &str) -> ParsedWebsite {
+        let lock = SharedRwLock::new();
+        let url_data = UrlExtraData::from(url::Url::parse("about:blank").unwrap());
+        let stylesheet = parse_stylesheet(&self.to_css(), url_data, &lock).unwrap();
+        ParsedWebsite::new(
+            name.to_owned(),
+            Html::parse_document(&self.document.to_html()),
+            vec![stylesheet],
+            lock,
+        )
+    }
+
+    /// Writes the case as a website folder at `path`: `index.html`, linking
+    /// `style.css`.
+    pub fn write_website(&self, path: &Path) -> Result<()> {
+        std::fs::create_dir_all(path).into_result(Some(path.to_path_buf()))?;
+        let html = self.document.to_html_with_head(r#"<link rel="stylesheet" href="style.css">"#);
+        let html_path = path.join("index.html");
+        std::fs::write(&html_path, html).into_result(Some(html_path))?;
+        let css_path = path.join("style.css");
+        std::fs::write(&css_path, self.to_css()).into_result(Some(css_path))
+    }
+
+    /// For each element matched by at least one selector, its XPath (as
+    /// `ElementPath::to_xpath` would write it) and the indices into
+    /// `selectors` of the selectors matching it.
+    pub fn expected_matches(&self) -> BTreeMap<String, BTreeSet<usize>> {
+        fn flatten<'a>(children: &'a [GenElement], parent: usize, elements: &mut Vec<FlatElement<'a>>) {
+            let mut seen: BTreeMap<&str, usize> = BTreeMap::new();
+            for child in children {
+                let type_index = seen.entry(child.name).or_default();
+                *type_index += 1;
+                let xpath = format!("{}/{}[{}]", elements[parent].xpath, child.name, type_index);
+                elements.push(FlatElement {
+                    element: Some(child),
+                    parent: Some(parent),
+                    xpath,
+                });
+                let index = elements.len() - 1;
+                flatten(&child.children, index, elements);
+            }
+        }
+        let mut elements = vec![
+            FlatElement { element: None, parent: None, xpath: "/html[1]".to_owned() },
+            FlatElement { element: None, parent: Some(0), xpath: "/html[1]/body[1]".to_owned() },
+        ];
+        flatten(&self.document.body, 1, &mut elements);
+        let mut expected = BTreeMap::new();
+        for (index, element) in elements.iter().enumerate() {
+            let matching: BTreeSet<usize> = self
+                .selectors
+                .iter()
+                .enumerate()
+                .filter(|(_, selector)| selector.matches(&elements, index, selector.compounds.len() - 1))
+                .map(|(i, _)| i)
+                .collect();
+            if !matching.is_empty() {
+                expected.insert(element.xpath.clone(), matching);
+            }
+        }
+        expected
+    }
+}
+
+/// The size and shape of a synthetic website. The same config always
+/// generates the same website.
+#[derive(Clone, Debug, clap::Args)]
+pub struct FixtureConfig {
+    /// Elements in `<body>`
+    #[arg(long, default_value_t = FixtureConfig::default().elements)]
+    pub elements: usize,
+    /// How deep elements can nest, counting the children of `<body>` as 1
+    #[arg(long, default_value_t = FixtureConfig::default().max_depth)]
+    pub max_depth: usize,
+    /// Classes per element, on average
+    #[arg(long, default_value_t = FixtureConfig::default().class_density)]
+    pub class_density: f64,
+    /// The fraction of elements with an id
+    #[arg(long, default_value_t = FixtureConfig::default().id_density)]
+    pub id_density: f64,
+    /// How many different class names elements choose from
+    #[arg(long, default_value_t = FixtureConfig::default().class_pool)]
+    pub class_pool: usize,
+    /// Selectors in the stylesheet, one rule each
+    #[arg(long, default_value_t = FixtureConfig::default().selectors)]
+    pub selectors: usize,
+    /// Compounds per selector, at most
+    #[arg(long, default_value_t = FixtureConfig::default().max_compounds)]
+    pub max_compounds: usize,
+    /// The fraction of combinators that are `>` rather than descendant
+    #[arg(long, default_value_t = FixtureConfig::default().child_ratio)]
+    pub child_ratio: f64,
+    #[arg(long, default_value_t = FixtureConfig::default().seed)]
+    pub seed: u64,
+}
+
+impl Default for FixtureConfig {
+    fn default() -> Self {
+        Self {
+            elements: 1000,
+            max_depth: 12,
+            class_density: 1.0,
+            id_density: 0.1,
+            class_pool: 50,
+            selectors: 500,
+            max_compounds: 3,
+            child_ratio: 0.3,
+            seed: 0,
+        }
+    }
+}
+
+impl FixtureConfig {
+    /// Elements get a parent chosen uniformly from `<body>` and the earlier
+    /// elements that aren't at `max_depth`. Each compound of a selector is
+    /// taken from a random element, so most selectors match something.
+    pub fn generate(&self) -> GenCase {
+        let mut rng = FixtureRng::new(self.seed);
+        let max_depth = self.max_depth.max(1);
+        let mut elements = Vec::with_capacity(self.elements);
+        let mut parents: Vec<Option<usize>> = Vec::with_capacity(self.elements);
+        let mut depths = Vec::with_capacity(self.elements);
+        // Elements that can still have children
+        let mut open = Vec::new();
+        for i in 0..self.elements {
+            let choice = rng.below(open.len() + 1);
+            let parent = open.get(choice).copied();
+            let depth = parent.map_or(1, |parent: usize| depths[parent] + 1);
+            parents.push(parent);
+            depths.push(depth);
+            if depth < max_depth {
+                open.push(i);
+            }
+            let whole_classes = self.class_density.max(0.0).floor();
+            let class_count = whole_classes as usize + usize::from(rng.chance(self.class_density - whole_classes));
+            let classes = if self.class_pool == 0 {
+                BTreeSet::new()
+            } else {
+                (0..class_count).map(|_| format!("c{}", rng.below(self.class_pool))).collect()
+            };
+            elements.push(GenElement {
+                name: NAMES[rng.below(NAMES.len())],
+                id: rng.chance(self.id_density).then(|| format!("i{i}")),
+                classes,
+                attrs: BTreeMap::new(),
+                children: Vec::new(),
+            });
+        }
+
+        let selectors = (0..self.selectors)
+            .map(|_| {
+                let compounds: Vec<GenCompound> = (0..1 + rng.below(self.max_compounds.max(1)))
+                    .map(|_| match elements.len() {
+                        0 => GenCompound { name: Some(NAMES[0]), id: None, classes: BTreeSet::new(), attrs: Vec::new() },
+                        len => fixture_compound(&elements[rng.below(len)], &mut rng),
+                    })
+                    .collect();
+                let combinators = (1..compounds.len())
+                    .map(|_| if rng.chance(self.child_ratio) { Combinator::Child } else { Combinator::Descendant })
+                    .collect();
+                GenSelector { compounds, combinators }
+            })
+            .collect();
+
+        // Parents come before their children, so building from the end
+        // finishes each element before it's moved into its parent
+        let mut children: Vec<Vec<GenElement>> = vec![Vec::new(); elements.len()];
+        let mut body = Vec::new();
+        for (i, mut element) in elements.into_iter().enumerate().rev() {
+            element.children = std::mem::take(&mut children[i]);
+            element.children.reverse();
+            match parents[i] {
+                Some(parent) => children[parent].push(element),
+                None => body.push(element),
+            }
+        }
+        body.reverse();
+        GenCase { document: GenDocument { body }, selectors }
+    }
+}
+
+/// A compound `element` matches: its type, a class, its id, or its type and a
+/// class.
+fn fixture_compound(element: &GenElement, rng: &mut FixtureRng) -> GenCompound {
+    let mut compound = GenCompound { name: None, id: None, classes: BTreeSet::new(), attrs: Vec::new() };
+    let class = (!element.classes.is_empty())
+        .then(|| element.classes.iter().nth(rng.below(element.classes.len())).unwrap().clone());
+    match (rng.below(4), class, &element.id) {
+        (1, Some(class), _) => {
+            compound.classes.insert(class);
+        }
+        (2, _, Some(id)) => compound.id = Some(id.clone()),
+        (3, Some(class), _) => {
+            compound.name = Some(element.name);
+            compound.classes.insert(class);
+        }
+        _ => compound.name = Some(element.name),
+    }
+    compound
+}
+
+struct FixtureRng(TestRng);
+
+impl FixtureRng {
+    fn new(seed: u64) -> Self {
+        let mut bytes = [0; 32];
+        bytes[..8].copy_from_slice(&seed.to_le_bytes());
+        Self(TestRng::from_seed(RngAlgorithm::ChaCha, &bytes))
+    }
+
+    /// In `0..n`; 0 if `n` is 0
+    fn below(&mut self, n: usize) -> usize {
+        if n == 0 {
+            return 0;
+        }
+        (self.0.next_u64() % n as u64) as usize
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        ((self.0.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use cssparser::ToCss as _;
+    use proptest::prelude::*;
+    use style::selector_parser::SelectorParser;
+    use style::stylesheets::UrlExtraData;
+
+    use super::{FixtureConfig, GenCase, GenConfig, GenElement};
+    use crate::structs::owned::OwnedDocumentMatches;
+    use crate::structs::set::SetDocumentMatches;
+    use crate::verify::verify_algorithms;
+    use crate::{Algorithm, match_selectors};
+
+    const ALGORITHMS: [Algorithm; 8] = [
+        Algorithm::Naive,
+        Algorithm::TagIndex,
+        Algorithm::SelectorTrie,
+        Algorithm::Bytecode,
+        Algorithm::WithStyleSharing,
+        Algorithm::WithIsConversion,
+        Algorithm::WithDistribution,
+        Algorithm::Mach7,
+    ];
+
+    /// What naive matching found, in the form of `GenCase::expected_matches`.
+    fn naive_matches(case: &GenCase) -> BTreeMap<String, BTreeSet<usize>> {
+        let website = case.to_website("generated");
+        let selectors = website.get_matcher().get_selectors();
+        let matches = SetDocumentMatches::from(OwnedDocumentMatches::from(&match_selectors(website.document(), &selectors)));
+        // Matches record Stylo's serialization of each selector
+        let url_data = UrlExtraData::from(url::Url::parse("about:blank").unwrap());
+        let indices: BTreeMap<String, BTreeSet<usize>> = case.selectors.iter().enumerate().fold(BTreeMap::new(), |mut indices, (i, selector)| {
+            let parsed = SelectorParser::parse_author_origin_no_namespace(&selector.to_css(), &url_data).unwrap();
+            indices.entry(parsed.slice()[0].to_css_string()).or_default().insert(i);
+            indices
+        });
+        matches
+            .0
+            .values()
+            .filter_map(|element_matches| {
+                let matched: BTreeSet<usize> = matches
+                    .find_selectors(element_matches.element.id)
+                    .iter()
+                    .flat_map(|selector| indices[&**selector].iter().copied())
+                    .collect();
+                (!matched.is_empty()).then(|| (element_matches.element.path.to_xpath(), matched))
+            })
+            .collect()
+    }
+
+    fn count_and_depth(elements: &[GenElement]) -> (usize, usize) {
+        elements.iter().fold((0, 0), |(count, depth), element| {
+            let (child_count, child_depth) = count_and_depth(&element.children);
+            (count + 1 + child_count, depth.max(1 + child_depth))
+        })
+    }
+
+    #[test]
+    fn fixtures_are_reproducible() {
+        let config = FixtureConfig { elements: 300, max_depth: 4, selectors: 40, ..FixtureConfig::default() };
+        let case = config.generate();
+        assert_eq!(case.document.to_html(), config.generate().document.to_html());
+        assert_eq!(case.to_css(), config.generate().to_css());
+        assert_ne!(case.to_css(), FixtureConfig { seed: 1, ..config.clone() }.generate().to_css());
+        let (count, depth) = count_and_depth(&case.document.body);
+        assert_eq!(count, 300);
+        assert!(depth <= 4);
+        assert_eq!(case.selectors.len(), 40);
+        assert_eq!(naive_matches(&case), case.expected_matches());
+
+        let dir = tempfile::tempdir().unwrap();
+        case.write_website(&dir.path().join("synthetic")).unwrap();
+        let website = crate::parse::get_document_and_selectors(&dir.path().join("synthetic")).unwrap().unwrap();
+        assert_eq!(website.stylesheet_names(), ["style.css"]);
+        assert!(!website.get_matcher().get_selectors().is_empty());
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn naive_matches_ground_truth(case in GenConfig::default().case()) {
+            prop_assert_eq!(naive_matches(&case), case.expected_matches());
+        }
+
+        #[test]
+        fn all_algorithms_agree(case in GenConfig::default().case()) {
+            prop_assert!(verify_algorithms(&ALGORITHMS, [&case.to_website("generated")]).is_empty());
+        }
+
+        #[test]
+        fn all_algorithms_agree_with_crowded_buckets(case in GenConfig::crowded_buckets().case()) {
+            prop_assert_eq!(naive_matches(&case), case.expected_matches());
+            prop_assert!(verify_algorithms(&ALGORITHMS, [&case.to_website("generated")]).is_empty());
+        }
+    }
+}
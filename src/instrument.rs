@@ -0,0 +1,135 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! End-to-end instrumentation of processing a website: wall-clock time and,
+//! with the `memory_stats` feature, allocations for each phase, from finding
+//! the website's files to serializing its results.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use derive_more::Display;
+use serde::{Serialize, Serializer};
+
+use crate::memory::{self, MemoryReport};
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// Choosing the website's main html file
+    Discovery,
+    ParseHtml,
+    ParseCss,
+    /// Building the `Stylist`, including its selector maps
+    BuildSelectorMap,
+    Match,
+    Serialize,
+}
+
+/// Wall-clock time per phase. Serialized in seconds.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Timings(pub BTreeMap<Phase, Duration>);
+
+impl Serialize for Timings {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(phase, time)| (phase, time.as_secs_f64())))
+    }
+}
+
+/// Phases can nest (e.g. the is-conversion algorithm builds a second
+/// `Stylist` while matching), in which case the outer phase's numbers include
+/// the inner one's. A phase that runs more than once is summed.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct PhaseReport {
+    pub timings: Timings,
+    /// Empty without the `memory_stats` feature
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub memory: MemoryReport,
+}
+
+impl PhaseReport {
+    pub fn merge(&mut self, other: PhaseReport) {
+        for (phase, time) in other.timings.0 {
+            *self.timings.0.entry(phase).or_default() += time;
+        }
+        for (phase, memory) in other.memory {
+            self.memory.entry(phase).or_default().add(memory);
+        }
+    }
+}
+
+thread_local! {
+    static REPORT: RefCell<PhaseReport> = Default::default();
+}
+
+/// Runs `f`, recording how long it took (and what it allocated) under `phase`.
+/// On wasm32-unknown-unknown, where there is no clock, phases take no time.
+pub fn phase<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    let (result, memory, elapsed) = {
+        let start = std::time::Instant::now();
+        let (result, memory) = memory::measure(f);
+        (result, memory, start.elapsed())
+    };
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    let ((result, memory), elapsed) = (memory::measure(f), Duration::ZERO);
+    REPORT.with_borrow_mut(|report| {
+        *report.timings.0.entry(phase).or_default() += elapsed;
+        if let Some(memory) = memory {
+            report.memory.entry(phase).or_default().add(memory);
+        }
+    });
+    result
+}
+
+/// Returns what this thread recorded since the last call, and starts over.
+pub fn take_report() -> PhaseReport {
+    REPORT.with_borrow_mut(std::mem::take)
+}
+
+/// Adds `report`, e.g. what another thread took, to what this thread
+/// recorded.
+pub fn add_report(report: PhaseReport) {
+    REPORT.with_borrow_mut(|recorded| recorded.merge(report));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Phase, PhaseReport, Timings, phase, take_report};
+    use test_log::test;
+
+    #[test]
+    fn records_and_takes_phases() {
+        take_report();
+        assert_eq!(phase(Phase::Match, || 1 + 1), 2);
+        phase(Phase::Match, || ());
+        phase(Phase::ParseHtml, || ());
+        let report = take_report();
+        assert_eq!(report.timings.0.keys().copied().collect::<Vec<_>>(), [Phase::ParseHtml, Phase::Match]);
+        assert_eq!(take_report(), PhaseReport::default());
+    }
+
+    #[test]
+    fn timings_serialize_in_seconds() {
+        let timings = Timings([(Phase::ParseCss, Duration::from_millis(1500))].into_iter().collect());
+        assert_eq!(serde_yml::to_string(&timings).unwrap(), "parse_css: 1.5\n");
+    }
+
+    #[test]
+    fn merge_sums_timings() {
+        let mut a = PhaseReport::default();
+        a.timings.0.insert(Phase::Match, Duration::from_secs(1));
+        let mut b = PhaseReport::default();
+        b.timings.0.insert(Phase::Match, Duration::from_secs(2));
+        b.timings.0.insert(Phase::Serialize, Duration::from_secs(3));
+        a.merge(b);
+        assert_eq!(a.timings.0[&Phase::Match], Duration::from_secs(3));
+        assert_eq!(a.timings.0[&Phase::Serialize], Duration::from_secs(3));
+    }
+}
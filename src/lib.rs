@@ -13,6 +13,7 @@ use style::animation::DocumentAnimationSet;
 use style::context::SharedStyleContext;
 use style::context::StyleSystemOptions;
 use style::context::ThreadLocalStyleContext;
+use style::media_queries::Device;
 #[cfg(feature = "debug_element")]
 use style::selector_map::debug_element_selector;
 use style::selector_parser::SnapshotMap;
@@ -26,10 +27,13 @@ use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fmt::Write as _;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use scraper::ElementRef;
 use scraper::Html;
+use selectors::attr::NamespaceConstraint;
 use selectors::context::SelectorCaches;
+use selectors::parser::{Component, SelectorList};
+use selectors::visitor::SelectorVisitor;
 use selectors::matching::{self, Statistics};
 use style::context::StyleContext;
 use style::rule_tree::CascadeLevel;
@@ -45,18 +49,60 @@ use smallvec::SmallVec;
 use tsc_timer::Start;
 
 mod stylo_interface;
+#[cfg(feature = "bloom_self_check")]
+pub mod bloom_check;
+pub mod analysis;
+#[cfg(all(feature = "async_loading", not(all(target_arch = "wasm32", target_os = "unknown"))))]
+pub mod async_load;
+pub mod budget;
+pub mod bytecode;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod checkpoint;
+pub mod corpus_source;
+pub mod device;
+#[cfg(feature = "miette")]
+pub mod diagnostics;
+pub mod explain;
+pub mod export;
+#[cfg(feature = "font_metrics")]
+mod font_metrics;
+#[cfg(any(test, feature = "generate"))]
+pub mod generate;
+pub mod instrument;
+pub mod matching;
+pub mod memory;
+pub mod output;
 pub mod parse;
 pub mod preprocessing;
+pub mod profiling;
+#[cfg(feature = "pyo3")]
+mod python;
+pub mod responsive;
 pub mod result;
+pub mod selector_filter;
+pub mod selector_normalization;
+pub mod selector_recovery;
+pub mod selector_trie;
+#[cfg(feature = "serve")]
+pub mod serve;
+pub mod source_map;
 pub mod structs;
+pub mod styles;
+pub mod timings_file;
+pub mod trace;
+#[cfg(feature = "ua_styles")]
+pub mod ua_styles;
+pub mod verify;
 
-pub use parse::get_all_documents_and_selectors;
-use crate::parse::ParsedWebsite;
-use crate::result::Result;
+use crate::device::DeviceOptions;
+use crate::instrument::{self, Phase};
+use crate::parse::{ParseOptions, ParsedWebsite};
+use crate::result::{Error, ErrorKind, Result};
 use crate::structs::owned::OwnedElementMatches;
 use crate::structs::owned::OwnedSelectorsOrSharedStyles;
 use crate::structs::{
-    Element, Selector,
+    CascadePriority, Element, Selector,
     borrowed::{
         DocumentMatches,
         ElementMatches,
@@ -66,15 +112,77 @@ use crate::structs::{
     set::SetDocumentMatches,
 };
 
-#[derive(Debug, Display, Clone, Copy, ValueEnum)]
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, ValueEnum, serde::Serialize, serde::Deserialize)]
 pub enum Algorithm {
     Naive,
+    /// Naive matching against only the selectors whose rightmost compound
+    /// has the element's type selector, or none. A middle ground between
+    /// `Naive` and Stylo's SelectorMap, which also buckets by id and class.
+    TagIndex,
+    /// Naive matching against the candidates from a trie of the selectors'
+    /// rightmost compounds. See `selector_trie`.
+    SelectorTrie,
+    /// Naive matching, running the selectors that compile to bytecode with
+    /// its interpreter. See `bytecode`.
+    Bytecode,
+    /// Each selector reparsed as a `scraper::Selector` and matched with
+    /// `Html::select`, as a user of scraper would. Scraper's parser rejects
+    /// pseudo-classes other than the tree-structural ones, and selectors it
+    /// rejects match nothing, so this agrees with the others only on
+    /// selectors it can parse.
+    ScraperNative,
     WithStyleSharing,
     WithIsConversion,
     WithDistribution,
     Mach7,
 }
 
+impl Algorithm {
+    /// Whether the algorithm matches elements one at a time, independently,
+    /// so that it can match them in any `ElementOrder`. The others walk the
+    /// tree, and style sharing needs each element's parent styled first.
+    pub fn supports_element_order(self) -> bool {
+        matches!(self, Self::Naive | Self::TagIndex | Self::SelectorTrie | Self::Bytecode)
+    }
+}
+
+/// The order `do_website` matches a document's elements in, to see how much
+/// the algorithms depend on visiting them in document order, e.g. through
+/// cache locality or the nth-index cache. Matches are put back in document
+/// order afterward, so results don't depend on it. Only for algorithms that
+/// `supports_element_order`; the others always match in document order.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ElementOrder {
+    #[default]
+    Document,
+    /// A random permutation of document order, chosen by `seed`
+    Shuffled { seed: u64 },
+}
+
+/// Matches `document`'s elements with `match_element` in `order`, then puts
+/// the matches in document order, as the preorder traversals leave them. The
+/// caches are shared between elements as in a traversal.
+pub(crate) fn match_in_element_order<'a>(
+    document: &'a Html,
+    order: ElementOrder,
+    mut match_element: impl FnMut(ElementRef<'a>, &mut SelectorCaches) -> ElementMatches<'a>,
+) -> DocumentMatches<'a> {
+    // `descendants` starts with the root and goes in preorder, like
+    // `child_elements` recursively
+    let mut elements: Vec<_> = document.root_element().descendants().filter_map(ElementRef::wrap).enumerate().collect();
+    if let ElementOrder::Shuffled { seed } = order {
+        let len = elements.len();
+        parse::shuffle(&mut elements, seed, len);
+    }
+    let mut caches: SelectorCaches = Default::default();
+    let mut matches: Vec<_> = elements
+        .into_iter()
+        .map(|(position, element)| (position, match_element(element, &mut caches)))
+        .collect();
+    matches.sort_unstable_by_key(|(position, _)| *position);
+    DocumentMatches(matches.into_iter().map(|(_, matches)| matches).collect())
+}
+
 #[derive(Debug, Default, Clone, Copy)]
 pub struct Optimizations {
     pub is_conversion: bool,
@@ -208,10 +316,10 @@ fn do_website_with_configured_optimizations(
 ) -> (OwnedDocumentMatches, Statistics) {
     // must return OwnedDocumentMatches, because the list of input selectors will be owned by this function
     let document = website.document();
-    let selectors = website.get_matcher().get_selectors();
+    let selectors = selectors_to_match(website, &website.get_matcher());
     let prepared = prepare_selectors(document, &selectors, optimizations);
     let (stylesheet, stylesheet_lock) = stylesheet_from_selectors(prepared.selectors.iter());
-    let matching_context = MatchingContext::new(std::iter::once(&stylesheet), stylesheet_lock);
+    let matching_context = MatchingContext::with_device(std::iter::once(&stylesheet), stylesheet_lock, website.device());
     let (matches, stats) = match_selectors_with_style_sharing(
         document,
         &matching_context,
@@ -241,19 +349,38 @@ impl MatchingContext {
         stylesheets: impl Iterator<Item = &'a DocumentStyleSheet>,
         stylesheet_lock: SharedRwLock,
     ) -> Self {
-        let mut stylist = Stylist::new(
-            stylo_interface::mock_device(),
-            selectors::matching::QuirksMode::NoQuirks,
-        );
+        Self::with_device(stylesheets, stylesheet_lock, &DeviceOptions::default())
+    }
+
+    pub fn with_device<'a>(
+        stylesheets: impl Iterator<Item = &'a DocumentStyleSheet>,
+        stylesheet_lock: SharedRwLock,
+        device: &DeviceOptions,
+    ) -> Self {
+        Self::with_stylo_device(stylesheets, stylesheet_lock, device.stylo_device())
+    }
+
+    /// Matches for a `Device` the caller built. The media features that
+    /// `DeviceOptions::media_features` resolves while parsing aren't
+    /// evaluated by `device`, so they are whatever the stylesheets were
+    /// parsed with.
+    pub fn with_stylo_device<'a>(
+        stylesheets: impl Iterator<Item = &'a DocumentStyleSheet>,
+        stylesheet_lock: SharedRwLock,
+        device: Device,
+    ) -> Self {
+        let mut stylist = Stylist::new(device, selectors::matching::QuirksMode::NoQuirks);
         for sheet in stylesheets {
             stylist.append_stylesheet(sheet.clone(), &stylesheet_lock.read());
         }
-        let ua_or_user_lock = SharedRwLock::new();
-        let ua_or_user_guard = ua_or_user_lock.read();
+        // A user-agent stylesheet, if there is one, is parsed with the
+        // website's lock like the rest
+        let guard = stylesheet_lock.read();
         stylist.flush_without_invalidation(&StylesheetGuards {
-            author: &stylesheet_lock.read(),
-            ua_or_user: &ua_or_user_guard,
+            author: &guard,
+            ua_or_user: &guard,
         });
+        drop(guard);
         Self {
             stylesheet_lock,
             stylist,
@@ -268,16 +395,66 @@ impl MatchingContext {
         &self.stylist
     }
 
+    /// The author origin's selector map for normal rules, if any rules were
+    /// added.
+    pub fn selector_map(&self) -> Option<&SelectorMap<Rule>> {
+        self.stylist.cascade_data().borrow_for_origin(Origin::Author).normal_rules(&[])
+    }
+
     pub fn get_selectors(&self) -> Vec<Selector> {
         let mut selectors = BTreeMap::new();
-        let cascade_data = self.stylist.cascade_data().borrow_for_origin(Origin::Author);
-        if let Some(map) = cascade_data.normal_rules(&[]) {
+        if let Some(map) = self.selector_map() {
+            collect_selectors_from_map(map, &mut selectors);
+        }
+        selectors.into_values().collect()
+    }
+
+    /// Like `get_selectors`, keeping only the first of the selectors that
+    /// serialize the same, so each is matched once. See
+    /// `analysis::dedup`.
+    pub fn get_unique_selectors(&self) -> Vec<Selector> {
+        let mut seen = HashSet::new();
+        self.get_selectors().into_iter().filter(|selector| seen.insert(selector.to_css_string())).collect()
+    }
+
+    /// Each selector's place in the cascade, by its serialization, for
+    /// `SetDocumentMatches::order_by_cascade`. A selector written in more
+    /// than one rule gets the place of the last to apply.
+    pub fn cascade_priorities(&self) -> HashMap<String, CascadePriority> {
+        let mut priorities = HashMap::new();
+        for (rank, origin) in [Origin::UserAgent, Origin::Author].into_iter().enumerate() {
+            let cascade_data = self.stylist.cascade_data().borrow_for_origin(origin);
+            let Some(map) = cascade_data.normal_rules(&[]) else {
+                continue;
+            };
+            for_each_rule(map, |rule| {
+                let priority = CascadePriority {
+                    origin: rank as u8,
+                    layer: cascade_data.layer_order_for(rule.layer_id),
+                    specificity: rule.selector.specificity(),
+                    source_order: rule.source_order,
+                };
+                priorities
+                    .entry(rule.selector.to_css_string())
+                    .and_modify(|p: &mut CascadePriority| *p = (*p).max(priority))
+                    .or_insert(priority);
+            });
+        }
+        priorities
+    }
+
+    /// The selectors of the user-agent origin's normal rules, from
+    /// `ParsedWebsite::with_ua_stylesheet`. Empty unless there is one.
+    pub fn get_ua_selectors(&self) -> Vec<Selector> {
+        let mut selectors = BTreeMap::new();
+        if let Some(map) = self.stylist.cascade_data().borrow_for_origin(Origin::UserAgent).normal_rules(&[]) {
             collect_selectors_from_map(map, &mut selectors);
         }
         selectors.into_values().collect()
     }
 }
 
+#[cfg(feature = "debug_element")]
 fn element_to_string(el: ElementRef<'_>) -> String {
     let name = el.value().name();
     let mut out = String::new();
@@ -302,26 +479,86 @@ fn assert_childrens_parent_is_me(parent: &ElementRef) {
     }
 }
 
-pub fn do_all_websites(websites: &Path, algorithm: Algorithm) -> Result<impl Iterator<Item = Result<(String, SetDocumentMatches, Statistics)>>> {
-    Ok(get_all_documents_and_selectors(websites)?
-        .map(move |r| {
-            r.map(|w| do_website(&w, algorithm, None))
+/// Parses every website folder in `websites_path`.
+#[deprecated(note = "use `mach_6::parse::get_all_documents_and_selectors`")]
+pub fn get_all_documents_and_selectors(websites_path: &Path) -> Result<impl Iterator<Item = Result<ParsedWebsite>> + use<>> {
+    parse::get_all_documents_and_selectors(websites_path)
+}
+
+pub fn do_all_websites(websites: &Path, algorithm: Algorithm) -> Result<impl Iterator<Item = Result<(String, SetDocumentMatches, Statistics)>> + use<>> {
+    do_all_websites_with_options(websites, algorithm, ParseOptions::default())
+}
+
+pub fn do_all_websites_with_options(
+    websites: &Path,
+    algorithm: Algorithm,
+    options: ParseOptions,
+) -> Result<impl Iterator<Item = Result<(String, SetDocumentMatches, Statistics)>> + use<>> {
+    let website_paths = parse::select_websites_dirs(websites, options.selection)?;
+    Ok(do_websites(website_paths, algorithm, options))
+}
+
+/// Like `do_all_websites_with_options`, for these website folders. A website that goes
+/// over `options.budget` is an error, and the rest are still done.
+pub fn do_websites(
+    website_paths: impl Iterator<Item = Result<PathBuf>>,
+    algorithm: Algorithm,
+    options: ParseOptions,
+) -> impl Iterator<Item = Result<(String, SetDocumentMatches, Statistics)>> {
+    website_paths.filter_map(move |r| {
+        r.and_then(|path| {
+            let options = options.clone();
+            options.budget.run(&path.clone(), move || {
+                let website = parse::get_document_and_selectors_with_options(&path, &options)?;
+                Ok(website.map(|website| do_website(&website, algorithm, None)))
+            })
         })
-    )
+        .transpose()
+    })
 }
 
 pub fn do_website(website: &ParsedWebsite, algorithm: Algorithm, mach7_oracle: Option<&DocumentMatches>) -> (String, SetDocumentMatches, Statistics){
-    let matching_context = website.get_matcher();
-    let (matches, stats) = match algorithm {
+    let matching_context = instrument::phase(Phase::BuildSelectorMap, || website.get_matcher());
+    let order = website.element_order();
+    if order != ElementOrder::Document && !algorithm.supports_element_order() {
+        log::warn!("{algorithm} matches {} in document order, since it can't shuffle elements", website.name);
+    }
+    let (matches, stats) = instrument::phase(Phase::Match, || profiling::profile(&website.name, algorithm, || match algorithm {
         Algorithm::Naive => (
-            OwnedDocumentMatches::from(&match_selectors(&website.document(), &matching_context.get_selectors())),
+            OwnedDocumentMatches::from(&match_selectors_in_order(&website.document(), &selectors_to_match(website, &matching_context), order)),
+            Statistics::default()
+        ),
+        Algorithm::TagIndex => (
+            OwnedDocumentMatches::from(&match_selectors_with_tag_index_in_order(&website.document(), &selectors_to_match(website, &matching_context), order)),
+            Statistics::default()
+        ),
+        Algorithm::SelectorTrie => (
+            OwnedDocumentMatches::from(&selector_trie::match_selectors_with_trie_in_order(&website.document(), &selectors_to_match(website, &matching_context), order)),
+            Statistics::default()
+        ),
+        Algorithm::Bytecode => (
+            OwnedDocumentMatches::from(&bytecode::match_selectors_with_bytecode_in_order(&website.document(), &selectors_to_match(website, &matching_context), order)),
+            Statistics::default()
+        ),
+        Algorithm::ScraperNative => (
+            OwnedDocumentMatches::from(&match_selectors_with_scraper(&website.document(), &selectors_to_match(website, &matching_context))),
             Statistics::default()
         ),
         Algorithm::WithStyleSharing => {
+            // Style sharing matches the Stylist's rules, so deduplicating
+            // means a Stylist of the unique selectors
+            let deduplicated;
+            let matching_context = if website.dedup_selectors() {
+                let (stylesheet, stylesheet_lock) = stylesheet_from_selectors(matching_context.get_unique_selectors().iter());
+                deduplicated = MatchingContext::with_device(std::iter::once(&stylesheet), stylesheet_lock, website.device());
+                &deduplicated
+            } else {
+                &matching_context
+            };
             let (matches, stats) =
                 match_selectors_with_style_sharing(
                     &website.document(),
-                    &matching_context,
+                    matching_context,
                     Optimizations::from_none(),
                     None,
                 );
@@ -350,7 +587,7 @@ pub fn do_website(website: &ParsedWebsite, algorithm: Algorithm, mach7_oracle: O
                     Statistics::default()
                 )
             } else {
-                let selectors = matching_context.get_selectors();
+                let selectors = selectors_to_match(website, &matching_context);
                 let document_matches = match_selectors(&website.document(), &selectors);
                 (
                     OwnedDocumentMatches::from(&mach_7(&document_matches)),
@@ -358,60 +595,378 @@ pub fn do_website(website: &ParsedWebsite, algorithm: Algorithm, mach7_oracle: O
                 )
             }
         },
-    };
-    (website.name.clone(), matches.into(), stats)
+    }));
+    let mut matches: SetDocumentMatches = matches.into();
+    // The algorithms are for author selectors; the user-agent stylesheet's
+    // are matched naively on the side
+    let ua_selectors = matching_context.get_ua_selectors();
+    if !ua_selectors.is_empty() {
+        matches.add_ua_matches(&match_selectors(&website.document(), &ua_selectors));
+    }
+    // Before renaming, since priorities are by the selectors' own
+    // serialization
+    if website.cascade_order() {
+        matches.order_by_cascade(&matching_context.cascade_priorities());
+    }
+    if website.normalize_selectors() {
+        let canonical: HashMap<String, std::sync::Arc<str>> = matching_context
+            .get_selectors()
+            .iter()
+            .chain(&ua_selectors)
+            .map(|selector| (selector.to_css_string(), selector_normalization::canonical_css(selector).into()))
+            .collect();
+        matches.rename_selectors(|selector| canonical.get(&**selector).cloned().unwrap_or_else(|| selector.clone()));
+    }
+    (website.name.clone(), matches, stats)
 }
+
+/// The selectors `do_website` matches: all of them, or each once with
+/// `ParsedWebsite::with_dedup_selectors`.
+fn selectors_to_match(website: &ParsedWebsite, matching_context: &MatchingContext) -> Vec<Selector> {
+    if website.dedup_selectors() {
+        matching_context.get_unique_selectors()
+    } else {
+        matching_context.get_selectors()
+    }
+}
+
+/// Parses and matches a website held in memory: `html` is its document and
+/// `files` are what its `<link>`s refer to, by href. Nothing is read from
+/// disk and no threads are started, so this works when compiled to
+/// wasm32-unknown-unknown. `options.main_file` is ignored.
+pub fn match_in_memory(html: &str, files: &[(&str, &str)], algorithm: Algorithm, options: &ParseOptions) -> Result<SetDocumentMatches> {
+    const WEBSITE: &str = "document";
+    // Not `.html`, so a linked file can't make the website have two documents
+    const DOCUMENT: &str = "document";
+    let mut source = corpus_source::MemorySource::new();
+    source.insert_file(WEBSITE, DOCUMENT, html.to_owned());
+    for (href, contents) in files {
+        source.insert_file(WEBSITE, href, (*contents).to_owned());
+    }
+    let options = ParseOptions { main_file: Some(PathBuf::from(DOCUMENT)), ..options.clone() };
+    let website = parse::parse_website_from_source(&source, WEBSITE, &options)?
+        .ok_or_else(|| Error::other("the document was skipped as a duplicate".to_owned()))?;
+    Ok(do_website(&website, algorithm, None).1)
+}
+
+/// Matches `selectors`, a comma-separated selector list, against `website`'s
+/// document with `algorithm`, in place of the website's own stylesheets.
+pub fn query(website: ParsedWebsite, selectors: &str, algorithm: Algorithm) -> Result<SetDocumentMatches> {
+    let selectors = parse_selector_list(selectors)?;
+    let (stylesheet, stylesheet_lock) = stylesheet_from_selectors(selectors.slice().iter());
+    let website = website.with_stylesheets(vec![stylesheet], stylesheet_lock);
+    let (_, matches, _) = do_website(&website, algorithm, None);
+    Ok(matches)
+}
+
+/// Parses a comma-separated selector list, without namespace prefixes.
+pub fn parse_selector_list(selectors: &str) -> Result<SelectorList<style::selector_parser::SelectorImpl>> {
+    let url_data = UrlExtraData::from(url::Url::parse("about:blank").unwrap());
+    style::selector_parser::SelectorParser::parse_author_origin_no_namespace(selectors, &url_data).map_err(|e| Error {
+        path: None,
+        error: ErrorKind::InvalidSelector {
+            selector: selectors.to_owned(),
+            line: e.location.line + 1,
+            column: e.location.column,
+            message: format!("{:?}", e.kind),
+        },
+    })
+}
+
+/// The naive matches of `selectors` (each a selector list) in the document
+/// `html`, for tests that need results to work with.
+#[cfg(test)]
+pub(crate) fn test_matches(html: &str, selectors: &[&str]) -> SetDocumentMatches {
+    let document = Html::parse_document(html);
+    let selectors: Vec<Selector> = selectors
+        .iter()
+        .flat_map(|selectors| parse_selector_list(selectors).unwrap().slice().to_vec())
+        .collect();
+    SetDocumentMatches::from(OwnedDocumentMatches::from(&match_selectors(&document, &selectors)))
+}
+
 // TODO: figure out why iteration yields more elements than traversal
 pub fn match_selectors<'a>(document: &'a Html, selectors: &'a [Selector]) -> DocumentMatches<'a>
 {
-    fn preorder_traversal<'a>(
-        element: ElementRef<'a>, 
-        selectors: &'a [Selector],
-        matches: &mut Vec<ElementMatches<'a>>,
-        caches: &mut SelectorCaches,
-    ) {
-        // 1. do thing
-        // 1.1: create a MatchingContext
-        let mut context = matching::MatchingContext::new(
-            matching::MatchingMode::Normal,
-            None,
-            caches,
-            matching::QuirksMode::NoQuirks,
-            matching::NeedsSelectorFlags::No,
-            matching::MatchingForInvalidation::No,
-        );
-        // 1.2: get matching selectors naively
-        let matched_selectors = selectors
-            .iter()
-            .filter(|s| {
-                // Debug element if applicable
-                #[cfg(feature = "debug_element")]
-                debug_element_selector(element, &element_to_string(element), s);
-                let (res, stats) = matching::matches_selector(s, 0, None, &element, &mut context);
-                debug_assert_eq!(stats.time_fast_rejecting, None);
-                res
-            })
-            .collect();
-        matches.push(ElementMatches{ element, selectors: SelectorsOrSharedStyles::Selectors(matched_selectors) });
-        // 2. traverse children
-        for child in element.child_elements() {
-            preorder_traversal(child, selectors, matches, caches);
-        }
+    let mut caches: SelectorCaches = Default::default();
+    let mut result = Vec::new();
+    naive_preorder_traversal(document.root_element(), selectors, &mut result, &mut caches);
+    DocumentMatches(result)
+}
+
+/// Like `match_selectors`, with the results in `bump`.
+#[cfg(feature = "arena")]
+pub fn match_selectors_in_arena<'bump, 'a>(
+    bump: &'bump bumpalo::Bump,
+    document: &'a Html,
+    selectors: &'a [Selector],
+) -> structs::arena::ArenaDocumentMatches<'bump, 'a> {
+    let mut caches: SelectorCaches = Default::default();
+    let mut result = structs::arena::ArenaDocumentMatches::new_in(bump);
+    arena_preorder_traversal(bump, document.root_element(), selectors, &mut result, &mut caches);
+    result
+}
+
+#[cfg(feature = "arena")]
+fn arena_preorder_traversal<'bump, 'a>(
+    bump: &'bump bumpalo::Bump,
+    element: ElementRef<'a>,
+    selectors: &'a [Selector],
+    matches: &mut structs::arena::ArenaDocumentMatches<'bump, 'a>,
+    caches: &mut SelectorCaches,
+) {
+    budget::stop_if_cancelled();
+    let mut context = matching::MatchingContext::new(
+        matching::MatchingMode::Normal,
+        None,
+        caches,
+        matching::QuirksMode::NoQuirks,
+        matching::NeedsSelectorFlags::No,
+        matching::MatchingForInvalidation::No,
+    );
+    let matched_selectors = bumpalo::collections::Vec::from_iter_in(
+        selectors.iter().filter(|s| matching::matches_selector(s, 0, None, &element, &mut context).0),
+        bump,
+    );
+    matches.0.push(structs::arena::ArenaElementMatches { element, selectors: matched_selectors });
+    for child in element.child_elements() {
+        arena_preorder_traversal(bump, child, selectors, matches, caches);
     }
+}
+
+/// Parses an HTML fragment, such as a component's template, for
+/// `match_fragment_selectors`. `Html::parse_fragment` puts the fragment's
+/// nodes under a synthetic `<html>` element; this moves them up to the tree's
+/// root and removes it, so that as in a `DocumentFragment` the top-level
+/// elements have no parent element, and `html > p` or `:root` match none of
+/// them.
+pub fn parse_fragment(fragment: &str) -> Html {
+    let mut html = Html::parse_fragment(fragment);
+    let synthetic_root = html.root_element().id();
+    let root = html.tree.root().id();
+    html.tree.get_mut(root).unwrap().reparent_from_id_append(synthetic_root);
+    html.tree.get_mut(synthetic_root).unwrap().detach();
+    html
+}
+
+/// The top-level elements of a fragment from `parse_fragment`.
+pub fn fragment_elements(fragment: &Html) -> impl Iterator<Item = ElementRef<'_>> {
+    fragment.tree.root().children().filter_map(ElementRef::wrap)
+}
+
+/// Like `match_selectors`, for a fragment from `parse_fragment`. Elements are
+/// in preorder, one top-level element after another.
+pub fn match_fragment_selectors<'a>(fragment: &'a Html, selectors: &'a [Selector]) -> DocumentMatches<'a> {
     let mut caches: SelectorCaches = Default::default();
     let mut result = Vec::new();
-    preorder_traversal(document.root_element(), selectors, &mut result, &mut caches);
+    for element in fragment_elements(fragment) {
+        naive_preorder_traversal(element, selectors, &mut result, &mut caches);
+    }
     DocumentMatches(result)
 }
 
+/// Like `match_selectors`, matching the elements in `order`.
+pub fn match_selectors_in_order<'a>(document: &'a Html, selectors: &'a [Selector], order: ElementOrder) -> DocumentMatches<'a> {
+    if order == ElementOrder::Document {
+        return match_selectors(document, selectors);
+    }
+    match_in_element_order(document, order, |element, caches| naive_match_element(element, selectors, caches))
+}
+
+fn naive_preorder_traversal<'a>(
+    element: ElementRef<'a>, 
+    selectors: &'a [Selector],
+    matches: &mut Vec<ElementMatches<'a>>,
+    caches: &mut SelectorCaches,
+) {
+    // 1. do thing
+    matches.push(naive_match_element(element, selectors, caches));
+    // 2. traverse children
+    for child in element.child_elements() {
+        naive_preorder_traversal(child, selectors, matches, caches);
+    }
+}
+
+fn naive_match_element<'a>(
+    element: ElementRef<'a>,
+    selectors: &'a [Selector],
+    caches: &mut SelectorCaches,
+) -> ElementMatches<'a> {
+    budget::stop_if_cancelled();
+    // 1. create a MatchingContext
+    let mut context = matching::MatchingContext::new(
+        matching::MatchingMode::Normal,
+        None,
+        caches,
+        matching::QuirksMode::NoQuirks,
+        matching::NeedsSelectorFlags::No,
+        matching::MatchingForInvalidation::No,
+    );
+    // 2. get matching selectors naively
+    let matched_selectors = selectors
+        .iter()
+        .filter(|s| {
+            // Debug element if applicable
+            #[cfg(feature = "debug_element")]
+            debug_element_selector(element, &element_to_string(element), s);
+            let (res, stats) = matching::matches_selector(s, 0, None, &element, &mut context);
+            debug_assert_eq!(stats.time_fast_rejecting, None);
+            res
+        })
+        .collect();
+    ElementMatches{ element, selectors: SelectorsOrSharedStyles::Selectors(matched_selectors) }
+}
+
+/// Like `match_selectors`, but each element is only matched against the
+/// selectors it could match by its type: those whose rightmost compound has
+/// its (ASCII-lowercased) local name, and those with no type selector there.
+/// Selectors are still in their order in `selectors`.
+pub fn match_selectors_with_tag_index<'a>(document: &'a Html, selectors: &'a [Selector]) -> DocumentMatches<'a> {
+    let index = TagIndex::new(selectors);
+    let mut caches: SelectorCaches = Default::default();
+    let mut result = Vec::new();
+    tag_index_preorder_traversal(document.root_element(), &index, &mut result, &mut caches);
+    DocumentMatches(result)
+}
+
+/// Indices into a selector slice, by the type selector of their rightmost
+/// compound.
+struct TagIndex<'a> {
+    selectors: &'a [Selector],
+    by_local_name: HashMap<String, Vec<usize>>,
+    /// Selectors without a type selector in their rightmost compound
+    untyped: Vec<usize>,
+}
+
+impl<'a> TagIndex<'a> {
+    fn new(selectors: &'a [Selector]) -> Self {
+        let mut by_local_name: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut untyped = Vec::new();
+        for (i, selector) in selectors.iter().enumerate() {
+            // `iter()` stops at the first combinator
+            let local_name = selector.iter().find_map(|component| match component {
+                Component::LocalName(local_name) => Some(local_name.lower_name.to_string()),
+                _ => None,
+            });
+            match local_name {
+                Some(local_name) => by_local_name.entry(local_name).or_default().push(i),
+                None => untyped.push(i),
+            }
+        }
+        Self { selectors, by_local_name, untyped }
+    }
+
+    /// The candidates for an element with this local name, in order.
+    fn candidates(&self, local_name: &str) -> impl Iterator<Item = &'a Selector> + '_ {
+        let typed = self.by_local_name.get(&local_name.to_ascii_lowercase()).map_or(&[][..], Vec::as_slice);
+        let (mut typed, mut untyped) = (typed.iter().peekable(), self.untyped.iter().peekable());
+        std::iter::from_fn(move || {
+            let next = match (typed.peek(), untyped.peek()) {
+                (Some(t), Some(u)) if t < u => typed.next(),
+                (Some(_), None) => typed.next(),
+                _ => untyped.next(),
+            }?;
+            Some(&self.selectors[*next])
+        })
+    }
+}
+
+/// Like `match_selectors_with_tag_index`, matching the elements in `order`.
+pub fn match_selectors_with_tag_index_in_order<'a>(
+    document: &'a Html,
+    selectors: &'a [Selector],
+    order: ElementOrder,
+) -> DocumentMatches<'a> {
+    if order == ElementOrder::Document {
+        return match_selectors_with_tag_index(document, selectors);
+    }
+    let index = TagIndex::new(selectors);
+    match_in_element_order(document, order, |element, caches| tag_index_match_element(element, &index, caches))
+}
+
+fn tag_index_preorder_traversal<'a>(
+    element: ElementRef<'a>,
+    index: &TagIndex<'a>,
+    matches: &mut Vec<ElementMatches<'a>>,
+    caches: &mut SelectorCaches,
+) {
+    matches.push(tag_index_match_element(element, index, caches));
+    for child in element.child_elements() {
+        tag_index_preorder_traversal(child, index, matches, caches);
+    }
+}
+
+fn tag_index_match_element<'a>(element: ElementRef<'a>, index: &TagIndex<'a>, caches: &mut SelectorCaches) -> ElementMatches<'a> {
+    budget::stop_if_cancelled();
+    let mut context = matching::MatchingContext::new(
+        matching::MatchingMode::Normal,
+        None,
+        caches,
+        matching::QuirksMode::NoQuirks,
+        matching::NeedsSelectorFlags::No,
+        matching::MatchingForInvalidation::No,
+    );
+    let matched_selectors = index
+        .candidates(element.value().name())
+        .filter(|s| matching::matches_selector(s, 0, None, &element, &mut context).0)
+        .collect();
+    ElementMatches{ element, selectors: SelectorsOrSharedStyles::Selectors(matched_selectors) }
+}
+
+/// Like `match_selectors`, but with `Html::select` on each selector reparsed
+/// by scraper. See `Algorithm::ScraperNative`.
+pub fn match_selectors_with_scraper<'a>(document: &'a Html, selectors: &'a [Selector]) -> DocumentMatches<'a> {
+    let mut matched: HashMap<ego_tree::NodeId, SmallVec<[&'a Selector; 16]>> = HashMap::new();
+    let mut unsupported = 0;
+    for selector in selectors {
+        // Scraper matches a selector at a time rather than an element at a
+        // time
+        budget::stop_if_cancelled();
+        let Ok(scraper_selector) = scraper::Selector::parse(&selector.to_css_string()) else {
+            unsupported += 1;
+            continue;
+        };
+        for element in document.select(&scraper_selector) {
+            matched.entry(element.id()).or_default().push(selector);
+        }
+    }
+    if unsupported > 0 {
+        log::warn!("scraper could not parse {unsupported} of {} selectors; they match nothing", selectors.len());
+    }
+    let mut result = Vec::new();
+    scraper_preorder_traversal(document.root_element(), &mut matched, &mut result);
+    DocumentMatches(result)
+}
+
+fn scraper_preorder_traversal<'a>(
+    element: ElementRef<'a>,
+    matched: &mut HashMap<ego_tree::NodeId, SmallVec<[&'a Selector; 16]>>,
+    matches: &mut Vec<ElementMatches<'a>>,
+) {
+    let matched_selectors = matched.remove(&element.id()).unwrap_or_default();
+    matches.push(ElementMatches{ element, selectors: SelectorsOrSharedStyles::Selectors(matched_selectors) });
+    for child in element.child_elements() {
+        scraper_preorder_traversal(child, matched, matches);
+    }
+}
+
+/// Builds a stylesheet with an empty rule for each of `selectors`. Selectors
+/// serialize namespaces by prefix (`svg|rect`), or not at all for the
+/// default namespace, so the `@namespace` rules they were parsed with are
+/// added back.
 pub fn stylesheet_from_selectors<'sel>(
     selectors: impl Iterator<Item = &'sel Selector>,
  ) -> (DocumentStyleSheet, SharedRwLock) {
     let stylesheet_lock = SharedRwLock::new();
-    let css = selectors
-        .map(|selector| format!("{} {{}}", selector.to_css_string()))
+    let mut namespaces = Namespaces::default();
+    let rules = selectors
+        .map(|selector| {
+            selector.visit(&mut namespaces);
+            format!("{} {{}}", selector.to_css_string())
+        })
         .collect::<Vec<_>>()
         .join("\n");
+    let css = namespaces.to_css() + &rules;
     let stylesheet = parse::parse_stylesheet(
         &css,
         UrlExtraData::from(url::Url::parse("about:blank").unwrap()),
@@ -421,50 +976,111 @@ pub fn stylesheet_from_selectors<'sel>(
     (stylesheet, stylesheet_lock)
 }
 
+/// The namespace prefixes a selector uses, for `stylesheet_from_selectors`.
+#[derive(Default)]
+struct Namespaces {
+    default: Option<style::Namespace>,
+    prefixed: BTreeMap<String, style::Namespace>,
+}
+
+impl Namespaces {
+    fn add(&mut self, prefix: &style::Prefix, url: &style::Namespace) {
+        let previous = self.prefixed.entry(prefix.to_string()).or_insert_with(|| url.clone());
+        if previous != url {
+            log::warn!("namespace prefix {prefix} is bound to both {previous} and {url}, using {previous}");
+        }
+    }
+
+    /// `@namespace` rules, which go before any style rule.
+    fn to_css(&self) -> String {
+        let mut css = String::new();
+        let mut rule = |prefix: Option<&str>, url: &style::Namespace| {
+            css.push_str("@namespace ");
+            if let Some(prefix) = prefix {
+                ::cssparser::serialize_identifier(prefix, &mut css).unwrap();
+                css.push(' ');
+            }
+            css.push_str("url(");
+            ::cssparser::serialize_string(url, &mut css).unwrap();
+            css.push_str(");\n");
+        };
+        if let Some(default) = &self.default {
+            rule(None, default);
+        }
+        for (prefix, url) in &self.prefixed {
+            rule(Some(prefix), url);
+        }
+        css
+    }
+}
+
+impl SelectorVisitor for Namespaces {
+    type Impl = style::selector_parser::SelectorImpl;
+
+    fn visit_simple_selector(&mut self, component: &Component<Self::Impl>) -> bool {
+        match component {
+            Component::Namespace(prefix, url) => self.add(prefix, url),
+            Component::DefaultNamespace(url) => {
+                self.default.get_or_insert_with(|| url.clone());
+            }
+            Component::AttributeOther(attribute) => {
+                if let Some(NamespaceConstraint::Specific((prefix, url))) = &attribute.namespace {
+                    self.add(prefix, url);
+                }
+            }
+            _ => (),
+        }
+        true
+    }
+}
+
 fn collect_selectors_from_map(
     map: &SelectorMap<Rule>,
     out: &mut BTreeMap<(u32, String), Selector>,
 ) {
-    let mut push_rule = |rule: &Rule| {
+    for_each_rule(map, |rule| {
         out.entry((rule.source_order, rule.selector.to_css_string()))
             .or_insert_with(|| rule.selector.clone());
-    };
+    });
+}
 
+/// Calls `f` with every rule of `map`, once per bucket it is in
+fn for_each_rule(map: &SelectorMap<Rule>, mut f: impl FnMut(&Rule)) {
     for rule in &map.root {
-        push_rule(rule);
+        f(rule);
     }
     for rule in &map.common_pseudo_classes {
-        push_rule(rule);
+        f(rule);
     }
     for rule in &map.rare_pseudo_classes {
-        push_rule(rule);
+        f(rule);
     }
     for rule in &map.other {
-        push_rule(rule);
+        f(rule);
     }
     for (_, bucket) in map.id_hash.iter() {
         for rule in bucket {
-            push_rule(rule);
+            f(rule);
         }
     }
     for (_, bucket) in map.class_hash.iter() {
         for rule in bucket {
-            push_rule(rule);
+            f(rule);
         }
     }
     for bucket in map.attribute_hash.values() {
         for rule in bucket {
-            push_rule(rule);
+            f(rule);
         }
     }
     for bucket in map.local_name_hash.values() {
         for rule in bucket {
-            push_rule(rule);
+            f(rule);
         }
     }
     for bucket in map.namespace_hash.values() {
         for rule in bucket {
-            push_rule(rule);
+            f(rule);
         }
     }
 }
@@ -485,6 +1101,7 @@ pub fn match_selectors_with_style_sharing<'document>(
         cascade_data: &CascadeData,
         stats: &mut Statistics,
     ) {
+        budget::stop_if_cancelled();
         // 0. debug element if applicable
         let debug_html_str: Option<String> = None;
         #[cfg(feature = "debug_element")]
@@ -570,6 +1187,8 @@ pub fn match_selectors_with_style_sharing<'document>(
                 if let Some(selector_stats) = selector_stats.as_deref_mut() {
                     selector_stats.extend(sel_stats.unwrap().into_iter())
                 }
+                #[cfg(feature = "bloom_self_check")]
+                check_bloom_rejections(element, context, selector_map);
                 // 1.3.4: insert the element into the style sharing cache
                 let start = Start::now();
                 context.thread_local.sharing_cache.insert_if_possible(
@@ -645,6 +1264,40 @@ pub fn match_selectors_with_style_sharing<'document>(
     (DocumentMatches(result), stats)
 }
 
+/// Fully matches every candidate rule the bloom filter rejects for `element`,
+/// panicking if one of them actually matches. See `bloom_check`.
+#[cfg(feature = "bloom_self_check")]
+fn check_bloom_rejections<'a>(
+    element: ElementRef<'a>,
+    context: &mut StyleContext<ElementRef<'a>>,
+    selector_map: &SelectorMap<Rule>,
+) {
+    let bloom_filter = context.thread_local.bloom_filter.filter();
+    let caches = &mut context.thread_local.selector_caches;
+    selector_map.lookup(element, matching::QuirksMode::NoQuirks, None, |rule| {
+        let filtered = !matching::selector_may_match(&rule.hashes, bloom_filter);
+        if filtered {
+            let mut matching_context = matching::MatchingContext::new(
+                matching::MatchingMode::Normal,
+                None,
+                caches,
+                matching::QuirksMode::NoQuirks,
+                matching::NeedsSelectorFlags::No,
+                matching::MatchingForInvalidation::No,
+            );
+            let (matched, _) = matching::matches_selector(&rule.selector, 0, None, &element, &mut matching_context);
+            assert!(
+                !matched,
+                "the bloom filter rejected {} for {}, but it matches",
+                rule.selector.to_css_string(),
+                Element::from(element).html(),
+            );
+        }
+        bloom_check::record(filtered);
+        true
+    });
+}
+
 pub fn mach_7<'a>(matches: &DocumentMatches<'a>) -> DocumentMatches<'a> {
     let mut res = Vec::new();
     let mut caches: SelectorCaches = Default::default();
@@ -690,9 +1343,12 @@ mod tests {
     use crate::parse::{get_document_and_selectors, websites_path};
     use crate::structs::set::{SetDocumentMatches, SetSelectorsOrSharedStyles};
     use crate::structs::Selector;
-    use crate::{Optimizations, do_website};
+    use crate::{Optimizations, do_website, match_fragment_selectors, match_in_memory, match_selectors, match_selectors_with_tag_index, parse_fragment, query};
+    use crate::structs::borrowed::SelectorsOrSharedStyles;
+    use crate::parse::{ParseOptions, ParsedWebsite};
+    use style::shared_lock::SharedRwLock;
     use crate::preprocessing::concretize::convert_to_is_selectors;
-    use crate::Algorithm;
+    use crate::{Algorithm, ElementOrder};
     use cssparser::ToCss as _;
     use style::selector_parser::SelectorParser;
     use style::stylesheets::UrlExtraData;
@@ -755,14 +1411,14 @@ mod tests {
         let element = matches
             .0
             .values()
-            .find(|element_matches| element_matches.element.html.contains(html_substring))
+            .find(|element_matches| element_matches.element.html().contains(html_substring))
             .unwrap_or_else(|| panic!("failed to find element containing {html_substring}"));
         match &element.selectors {
             SetSelectorsOrSharedStyles::Selectors(selectors) => {
-                selectors.iter().cloned().collect()
+                selectors.iter().map(|s| s.to_string()).collect()
             }
             SetSelectorsOrSharedStyles::SharedWithElement(id) => {
-                matches.find_selectors(*id).iter().cloned().collect()
+                matches.find_selectors(*id).iter().map(|s| s.to_string()).collect()
             }
         }
     }
@@ -853,6 +1509,63 @@ mod tests {
         }
     }
 
+    #[test]
+    fn query_matches_only_the_given_selectors() -> Result<()> {
+        let document = scraper::Html::parse_document("<p class='a'></p><p></p><div class='a'></div>");
+        let website = ParsedWebsite::new("test".to_owned(), document, Vec::new(), SharedRwLock::new());
+        let matches = query(website, "p.a, div", Algorithm::WithStyleSharing)?;
+        assert_eq!(selectors_for_element(&matches, "<p class=\"a\">"), BTreeSet::from(["p.a".to_string()]));
+        assert_eq!(selectors_for_element(&matches, "<div"), BTreeSet::from(["div".to_string()]));
+        assert!(selectors_for_element(&matches, "<p>").is_empty());
+        let website = ParsedWebsite::new("test".to_owned(), scraper::Html::parse_document(""), Vec::new(), SharedRwLock::new());
+        assert!(query(website, "p..a", Algorithm::Naive).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn matches_websites_in_memory() -> Result<()> {
+        let html = "<link rel='stylesheet' href='css/a.css'><style>div {}</style><p class='a'></p><div></div>";
+        let matches = match_in_memory(html, &[("css/a.css", ".a {}")], Algorithm::Naive, &Default::default())?;
+        assert_eq!(selectors_for_element(&matches, "<p class=\"a\">"), BTreeSet::from([".a".to_string()]));
+        assert_eq!(selectors_for_element(&matches, "<div"), BTreeSet::from(["div".to_string()]));
+        Ok(())
+    }
+
+    #[test]
+    fn shuffled_element_order_matches_the_same() -> Result<()> {
+        let html = "<link rel='stylesheet' href='a.css'><ul><li class='a'></li><li></li><li class='a'><b></b></li></ul>";
+        let css = "li:nth-child(2n+1) {} .a > b {} ul li:last-child {} b {}";
+        let document = match_in_memory(html, &[("a.css", css)], Algorithm::Naive, &Default::default())?;
+        for algorithm in [Algorithm::Naive, Algorithm::TagIndex, Algorithm::SelectorTrie, Algorithm::Bytecode] {
+            for seed in 0..4 {
+                let options = ParseOptions { element_order: ElementOrder::Shuffled { seed }, ..Default::default() };
+                assert_eq!(match_in_memory(html, &[("a.css", css)], algorithm, &options)?, document, "{algorithm}, seed {seed}");
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn orders_matched_selectors_by_cascade() -> Result<()> {
+        let html = "<link rel='stylesheet' href='a.css'><p class='a' id='x'></p><p class='a'></p>";
+        let css = "p.a {} @layer base { #x {} } .a {} p {} div {}";
+        let options = ParseOptions { cascade_order: true, ..Default::default() };
+        for algorithm in [Algorithm::Naive, Algorithm::WithStyleSharing] {
+            let matches = match_in_memory(html, &[("a.css", css)], algorithm, &options)?;
+            let cascade_order = |html: &str| -> Vec<String> {
+                let element_matches = matches.0.values().find(|m| m.element.html().starts_with(html)).unwrap();
+                element_matches.cascade_order.iter().map(|s| s.to_string()).collect()
+            };
+            // Layered rules come before unlayered ones, whatever their
+            // specificity
+            assert_eq!(cascade_order("<p class=\"a\" id"), ["#x", "p", ".a", "p.a"], "{algorithm}");
+            assert_eq!(cascade_order("<p class=\"a\"></p>"), ["p", ".a", "p.a"], "{algorithm}");
+        }
+        let matches = match_in_memory(html, &[("a.css", css)], Algorithm::Naive, &Default::default())?;
+        assert!(matches.0.values().all(|element_matches| element_matches.cascade_order.is_empty()));
+        Ok(())
+    }
+
     #[test]
     fn optimized_matching_returns_original_selectors() -> Result<()> {
         let website = get_document_and_selectors(
@@ -866,4 +1579,144 @@ mod tests {
         assert_eq!(actual, BTreeSet::from([".section[class*=\"-up\"]".to_string()]));
         Ok(())
     }
+
+    #[test]
+    fn fragments_have_no_synthetic_root() {
+        let fragment = parse_fragment("<p class='a'><span></span></p><div></div>");
+        let selectors: Vec<Selector> = ["p > span", "html > p", ":root", "div", "*"]
+            .iter()
+            .map(|selector| {
+                SelectorParser::parse_author_origin_no_namespace(
+                    selector,
+                    &UrlExtraData::from(url::Url::parse("about:blank").unwrap()),
+                ).unwrap().slice()[0].clone()
+            })
+            .collect();
+        let matches = SetDocumentMatches::from(crate::structs::owned::OwnedDocumentMatches::from(
+            &match_fragment_selectors(&fragment, &selectors),
+        ));
+        // p, span and div, but not the html element parse_fragment added
+        assert_eq!(matches.0.len(), 3);
+        assert_eq!(selectors_for_element(&matches, "<p"), BTreeSet::from(["*".to_string()]));
+        assert_eq!(selectors_for_element(&matches, "<span"), BTreeSet::from(["p > span".to_string(), "*".to_string()]));
+        assert_eq!(selectors_for_element(&matches, "<div"), BTreeSet::from(["div".to_string(), "*".to_string()]));
+    }
+
+    #[test]
+    fn tag_index_matches_like_naive_in_order() {
+        let document = scraper::Html::parse_document("<div class='a'><P></P><span class='a'></span></div>");
+        let selectors: Vec<Selector> = [".a", "div.a", "p", "div p, span", "*"]
+            .iter()
+            .flat_map(|selector| {
+                SelectorParser::parse_author_origin_no_namespace(
+                    selector,
+                    &UrlExtraData::from(url::Url::parse("about:blank").unwrap()),
+                ).unwrap().slice().to_vec()
+            })
+            .collect();
+        let naive = match_selectors(&document, &selectors);
+        let tag_index = match_selectors_with_tag_index(&document, &selectors);
+        let to_css = |matches: &crate::structs::borrowed::DocumentMatches| -> Vec<Vec<String>> {
+            matches.0.iter().map(|element_matches| match &element_matches.selectors {
+                SelectorsOrSharedStyles::Selectors(selectors) => selectors.iter().map(|s| s.to_css_string()).collect(),
+                SelectorsOrSharedStyles::SharedWithElement(_) => unreachable!(),
+            }).collect()
+        };
+        assert_eq!(to_css(&tag_index), to_css(&naive));
+        let span = to_css(&tag_index).into_iter().last().unwrap();
+        assert_eq!(span, [".a", "span", "*"]);
+    }
+
+    #[test]
+    fn scraper_native_matches_what_scraper_parses() -> Result<()> {
+        let website = || ParsedWebsite::new(
+            "test".to_owned(),
+            scraper::Html::parse_document("<p class='a'></p><p></p><div class='a'></div>"),
+            Vec::new(),
+            SharedRwLock::new(),
+        );
+        let native = query(website(), "p.a, div, p:first-child", Algorithm::ScraperNative)?;
+        assert_eq!(native, query(website(), "p.a, div, p:first-child", Algorithm::Naive)?);
+        // Stylo parses :hover, but scraper doesn't
+        let matches = query(website(), "p:hover, div", Algorithm::ScraperNative)?;
+        assert_eq!(selectors_for_element(&matches, "<div"), BTreeSet::from(["div".to_string()]));
+        assert!(selectors_for_element(&matches, "<p>").is_empty());
+        Ok(())
+    }
+
+    #[cfg(feature = "arena")]
+    #[test]
+    fn arena_matches_like_naive() -> Result<()> {
+        let website = get_document_and_selectors(&websites_path().join("ten_divs_style_sharing"))?.unwrap();
+        let selectors = website.get_matcher().get_selectors();
+        let bump = bumpalo::Bump::new();
+        let arena = crate::match_selectors_in_arena(&bump, website.document(), &selectors);
+        let naive = crate::match_selectors(website.document(), &selectors);
+        assert_eq!(
+            SetDocumentMatches::from(crate::structs::owned::OwnedDocumentMatches::from(&arena)),
+            SetDocumentMatches::from(crate::structs::owned::OwnedDocumentMatches::from(&naive)),
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "ua_styles")]
+    #[test]
+    fn ua_matches_are_kept_apart() -> Result<()> {
+        let lock = SharedRwLock::new();
+        let stylesheet = crate::parse::parse_stylesheet(
+            "p {}",
+            UrlExtraData::from(url::Url::parse("about:blank").unwrap()),
+            &lock,
+        )?;
+        let document = scraper::Html::parse_document("<div><p></p></div>");
+        let website = ParsedWebsite::new("test".to_owned(), document, vec![stylesheet], lock).with_ua_stylesheet();
+        assert_eq!(website.get_matcher().get_selectors().len(), 1);
+        let (_, matches, _) = do_website(&website, Algorithm::WithStyleSharing, None);
+        let p = matches.0.values().find(|element_matches| element_matches.element.path.to_xpath().ends_with("p[1]")).unwrap();
+        let selectors = matches.find_selectors(p.element.id);
+        assert!(selectors.len() == 1 && selectors.contains("p"));
+        assert!(p.ua_selectors.contains("p"));
+        assert!(!p.ua_selectors.contains("div"));
+        Ok(())
+    }
+
+    #[test]
+    fn namespaced_selectors_match_with_every_algorithm() {
+        let document = scraper::Html::parse_document(
+            "<svg><rect></rect><a xlink:href='#r'><rect class='x'></rect></a></svg><rect></rect>",
+        );
+        let css = "@namespace svg url(http://www.w3.org/2000/svg);
+            @namespace xlink url(http://www.w3.org/1999/xlink);
+            svg|rect {} [xlink|href] svg|rect.x {} |rect {} svg|a:link {}";
+        let lock = SharedRwLock::new();
+        let stylesheet = crate::parse::parse_stylesheet(
+            css,
+            UrlExtraData::from(url::Url::parse("about:blank").unwrap()),
+            &lock,
+        ).unwrap();
+        let website = ParsedWebsite::new("test".to_owned(), document, vec![stylesheet], lock);
+        for algorithm in [Algorithm::Naive, Algorithm::TagIndex, Algorithm::SelectorTrie, Algorithm::Bytecode, Algorithm::WithStyleSharing, Algorithm::WithIsConversion, Algorithm::WithDistribution] {
+            let (_, matches, _) = do_website(&website, algorithm, None);
+            let mut rects: Vec<_> = matches
+                .0
+                .iter()
+                .filter(|(_, element_matches)| element_matches.element.html().starts_with("<rect"))
+                .map(|(&id, _)| {
+                    let mut selectors: Vec<_> = matches.find_selectors(id).iter().map(|s| s.to_string()).collect();
+                    selectors.sort();
+                    selectors
+                })
+                .collect();
+            rects.sort();
+            assert_eq!(rects, [
+                vec!["[xlink|href] svg|rect.x".to_owned(), "svg|rect".to_owned()],
+                vec!["svg|rect".to_owned()],
+                // The rect outside the svg is an html element in the html namespace
+                vec![],
+            ], "{algorithm}");
+            assert!(matches.0.iter().any(|(&id, element_matches)| {
+                element_matches.element.html().starts_with("<a") && !matches.find_selectors(id).is_empty()
+            }), "{algorithm}");
+        }
+    }
 }
@@ -4,52 +4,1213 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-use std::{collections::HashMap, path::PathBuf};
-use clap::Parser;
+use std::{cell::RefCell, collections::{BTreeMap, HashMap}, io::Write as _, path::PathBuf, rc::Rc};
+use clap::{Parser, Subcommand, ValueEnum};
 use mach_6::{
     Algorithm,
-    parse::get_document_and_selectors,
-    result::Result,
-    structs::{ser::SerDocumentMatches, set::SetDocumentMatches},
+    ElementOrder,
+    budget::WebsiteBudget,
+    checkpoint::Checkpoint,
+    analysis::{
+        buckets::SelectorMapBuckets,
+        complexity::{ComplexityReport, ComplexitySummary},
+        corpus::CorpusStats,
+        coverage::{CoverageReport, CoverageSummary},
+        custom_properties::{CustomPropertiesReport, CustomPropertiesSummary},
+        dedup::{DedupReport, DedupSummary},
+        excluded::{ExcludedReport, ExcludedSummary},
+        html_errors::{HtmlErrorReport, HtmlErrorSummary},
+        provenance::{ProvenanceReport, ProvenanceSummary},
+        reach::{ReachReport, ReachSummary},
+        unmatched::{UnmatchedReport, UnmatchedSummary},
+    },
+    device::{DeviceOptions, MediaFeatures, MediaType, Viewport},
+    export::csv::{Delimiter, MatchesWriter},
+    instrument::{self, Phase, PhaseReport, Timings},
+    parse::{DuplicateDocuments, ParseOptions, ParsedWebsite, ScriptOptions, StylesheetCache, StylesheetLinks, TemplateContents, VisitedMode, VisitedOptions, WebsiteSelection, get_document_and_selectors_with_options},
+    result::{Error, IntoResultExt, Result},
+    timings_file::{TimingsFile, TimingsRun},
+    trace::{TraceFilter, TraceWriter},
+    structs::{ser::SerDocumentMatches, set::SetDocumentMatches, stats::{DocumentStats, MatchHistograms}},
 };
+#[cfg(feature = "msgpack")]
+use mach_6::export::msgpack::MsgpackWriter;
+use serde::Serialize;
 use serde_yml;
-use selectors::matching::Statistics;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// The directory of website folders
     #[arg(long, conflicts_with = "website")]
     websites: Option<PathBuf>,
 
-    /// A single website folder containing one html file
+    /// A single website folder containing one html file (or a main file
+    /// chosen by `--main-file` or its `mach6.toml`)
     #[arg(long, conflicts_with = "websites")]
     website: Option<PathBuf>,
 
+    /// Only process the first this many websites of `--websites`, in path
+    /// order
+    #[arg(long, conflicts_with = "website")]
+    limit: Option<usize>,
+
+    /// Only process a random subset of this many websites of `--websites`,
+    /// chosen by `--seed`. Combined with `--limit`, the limit applies to the
+    /// sample
+    #[arg(long, conflicts_with = "website")]
+    sample: Option<usize>,
+
+    /// The seed for `--sample` and `--shuffle-elements`. The same seed picks
+    /// the same websites and the same element order
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Match each document's elements in a random order chosen by `--seed`,
+    /// instead of document order, to see whether the algorithms depend on
+    /// it. Matches are put back in document order, so results are the same.
+    /// Only for the naive, tag-index, selector-trie and bytecode algorithms
+    #[arg(long)]
+    shuffle_elements: bool,
+
+    /// Match each selector once, when the stylesheets write it more than
+    /// once. Results are the same; `--report dedup` shows the duplicates
+    #[arg(long)]
+    dedup_selectors: bool,
+
+    /// Name matched selectors canonically: type selectors lowercased and
+    /// attribute selectors sorted, so differently written stylesheets give
+    /// the same output. `--report provenance` keeps them as written
+    #[arg(long)]
+    normalize_selectors: bool,
+
+    /// Also list each element's matched selectors in cascade order: by
+    /// origin, layer, specificity and source order, lowest priority first
+    #[arg(long)]
+    cascade_order: bool,
+
     /// Which matching algorithm to run
     #[arg(long, value_enum, default_value_t = Algorithm::Naive)]
     algorithm: Algorithm,
+
+    /// The html file to use as the main document of each website, relative to
+    /// the website folder. Overrides `main_file` in a website's `mach6.toml`
+    #[arg(long)]
+    main_file: Option<PathBuf>,
+
+    /// How long parsing and matching one website may take, e.g. `60s` or
+    /// `500ms`. A website that takes longer fails with a
+    /// `budget_exceeded` error and the rest of the run goes on
+    #[arg(long, value_parser = mach_6::budget::parse_duration)]
+    per_site_timeout: Option<std::time::Duration>,
+
+    /// How many selectors one website's stylesheets may have. A website with
+    /// more fails with a `budget_exceeded` error before it is matched
+    #[arg(long)]
+    per_site_max_selectors: Option<usize>,
+
+    /// The viewport size in CSS pixels that media queries are evaluated
+    /// against, e.g. `375x667`
+    #[arg(long, default_value_t = DeviceOptions::default().viewport)]
+    viewport: Viewport,
+
+    /// Device pixels per CSS pixel, for `resolution` media queries
+    #[arg(long, default_value_t = DeviceOptions::default().device_pixel_ratio)]
+    dpr: f32,
+
+    /// The media type that media queries are evaluated against
+    #[arg(long, value_enum, default_value_t = MediaType::Screen)]
+    media: MediaType,
+
+    #[command(flatten)]
+    media_features: MediaFeatures,
+
+    /// Whether the contents of `<template>` elements are matched
+    #[arg(long, value_enum, default_value_t = TemplateContents::Exclude)]
+    template_contents: TemplateContents,
+
+    #[command(flatten)]
+    scripts: ScriptOptions,
+
+    #[command(flatten)]
+    stylesheet_links: StylesheetLinks,
+
+    #[command(flatten)]
+    visited: VisitedOptions,
+
+    /// Open each document at this URL fragment: the element with this id
+    /// (or the `<a>` with this name) matches `:target`
+    #[arg(long, value_name = "ID")]
+    fragment: Option<String>,
+
+    /// What to print for each website
+    #[arg(long, value_enum, default_value_t = Emit::Matches)]
+    emit: Emit,
+
+    /// The output format. `jsonl` prints one JSON object per website. `csv`
+    /// and `tsv` print one row per matched (website, element, selector) and
+    /// only support `--emit matches`
+    #[arg(long, value_enum, default_value_t = Format::Yaml)]
+    format: Format,
+
+    /// Write all results into a new SQLite database at this path instead of
+    /// printing them
+    #[cfg(feature = "sqlite")]
+    #[arg(long)]
+    sqlite: Option<PathBuf>,
+
+    /// Write all results and stats as Parquet files into this directory
+    /// instead of printing them
+    #[cfg(feature = "parquet")]
+    #[arg(long)]
+    parquet: Option<PathBuf>,
+
+    /// Write bytes allocated and peak heap growth per phase for each website,
+    /// and the peak RSS of the whole run, as YAML to this path
+    #[cfg(feature = "memory_stats")]
+    #[arg(long)]
+    memory_report: Option<PathBuf>,
+
+    /// Include the wall-clock time of each phase (discovery, parsing, matching,
+    /// serialization) for each website in a `timings` section. Only supports
+    /// `--format yaml` and `--format jsonl`
+    #[arg(long)]
+    timings: bool,
+
+    /// Add this run's wall time for each website (discovery through matching)
+    /// to this JSON file, creating it if it doesn't exist. See the
+    /// `compare-timings` subcommand
+    #[arg(long)]
+    timings_file: Option<PathBuf>,
+
+    /// Write a flamegraph of matching for each website into this directory
+    #[cfg(feature = "profiling")]
+    #[arg(long)]
+    flamegraphs: Option<PathBuf>,
+
+    /// Instead of printing results, compare them with `querySelectorAll` in a
+    /// headless browser driven by the WebDriver server at this url (e.g.
+    /// `http://localhost:4444`), and print the selectors where they differ
+    #[cfg(feature = "browser_oracle")]
+    #[arg(long)]
+    webdriver: Option<String>,
+
+    /// Instead of matching websites, run the `matches()` assertions in the Web
+    /// Platform Tests under this directory (e.g. `wpt/css/selectors`) and print
+    /// a pass/fail report per selector feature
+    #[arg(long, conflicts_with_all = ["website", "websites"])]
+    wpt: Option<PathBuf>,
+
+    /// Instead of matches, print the declared value of these comma-separated
+    /// properties (e.g. `color,display,font-size`) for each element of each
+    /// website: the winning declaration as written, not the computed value
+    #[arg(long, value_delimiter = ',')]
+    declared_styles: Vec<String>,
+
+    /// Instead of printing results, match each website at each of these
+    /// comma-separated viewport widths (e.g. `360,768,1200`) and print how the
+    /// matches change from one width to the next
+    #[arg(long, value_delimiter = ',', conflicts_with = "declared_styles")]
+    breakpoints: Vec<f32>,
+
+    /// Like --breakpoints, at the widths each website's `@media` rules
+    /// compare the viewport width with
+    #[arg(long, conflicts_with_all = ["declared_styles", "breakpoints", "color_scheme_diff", "report", "trace", "verify_determinism"])]
+    auto_breakpoints: bool,
+
+    /// Instead of printing results, match each website with
+    /// `prefers-color-scheme: light` and `dark` and print only the selectors
+    /// and elements whose matches differ
+    #[arg(long, conflicts_with_all = ["declared_styles", "breakpoints"])]
+    color_scheme_diff: bool,
+
+    /// Instead of printing results, print this report for each website and
+    /// for all of them together
+    #[arg(long, value_enum, conflicts_with_all = ["declared_styles", "breakpoints", "color_scheme_diff"])]
+    report: Option<Report>,
+
+    /// For `--report reach`, the fraction of a website's elements a selector
+    /// must match to be listed
+    #[arg(long, default_value_t = 0.5)]
+    reach_threshold: f64,
+
+    /// For `--report unmatched`, leave out `<head>` and the elements in it
+    #[arg(long)]
+    unmatched_exclude_head: bool,
+
+    /// Instead of printing results, write every selector and element pair
+    /// the algorithm decided on to this file, one JSON object per line
+    #[arg(long, conflicts_with_all = ["declared_styles", "breakpoints", "color_scheme_diff", "report"])]
+    trace: Option<PathBuf>,
+
+    /// With --trace, only this element, by its path as in the results or
+    /// its XPath
+    #[arg(long, requires = "trace")]
+    trace_element: Option<String>,
+
+    /// With --trace, only pairs the algorithm decided differently from
+    /// naive matching
+    #[arg(long, requires = "trace")]
+    trace_discrepancies: bool,
+
+    /// Instead of printing results, match each website twice and print where
+    /// the two runs' serialized matches differ, failing if they do
+    #[arg(long, conflicts_with_all = ["declared_styles", "breakpoints", "color_scheme_diff", "report", "trace"])]
+    verify_determinism: bool,
+
+    /// Parse stylesheets that appear with identical contents in several
+    /// websites only once, and print cache statistics to stderr at the end
+    #[arg(long)]
+    cache_stylesheets: bool,
+
+    /// Keep the valid selectors of a rule whose selector list has an invalid
+    /// one, instead of dropping the rule as browsers do
+    #[arg(long)]
+    forgiving_selector_lists: bool,
+
+    /// Skip websites whose document and stylesheets are the same as an
+    /// earlier website's, and print how many were skipped to stderr at the
+    /// end
+    #[arg(long)]
+    skip_duplicates: bool,
+
+    /// Also match the bundled user-agent stylesheet, listing its matches
+    /// separately as `ua_selectors`
+    #[cfg(feature = "ua_styles")]
+    #[arg(long)]
+    include_ua_styles: bool,
+
+    /// Print each website's results as soon as they are computed, instead of
+    /// collecting every website first. YAML output becomes one document per
+    /// website
+    #[arg(long)]
+    stream: bool,
+
+    /// Record each website in this manifest file once its results are
+    /// printed, and skip websites it already records (by a hash of their
+    /// files) when run again. Creates the file if it doesn't exist
+    #[arg(long, requires = "stream")]
+    resume: Option<PathBuf>,
+
+    /// With `--resume`, process websites the manifest records too (and
+    /// record them again)
+    #[arg(long, requires = "resume")]
+    force: bool,
+}
+
+/// Debugging tools. Options like `--algorithm` and `--viewport` go before the
+/// subcommand.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Match one selector (or comma-separated selector list) against a
+    /// website, and print the elements it matched
+    Query {
+        /// The website folder
+        website: PathBuf,
+        selector: String,
+    },
+    /// Trace a selector (or each in a comma-separated list) against one
+    /// element of a website, compound by compound, showing which element
+    /// each compound matched and where matching failed
+    Explain {
+        /// The website folder
+        website: PathBuf,
+        /// The element, by its key in matches output (e.g.
+        /// `html[0]/body[1]/p[0]`) or its XPath
+        #[arg(long)]
+        element: String,
+        #[arg(long)]
+        selector: String,
+    },
+    /// Compare the last run of each algorithm in a `--timings-file` with the
+    /// run before it (or with the last run in `--baseline`), print the
+    /// websites that got slower by more than `--threshold`, and fail if there
+    /// are any
+    CompareTimings {
+        timings_file: PathBuf,
+        #[arg(long)]
+        baseline: Option<PathBuf>,
+        /// The slowdown to flag, as a fraction: 0.1 flags websites more than
+        /// 10% slower
+        #[arg(long, default_value_t = 0.1)]
+        threshold: f64,
+    },
+    /// Report what a websites directory is made of (HTML sizes, element
+    /// counts, stylesheet counts and sizes, selectors by kind, and parse
+    /// failure rates) without matching anything. Honors `--limit` and
+    /// `--sample`
+    CorpusStats {
+        websites: PathBuf,
+    },
+    /// Serve matching over HTTP: `POST /match` with a document and its
+    /// stylesheets as JSON or multipart form data returns the matches as JSON.
+    /// The algorithm and device options given here are the defaults, which
+    /// each request can override
+    #[cfg(feature = "serve")]
+    Serve {
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+        /// The address to listen on
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+    },
+    /// Write synthetic website folders, for benchmarking how matching scales
+    /// independent of a scraped corpus. Website `i` is generated with seed
+    /// `--seed + i`
+    #[cfg(feature = "generate")]
+    Generate {
+        /// The websites directory to write to
+        out_dir: PathBuf,
+        #[arg(long, default_value_t = 1)]
+        websites: usize,
+        #[command(flatten)]
+        config: mach_6::generate::FixtureConfig,
+    },
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Format {
+    Yaml,
+    Jsonl,
+    Csv,
+    Tsv,
+    /// `SetDocumentMatches` as MessagePack, readable with
+    /// `mach_6::export::msgpack::MsgpackReader`. Only supports `--emit matches`
+    #[cfg(feature = "msgpack")]
+    Msgpack,
 }
 
-fn main() -> mach_6::result::Result<()> {
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Emit {
+    /// The selectors matching each element
+    Matches,
+    /// Summary statistics about the matches
+    Stats,
+    /// Both statistics and matches
+    Both,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Report {
+    /// Component and combinator counts, specificity and the rightmost
+    /// compound's kind for each selector (`mach_6::analysis::complexity`)
+    Complexity,
+    /// The fraction of each stylesheet's rules that matched at least one
+    /// element, and the stylesheets where none did
+    Coverage,
+    /// How many rules landed in each kind of SelectorMap bucket (id, class,
+    /// local name, attribute, catch-all, ...)
+    Buckets,
+    /// Histograms of selectors matched per element and elements matched per
+    /// selector (also in `--emit stats`)
+    Histograms,
+    /// Selectors matching more than `--reach-threshold` of a website's
+    /// elements
+    Reach,
+    /// How many errors the HTML parser recovered from in each document, and
+    /// the first few
+    HtmlErrors,
+    /// Per stylesheet, how many selectors can never match because they need
+    /// a pseudo-class like `:hover` that the document has no state for, and
+    /// the first few
+    Excluded,
+    /// The stylesheet and line of each matched selector's rule and, through
+    /// the stylesheet's source map if it has a local one, the SCSS or LESS
+    /// file and line it came from
+    Provenance,
+    /// Selectors the stylesheets write more than once, how many times and
+    /// where, and the ratio of unique selectors to all of them; and rules
+    /// (selectors and declarations) in more than one stylesheet
+    Dedup,
+    /// Elements that matched no selector, and how many there are
+    Unmatched,
+    /// Where each custom property is defined and referenced with `var()`,
+    /// and the references that no rule defines
+    CustomProperties,
+}
+
+/// A `--report`: one entry per website, and one for the whole corpus.
+#[derive(Serialize)]
+struct ReportFile<W, A> {
+    websites: BTreeMap<String, W>,
+    aggregate: A,
+}
+
+#[derive(Serialize)]
+struct WebsiteOutput {
+    stats: DocumentStats,
+    matches: SerDocumentMatches,
+}
+
+/// One website's output when printing a document per website.
+#[derive(Serialize)]
+struct WebsiteDocument<'a> {
+    website: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats: Option<DocumentStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    matches: Option<SerDocumentMatches>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timings: Option<Timings>,
+}
+
+impl<'a> WebsiteDocument<'a> {
+    fn new(website: &'a str, matches: &SetDocumentMatches, emit: Emit) -> Self {
+        let with_stats = matches!(emit, Emit::Stats | Emit::Both);
+        let with_matches = matches!(emit, Emit::Matches | Emit::Both);
+        Self {
+            website,
+            stats: with_stats.then(|| DocumentStats::from(matches)),
+            matches: with_matches.then(|| SerDocumentMatches::from(matches)),
+            timings: None,
+        }
+    }
+}
+
+/// The YAML output with `--timings` when not printing a document per website.
+#[derive(Serialize)]
+struct WithTimings<T> {
+    results: HashMap<String, T>,
+    timings: BTreeMap<String, Timings>,
+}
+
+/// What `instrument` recorded for each website.
+type PhaseReports = Rc<RefCell<BTreeMap<String, PhaseReport>>>;
+
+/// Websites that failed, which the run kept going past so every failure can
+/// be reported at the end.
+type Failures = Rc<RefCell<Vec<Error>>>;
+
+/// Runs `f` as the serialization of one website's results, adding it to that
+/// website's report.
+fn serialize_website<T>(reports: &PhaseReports, name: &str, f: impl FnOnce() -> T) -> T {
+    let result = instrument::phase(Phase::Serialize, f);
+    reports
+        .borrow_mut()
+        .entry(name.to_owned())
+        .or_default()
+        .merge(instrument::take_report());
+    result
+}
+
+/// Prints a website per YAML document or JSON line, flushing after each one.
+fn write_documents(
+    results: impl Iterator<Item = Result<(String, SetDocumentMatches)>>,
+    emit: Emit,
+    format: Format,
+    reports: &PhaseReports,
+    timings: bool,
+) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    for result in results {
+        let (name, matches) = result?;
+        let mut document = serialize_website(reports, &name, || WebsiteDocument::new(&name, &matches, emit));
+        if timings {
+            document.timings = Some(reports.borrow()[&name].timings.clone());
+        }
+        match format {
+            Format::Jsonl => {
+                serde_json::to_writer(&mut stdout, &document).map_err(std::io::Error::from).into_result(None)?;
+                writeln!(stdout).into_result(None)?;
+            }
+            _ => {
+                writeln!(stdout, "---").into_result(None)?;
+                serde_yml::to_writer(&mut stdout, &document)
+                    .map_err(|e| Error::other(format!("couldn't write {name}'s results: {e}")))?;
+            }
+        }
+        stdout.flush().into_result(None)?;
+    }
+    Ok(())
+}
+
+/// Converts every website's results with `f` and prints them as one YAML map.
+fn write_yaml<T: Serialize>(
+    results: impl Iterator<Item = (String, SetDocumentMatches)>,
+    reports: &PhaseReports,
+    timings: bool,
+    f: impl Fn(&SetDocumentMatches) -> T,
+) {
+    let results: HashMap<String, T> = results
+        .map(|(name, matches)| {
+            let output = serialize_website(reports, &name, || f(&matches));
+            (name, output)
+        })
+        .collect();
+    let output = if timings {
+        let timings = reports
+            .borrow()
+            .iter()
+            .map(|(name, report)| (name.clone(), report.timings.clone()))
+            .collect();
+        serde_yml::to_string(&WithTimings { results, timings })
+    } else {
+        serde_yml::to_string(&results)
+    };
+    println!("{}", output.unwrap());
+}
+
+/// The website at `website`, or else every website in `websites`.
+fn parsed_websites(
+    website: Option<PathBuf>,
+    websites: Option<PathBuf>,
+    options: ParseOptions,
+) -> Result<Box<dyn Iterator<Item = Result<ParsedWebsite>>>> {
+    Ok(match website {
+        Some(website) => Box::new(get_document_and_selectors_with_options(&website, &options)?.map(Ok).into_iter()),
+        None => Box::new(mach_6::parse::get_all_documents_and_selectors_with_options(
+            &websites.unwrap_or_else(|| PathBuf::from("websites")),
+            options,
+        )?),
+    })
+}
+
+/// `websites` without the ones that couldn't be read. Like matching, those
+/// are logged and recorded in `failures`, so they still fail the run.
+fn readable_websites<'a>(
+    websites: impl Iterator<Item = Result<ParsedWebsite>> + 'a,
+    failures: &'a Failures,
+) -> impl Iterator<Item = ParsedWebsite> + 'a {
+    websites.filter_map(|website| match website {
+        Ok(website) => Some(website),
+        Err(e) => {
+            log::error!("{e}");
+            failures.borrow_mut().push(e);
+            None
+        }
+    })
+}
+
+fn run_command(command: Command, algorithm: Algorithm, options: &ParseOptions) -> Result<()> {
+    match command {
+        Command::Query { website, selector } => {
+            let Some(parsed) = get_document_and_selectors_with_options(&website, options)? else {
+                return Err(Error::other(format!("{} is not a website", website.display())));
+            };
+            let matches = mach_6::query(parsed, &selector, algorithm)?;
+            let mut matches = SerDocumentMatches::from(&matches);
+            matches.0.retain(|_, element_matches| !element_matches.selectors.is_empty());
+            println!("{}", serde_yml::to_string(&matches).unwrap());
+            Ok(())
+        }
+        Command::Explain { website, element, selector } => {
+            let Some(parsed) = get_document_and_selectors_with_options(&website, options)? else {
+                return Err(Error::other(format!("{} is not a website", website.display())));
+            };
+            let Some(found) = mach_6::explain::find_element(parsed.document(), &element) else {
+                return Err(Error::other(format!("no element {element} in {}", website.display())));
+            };
+            let explanations = mach_6::explain::explain(found, &selector)?;
+            println!("{}", serde_yml::to_string(&explanations).unwrap());
+            Ok(())
+        }
+        Command::CompareTimings { timings_file, baseline, threshold } => {
+            let current = TimingsFile::read(&timings_file)?;
+            let baseline = baseline.map(|baseline| TimingsFile::read(&baseline)).transpose()?;
+            let regressions = mach_6::timings_file::compare(&current, baseline.as_ref(), threshold);
+            println!("{}", serde_yml::to_string(&regressions).unwrap());
+            if regressions.is_empty() {
+                Ok(())
+            } else {
+                Err(Error::other(format!("{} websites got more than {}% slower", regressions.len(), threshold * 100.0)))
+            }
+        }
+        Command::CorpusStats { websites } => {
+            let website_paths = mach_6::parse::select_websites_dirs(&websites, options.selection)?;
+            let stats = CorpusStats::new(website_paths, options)?;
+            println!("{}", serde_yml::to_string(&stats).unwrap());
+            Ok(())
+        }
+        #[cfg(feature = "serve")]
+        Command::Serve { port, host } => mach_6::serve::serve(&format!("{host}:{port}"), algorithm, options),
+        #[cfg(feature = "generate")]
+        Command::Generate { out_dir, websites, config } => {
+            let width = websites.saturating_sub(1).to_string().len();
+            for i in 0..websites {
+                let config = mach_6::generate::FixtureConfig { seed: config.seed.wrapping_add(i as u64), ..config.clone() };
+                config.generate().write_website(&out_dir.join(format!("synthetic-{i:0width$}")))?;
+            }
+            println!("wrote {websites} websites to {}", out_dir.display());
+            Ok(())
+        }
+    }
+}
+
+fn write_report(
+    report: Report,
+    websites: impl Iterator<Item = ParsedWebsite>,
+    algorithm: Algorithm,
+    reach_threshold: f64,
+    unmatched_exclude_head: bool,
+) -> Result<std::result::Result<String, serde_yml::Error>> {
+    match report {
+        Report::Complexity => {
+            let mut reports = BTreeMap::new();
+            for website in websites {
+                let selectors = website.get_matcher().get_selectors();
+                reports.insert(website.name.clone(), ComplexityReport::new(&selectors));
+            }
+            let aggregate: ComplexitySummary = reports.values().flat_map(|report| &report.selectors).collect();
+            Ok(serde_yml::to_string(&ReportFile { websites: reports, aggregate }))
+        }
+        Report::Buckets => {
+            let mut reports = BTreeMap::new();
+            for website in websites {
+                let matching_context = website.get_matcher();
+                let buckets = matching_context.selector_map().map(SelectorMapBuckets::new).unwrap_or_default();
+                reports.insert(website.name.clone(), buckets);
+            }
+            let mut aggregate = SelectorMapBuckets::default();
+            for buckets in reports.values() {
+                aggregate += *buckets;
+            }
+            Ok(serde_yml::to_string(&ReportFile { websites: reports, aggregate }))
+        }
+        Report::Histograms => {
+            let mut reports = BTreeMap::new();
+            for website in websites {
+                let (name, matches, _stats) = mach_6::do_website(&website, algorithm, None);
+                reports.insert(name, MatchHistograms::from(&matches));
+            }
+            let mut aggregate = MatchHistograms::default();
+            for histograms in reports.values() {
+                aggregate.merge(histograms);
+            }
+            Ok(serde_yml::to_string(&ReportFile { websites: reports, aggregate }))
+        }
+        Report::Reach => {
+            if !(0.0..=1.0).contains(&reach_threshold) {
+                return Err(Error::other(format!("--reach-threshold must be between 0 and 1, got {reach_threshold}")));
+            }
+            let mut reports = BTreeMap::new();
+            for website in websites {
+                let (name, matches, _stats) = mach_6::do_website(&website, algorithm, None);
+                reports.insert(name, ReachReport::new(&matches, reach_threshold));
+            }
+            let aggregate: ReachSummary = reports.values().collect();
+            Ok(serde_yml::to_string(&ReportFile { websites: reports, aggregate }))
+        }
+        Report::HtmlErrors => {
+            let mut reports = BTreeMap::new();
+            for website in websites {
+                reports.insert(website.name.clone(), HtmlErrorReport::new(website.document()));
+            }
+            let aggregate: HtmlErrorSummary = reports.values().collect();
+            Ok(serde_yml::to_string(&ReportFile { websites: reports, aggregate }))
+        }
+        Report::Excluded => {
+            let mut reports = BTreeMap::new();
+            for website in websites {
+                reports.insert(website.name.clone(), ExcludedReport::new(&website));
+            }
+            let aggregate: ExcludedSummary = reports.values().collect();
+            Ok(serde_yml::to_string(&ReportFile { websites: reports, aggregate }))
+        }
+        Report::Provenance => {
+            let mut reports = BTreeMap::new();
+            for website in websites {
+                let (name, matches, _stats) = mach_6::do_website(&website, algorithm, None);
+                reports.insert(name, ProvenanceReport::new(&website, &matches));
+            }
+            let aggregate: ProvenanceSummary = reports.values().collect();
+            Ok(serde_yml::to_string(&ReportFile { websites: reports, aggregate }))
+        }
+        Report::Dedup => {
+            let mut reports = BTreeMap::new();
+            for website in websites {
+                reports.insert(website.name.clone(), DedupReport::new(&website));
+            }
+            let aggregate: DedupSummary = reports.values().collect();
+            Ok(serde_yml::to_string(&ReportFile { websites: reports, aggregate }))
+        }
+        Report::Coverage => {
+            let mut reports = BTreeMap::new();
+            for website in websites {
+                let (name, matches, _stats) = mach_6::do_website(&website, algorithm, None);
+                reports.insert(name, CoverageReport::new(&website, &matches));
+            }
+            let aggregate: CoverageSummary = reports.values().collect();
+            Ok(serde_yml::to_string(&ReportFile { websites: reports, aggregate }))
+        }
+        Report::Unmatched => {
+            let mut reports = BTreeMap::new();
+            for website in websites {
+                let (name, matches, _stats) = mach_6::do_website(&website, algorithm, None);
+                reports.insert(name, UnmatchedReport::new(&matches, unmatched_exclude_head));
+            }
+            let aggregate: UnmatchedSummary = reports.values().collect();
+            Ok(serde_yml::to_string(&ReportFile { websites: reports, aggregate }))
+        }
+        Report::CustomProperties => {
+            let mut reports = BTreeMap::new();
+            for website in websites {
+                reports.insert(website.name.clone(), CustomPropertiesReport::new(&website));
+            }
+            let aggregate: CustomPropertiesSummary = reports.values().collect();
+            Ok(serde_yml::to_string(&ReportFile { websites: reports, aggregate }))
+        }
+    }
+}
+
+/// Prints a YAML document per website with where the browser disagreed.
+#[cfg(feature = "browser_oracle")]
+fn verify_with_browser(
+    webdriver: &str,
+    website_paths: impl Iterator<Item = Result<PathBuf>>,
+    algorithm: mach_6::Algorithm,
+    options: &ParseOptions,
+) -> Result<()> {
+    use mach_6::verify::browser::{BrowserOracle, BrowserReport};
+
+    #[derive(Serialize)]
+    struct VerifyDocument<'a> {
+        website: &'a str,
+        #[serde(flatten)]
+        report: &'a BrowserReport,
+    }
+
+    let oracle = BrowserOracle::connect(webdriver)?;
+    let mut stdout = std::io::stdout().lock();
+    let mut divergent = 0;
+    let result = website_paths.map(|path| {
+        let Some((website, report)) = oracle.verify_website(&path?, algorithm, options)? else {
+            return Ok(());
+        };
+        if !report.is_empty() {
+            divergent += 1;
+        }
+        writeln!(stdout, "---").into_result(None)?;
+        serde_yml::to_writer(&mut stdout, &VerifyDocument { website: &website, report: &report }).unwrap();
+        stdout.flush().into_result(None)
+    }).collect::<Result<()>>();
+    oracle.close()?;
+    if divergent > 0 {
+        log::warn!("{divergent} websites matched differently in the browser");
+    }
+    result
+}
+
+#[cfg(feature = "memory_stats")]
+#[global_allocator]
+static GLOBAL: mach_6::memory::CountingAllocator = mach_6::memory::CountingAllocator;
+
+#[cfg(feature = "memory_stats")]
+#[derive(Serialize)]
+struct MemoryReportFile {
+    /// Of the whole process, over the run; phases and websites only have
+    /// heap growth
+    peak_rss_bytes: Option<u64>,
+    /// Phases not attributed to a single website, i.e. writing output formats
+    /// that take every website at once
+    run: mach_6::memory::MemoryReport,
+    websites: BTreeMap<String, mach_6::memory::MemoryReport>,
+}
+
+fn main() -> std::process::ExitCode {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    let Err(e) = try_main() else {
+        return std::process::ExitCode::SUCCESS;
+    };
+    let code = e.code();
+    #[cfg(feature = "miette")]
+    eprintln!("error[{code}]: {:?}", mach_6::diagnostics::report(&e));
+    #[cfg(not(feature = "miette"))]
+    eprintln!("error[{code}]: {}", e.to_string().trim_end());
+    std::process::ExitCode::from(code.exit_code())
+}
+
+fn try_main() -> Result<()> {
+    let args = Args::parse();
+    let stylesheet_cache = args.cache_stylesheets.then(StylesheetCache::new);
+    let duplicate_documents = args.skip_duplicates.then(DuplicateDocuments::new);
+    #[cfg(feature = "memory_stats")]
+    let memory_report = args.memory_report.clone();
+    let timings_file = args.timings_file.clone().map(|path| (path, args.algorithm));
+    let reports = PhaseReports::default();
+    let failures = Failures::default();
+    let result = run(args, stylesheet_cache.clone(), duplicate_documents.clone(), reports.clone(), failures.clone());
+    let mut failures = failures.take();
+    if !failures.is_empty() {
+        log::error!("{} websites failed", failures.len());
+    }
+    failures.extend(result.err());
+    let result = Error::from_errors(failures).map_or(Ok(()), Err);
+    if let Some((path, algorithm)) = timings_file
+        && result.is_ok()
+    {
+        TimingsFile::append(&path, TimingsRun::new(algorithm, &reports.borrow()))?;
+    }
+    #[cfg(feature = "memory_stats")]
+    if let Some(path) = memory_report {
+        let file = MemoryReportFile {
+            peak_rss_bytes: mach_6::memory::peak_rss_bytes(),
+            run: instrument::take_report().memory,
+            websites: reports
+                .take()
+                .into_iter()
+                .map(|(name, report)| (name, report.memory))
+                .collect(),
+        };
+        let f = std::fs::File::create(&path).into_result(Some(path.clone()))?;
+        serde_yml::to_writer(f, &file).unwrap();
+    }
+    #[cfg(feature = "bloom_self_check")]
+    {
+        let counts = mach_6::bloom_check::take_counts();
+        eprintln!(
+            "bloom filter self-check: {} rejected candidates confirmed not to match, {} candidates let through",
+            counts.filtered, counts.verified,
+        );
+    }
+    if let Some(cache) = stylesheet_cache {
+        let stats = cache.stats();
+        eprintln!(
+            "stylesheet cache: {} hits, {} misses, {} bytes of CSS not reparsed",
+            stats.hits, stats.misses, stats.bytes_saved,
+        );
+    }
+    if let Some(duplicate_documents) = duplicate_documents {
+        let stats = duplicate_documents.stats();
+        eprintln!(
+            "duplicate websites: {} skipped of {}, {} bytes of HTML and CSS not matched again",
+            stats.duplicates, stats.unique + stats.duplicates, stats.bytes_skipped,
+        );
+    }
+    result
+}
+
+fn run(
+    args: Args,
+    stylesheet_cache: Option<StylesheetCache>,
+    duplicate_documents: Option<DuplicateDocuments>,
+    reports: PhaseReports,
+    failures: Failures,
+) -> Result<()> {
     let Args {
+        command,
         websites,
         website,
+        limit,
+        sample,
+        seed,
+        shuffle_elements,
+        dedup_selectors,
+        normalize_selectors,
+        cascade_order,
         algorithm,
-    } = Args::parse();
-    let result: Result<Vec<(String, SetDocumentMatches, Statistics)>> = if let Some(website) = website {
-        Ok(get_document_and_selectors(&website)?
-            .map(|website| vec![mach_6::do_website(&website, algorithm, None)])
-            .unwrap_or_default())
+        main_file,
+        per_site_timeout,
+        per_site_max_selectors,
+        viewport,
+        dpr,
+        media,
+        media_features,
+        template_contents,
+        scripts,
+        stylesheet_links,
+        visited,
+        fragment,
+        emit,
+        format,
+        #[cfg(feature = "sqlite")]
+        sqlite,
+        #[cfg(feature = "parquet")]
+        parquet,
+        #[cfg(feature = "memory_stats")]
+        memory_report: _,
+        timings,
+        timings_file: _,
+        #[cfg(feature = "profiling")]
+        flamegraphs,
+        #[cfg(feature = "browser_oracle")]
+        webdriver,
+        wpt,
+        declared_styles,
+        breakpoints,
+        auto_breakpoints,
+        color_scheme_diff,
+        report,
+        reach_threshold,
+        unmatched_exclude_head,
+        trace,
+        trace_element,
+        trace_discrepancies,
+        verify_determinism,
+        cache_stylesheets: _,
+        forgiving_selector_lists,
+        skip_duplicates: _,
+        #[cfg(feature = "ua_styles")]
+        include_ua_styles,
+        stream,
+        resume,
+        force,
+    } = args;
+    if let Some(wpt) = wpt {
+        let report = mach_6::verify::wpt::run_directory(&wpt)?;
+        println!("{}", serde_yml::to_string(&report).unwrap());
+        return Ok(());
+    }
+    let delimiter = match format {
+        Format::Csv => Some(Delimiter::Comma),
+        Format::Tsv => Some(Delimiter::Tab),
+        _ => None,
+    };
+    #[cfg(feature = "msgpack")]
+    let is_msgpack = matches!(format, Format::Msgpack);
+    #[cfg(not(feature = "msgpack"))]
+    let is_msgpack = false;
+    if (delimiter.is_some() || is_msgpack) && !matches!(emit, Emit::Matches) {
+        return Err(Error::other(format!("--format {format:?} only supports --emit matches")));
+    }
+    if timings && !matches!(format, Format::Yaml | Format::Jsonl) {
+        return Err(Error::other(format!("--timings is not supported with --format {format:?}")));
+    }
+    #[cfg(feature = "sqlite")]
+    if timings && sqlite.is_some() {
+        return Err(Error::other("--timings is not supported with --sqlite".to_owned()));
+    }
+    #[cfg(feature = "parquet")]
+    if timings && parquet.is_some() {
+        return Err(Error::other("--timings is not supported with --parquet".to_owned()));
+    }
+    #[cfg(feature = "sqlite")]
+    if stream && sqlite.is_some() {
+        return Err(Error::other("--stream is not supported with --sqlite".to_owned()));
+    }
+    #[cfg(feature = "parquet")]
+    if stream && parquet.is_some() {
+        return Err(Error::other("--stream is not supported with --parquet".to_owned()));
+    }
+    #[cfg(feature = "profiling")]
+    if let Some(flamegraphs) = flamegraphs {
+        mach_6::profiling::write_flamegraphs_to(flamegraphs)?;
+    }
+    if !visited.visited_urls.is_empty() && visited.visited_mode != VisitedMode::RelevantLinkVisited {
+        return Err(Error::other("--visited only has an effect with --visited-mode relevant-link-visited".to_owned()));
+    }
+    if !(dpr.is_finite() && dpr > 0.0) {
+        return Err(Error::other(format!("--dpr must be positive, got {dpr}")));
+    }
+    let device = DeviceOptions { viewport, device_pixel_ratio: dpr, media_type: media, media_features };
+    if shuffle_elements && !algorithm.supports_element_order() {
+        return Err(Error::other(format!(
+            "--shuffle-elements is not supported with --algorithm {}",
+            algorithm.to_possible_value().unwrap().get_name(),
+        )));
+    }
+    let selection = WebsiteSelection { limit, sample, seed };
+    let element_order = if shuffle_elements { ElementOrder::Shuffled { seed } } else { ElementOrder::Document };
+    let options = ParseOptions {
+        main_file,
+        stylesheet_cache,
+        device,
+        selection,
+        stylesheet_links,
+        template_contents,
+        scripts,
+        visited,
+        fragment,
+        forgiving_selector_lists,
+        duplicate_documents,
+        element_order,
+        dedup_selectors,
+        normalize_selectors,
+        cascade_order,
+        budget: WebsiteBudget { timeout: per_site_timeout, max_selectors: per_site_max_selectors },
+        #[cfg(feature = "ua_styles")]
+        include_ua_styles,
+    };
+    if let Some(command) = command {
+        return run_command(command, algorithm, &options);
+    }
+    #[cfg(feature = "browser_oracle")]
+    if let Some(webdriver) = webdriver {
+        let website_paths: Box<dyn Iterator<Item = Result<PathBuf>>> = match website {
+            Some(website) => Box::new(std::iter::once(Ok(website))),
+            None => mach_6::parse::select_websites_dirs(
+                &websites.unwrap_or_else(|| PathBuf::from("websites")),
+                selection,
+            )?,
+        };
+        return verify_with_browser(&webdriver, website_paths, algorithm, &options);
+    }
+    if let Some(trace) = trace {
+        let filter = TraceFilter { element: trace_element, discrepancies_only: trace_discrepancies };
+        let mut writer = TraceWriter::create(&trace, filter)?;
+        for website in readable_websites(parsed_websites(website, websites, options)?, &failures) {
+            let (_, matches, _) = mach_6::do_website(&website, algorithm, None);
+            writer.trace_website(&website, &matches)?;
+        }
+        let events = writer.finish()?;
+        eprintln!("wrote {events} trace events to {}", trace.display());
+        return Ok(());
+    }
+    if verify_determinism {
+        let mut nondeterminism = Vec::new();
+        for website in readable_websites(parsed_websites(website, websites, options)?, &failures) {
+            nondeterminism.extend(mach_6::verify::verify_determinism(algorithm, [&website]));
+        }
+        println!("{}", serde_yml::to_string(&nondeterminism).unwrap());
+        return if nondeterminism.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::other(format!("{} websites matched differently when run twice", nondeterminism.len())))
+        };
+    }
+    if !declared_styles.is_empty() || !breakpoints.is_empty() || auto_breakpoints || color_scheme_diff || report.is_some() {
+        if let Some(width) = breakpoints.iter().find(|width| !(width.is_finite() && **width > 0.0)) {
+            return Err(Error::other(format!("breakpoints must be positive, got {width}")));
+        }
+        let parsed = readable_websites(parsed_websites(website, websites, options)?, &failures);
+        let output = if let Some(report) = report {
+            write_report(report, parsed, algorithm, reach_threshold, unmatched_exclude_head)?
+        } else if !declared_styles.is_empty() {
+            let mut output = BTreeMap::new();
+            for website in parsed {
+                output.insert(website.name.clone(), mach_6::styles::declared_styles(&website, &declared_styles)?);
+            }
+            serde_yml::to_string(&output)
+        } else if color_scheme_diff {
+            let mut output = BTreeMap::new();
+            for mut website in parsed {
+                let diff = mach_6::responsive::match_color_schemes(&mut website, algorithm);
+                if !diff.is_empty() {
+                    output.insert(website.name.clone(), diff);
+                }
+            }
+            serde_yml::to_string(&output)
+        } else {
+            let mut output = BTreeMap::new();
+            for mut website in parsed {
+                let widths = if auto_breakpoints {
+                    mach_6::responsive::media_breakpoints(&website)
+                } else {
+                    breakpoints.clone()
+                };
+                let report = mach_6::responsive::match_at_breakpoints(&mut website, algorithm, &widths);
+                output.insert(website.name.clone(), report);
+            }
+            serde_yml::to_string(&output)
+        };
+        println!("{}", output.unwrap());
+        return Ok(());
+    }
+    let checkpoint = resume
+        .map(|manifest| Checkpoint::open(&manifest).map(|checkpoint| Rc::new(RefCell::new(checkpoint))))
+        .transpose()?;
+    let results: Box<dyn Iterator<Item = Result<(String, SetDocumentMatches)>>> = if let Some(checkpoint) = &checkpoint {
+        let website_paths: Box<dyn Iterator<Item = Result<PathBuf>>> = match website {
+            Some(website) => Box::new(std::iter::once(Ok(website))),
+            None => mach_6::parse::select_websites_dirs(
+                &websites.unwrap_or_else(|| PathBuf::from("websites")),
+                selection,
+            )?,
+        };
+        let website_paths = Checkpoint::skip_completed(checkpoint.clone(), website_paths, force);
+        let results = mach_6::do_websites(website_paths, algorithm, options)
+            .map(|result| result.map(|(name, matches, _stats)| (name, matches)));
+        Box::new(Checkpoint::record_completed(checkpoint.clone(), results))
+    } else if let Some(website) = website {
+        Box::new(
+            mach_6::do_websites(std::iter::once(Ok(website)), algorithm, options)
+                .map(|result| result.map(|(name, matches, _stats)| (name, matches))),
+        )
     } else {
         let websites = websites.unwrap_or_else(|| PathBuf::from("websites"));
-        mach_6::do_all_websites(&websites, algorithm)?.collect()
+        Box::new(
+            mach_6::do_all_websites_with_options(&websites, algorithm, options)?
+                .map(|result| result.map(|(name, matches, _stats)| (name, matches))),
+        )
     };
-    let result: HashMap<String, SerDocumentMatches> = result?
-        .into_iter()
-        .map(|(name, matches, _stats)| (name, SerDocumentMatches::from(&matches)))
-        .collect();
-    println!("{}", serde_yml::to_string(&result).unwrap());
+    let results = {
+        let reports = reports.clone();
+        results.map(move |result| {
+            // Everything recorded since the last website (discovery through
+            // matching) was for this one
+            if let Ok((name, _)) = &result {
+                reports.borrow_mut().insert(name.clone(), instrument::take_report());
+            }
+            result
+        })
+    };
+    let results = results.filter_map(move |result| match result {
+        Ok(website) => Some(Ok(website)),
+        Err(e) => {
+            log::error!("{e}");
+            failures.borrow_mut().push(e);
+            None
+        }
+    });
+    #[cfg(feature = "msgpack")]
+    if is_msgpack {
+        // Written in order of completion even without --stream; readers
+        // get the website name with each entry.
+        let mut writer = MsgpackWriter::new(std::io::stdout().lock());
+        for result in results {
+            let (name, matches) = result?;
+            serialize_website(&reports, &name, || writer.write_website(&name, &matches))?;
+        }
+        return writer.finish().map(|_| ());
+    }
+    if stream {
+        return match delimiter {
+            Some(delimiter) => {
+                let mut writer = MatchesWriter::new(std::io::stdout().lock(), delimiter).into_result(None)?;
+                for result in results {
+                    let (name, matches) = result?;
+                    serialize_website(&reports, &name, || writer.write_website(&name, &matches)).into_result(None)?;
+                }
+                writer.finish().into_result(None)
+            }
+            None => write_documents(results, emit, format, &reports, timings),
+        };
+    }
+    let mut result: Vec<(String, SetDocumentMatches)> = results.collect::<Result<_>>()?;
+    result.sort_by(|(a, _), (b, _)| a.cmp(b));
+    // These formats take every website at once, so their serialization is
+    // only recorded for the run as a whole.
+    #[cfg(feature = "sqlite")]
+    if let Some(sqlite) = sqlite {
+        return instrument::phase(Phase::Serialize, || {
+            mach_6::export::sqlite::write_database(
+                &sqlite,
+                result.iter().map(|(name, matches)| (name.as_str(), matches)),
+            )
+        });
+    }
+    #[cfg(feature = "parquet")]
+    if let Some(parquet) = parquet {
+        return instrument::phase(Phase::Serialize, || {
+            mach_6::export::parquet::write_parquet(
+                &parquet,
+                result.iter().map(|(name, matches)| (name.as_str(), matches)),
+            )
+        });
+    }
+    if let Some(delimiter) = delimiter {
+        return instrument::phase(Phase::Serialize, || {
+            mach_6::export::csv::write_matches(
+                std::io::stdout().lock(),
+                result.iter().map(|(name, matches)| (name.as_str(), matches)),
+                delimiter,
+            )
+            .into_result(None)
+        });
+    }
+    if let Format::Jsonl = format {
+        return write_documents(result.into_iter().map(Ok), emit, format, &reports, timings);
+    }
+    let result = result.into_iter();
+    match emit {
+        Emit::Matches => write_yaml(result, &reports, timings, |matches| SerDocumentMatches::from(matches)),
+        Emit::Stats => write_yaml(result, &reports, timings, |matches| DocumentStats::from(matches)),
+        Emit::Both => write_yaml(result, &reports, timings, |matches| WebsiteOutput {
+            stats: DocumentStats::from(matches),
+            matches: SerDocumentMatches::from(matches),
+        }),
+    }
     Ok(())
 }
@@ -0,0 +1,25 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Matching selectors against documents: `do_website` runs an `Algorithm` on
+//! a website from `crate::parse`, and the `match_selectors*` functions run
+//! one algorithm on a document directly. Results are the types in
+//! `crate::output`.
+//!
+//! The definitions live at the crate root, where existing code uses them;
+//! this is the path to use in new code.
+
+pub use crate::{
+    Algorithm, ElementOrder, MatchingContext, Optimizations, do_all_websites, do_all_websites_with_options,
+    do_website, do_websites, fragment_elements, mach_7, match_in_memory, match_fragment_selectors, match_selectors,
+    match_selectors_in_order, match_selectors_with_scraper, match_selectors_with_style_sharing,
+    match_selectors_with_tag_index, match_selectors_with_tag_index_in_order, parse_fragment, parse_selector_list, query,
+    stylesheet_from_selectors,
+};
+#[cfg(feature = "arena")]
+pub use crate::match_selectors_in_arena;
+pub use crate::bytecode::{match_selectors_with_bytecode, match_selectors_with_bytecode_in_order};
+pub use crate::structs::Selector;
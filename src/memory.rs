@@ -0,0 +1,155 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Allocation accounting for `instrument::phase`.
+//!
+//! With the `memory_stats` feature, a binary that installs `CountingAllocator`
+//! as its `#[global_allocator]` gets the bytes allocated by, and the peak heap
+//! growth during, each phase. The counters are process-wide, so numbers are
+//! only meaningful when one website is processed at a time.
+//!
+//! Resident set size is not broken down: `peak_rss_bytes` is the peak of the
+//! whole process, reported once per run. Per phase and per website, the heap
+//! growth the allocator saw (`PhaseMemory::peak_bytes`) is what there is.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::instrument::Phase;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct PhaseMemory {
+    /// Total bytes allocated during the phase, including memory freed again
+    pub allocated_bytes: usize,
+    pub allocations: usize,
+    /// The most the heap grew above where it was when the phase started
+    pub peak_bytes: usize,
+}
+
+impl PhaseMemory {
+    pub(crate) fn add(&mut self, other: PhaseMemory) {
+        self.allocated_bytes += other.allocated_bytes;
+        self.allocations += other.allocations;
+        self.peak_bytes = self.peak_bytes.max(other.peak_bytes);
+    }
+}
+
+pub type MemoryReport = BTreeMap<Phase, PhaseMemory>;
+
+#[cfg(feature = "memory_stats")]
+mod counting {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    pub(super) static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+    pub(super) static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+    pub(super) static IN_USE: AtomicUsize = AtomicUsize::new(0);
+    pub(super) static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+    /// The system allocator, counting every allocation.
+    pub struct CountingAllocator;
+
+    fn record_alloc(size: usize) {
+        ALLOCATED.fetch_add(size, Ordering::Relaxed);
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        let in_use = IN_USE.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK.fetch_max(in_use, Ordering::Relaxed);
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let ptr = unsafe { System.alloc(layout) };
+            if !ptr.is_null() {
+                record_alloc(layout.size());
+            }
+            ptr
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            let ptr = unsafe { System.alloc_zeroed(layout) };
+            if !ptr.is_null() {
+                record_alloc(layout.size());
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) };
+            IN_USE.fetch_sub(layout.size(), Ordering::Relaxed);
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+            if !new_ptr.is_null() {
+                IN_USE.fetch_sub(layout.size(), Ordering::Relaxed);
+                record_alloc(new_size);
+            }
+            new_ptr
+        }
+    }
+}
+
+#[cfg(feature = "memory_stats")]
+pub use counting::CountingAllocator;
+
+/// Runs `f`, returning what it allocated. Always `None` without the
+/// `memory_stats` feature.
+#[cfg(feature = "memory_stats")]
+pub(crate) fn measure<T>(f: impl FnOnce() -> T) -> (T, Option<PhaseMemory>) {
+    use std::sync::atomic::Ordering;
+    use counting::{ALLOCATED, ALLOCATIONS, IN_USE, PEAK};
+
+    let allocated = ALLOCATED.load(Ordering::Relaxed);
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let in_use = IN_USE.load(Ordering::Relaxed);
+    let outer_peak = PEAK.swap(in_use, Ordering::Relaxed);
+    let result = f();
+    let peak = PEAK.fetch_max(outer_peak, Ordering::Relaxed);
+    let memory = PhaseMemory {
+        allocated_bytes: ALLOCATED.load(Ordering::Relaxed) - allocated,
+        allocations: ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        peak_bytes: peak.saturating_sub(in_use),
+    };
+    (result, Some(memory))
+}
+
+#[cfg(not(feature = "memory_stats"))]
+#[inline(always)]
+pub(crate) fn measure<T>(f: impl FnOnce() -> T) -> (T, Option<PhaseMemory>) {
+    (f(), None)
+}
+
+/// The peak resident set size of this process so far, over every phase and
+/// website, from `/proc/self/status`, so only available on Linux.
+pub fn peak_rss_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kb: u64 = line.trim_start_matches("VmHWM:").trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::measure;
+    use test_log::test;
+
+    #[test]
+    fn measure_returns_closure_result() {
+        let (result, memory) = measure(|| vec![0u8; 1024].len());
+        assert_eq!(result, 1024);
+        #[cfg(feature = "memory_stats")]
+        assert!(memory.is_some_and(|memory| memory.allocated_bytes >= 1024 && memory.allocations >= 1));
+        #[cfg(not(feature = "memory_stats"))]
+        assert!(memory.is_none());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn reads_peak_rss() {
+        assert!(super::peak_rss_bytes().is_some_and(|rss| rss > 0));
+    }
+}
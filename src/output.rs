@@ -0,0 +1,23 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! What matching produces and how it's written out. Algorithms return
+//! `DocumentMatches`, borrowing the document; `do_website` returns
+//! `SetDocumentMatches`, which owns its elements and selectors and can be
+//! compared, diffed and read back; `SerDocumentMatches` is how both are
+//! printed, and `export` writes them to other formats. Every one of them
+//! describes elements with `Element`, and every fallible step returns
+//! `Error`.
+
+pub use crate::export;
+pub use crate::result::{CssParseError, Error, ErrorKind, IntoResultExt, Result};
+pub use crate::structs::borrowed::{DocumentMatches, ElementMatches, SelectorsOrSharedStyles};
+pub use crate::structs::diff::DocumentMatchesDiff;
+pub use crate::structs::owned::OwnedDocumentMatches;
+pub use crate::structs::ser::{SerDocumentMatches, SerElementMatches};
+pub use crate::structs::set::{SetDocumentMatches, SetElementMatches, SetSelectorsOrSharedStyles};
+pub use crate::structs::stats::DocumentStats;
+pub use crate::structs::{Element, ElementPath, element_id};
@@ -4,16 +4,27 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-use crate::{MatchingContext};
-use crate::result::{Error, ErrorKind, IntoResultExt, Result};
-use log::warn;
-use scraper::Html;
-use std::ffi::OsStr;
+use crate::{ElementOrder, MatchingContext};
+use crate::analysis::html_errors::HtmlErrorReport;
+use crate::budget::{self, WebsiteBudget};
+use crate::corpus_source::{CorpusSource, FsSource};
+use crate::device::{self, DeviceOptions};
+use crate::instrument::{self, Phase};
+use crate::result::{CssParseError, Error, ErrorKind, IntoResultExt, Result};
+use crate::selector_filter::SelectorFilter;
+use crate::selector_recovery;
+use crate::source_map;
+use clap::ValueEnum;
+use log::{info, warn};
+use scraper::{ElementRef, Html};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, hash_map::Entry};
 use std::fs::{self, DirEntry};
 use std::io;
 use std::path::{Path, PathBuf};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use style::context::QuirksMode;
+use style::error_reporting::{ContextualParseError, ParseErrorReporter};
 use style::media_queries::MediaList;
 use style::servo_arc::Arc;
 use style::shared_lock::SharedRwLock;
@@ -29,7 +40,24 @@ pub struct ParsedWebsite {
     pub name: String,
     document: Html,
     stylesheets: Vec<DocumentStyleSheet>,
-    stylesheet_lock: SharedRwLock
+    /// Where each stylesheet came from, for reports
+    stylesheet_names: Vec<String>,
+    stylesheet_lock: SharedRwLock,
+    device: DeviceOptions,
+    element_order: ElementOrder,
+    dedup_selectors: bool,
+    normalize_selectors: bool,
+    cascade_order: bool,
+    excluded_template_elements: usize,
+    script_report: ScriptReport,
+    css_errors: Vec<Error>,
+    /// Parsed with `stylesheet_lock`
+    ua_stylesheet: Option<DocumentStyleSheet>,
+    /// Where each stylesheet's source map would be, if it has a local one
+    source_map_paths: Vec<Option<PathBuf>>,
+    /// Of each stylesheet's CSS, in bytes, if known
+    stylesheet_sizes: Vec<Option<usize>>,
+    stylesheet_kinds: Vec<Option<StylesheetKind>>,
 }
 
 impl ParsedWebsite {
@@ -39,14 +67,202 @@ impl ParsedWebsite {
         stylesheets: Vec<DocumentStyleSheet>,
         stylesheet_lock: SharedRwLock
     ) -> Self {
+        let stylesheet_names = (1..=stylesheets.len()).map(|i| format!("stylesheet {i}")).collect();
+        let source_map_paths = vec![None; stylesheets.len()];
+        let stylesheet_sizes = vec![None; stylesheets.len()];
+        let stylesheet_kinds = vec![None; stylesheets.len()];
         Self {
             name,
             document,
             stylesheets,
+            stylesheet_names,
             stylesheet_lock,
+            device: DeviceOptions::default(),
+            element_order: ElementOrder::default(),
+            dedup_selectors: false,
+            normalize_selectors: false,
+            cascade_order: false,
+            excluded_template_elements: 0,
+            script_report: ScriptReport::default(),
+            css_errors: Vec::new(),
+            ua_stylesheet: None,
+            source_map_paths,
+            stylesheet_sizes,
+            stylesheet_kinds,
         }
     }
 
+    pub fn with_css_errors(self, css_errors: Vec<Error>) -> Self {
+        Self { css_errors, ..self }
+    }
+
+    /// Stylesheets that couldn't be read (and were skipped) or had parse
+    /// errors (`ErrorKind::CssParse`, and were used without the rules that
+    /// didn't parse)
+    pub fn css_errors(&self) -> &[Error] {
+        &self.css_errors
+    }
+
+    pub fn with_excluded_template_elements(self, excluded_template_elements: usize) -> Self {
+        Self { excluded_template_elements, ..self }
+    }
+
+    /// How many elements inside `<template>`s were left out of the document
+    /// (see `TemplateContents`)
+    pub fn excluded_template_elements(&self) -> usize {
+        self.excluded_template_elements
+    }
+
+    pub fn with_script_report(self, script_report: ScriptReport) -> Self {
+        Self { script_report, ..self }
+    }
+
+    /// What `ScriptOptions` removed or unwrapped
+    pub fn script_report(&self) -> ScriptReport {
+        self.script_report
+    }
+
+    /// Also matches the bundled user-agent stylesheet, at the user-agent
+    /// origin. Its matches are kept apart from the author stylesheets' (see
+    /// `SetElementMatches::ua_selectors`).
+    #[cfg(feature = "ua_styles")]
+    pub fn with_ua_stylesheet(self) -> Self {
+        let url_data = UrlExtraData::from(url::Url::parse("about:blank").unwrap());
+        let (stylesheet, errors) = parse_stylesheet_with_origin(
+            crate::ua_styles::HTML_CSS,
+            url_data,
+            Origin::UserAgent,
+            &self.stylesheet_lock,
+        );
+        if !errors.is_empty() {
+            log::debug!("dropped {} declarations or rules from the user-agent stylesheet", errors.len());
+        }
+        Self { ua_stylesheet: Some(stylesheet), ..self }
+    }
+
+    pub fn ua_stylesheet(&self) -> Option<&DocumentStyleSheet> {
+        self.ua_stylesheet.as_ref()
+    }
+
+    /// Replaces the website's stylesheets with `stylesheets`, which can only
+    /// be read with `stylesheet_lock`. The user-agent stylesheet is dropped,
+    /// since it was parsed with the old lock, and so are the source map
+    /// paths, stylesheet sizes and stylesheet kinds.
+    pub fn with_stylesheets(self, stylesheets: Vec<DocumentStyleSheet>, stylesheet_lock: SharedRwLock) -> Self {
+        let (device, element_order) = (self.device, self.element_order);
+        let (dedup_selectors, normalize_selectors) = (self.dedup_selectors, self.normalize_selectors);
+        let cascade_order = self.cascade_order;
+        Self::new(self.name, self.document, stylesheets, stylesheet_lock)
+            .with_device(device)
+            .with_element_order(element_order)
+            .with_dedup_selectors(dedup_selectors)
+            .with_normalized_selectors(normalize_selectors)
+            .with_cascade_order(cascade_order)
+    }
+
+    /// Replaces the website's stylesheets with one of the selectors `filter`
+    /// keeps, like `crate::query` does, so every algorithm matches those
+    /// alone. Drops what `with_stylesheets` drops.
+    pub fn with_selector_filter(self, filter: &SelectorFilter) -> Self {
+        let selectors = filter.retain(self.get_matcher().get_selectors());
+        let (stylesheet, stylesheet_lock) = crate::stylesheet_from_selectors(selectors.iter());
+        self.with_stylesheets(vec![stylesheet], stylesheet_lock)
+    }
+
+    /// Names the stylesheets (in order), e.g. by their paths, instead of
+    /// `stylesheet 1`, `stylesheet 2`, etc.
+    pub fn with_stylesheet_names(self, stylesheet_names: Vec<String>) -> Self {
+        assert_eq!(stylesheet_names.len(), self.stylesheets.len(), "one name per stylesheet");
+        Self { stylesheet_names, ..self }
+    }
+
+    /// Gives the stylesheets (in order) the source maps their
+    /// `sourceMappingURL` comments point to. The files needn't exist.
+    pub fn with_source_map_paths(self, source_map_paths: Vec<Option<PathBuf>>) -> Self {
+        assert_eq!(source_map_paths.len(), self.stylesheets.len(), "one source map path per stylesheet");
+        Self { source_map_paths, ..self }
+    }
+
+    pub fn source_map_paths(&self) -> &[Option<PathBuf>] {
+        &self.source_map_paths
+    }
+
+    /// Records how big each stylesheet's CSS was (in order), before it was
+    /// parsed.
+    pub fn with_stylesheet_sizes(self, stylesheet_sizes: Vec<Option<usize>>) -> Self {
+        assert_eq!(stylesheet_sizes.len(), self.stylesheets.len(), "one size per stylesheet");
+        Self { stylesheet_sizes, ..self }
+    }
+
+    pub fn stylesheet_sizes(&self) -> &[Option<usize>] {
+        &self.stylesheet_sizes
+    }
+
+    /// Records how the document included each stylesheet (in order).
+    pub fn with_stylesheet_kinds(self, stylesheet_kinds: Vec<Option<StylesheetKind>>) -> Self {
+        assert_eq!(stylesheet_kinds.len(), self.stylesheets.len(), "one kind per stylesheet");
+        Self { stylesheet_kinds, ..self }
+    }
+
+    pub fn stylesheet_kinds(&self) -> &[Option<StylesheetKind>] {
+        &self.stylesheet_kinds
+    }
+
+    /// Matches for `device` instead of the default one. Media features other
+    /// than `prefers-color-scheme` are resolved while parsing, so they only
+    /// take effect through `ParseOptions::device`.
+    pub fn with_device(self, device: DeviceOptions) -> Self {
+        Self { device, ..self }
+    }
+
+    pub fn set_device(&mut self, device: DeviceOptions) {
+        self.device = device;
+    }
+
+    pub fn device(&self) -> &DeviceOptions {
+        &self.device
+    }
+
+    /// Matches the elements in `element_order`. See `ElementOrder`.
+    pub fn with_element_order(self, element_order: ElementOrder) -> Self {
+        Self { element_order, ..self }
+    }
+
+    pub fn element_order(&self) -> ElementOrder {
+        self.element_order
+    }
+
+    /// Has `do_website` match each of the selectors that serialize the same
+    /// once, instead of once per rule. Results are the same, since they
+    /// name matched selectors by their serialization.
+    pub fn with_dedup_selectors(self, dedup_selectors: bool) -> Self {
+        Self { dedup_selectors, ..self }
+    }
+
+    pub fn dedup_selectors(&self) -> bool {
+        self.dedup_selectors
+    }
+
+    /// Has `do_website` name matched selectors by their canonical string.
+    /// See `selector_normalization`.
+    pub fn with_normalized_selectors(self, normalize_selectors: bool) -> Self {
+        Self { normalize_selectors, ..self }
+    }
+
+    pub fn normalize_selectors(&self) -> bool {
+        self.normalize_selectors
+    }
+
+    /// Has `do_website` list each element's matched selectors in cascade
+    /// order too, in `SetElementMatches::cascade_order`.
+    pub fn with_cascade_order(self, cascade_order: bool) -> Self {
+        Self { cascade_order, ..self }
+    }
+
+    pub fn cascade_order(&self) -> bool {
+        self.cascade_order
+    }
+
     pub fn document(&self) -> &Html {
         &self.document
     }
@@ -59,77 +275,841 @@ impl ParsedWebsite {
         &self.stylesheets
     }
 
+    pub fn stylesheet_names(&self) -> &[String] {
+        &self.stylesheet_names
+    }
+
     pub fn get_matcher(&self) -> MatchingContext {
-        MatchingContext::new(self.stylesheets.iter(), self.stylesheet_lock.clone())
+        MatchingContext::with_device(
+            self.stylesheets.iter().chain(&self.ua_stylesheet),
+            self.stylesheet_lock.clone(),
+            &self.device,
+        )
+    }
+}
+
+/// Options controlling how a website folder is turned into a `ParsedWebsite`.
+#[derive(Debug, Default, Clone)]
+pub struct ParseOptions {
+    /// The html file (relative to the website folder) to use as the main
+    /// document. Takes precedence over `main_file` in the website's
+    /// `mach6.toml`. When neither is given, the website folder must contain
+    /// exactly one html file.
+    pub main_file: Option<PathBuf>,
+    /// Reuse parsed stylesheets between websites with identical CSS.
+    pub stylesheet_cache: Option<StylesheetCache>,
+    /// The device to match for
+    pub device: DeviceOptions,
+    /// Which websites of a websites directory to parse
+    pub selection: WebsiteSelection,
+    pub stylesheet_links: StylesheetLinks,
+    pub template_contents: TemplateContents,
+    pub scripts: ScriptOptions,
+    pub visited: VisitedOptions,
+    /// The URL fragment the document is opened at, which `:target` matches.
+    /// See `apply_fragment`.
+    pub fragment: Option<String>,
+    /// Keep the valid selectors of a style rule whose selector list has an
+    /// invalid one, instead of dropping the rule. See `selector_recovery`.
+    pub forgiving_selector_lists: bool,
+    /// Skip websites that are duplicates of one parsed before them.
+    pub duplicate_documents: Option<DuplicateDocuments>,
+    pub element_order: ElementOrder,
+    /// See `ParsedWebsite::with_dedup_selectors`
+    pub dedup_selectors: bool,
+    /// See `ParsedWebsite::with_normalized_selectors`
+    pub normalize_selectors: bool,
+    /// See `ParsedWebsite::with_cascade_order`
+    pub cascade_order: bool,
+    /// Limits on each website, for `do_websites`. `max_selectors` applies
+    /// wherever websites are parsed.
+    pub budget: WebsiteBudget,
+    /// See `ParsedWebsite::with_ua_stylesheet`
+    #[cfg(feature = "ua_styles")]
+    pub include_ua_styles: bool,
+}
+
+/// Making the document look like it would to a browser with scripting
+/// disabled. Nothing is done by default: the parser already parses as if
+/// scripting were enabled, so `<noscript>` contents are a single text node.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::Args)]
+pub struct ScriptOptions {
+    /// Remove the text of `<script>` elements, leaving them empty
+    #[arg(long)]
+    pub strip_scripts: bool,
+    /// Parse the text of `<noscript>` elements as HTML, so their elements
+    /// are matched and their stylesheets load
+    #[arg(long)]
+    pub unwrap_noscript: bool,
+}
+
+/// Which `<link>`s load stylesheets. `rel="stylesheet"` links always do.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::Args)]
+pub struct StylesheetLinks {
+    /// Don't load `<link rel="preload" as="style">`s as stylesheets. Pages
+    /// usually turn them into stylesheets from a script once they load.
+    #[arg(long)]
+    pub no_preload_stylesheets: bool,
+    /// Also load `<link rel="alternate stylesheet">`s, which browsers only
+    /// apply when the user picks them
+    #[arg(long)]
+    pub alternate_stylesheets: bool,
+}
+
+/// How a document includes a stylesheet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StylesheetKind {
+    /// A `<style>` element
+    Style,
+    /// `<link rel="stylesheet">`
+    Link,
+    /// `<link rel="preload" as="style">`
+    Preload,
+    /// `<link rel="alternate stylesheet">`
+    Alternate,
+}
+
+/// What `apply_script_options` did to a document.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ScriptReport {
+    /// `<script>`s whose text was removed
+    pub scripts_stripped: usize,
+    /// Bytes of script text removed
+    pub script_bytes: usize,
+    /// `<noscript>`s whose text was parsed
+    pub noscripts_unwrapped: usize,
+    /// Elements that parsing them added
+    pub noscript_elements: usize,
+}
+
+impl ScriptReport {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Strips scripts and unwraps `<noscript>`s as `options` says.
+pub fn apply_script_options(document: &mut Html, options: ScriptOptions) -> ScriptReport {
+    let mut report = ScriptReport::default();
+    let elements_named = |document: &Html, name: &str| -> Vec<ego_tree::NodeId> {
+        document
+            .tree
+            .nodes()
+            .filter(|node| node.value().as_element().is_some_and(|e| e.name() == name))
+            .map(|node| node.id())
+            .collect()
+    };
+    // Returns the element's text and detaches its children
+    let take_text = |document: &mut Html, element: ego_tree::NodeId| -> String {
+        let children: Vec<_> = document.tree.get(element).unwrap().children().map(|child| child.id()).collect();
+        let mut text = String::new();
+        for child in children {
+            if let Some(child_text) = document.tree.get(child).unwrap().value().as_text() {
+                text.push_str(child_text);
+            }
+            document.tree.get_mut(child).unwrap().detach();
+        }
+        text
+    };
+    if options.strip_scripts {
+        for script in elements_named(document, "script") {
+            let text = take_text(document, script);
+            report.scripts_stripped += 1;
+            report.script_bytes += text.len();
+        }
+    }
+    if options.unwrap_noscript {
+        for noscript in elements_named(document, "noscript") {
+            // Elements in a `<noscript>` parsed with scripting disabled, as
+            // in `<head>`, are already there
+            if document.tree.get(noscript).unwrap().children().any(|child| child.value().is_element()) {
+                continue;
+            }
+            let fragment = crate::parse_fragment(&take_text(document, noscript));
+            for child in fragment.tree.root().children() {
+                report.noscript_elements += append_copy(document, noscript, child);
+            }
+            report.noscripts_unwrapped += 1;
+        }
+    }
+    report
+}
+
+/// How links match `:link` and `:visited`, after Stylo's
+/// `VisitedHandlingMode`. Without a browsing history, every link is unvisited.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum VisitedMode {
+    /// Every link matches `:link` and none match `:visited`
+    #[default]
+    AllLinksUnvisited,
+    /// Links to a `VisitedOptions::visited_urls` URL match `:visited`
+    /// instead of `:link`, as for a user who has visited them
+    RelevantLinkVisited,
+    /// Every link matches both, as when Stylo collects the selectors a
+    /// link's state change could affect
+    AllLinksVisitedAndUnvisited,
+}
+
+impl From<VisitedMode> for selectors::context::VisitedHandlingMode {
+    fn from(value: VisitedMode) -> Self {
+        match value {
+            VisitedMode::AllLinksUnvisited => Self::AllLinksUnvisited,
+            VisitedMode::RelevantLinkVisited => Self::RelevantLinkVisited,
+            VisitedMode::AllLinksVisitedAndUnvisited => Self::AllLinksVisitedAndUnvisited,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, clap::Args)]
+pub struct VisitedOptions {
+    /// How links match `:link` and `:visited`
+    #[arg(long, value_enum, default_value_t = VisitedMode::AllLinksUnvisited)]
+    pub visited_mode: VisitedMode,
+    /// With `--visited-mode relevant-link-visited`, a URL that counts as
+    /// visited. Relative URLs are resolved against the document's `<base>`.
+    /// Can be given more than once.
+    #[arg(long = "visited", value_name = "URL")]
+    pub visited_urls: Vec<String>,
+}
+
+/// Sets which of `document`'s links are visited, as `options` says. Returns
+/// how many links match `:visited`.
+pub fn apply_visited_options(document: &mut Html, options: &VisitedOptions) -> usize {
+    use selectors::Element as _;
+    use stylo_dom::ElementState;
+
+    if options.visited_mode == VisitedMode::AllLinksUnvisited {
+        return 0;
+    }
+    let base = document
+        .select(&scraper::Selector::parse("base[href]").unwrap())
+        .next()
+        .and_then(|base| url::Url::parse(base.value().attr("href")?).ok());
+    let resolve = |href: &str| match &base {
+        Some(base) => base.join(href).ok(),
+        None => url::Url::parse(href).ok(),
+    };
+    let visited_urls: HashSet<url::Url> = options.visited_urls.iter().filter_map(|url| resolve(url)).collect();
+    let is_visited = |href: &str| {
+        options.visited_urls.iter().any(|url| url == href) || resolve(href).is_some_and(|url| visited_urls.contains(&url))
+    };
+    let visited: Vec<ego_tree::NodeId> = document
+        .tree
+        .nodes()
+        .filter_map(ElementRef::wrap)
+        .filter(|element| element.is_link())
+        .filter(|element| match options.visited_mode {
+            VisitedMode::RelevantLinkVisited => {
+                // SVG links may only have an `xlink:href`, which `attr` can't
+                // look up
+                element.value().attr("href").is_some_and(is_visited)
+            }
+            _ => true,
+        })
+        .map(|element| element.id())
+        .collect();
+    let state = match options.visited_mode {
+        VisitedMode::RelevantLinkVisited => ElementState::VISITED,
+        _ => ElementState::VISITED | ElementState::UNVISITED,
+    };
+    for &id in &visited {
+        if let scraper::Node::Element(element) = document.tree.get_mut(id).unwrap().value() {
+            element.set_link_state(state);
+        }
+    }
+    visited.len()
+}
+
+/// Makes the element `fragment` (with or without its `#`) indicates the
+/// document's target element: the first element with that id, or failing
+/// that, the first `<a>` with that name. Returns whether there was one.
+/// <https://html.spec.whatwg.org/multipage/browsing-the-web.html#the-indicated-part-of-the-document>
+pub fn apply_fragment(document: &mut Html, fragment: &str) -> bool {
+    let fragment = fragment.strip_prefix('#').unwrap_or(fragment);
+    if fragment.is_empty() {
+        return false;
+    }
+    let names = [Some(fragment.to_owned()), percent_decode(fragment).filter(|decoded| decoded != fragment)];
+    let elements = || document.tree.nodes().filter_map(ElementRef::wrap);
+    let target = names.iter().flatten().find_map(|name| {
+        elements()
+            .find(|element| element.value().id() == Some(name.as_str()))
+            .or_else(|| {
+                elements().find(|element| element.value().name() == "a" && element.value().attr("name") == Some(name.as_str()))
+            })
+            .map(|element| element.id())
+    });
+    let Some(target) = target else {
+        return false;
+    };
+    if let scraper::Node::Element(element) = document.tree.get_mut(target).unwrap().value() {
+        element.set_target(true);
+    }
+    true
+}
+
+/// `None` if the decoded bytes aren't UTF-8
+fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Appends a copy of `node` and its descendants, from another tree, to
+/// `parent`. Returns how many elements were copied.
+fn append_copy(document: &mut Html, parent: ego_tree::NodeId, node: ego_tree::NodeRef<scraper::Node>) -> usize {
+    let copy = document.tree.get_mut(parent).unwrap().append(node.value().clone()).id();
+    let elements = usize::from(node.value().is_element());
+    elements + node.children().map(|child| append_copy(document, copy, child)).sum::<usize>()
+}
+
+/// What to do with the contents of `<template>` elements. The parser keeps
+/// them in a document fragment under the template, which element traversal
+/// skips but descendant iteration doesn't; resolving them one way or the
+/// other when parsing makes every algorithm and report agree.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TemplateContents {
+    /// Remove them, as they are inert in a browser: no style applies to
+    /// them and their `<style>`s and `<link>`s don't load
+    #[default]
+    Exclude,
+    /// Make them the template's children, so they are matched like any other
+    /// elements
+    Include,
+}
+
+/// Resolves `document`'s template contents as `template_contents` says,
+/// returning how many elements were removed.
+pub fn resolve_template_contents(document: &mut Html, template_contents: TemplateContents) -> usize {
+    // The root is a fragment too in parsed fragments
+    let is_template_contents = |node: ego_tree::NodeRef<scraper::Node>| node.value().is_fragment() && node.parent().is_some();
+    let fragments: Vec<_> = document
+        .tree
+        .nodes()
+        .filter(|&node| is_template_contents(node))
+        .map(|node| (node.id(), node.ancestors().any(is_template_contents)))
+        .collect();
+    let mut excluded = 0;
+    for (fragment, nested) in fragments {
+        let node = document.tree.get(fragment).unwrap();
+        let template = node.parent().unwrap().id();
+        match template_contents {
+            TemplateContents::Exclude => {
+                // Nested templates are excluded with the outermost one
+                if nested {
+                    continue;
+                }
+                excluded += node.descendants().filter(|d| d.value().is_element()).count();
+            }
+            TemplateContents::Include => {
+                document.tree.get_mut(template).unwrap().reparent_from_id_append(fragment);
+            }
+        }
+        document.tree.get_mut(fragment).unwrap().detach();
+    }
+    excluded
+}
+
+/// A subset of a websites directory, for iterating on a large corpus without
+/// copying it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WebsiteSelection {
+    /// Only the first this many websites in path order (after sampling)
+    pub limit: Option<usize>,
+    /// A random subset of this many websites, chosen by `seed`, in path order
+    pub sample: Option<usize>,
+    pub seed: u64,
+}
+
+impl WebsiteSelection {
+    pub fn is_all(&self) -> bool {
+        self.limit.is_none() && self.sample.is_none()
+    }
+
+    /// Selects from `paths`, returning them in path order.
+    pub fn select(&self, mut paths: Vec<PathBuf>) -> Vec<PathBuf> {
+        paths.sort();
+        if let Some(sample) = self.sample {
+            let sample = sample.min(paths.len());
+            shuffle(&mut paths, self.seed, sample);
+            paths.truncate(sample);
+            paths.sort();
+        }
+        if let Some(limit) = self.limit {
+            paths.truncate(limit);
+        }
+        paths
+    }
+}
+
+/// Moves `count` items of `items`, chosen by `seed`, to its front in random
+/// order: a partial Fisher-Yates shuffle, with splitmix64 so that a seed
+/// shuffles the same way on every platform and version. A `count` of
+/// `items.len()` shuffles all of them.
+pub(crate) fn shuffle<T>(items: &mut [T], seed: u64, count: usize) {
+    let mut state = seed;
+    let mut next = || {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+    for i in 0..count.min(items.len()) {
+        let j = i + (next() % (items.len() - i) as u64) as usize;
+        items.swap(i, j);
+    }
+}
+
+/// Parsed stylesheets shared between websites, keyed by the SHA-256 of their
+/// CSS text, so the text itself isn't kept. Many
+/// corpora copy the same CDN stylesheet (bootstrap.css, fonts.css) into every
+/// website, and it only needs to be parsed once. A stylesheet can only be read
+/// with the lock it was parsed with, so every website parsed with a cache
+/// uses the cache's lock. Cached stylesheets keep the url of the first
+/// website that had them. Cloning a cache shares it.
+#[derive(Clone)]
+pub struct StylesheetCache(std::sync::Arc<StylesheetCacheInner>);
+
+struct StylesheetCacheInner {
+    lock: SharedRwLock,
+    sheets: parking_lot::Mutex<HashMap<[u8; 32], (DocumentStyleSheet, Vec<CssParseError>)>>,
+    stats: parking_lot::Mutex<StylesheetCacheStats>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct StylesheetCacheStats {
+    /// Stylesheets that were already in the cache
+    pub hits: usize,
+    /// Stylesheets that had to be parsed
+    pub misses: usize,
+    /// Bytes of CSS that didn't have to be parsed again
+    pub bytes_saved: usize,
+}
+
+impl StylesheetCache {
+    pub fn new() -> Self {
+        Self(std::sync::Arc::new(StylesheetCacheInner {
+            lock: SharedRwLock::new(),
+            sheets: Default::default(),
+            stats: Default::default(),
+        }))
+    }
+
+    pub fn lock(&self) -> &SharedRwLock {
+        &self.0.lock
+    }
+
+    pub fn stats(&self) -> StylesheetCacheStats {
+        *self.0.stats.lock()
+    }
+
+    fn get_or_parse(&self, css: &str, url_data: UrlExtraData) -> (DocumentStyleSheet, Vec<CssParseError>) {
+        use sha2::{Digest as _, Sha256};
+
+        let hash: [u8; 32] = Sha256::digest(css.as_bytes()).into();
+        // Parsing under the lock keeps two websites from both parsing a
+        // stylesheet neither found
+        match self.0.sheets.lock().entry(hash) {
+            Entry::Occupied(entry) => {
+                let mut stats = self.0.stats.lock();
+                stats.hits += 1;
+                stats.bytes_saved += css.len();
+                entry.get().clone()
+            }
+            Entry::Vacant(entry) => {
+                let parsed = entry.insert(parse_stylesheet_with_errors(css, url_data, &self.0.lock)).clone();
+                self.0.stats.lock().misses += 1;
+                parsed
+            }
+        }
+    }
+}
+
+impl Default for StylesheetCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for StylesheetCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StylesheetCache")
+            .field("len", &self.0.sheets.lock().len())
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+/// The websites parsed so far, by the hash of their document and
+/// stylesheets, for skipping duplicates. Templated corpora often have the
+/// same page under several names. The document is hashed as serialized
+/// after parsing (and after `TemplateContents` and `ScriptOptions`), so
+/// documents that differ only in ways the parser normalizes away, like
+/// whitespace between attributes or omitted end tags, are duplicates too.
+/// Linked stylesheets are hashed byte for byte, since the same document with
+/// other styles has other matches. Cloning shares the websites seen.
+#[derive(Clone, Default)]
+pub struct DuplicateDocuments(std::sync::Arc<parking_lot::Mutex<DuplicateDocumentsInner>>);
+
+#[derive(Default)]
+struct DuplicateDocumentsInner {
+    /// The first website with each hash
+    seen: HashMap<[u8; 32], String>,
+    /// Skipped websites and the website each duplicates
+    duplicates: Vec<(String, String)>,
+    stats: DuplicateDocumentsStats,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DuplicateDocumentsStats {
+    pub unique: usize,
+    pub duplicates: usize,
+    /// Bytes of HTML and linked CSS in the skipped websites
+    pub bytes_skipped: usize,
+}
+
+impl DuplicateDocuments {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stats(&self) -> DuplicateDocumentsStats {
+        self.0.lock().stats
+    }
+
+    /// Skipped websites and the website each duplicates, in the order they
+    /// were skipped
+    pub fn duplicates(&self) -> Vec<(String, String)> {
+        self.0.lock().duplicates.clone()
+    }
+
+    /// Records the website `name`, returning the name of the website it
+    /// duplicates if there was one.
+    fn check(
+        &self,
+        name: &str,
+        document: &Html,
+        links: StylesheetLinks,
+        read_stylesheet: impl Fn(&Path) -> Vec<u8>,
+    ) -> Option<String> {
+        use sha2::{Digest as _, Sha256};
+        let html = document.html();
+        let mut hasher = Sha256::new();
+        let mut bytes = html.len();
+        hasher.update((html.len() as u64).to_le_bytes());
+        hasher.update(&html);
+        for (CssFile(css_path), _) in get_stylesheet_paths(document, links) {
+            let css = read_stylesheet(css_path.as_path());
+            bytes += css.len();
+            hasher.update((css.len() as u64).to_le_bytes());
+            hasher.update(&css);
+        }
+        let hash: [u8; 32] = hasher.finalize().into();
+        let mut inner = self.0.lock();
+        match inner.seen.get(&hash).cloned() {
+            Some(original) => {
+                inner.duplicates.push((name.to_owned(), original.clone()));
+                inner.stats.duplicates += 1;
+                inner.stats.bytes_skipped += bytes;
+                Some(original)
+            }
+            None => {
+                inner.seen.insert(hash, name.to_owned());
+                inner.stats.unique += 1;
+                None
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for DuplicateDocuments {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DuplicateDocuments")
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+/// The name of the optional per-website configuration file.
+pub const WEBSITE_CONFIG_FILE: &str = "mach6.toml";
+
+/// Per-website configuration, read from `mach6.toml` in the website folder.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebsiteConfig {
+    /// The html file (relative to the website folder) to use as the main
+    /// document.
+    pub main_file: Option<PathBuf>,
+}
+
+impl WebsiteConfig {
+    /// Reads `mach6.toml` from the website folder, returning the default
+    /// config if there is none.
+    pub fn read(website: &Path) -> Result<Self> {
+        let config_path = website.join(WEBSITE_CONFIG_FILE);
+        if !config_path.is_file() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(&config_path).into_result(Some(config_path.clone()))?;
+        toml::from_str(&contents).map_err(|e| Error {
+            path: Some(config_path),
+            error: ErrorKind::InvalidConfig(e),
+        })
     }
 }
 
 pub fn get_all_documents_and_selectors(websites_path: &Path) -> Result<impl Iterator<Item = Result<ParsedWebsite>> + use<>> {
-    let websites = get_websites_dirs(websites_path)?;
-    Ok(
-        websites.filter_map(|r|
-            r.and_then(|path|
-                get_document_and_selectors(&path)
-            ).transpose()
-        )
+    get_all_documents_and_selectors_with_options(websites_path, ParseOptions::default())
+}
+
+pub fn get_all_documents_and_selectors_with_options(
+    websites_path: &Path,
+    options: ParseOptions,
+) -> Result<impl Iterator<Item = Result<ParsedWebsite>> + use<>> {
+    let websites = select_websites_dirs(websites_path, options.selection)?;
+    Ok(get_documents_and_selectors_with_options(websites, options))
+}
+
+/// Parses each of `website_paths`, skipping paths that aren't websites.
+pub fn get_documents_and_selectors_with_options(
+    website_paths: impl Iterator<Item = Result<PathBuf>>,
+    options: ParseOptions,
+) -> impl Iterator<Item = Result<ParsedWebsite>> {
+    website_paths.filter_map(move |r|
+        r.and_then(|path|
+            get_document_and_selectors_with_options(&path, &options)
+        ).transpose()
     )
 }
 
+/// The name a website folder's results are keyed by: its folder name, with
+/// anything that isn't UTF-8 replaced by U+FFFD. Two folders whose names
+/// only differ there get the same name.
+pub fn website_name(website_path: &Path) -> String {
+    let name = website_path.file_name().unwrap_or(website_path.as_os_str());
+    if name.to_str().is_none() {
+        warn!("website folder {} is not valid UTF-8, calling it {}", website_path.display(), name.to_string_lossy());
+    }
+    name.to_string_lossy().into_owned()
+}
+
 pub fn get_document_and_selectors(
     website_path: &Path
 ) -> Result<Option<ParsedWebsite>> {
-    if !website_path.is_dir() {
-        warn!("ignoring {} because it is not a directory", website_path.display());
-        return Ok(None);
-    }
-    let document = match parse_website(website_path) {
-        Ok(Some(html)) => html,
-        Ok(None) =>  {
+    get_document_and_selectors_with_options(website_path, &ParseOptions::default())
+}
+
+pub fn get_document_and_selectors_with_options(
+    website_path: &Path,
+    options: &ParseOptions,
+) -> Result<Option<ParsedWebsite>> {
+    let source = FsSource::single(website_path);
+    let website = source.websites()?.swap_remove(0);
+    parse_website_from_source(&source, &website, options)
+}
+
+/// Parses `website` of `source`. Stylesheets are read through the source, so
+/// only a source with a `website_dir` gives them file URLs and source maps.
+pub fn parse_website_from_source(
+    source: &dyn CorpusSource,
+    website: &str,
+    options: &ParseOptions,
+) -> Result<Option<ParsedWebsite>> {
+    let website_dir = source.website_dir(website);
+    // Where errors and logs say things are
+    let website_path = website_dir.clone().unwrap_or_else(|| PathBuf::from(website));
+    let website_path = website_path.as_path();
+    let html = instrument::phase(Phase::Discovery, || source.open_document(website, options.main_file.as_deref()))?;
+    let mut document = match html {
+        Some(html) => instrument::phase(Phase::ParseHtml, || Html::parse_document(&html)),
+        None =>  {
             warn!("ignoring {}, no html file found", website_path.display());
             return Ok(None);
         },
-        Err(e) => return Err(e),
     };
-    let stylesheet_lock = SharedRwLock::new();
+    let excluded_template_elements = resolve_template_contents(&mut document, options.template_contents);
+    if excluded_template_elements > 0 {
+        info!("excluded {excluded_template_elements} elements inside <template>s from {}", website_path.display());
+    }
+    let script_report = apply_script_options(&mut document, options.scripts);
+    if !script_report.is_empty() {
+        info!(
+            "stripped {} scripts ({} bytes) and unwrapped {} <noscript>s ({} elements) in {}",
+            script_report.scripts_stripped,
+            script_report.script_bytes,
+            script_report.noscripts_unwrapped,
+            script_report.noscript_elements,
+            website_path.display(),
+        );
+    }
+    let visited_links = apply_visited_options(&mut document, &options.visited);
+    if visited_links > 0 {
+        info!("{visited_links} links match :visited in {}", website_path.display());
+    }
+    if let Some(fragment) = &options.fragment
+        && !apply_fragment(&mut document, fragment)
+    {
+        info!("no element in {} is the target of #{fragment}", website_path.display());
+    }
+    let read_stylesheet = |href: &Path| {
+        // A missing stylesheet is hashed as empty, like it is matched
+        source.open_stylesheet(website, &href.to_string_lossy()).map(String::into_bytes).unwrap_or_default()
+    };
+    if let Some(duplicate_documents) = &options.duplicate_documents
+        && let Some(original) = duplicate_documents.check(website, &document, options.stylesheet_links, read_stylesheet)
+    {
+        info!("skipping {}, it has the same document and stylesheets as {original}", website_path.display());
+        return Ok(None);
+    }
+    let html_errors = HtmlErrorReport::new(&document);
+    if html_errors.count > 0 {
+        warn!(
+            "{} has {} html parse errors, so its DOM may differ from a browser's. The first {}: {}",
+            website_path.display(),
+            html_errors.count,
+            html_errors.first.len(),
+            html_errors.first.join("; "),
+        );
+    }
+    let stylesheet_lock = options
+        .stylesheet_cache
+        .as_ref()
+        .map_or_else(SharedRwLock::new, |cache| cache.lock().clone());
+    // Returns the stylesheet, its errors and, if there are any, the CSS they
+    // are in
+    let parse = |css: &str, url_data: UrlExtraData| {
+        budget::stop_if_cancelled();
+        let css = device::resolve_media_features(css, &options.device.media_features);
+        let parse_css = |css: &str, url_data: UrlExtraData| match &options.stylesheet_cache {
+            Some(cache) => cache.get_or_parse(css, url_data),
+            None => parse_stylesheet_with_errors(css, url_data, &stylesheet_lock),
+        };
+        let (mut sheet, mut errors) = parse_css(&css, url_data.clone());
+        if options.forgiving_selector_lists && !errors.is_empty() {
+            let (recovered, dropped) = selector_recovery::recover_selector_lists(&css);
+            if !dropped.is_empty() {
+                warn!(
+                    "dropped {} invalid selectors from their selector lists in {}, keeping the rest: {}",
+                    dropped.len(),
+                    website_path.display(),
+                    dropped.iter().map(|error| error.to_string()).collect::<Vec<_>>().join("; "),
+                );
+                (sheet, errors) = parse_css(&recovered, url_data);
+                // Line numbers are for the original CSS, which is what
+                // reports show
+                errors.extend(dropped);
+            }
+        }
+        let source = (!errors.is_empty()).then(|| std::sync::Arc::from(&*css));
+        (sheet, errors, source)
+    };
+    // Problems with individual stylesheets don't stop the website from being
+    // parsed; they are kept for `ParsedWebsite::css_errors`
+    let css_errors = RefCell::new(Vec::new());
+    let keep_parse_errors = |name: String, path: PathBuf, (sheet, errors, source): (DocumentStyleSheet, Vec<CssParseError>, Option<std::sync::Arc<str>>), source_map: Option<PathBuf>, size: usize, kind: StylesheetKind| {
+        if let Some(source) = source {
+            let error = Error { path: Some(path), error: ErrorKind::CssParse { file: name.clone(), errors, source } };
+            #[cfg(feature = "miette")]
+            log::debug!("{:?}", crate::diagnostics::report(&error));
+            #[cfg(not(feature = "miette"))]
+            log::debug!("{error}");
+            css_errors.borrow_mut().push(error);
+        }
+        (name, sheet, source_map, size, kind)
+    };
+    let source_map_path = |css: &str, dir: &Path| {
+        source_map::source_mapping_url(css).and_then(|url| source_map::local_source_map_path(dir, url))
+    };
     let style_tag_selector = scraper::Selector::parse("style").unwrap();
     let style_tags = document.select(&style_tag_selector);
-    let stylesheets_from_style_tags = style_tags.filter_map(|elt| {
+    let stylesheets_from_style_tags = style_tags.enumerate().map(|(i, elt)| {
         let css = elt.text().collect::<String>();
-        match parse_stylesheet(
+        let parsed = parse(
             &css,
             UrlExtraData::from(url::Url::parse("about:blank").unwrap()),
-            &stylesheet_lock,
-        ) {
-            Ok(v) => Some(v),
-            Err(e) => {
-                warn!("error parsing a style tag from website {}: {}. Skipping.", website_path.display(), e);
-                None
-            }
-        }
+        );
+        let source_map = website_dir.as_ref().and_then(|dir| source_map_path(&css, dir));
+        keep_parse_errors(format!("<style> {}", i + 1), website_path.to_path_buf(), parsed, source_map, css.len(), StylesheetKind::Style)
     });
-    let stylesheet_paths: Vec<CssFile> = get_stylesheet_paths(&document);
+    let stylesheet_paths = get_stylesheet_paths(&document, options.stylesheet_links);
     let stylesheets_from_files = stylesheet_paths.into_iter()
-        .filter_map(|f| {
-            match parse_css_file(&website_path, &f, &stylesheet_lock) {
-                Ok(v) => Some(v),
+        .filter_map(|(f, kind)| {
+            match source.open_stylesheet(website, &f.0.to_string_lossy()) {
+                Ok(css) => {
+                    let url = website_dir
+                        .as_ref()
+                        .and_then(|dir| url::Url::from_file_path(dir.join(&f.0)).ok())
+                        .unwrap_or_else(|| url::Url::parse("about:blank").unwrap());
+                    let parsed = parse(&css, UrlExtraData::from(url));
+                    let path = website_path.join(&f.0);
+                    let source_map = website_dir
+                        .as_ref()
+                        .and_then(|_| source_map_path(&css, path.parent().unwrap_or(website_path)));
+                    Some(keep_parse_errors(f.0.display().to_string(), path, parsed, source_map, css.len(), kind))
+                }
                 Err(e) => {
-                    warn!("error parsing CSS file {}: {}. Skipping.", f.0.display(), e);
+                    warn!("error reading CSS file {}: {}. Skipping.", f.0.display(), e);
+                    css_errors.borrow_mut().push(e);
                     None
                 },
             }
         });
-    let stylesheets = stylesheets_from_style_tags.chain(stylesheets_from_files).collect();
-    let website_name = website_path
-        .file_name()
-        .and_then(OsStr::to_str)
-        .unwrap()
-        .to_owned();
-    Ok(Some(ParsedWebsite::new(
-        website_name,
+    let parsed_stylesheets: Vec<_> = instrument::phase(Phase::ParseCss, || {
+        stylesheets_from_style_tags.chain(stylesheets_from_files).collect()
+    });
+    let mut stylesheet_names = Vec::new();
+    let mut stylesheets = Vec::new();
+    let mut source_map_paths = Vec::new();
+    let mut stylesheet_sizes = Vec::new();
+    let mut stylesheet_kinds = Vec::new();
+    for (name, sheet, source_map, size, kind) in parsed_stylesheets {
+        stylesheet_names.push(name);
+        stylesheets.push(sheet);
+        source_map_paths.push(source_map);
+        stylesheet_sizes.push(Some(size));
+        stylesheet_kinds.push(Some(kind));
+    }
+    let website = ParsedWebsite::new(
+        website.to_owned(),
         document,
         stylesheets,
         stylesheet_lock,
-    )))
+    )
+    .with_stylesheet_names(stylesheet_names)
+    .with_source_map_paths(source_map_paths)
+    .with_stylesheet_sizes(stylesheet_sizes)
+    .with_stylesheet_kinds(stylesheet_kinds)
+    .with_device(options.device.clone())
+    .with_element_order(options.element_order)
+    .with_dedup_selectors(options.dedup_selectors)
+    .with_normalized_selectors(options.normalize_selectors)
+    .with_cascade_order(options.cascade_order)
+    .with_excluded_template_elements(excluded_template_elements)
+    .with_script_report(script_report)
+    .with_css_errors(css_errors.into_inner());
+    options.budget.check_selectors(&website, website_dir.as_deref())?;
+    #[cfg(feature = "ua_styles")]
+    let website = if options.include_ua_styles { website.with_ua_stylesheet() } else { website };
+    Ok(Some(website))
 }
 
 pub fn get_websites_dirs(websites_path: &Path) -> Result<impl Iterator<Item = Result<PathBuf>> + use<>> {
@@ -142,9 +1122,36 @@ pub fn get_websites_dirs(websites_path: &Path) -> Result<impl Iterator<Item = Re
     )
 }
 
-fn parse_website(website: &Path)-> Result<Option<Html>> {
-    let main = get_main_html(website)?;
-    main.map(parse_main_html).transpose()
+/// Like `get_websites_dirs`, but only the websites in `selection`. Selecting
+/// lists the whole directory up front; selecting everything doesn't.
+pub fn select_websites_dirs(
+    websites_path: &Path,
+    selection: WebsiteSelection,
+) -> Result<Box<dyn Iterator<Item = Result<PathBuf>>>> {
+    let websites = get_websites_dirs(websites_path)?;
+    if selection.is_all() {
+        return Ok(Box::new(websites));
+    }
+    let paths = websites.collect::<Result<Vec<_>>>()?;
+    Ok(Box::new(selection.select(paths).into_iter().map(Ok)))
+}
+
+/// Returns the html file `get_document_and_selectors_with_options` would use
+/// as the website's main document.
+pub fn main_html_file(website: &Path, options: &ParseOptions) -> Result<Option<HtmlFile>> {
+    main_html_file_with(website, options.main_file.as_deref())
+}
+
+/// Like `main_html_file`, with `main_file` in place of `ParseOptions::main_file`
+pub fn main_html_file_with(website: &Path, main_file: Option<&Path>) -> Result<Option<HtmlFile>> {
+    let main_file = match main_file {
+        Some(main_file) => Some(main_file.to_path_buf()),
+        None => WebsiteConfig::read(website)?.main_file,
+    };
+    match main_file {
+        Some(main_file) => Ok(Some(HtmlFile(website.join(main_file)))),
+        None => get_main_html(website),
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Hash)]
@@ -172,70 +1179,108 @@ fn get_main_html(website: &Path) -> Result<Option<HtmlFile>> {
     }
 }
 
-fn parse_main_html(HtmlFile(website): HtmlFile) -> Result<Html> {
-    let contents = fs::read_to_string(&website).into_result(Some(website))?;
-    Ok(Html::parse_document(&contents))
-}
-
-/// Returns the relative paths of stylesheets referenced by the given document.
-fn get_stylesheet_paths(document: &Html) -> Vec<CssFile> {
-    let selector = scraper::Selector::parse(r#"link[rel="stylesheet"]"#).unwrap();
+/// Returns the relative paths of stylesheets the given document links to,
+/// in document order, with how each is linked. A path linked more than once
+/// is only returned the first time.
+pub(crate) fn get_stylesheet_paths(document: &Html, links: StylesheetLinks) -> Vec<(CssFile, StylesheetKind)> {
+    let selector = scraper::Selector::parse("link[rel]").unwrap();
+    let mut seen = HashSet::new();
     document.select(&selector).filter_map(|elt| {
+        let rel = elt.attr("rel").unwrap_or_default().to_ascii_lowercase();
+        let has = |keyword: &str| rel.split_ascii_whitespace().any(|token| token == keyword);
+        let preload_style = elt.attr("as").is_some_and(|r#as| r#as.eq_ignore_ascii_case("style"));
+        let kind = match (has("stylesheet"), has("alternate"), has("preload")) {
+            (true, false, _) => StylesheetKind::Link,
+            (true, true, _) if links.alternate_stylesheets => StylesheetKind::Alternate,
+            (false, _, true) if preload_style && !links.no_preload_stylesheets => StylesheetKind::Preload,
+            _ => return None,
+        };
         let Some(path) = elt.attr("href") else {
             warn!("Found no href attribute in link element: {}. Skipping.", elt.html());
             return None;
         };
-        Some(CssFile(PathBuf::from(path)))
+        seen.insert(path).then(|| (CssFile(PathBuf::from(path)), kind))
     }).collect()
 }
 
-// TODO: returning iterator from these would probably be ideal.
-fn parse_css_file(
-    base: &Path,
-    CssFile(stylesheet_path): &CssFile,
+pub(crate) fn parse_stylesheet(
+    css: &str,
+    url_data: UrlExtraData,
     shared_lock: &SharedRwLock,
 ) -> Result<DocumentStyleSheet> {
-    let full_path = base.join(stylesheet_path);
-    let css = fs::read_to_string(&full_path).into_result(Some(full_path))?;
-    let url = url::Url::from_file_path(base.join(stylesheet_path))
-        .unwrap_or_else(|_| url::Url::parse("about:blank").unwrap());
-    parse_stylesheet(&css, UrlExtraData::from(url), shared_lock)
+    Ok(parse_stylesheet_with_errors(css, url_data, shared_lock).0)
 }
 
-pub(crate) fn parse_stylesheet(
+/// Parses a stylesheet, also returning what stylo dropped from it.
+pub(crate) fn parse_stylesheet_with_errors(
     css: &str,
     url_data: UrlExtraData,
     shared_lock: &SharedRwLock,
-) -> Result<DocumentStyleSheet> {
+) -> (DocumentStyleSheet, Vec<CssParseError>) {
+    parse_stylesheet_with_origin(css, url_data, Origin::Author, shared_lock)
+}
+
+pub(crate) fn parse_stylesheet_with_origin(
+    css: &str,
+    url_data: UrlExtraData,
+    origin: Origin,
+    shared_lock: &SharedRwLock,
+) -> (DocumentStyleSheet, Vec<CssParseError>) {
     let media = Arc::new(shared_lock.wrap(MediaList::empty()));
-    Ok(DocumentStyleSheet(Arc::new(Stylesheet::from_str(
+    let reporter = CollectingErrorReporter::default();
+    let stylesheet = DocumentStyleSheet(Arc::new(Stylesheet::from_str(
         css,
         url_data,
-        Origin::Author,
+        origin,
         media,
         shared_lock.clone(),
         None,
-        None,
+        Some(&reporter),
         QuirksMode::NoQuirks,
         AllowImportRules::No,
-    ))))
+    )));
+    (stylesheet, reporter.0.into_inner())
+}
+
+#[derive(Default)]
+struct CollectingErrorReporter(RefCell<Vec<CssParseError>>);
+
+impl ParseErrorReporter for CollectingErrorReporter {
+    fn report_error(&self, _url: &UrlExtraData, location: cssparser::SourceLocation, error: ContextualParseError) {
+        self.0.borrow_mut().push(CssParseError {
+            // stylo's lines are 0-based
+            line: location.line + 1,
+            column: location.column,
+            message: error.to_string(),
+        });
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::fmt::Write as _;
-    use std::{fs, path::PathBuf};
+    use std::{fs, path::{Path, PathBuf}};
     use crate::Selector;
+    use crate::corpus_source::{CorpusSource, FsSource};
     use crate::result::IntoResultExt;
-    use crate::parse::{CssFile, get_document_and_selectors, get_main_html, get_stylesheet_paths, parse_css_file, parse_main_html};
+    use crate::parse::{CssFile, ParseOptions, StylesheetCache, StylesheetCacheStats, WEBSITE_CONFIG_FILE, get_document_and_selectors, get_document_and_selectors_with_options, get_main_html, get_stylesheet_paths, parse_stylesheet, resolve_template_contents, select_websites_dirs, StylesheetKind, StylesheetLinks, TemplateContents, WebsiteSelection, apply_fragment, apply_script_options, apply_visited_options, DuplicateDocuments, ScriptOptions, ScriptReport, VisitedMode, VisitedOptions};
     use cssparser::ToCss as _;
     use style::shared_lock::SharedRwLock;
+    use style::stylesheets::UrlExtraData;
     use test_log::test;
 
     /// In all of these tests:
     ///   - Err() represents an unexpected error occurring during the test
     ///   - panic represents a test failure
 
+    /// The main document of the website at `website_path`, read the way
+    /// websites are when they are parsed
+    fn open_main_document(website_path: &Path) -> super::Result<scraper::Html> {
+        let source = FsSource::single(website_path);
+        let website = source.websites()?.swap_remove(0);
+        let html = source.open_document(&website, None)?.unwrap();
+        Ok(scraper::Html::parse_document(&html))
+    }
 
     #[test]
     fn ensures_main_html_exists() -> super::Result<()> {
@@ -276,8 +1321,9 @@ mod tests {
         let website_path = website_dir.path();
         fs::write(website_path.join("index.html"), "<html><body><h1>Hello, World!</h1></body></html>").into_result(Some(website_path.to_path_buf()))?;
         println!("{:?}", website_path);
-        let main_html = get_main_html(website_path)?.unwrap();
-        parse_main_html(main_html).unwrap();
+        let document = open_main_document(website_path)?;
+        let h1 = scraper::Selector::parse("h1").unwrap();
+        assert_eq!(document.select(&h1).count(), 1);
         Ok(())
     }
 
@@ -287,9 +1333,11 @@ mod tests {
         let website_path = website_dir.path();
         fs::write(website_path.join("index.html"), r#"<html><head><link rel="stylesheet" href="style1.css"><link rel="stylesheet" href="style2.css"></head><body><h1>Hello, World!</h1></body></html>"#)
             .into_result(Some(website_path.to_path_buf()))?;
-        let main_html = get_main_html(website_path)?.unwrap();
-        let document = parse_main_html(main_html)?;
-        let mut stylesheets = get_stylesheet_paths(&document);
+        let document = open_main_document(website_path)?;
+        let mut stylesheets: Vec<_> = get_stylesheet_paths(&document, Default::default())
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
         let mut expected: Vec<_> = vec!["style1.css", "style2.css"]
             .into_iter()
             .map(|s| CssFile(PathBuf::from(s)))
@@ -306,9 +1354,11 @@ mod tests {
         let website_path = website_dir.path();
         let index_html_path = website_path.join("index.html");
         fs::write(&index_html_path, r#"<html><head><link rel="stylesheet" href="style1.css"><link rel="stylesheet" href="style2.css"><link rel="prerender" href="boogeyman"></head><body><h1>Hello, World!</h1></body></html>"#).into_result(Some(index_html_path))?;
-        let main_html = get_main_html(website_path)?.unwrap();
-        let document = parse_main_html(main_html)?;
-        let mut stylesheets = get_stylesheet_paths(&document);
+        let document = open_main_document(website_path)?;
+        let mut stylesheets: Vec<_> = get_stylesheet_paths(&document, Default::default())
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
         let mut expected: Vec<_> = vec!["style1.css", "style2.css"]
             .into_iter()
             .map(|s| CssFile(PathBuf::from(s)))
@@ -319,12 +1369,46 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn finds_preload_and_alternate_stylesheets() {
+        let document = scraper::Html::parse_document(r#"
+            <link rel="preload" as="style" href="preloaded.css">
+            <link rel="preload" as="font" href="font.woff2">
+            <link rel="Alternate StyleSheet" href="alternate.css">
+            <link rel="stylesheet" href="preloaded.css">
+            <link rel="icon stylesheet" href="main.css">
+        "#);
+        let paths = |links| {
+            get_stylesheet_paths(&document, links)
+                .into_iter()
+                .map(|(CssFile(path), kind)| (path.display().to_string(), kind))
+                .collect::<Vec<_>>()
+        };
+        let strings = |paths: &[(&str, StylesheetKind)]| paths.iter().map(|&(path, kind)| (path.to_owned(), kind)).collect::<Vec<_>>();
+        assert_eq!(
+            paths(StylesheetLinks::default()),
+            strings(&[("preloaded.css", StylesheetKind::Preload), ("main.css", StylesheetKind::Link)]),
+        );
+        assert_eq!(
+            paths(StylesheetLinks { no_preload_stylesheets: true, alternate_stylesheets: true }),
+            strings(&[
+                ("alternate.css", StylesheetKind::Alternate),
+                ("preloaded.css", StylesheetKind::Link),
+                ("main.css", StylesheetKind::Link),
+            ]),
+        );
+    }
+
     #[test]
     fn parses_github_rust_scraper_css() -> super::Result<()> {
         let base = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        let css_path = CssFile(PathBuf::from("src/test_github_rust_scraper.css"));
+        let href = "src/test_github_rust_scraper.css";
+        let source = FsSource::single(&base);
+        let website = source.websites()?.swap_remove(0);
+        let css = source.open_stylesheet(&website, href)?;
+        let url = url::Url::from_file_path(base.join(href)).unwrap();
         let lock = SharedRwLock::new();
-        let stylesheet = parse_css_file(&base, &css_path, &lock)?;
+        let stylesheet = parse_stylesheet(&css, UrlExtraData::from(url), &lock)?;
         let context = crate::MatchingContext::new(
             std::iter::once(&stylesheet),
             lock,
@@ -361,4 +1445,292 @@ mod tests {
         assert!(selectors.iter().all(|selector| !selector.contains("&gt;")));
         Ok(())
     }
+
+    fn write_two_html_files(website_path: &std::path::Path) -> super::Result<()> {
+        for (name, heading) in [("index.html", "index"), ("other.html", "other")] {
+            let html_path = website_path.join(name);
+            fs::write(&html_path, format!("<html><body><h1>{heading}</h1></body></html>"))
+                .into_result(Some(html_path))?;
+        }
+        Ok(())
+    }
+
+    fn heading_text(website: &super::ParsedWebsite) -> String {
+        let selector = scraper::Selector::parse("h1").unwrap();
+        website.document().select(&selector).next().unwrap().text().collect()
+    }
+
+    #[test]
+    fn main_file_option_overrides_multiple_html_files() -> super::Result<()> {
+        let website_dir = tempfile::tempdir().into_result(None)?;
+        let website_path = website_dir.path();
+        write_two_html_files(website_path)?;
+        let options = ParseOptions {
+            main_file: Some(PathBuf::from("other.html")),
+            ..Default::default()
+        };
+        let website = get_document_and_selectors_with_options(website_path, &options)?
+            .expect("expected parsed website");
+        assert_eq!(heading_text(&website), "other");
+        Ok(())
+    }
+
+    #[test]
+    fn website_config_chooses_main_file() -> super::Result<()> {
+        let website_dir = tempfile::tempdir().into_result(None)?;
+        let website_path = website_dir.path();
+        write_two_html_files(website_path)?;
+        let config_path = website_path.join(WEBSITE_CONFIG_FILE);
+        fs::write(&config_path, "main_file = \"index.html\"\n").into_result(Some(config_path))?;
+        let website = get_document_and_selectors(website_path)?
+            .expect("expected parsed website");
+        assert_eq!(heading_text(&website), "index");
+
+        // the command-line option wins over the config file
+        let options = ParseOptions {
+            main_file: Some(PathBuf::from("other.html")),
+            ..Default::default()
+        };
+        let website = get_document_and_selectors_with_options(website_path, &options)?
+            .expect("expected parsed website");
+        assert_eq!(heading_text(&website), "other");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn names_non_utf8_websites_lossily() -> super::Result<()> {
+        use std::os::unix::ffi::OsStrExt as _;
+
+        let websites_dir = tempfile::tempdir().into_result(None)?;
+        let website_path = websites_dir.path().join(std::ffi::OsStr::from_bytes(b"caf\xe9"));
+        fs::create_dir(&website_path).into_result(Some(website_path.clone()))?;
+        fs::write(website_path.join("index.html"), "<p></p>").into_result(Some(website_path.clone()))?;
+        let website = get_document_and_selectors(&website_path)?.expect("expected parsed website");
+        assert_eq!(website.name, "caf\u{fffd}");
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_website_config() -> super::Result<()> {
+        let website_dir = tempfile::tempdir().into_result(None)?;
+        let website_path = website_dir.path();
+        write_two_html_files(website_path)?;
+        let config_path = website_path.join(WEBSITE_CONFIG_FILE);
+        fs::write(&config_path, "main_fiel = \"index.html\"\n").into_result(Some(config_path))?;
+        let res = get_document_and_selectors(website_path);
+        assert!(matches!(res, Err(super::Error { error: super::ErrorKind::InvalidConfig(_), .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn stylesheet_cache_parses_shared_css_once() -> super::Result<()> {
+        let websites_dir = tempfile::tempdir().into_result(None)?;
+        let shared_css = "div > p { color: red; }";
+        for (name, own_css) in [("a", ".a {}"), ("b", ".b {}")] {
+            let website_path = websites_dir.path().join(name);
+            fs::create_dir(&website_path).into_result(Some(website_path.clone()))?;
+            for (file, css) in [("shared.css", shared_css), ("own.css", own_css)] {
+                let css_path = website_path.join(file);
+                fs::write(&css_path, css).into_result(Some(css_path))?;
+            }
+            let html_path = website_path.join("index.html");
+            fs::write(&html_path, r#"<html><head><link rel="stylesheet" href="shared.css"><link rel="stylesheet" href="own.css"></head><body></body></html>"#)
+                .into_result(Some(html_path))?;
+        }
+        let cache = StylesheetCache::new();
+        let options = ParseOptions {
+            stylesheet_cache: Some(cache.clone()),
+            ..Default::default()
+        };
+        let a = get_document_and_selectors_with_options(&websites_dir.path().join("a"), &options)?.unwrap();
+        let b = get_document_and_selectors_with_options(&websites_dir.path().join("b"), &options)?.unwrap();
+        assert_eq!(cache.stats(), StylesheetCacheStats {
+            hits: 1,
+            misses: 3,
+            bytes_saved: shared_css.len(),
+        });
+        assert!(std::ptr::eq(&*a.stylesheets()[0].0, &*b.stylesheets()[0].0));
+        // Both websites can still be matched with their (shared) lock
+        let selectors: Vec<_> = b.get_matcher().get_selectors().iter().map(|s| s.to_css_string()).collect();
+        assert_eq!(selectors.len(), 2);
+        assert!(selectors.contains(&".b".to_owned()));
+        assert_eq!(a.get_matcher().get_selectors().len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn selects_websites() -> super::Result<()> {
+        let websites_dir = tempfile::tempdir().into_result(None)?;
+        for i in 0..10 {
+            let website_path = websites_dir.path().join(format!("site{i}"));
+            fs::create_dir(&website_path).into_result(Some(website_path))?;
+        }
+        let select = |selection: WebsiteSelection| -> super::Result<Vec<String>> {
+            select_websites_dirs(websites_dir.path(), selection)?
+                .map(|path| path.map(|path| path.file_name().unwrap().to_str().unwrap().to_owned()))
+                .collect()
+        };
+        assert_eq!(select(WebsiteSelection::default())?.len(), 10);
+        let limited = select(WebsiteSelection { limit: Some(3), ..Default::default() })?;
+        assert_eq!(limited, ["site0", "site1", "site2"]);
+
+        let sampling = WebsiteSelection { sample: Some(4), seed: 7, ..Default::default() };
+        let sampled = select(sampling)?;
+        assert_eq!(sampled.len(), 4);
+        assert!(sampled.is_sorted());
+        // the same seed picks the same websites
+        assert_eq!(select(sampling)?, sampled);
+        let both = select(WebsiteSelection { limit: Some(2), ..sampling })?;
+        assert_eq!(both, sampled[..2]);
+        assert_eq!(select(WebsiteSelection { sample: Some(20), ..Default::default() })?.len(), 10);
+        Ok(())
+    }
+
+    #[test]
+    fn resolves_template_contents() {
+        let html = "<template><p><template><b></b></template></p><i></i></template><div></div>";
+        let names = |document: &scraper::Html| -> Vec<String> {
+            document.root_element().descendent_elements().map(|e| e.value().name().to_owned()).collect()
+        };
+        let mut excluded = scraper::Html::parse_document(html);
+        assert_eq!(resolve_template_contents(&mut excluded, TemplateContents::Exclude), 4);
+        assert_eq!(names(&excluded), ["head", "template", "body", "div"]);
+
+        let mut included = scraper::Html::parse_document(html);
+        assert_eq!(resolve_template_contents(&mut included, TemplateContents::Include), 0);
+        assert_eq!(names(&included), ["head", "template", "p", "template", "b", "i", "body", "div"]);
+        let template = included.root_element().descendent_elements().find(|e| e.value().name() == "template").unwrap();
+        assert_eq!(template.child_elements().count(), 2);
+    }
+
+    #[test]
+    fn applies_script_options() {
+        let html = "<p>a</p><script>let x = 1;</script><noscript><style>p {}</style><div class='x'><i></i></div></noscript>";
+        let names = |document: &scraper::Html| -> Vec<String> {
+            document.root_element().descendent_elements().map(|e| e.value().name().to_owned()).collect()
+        };
+        let mut untouched = scraper::Html::parse_document(html);
+        assert!(apply_script_options(&mut untouched, ScriptOptions::default()).is_empty());
+        assert_eq!(names(&untouched), ["head", "body", "p", "script", "noscript"]);
+
+        let mut document = scraper::Html::parse_document(html);
+        let report = apply_script_options(&mut document, ScriptOptions { strip_scripts: true, unwrap_noscript: true });
+        assert_eq!(report, ScriptReport { scripts_stripped: 1, script_bytes: 10, noscripts_unwrapped: 1, noscript_elements: 3 });
+        assert_eq!(names(&document), ["head", "body", "p", "script", "noscript", "style", "div", "i"]);
+        let script = document.root_element().descendent_elements().find(|e| e.value().name() == "script").unwrap();
+        assert_eq!(script.text().count(), 0);
+    }
+
+    #[test]
+    fn applies_visited_options() {
+        let html = "<base href='https://example.com/'><a href='/seen'></a><a href='https://example.com/new'></a><a></a>";
+        let selectors = crate::parse_selector_list(":link, :visited").unwrap();
+        // Per link (the `<a>`s in order), whether it matches `:link` and `:visited`
+        let link_matches = |options: VisitedOptions| -> Vec<(bool, bool)> {
+            let mut document = scraper::Html::parse_document(html);
+            apply_visited_options(&mut document, &options);
+            crate::match_selectors(&document, selectors.slice())
+                .0
+                .iter()
+                .filter(|element_matches| element_matches.element.value().name() == "a")
+                .map(|element_matches| match &element_matches.selectors {
+                    crate::structs::borrowed::SelectorsOrSharedStyles::Selectors(matched) => {
+                        let matched: Vec<_> = matched.iter().map(|s| s.to_css_string()).collect();
+                        (matched.contains(&":link".to_owned()), matched.contains(&":visited".to_owned()))
+                    }
+                    _ => unreachable!(),
+                })
+                .collect()
+        };
+        let visited_urls = vec!["https://example.com/seen".to_owned()];
+        assert_eq!(
+            link_matches(VisitedOptions { visited_mode: VisitedMode::AllLinksUnvisited, visited_urls: visited_urls.clone() }),
+            [(true, false), (true, false), (false, false)],
+        );
+        assert_eq!(
+            link_matches(VisitedOptions { visited_mode: VisitedMode::RelevantLinkVisited, visited_urls: visited_urls.clone() }),
+            [(false, true), (true, false), (false, false)],
+        );
+        assert_eq!(
+            link_matches(VisitedOptions { visited_mode: VisitedMode::AllLinksVisitedAndUnvisited, visited_urls }),
+            [(true, true), (true, true), (false, false)],
+        );
+    }
+
+    #[test]
+    fn applies_fragment() {
+        let target = |html: &str, fragment: &str| -> Option<String> {
+            let mut document = scraper::Html::parse_document(html);
+            apply_fragment(&mut document, fragment);
+            let selectors = crate::parse_selector_list(":target").unwrap();
+            crate::match_selectors(&document, selectors.slice())
+                .0
+                .iter()
+                .find(|element_matches| match &element_matches.selectors {
+                    crate::structs::borrowed::SelectorsOrSharedStyles::Selectors(matched) => !matched.is_empty(),
+                    _ => unreachable!(),
+                })
+                .map(|element_matches| element_matches.element.value().name().to_owned())
+        };
+        assert_eq!(target("<a name='x'></a><p id='x'></p>", "#x").as_deref(), Some("p"));
+        assert_eq!(target("<a name='x'></a><p></p>", "x").as_deref(), Some("a"));
+        assert_eq!(target("<p id='caf\u{e9}'></p>", "caf%C3%A9").as_deref(), Some("p"));
+        assert_eq!(target("<p id='x'></p>", "y"), None);
+        assert_eq!(target("<p id='x'></p>", "#"), None);
+    }
+
+    #[test]
+    fn skips_duplicate_documents() -> super::Result<()> {
+        let websites_dir = tempfile::tempdir().into_result(None)?;
+        let write = |name: &str, html: &str, css: &str| -> super::Result<()> {
+            let dir = websites_dir.path().join(name);
+            fs::create_dir(&dir).into_result(Some(dir.clone()))?;
+            fs::write(dir.join("index.html"), html).into_result(Some(dir.join("index.html")))?;
+            fs::write(dir.join("a.css"), css).into_result(Some(dir.join("a.css")))
+        };
+        let html = r#"<html><head><link rel="stylesheet" href="a.css"></head><body><p class=x>hi</p></body></html>"#;
+        write("a", html, "p {}")?;
+        // The same DOM, written differently
+        write("b", r#"<link rel="stylesheet" href="a.css"><p class="x">hi"#, "p {}")?;
+        // Other styles
+        write("c", html, "div {}")?;
+        let duplicate_documents = DuplicateDocuments::new();
+        let options = ParseOptions {
+            duplicate_documents: Some(duplicate_documents.clone()),
+            ..Default::default()
+        };
+        let parsed: Vec<_> = ["a", "b", "c"]
+            .into_iter()
+            .map(|name| get_document_and_selectors_with_options(&websites_dir.path().join(name), &options))
+            .collect::<super::Result<_>>()?;
+        assert_eq!(parsed.iter().map(Option::is_some).collect::<Vec<_>>(), [true, false, true]);
+        assert_eq!(duplicate_documents.duplicates(), [("b".to_owned(), "a".to_owned())]);
+        let stats = duplicate_documents.stats();
+        assert_eq!((stats.unique, stats.duplicates), (2, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_css_errors() -> super::Result<()> {
+        let website_dir = tempfile::tempdir().into_result(None)?;
+        let website_path = website_dir.path();
+        let css_path = website_path.join("broken.css");
+        fs::write(&css_path, "p { color: red; }\ndiv { colr: blue; }\n").into_result(Some(css_path))?;
+        let index_html_path = website_path.join("index.html");
+        fs::write(
+            &index_html_path,
+            r#"<html><head><link rel="stylesheet" href="broken.css"><link rel="stylesheet" href="missing.css"></head><body></body></html>"#,
+        ).into_result(Some(index_html_path))?;
+        let website = get_document_and_selectors(website_path)?.unwrap();
+        // The broken stylesheet is still used
+        assert_eq!(website.stylesheets().len(), 1);
+        let errors = website.css_errors();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].is_css_parse_and(|file, errors| {
+            file == "broken.css" && errors.len() == 1 && errors[0].line == 2
+        }));
+        assert!(errors[1].is_io_and(|e| e.kind() == std::io::ErrorKind::NotFound));
+        Ok(())
+    }
 }
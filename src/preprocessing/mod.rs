@@ -7,7 +7,7 @@ use crate::structs::Selector;
 pub mod concretize;
 pub mod distribute;
 
-fn selector_from_iter(components: impl Iterator<Item = Component<SelectorImpl>>) -> Selector {
+pub(crate) fn selector_from_iter(components: impl Iterator<Item = Component<SelectorImpl>>) -> Selector {
     let mut builder = SelectorBuilder::default();
     for component in components {
         if let Some(combinator) = component.as_combinator() {
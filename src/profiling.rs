@@ -0,0 +1,98 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Flamegraphs of matching, with the `profiling` feature.
+//!
+//! After `write_flamegraphs_to(dir)`, every `do_website` samples its matching
+//! with pprof and writes `{website}.{algorithm}.svg` into `dir`. Without the
+//! feature, or before it is enabled, matching runs unprofiled.
+
+use crate::Algorithm;
+
+#[cfg(feature = "profiling")]
+static OUTPUT_DIR: std::sync::OnceLock<std::path::PathBuf> = std::sync::OnceLock::new();
+
+/// Samples per second. High enough to see selector-level detail on small
+/// websites.
+#[cfg(feature = "profiling")]
+const FREQUENCY: i32 = 1000;
+
+/// Starts profiling every matching run in this process. Can only be called
+/// once.
+#[cfg(feature = "profiling")]
+pub fn write_flamegraphs_to(dir: std::path::PathBuf) -> crate::result::Result<()> {
+    use crate::result::{Error, IntoResultExt as _};
+
+    std::fs::create_dir_all(&dir).into_result(Some(dir.clone()))?;
+    OUTPUT_DIR
+        .set(dir)
+        .map_err(|_| Error::other("flamegraphs are already being written".to_owned()))
+}
+
+#[cfg(feature = "profiling")]
+pub(crate) fn profile<T>(website: &str, algorithm: Algorithm, f: impl FnOnce() -> T) -> T {
+    match OUTPUT_DIR.get() {
+        Some(dir) => profile_to(dir, website, algorithm, f),
+        None => f(),
+    }
+}
+
+#[cfg(not(feature = "profiling"))]
+#[inline(always)]
+pub(crate) fn profile<T>(_website: &str, _algorithm: Algorithm, f: impl FnOnce() -> T) -> T {
+    f()
+}
+
+/// Profiling is best-effort: if the profiler can't start or the flamegraph
+/// can't be written, `f` still runs and the failure is only logged.
+#[cfg(feature = "profiling")]
+fn profile_to<T>(dir: &std::path::Path, website: &str, algorithm: Algorithm, f: impl FnOnce() -> T) -> T {
+    use log::warn;
+
+    let guard = match pprof::ProfilerGuardBuilder::default()
+        .frequency(FREQUENCY)
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()
+    {
+        Ok(guard) => guard,
+        Err(e) => {
+            warn!("could not start profiling {website} with {algorithm}: {e}");
+            return f();
+        }
+    };
+    let result = f();
+    let path = dir.join(format!("{website}.{algorithm}.svg"));
+    let written = guard
+        .report()
+        .build()
+        .map_err(|e| e.to_string())
+        .and_then(|report| {
+            let file = std::fs::File::create(&path).map_err(|e| e.to_string())?;
+            report.flamegraph(file).map_err(|e| e.to_string())
+        });
+    if let Err(e) = written {
+        warn!("could not write flamegraph {}: {e}", path.display());
+    }
+    result
+}
+
+#[cfg(all(test, feature = "profiling"))]
+mod tests {
+    use super::profile_to;
+    use crate::Algorithm;
+    use test_log::test;
+
+    #[test]
+    fn writes_a_flamegraph_per_website_and_algorithm() {
+        let dir = tempfile::tempdir().unwrap();
+        let sum = profile_to(dir.path(), "example", Algorithm::Naive, || {
+            (0..10_000_000u64).fold(0u64, |acc, i| acc.wrapping_add(i * i))
+        });
+        assert_ne!(sum, 0);
+        let svg = std::fs::read_to_string(dir.path().join(format!("example.{}.svg", Algorithm::Naive))).unwrap();
+        assert!(svg.contains("<svg"));
+    }
+}
@@ -0,0 +1,130 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Python bindings, with the `pyo3` feature. `maturin develop` (see
+//! `pyproject.toml`) builds and installs them as the `mach_6` module:
+//!
+//! ```python
+//! import mach_6
+//! result = mach_6.match_strings("<p class=a></p>", {"a.css": ".a {}"}, algorithm="naive")
+//! result["stats"]["matched_pairs"]
+//! ```
+//!
+//! Results are plain dicts and lists, shaped like the YAML the command line
+//! writes: matches are `SerDocumentMatches` and stats are `DocumentStats`.
+//! Algorithms are named as on the command line.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use clap::ValueEnum as _;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use serde::Serialize;
+
+use crate::Algorithm;
+use crate::analysis::corpus::CorpusStats;
+use crate::parse::{self, ParseOptions};
+use crate::structs::{ser::SerDocumentMatches, set::SetDocumentMatches, stats::DocumentStats};
+
+#[derive(Serialize)]
+struct PyMatches {
+    matches: SerDocumentMatches,
+    stats: DocumentStats,
+}
+
+impl From<&SetDocumentMatches> for PyMatches {
+    fn from(matches: &SetDocumentMatches) -> Self {
+        Self { matches: matches.into(), stats: matches.into() }
+    }
+}
+
+fn algorithm(name: &str) -> PyResult<Algorithm> {
+    Algorithm::from_str(name, true).map_err(PyValueError::new_err)
+}
+
+fn runtime_error(e: crate::result::Error) -> PyErr {
+    PyRuntimeError::new_err(e.to_string())
+}
+
+fn to_python(py: Python<'_>, value: &impl Serialize) -> PyResult<PyObject> {
+    let value = serde_json::to_value(value).map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+    json_to_python(py, &value)
+}
+
+fn json_to_python(py: Python<'_>, value: &serde_json::Value) -> PyResult<PyObject> {
+    use serde_json::Value;
+    Ok(match value {
+        Value::Null => py.None(),
+        Value::Bool(b) => b.into_py(py),
+        Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => i.into_py(py),
+            (None, Some(u)) => u.into_py(py),
+            (None, None) => n.as_f64().into_py(py),
+        },
+        Value::String(s) => s.into_py(py),
+        Value::Array(values) => {
+            let values = values.iter().map(|value| json_to_python(py, value)).collect::<PyResult<Vec<_>>>()?;
+            PyList::new_bound(py, values).into_py(py)
+        }
+        Value::Object(map) => {
+            let dict = PyDict::new_bound(py);
+            for (key, value) in map {
+                dict.set_item(key, json_to_python(py, value)?)?;
+            }
+            dict.into_py(py)
+        }
+    })
+}
+
+/// Matches a document's stylesheets against it, with `stylesheets` as the
+/// files its `<link>`s refer to, by href. See `crate::match_in_memory`.
+#[pyfunction]
+#[pyo3(signature = (html, stylesheets = HashMap::new(), algorithm = "naive"))]
+fn match_strings(py: Python<'_>, html: &str, stylesheets: HashMap<String, String>, algorithm: &str) -> PyResult<PyObject> {
+    let algorithm = self::algorithm(algorithm)?;
+    let files: Vec<_> = stylesheets.iter().map(|(href, css)| (href.as_str(), css.as_str())).collect();
+    let matches = crate::match_in_memory(html, &files, algorithm, &ParseOptions::default()).map_err(runtime_error)?;
+    to_python(py, &PyMatches::from(&matches))
+}
+
+/// Matches every website in the websites directory `websites`, returning
+/// each website's matches and stats by name. Stops at the first website that
+/// fails.
+#[pyfunction]
+#[pyo3(signature = (websites, algorithm = "naive"))]
+fn do_all_websites(py: Python<'_>, websites: PathBuf, algorithm: &str) -> PyResult<PyObject> {
+    let algorithm = self::algorithm(algorithm)?;
+    let results = py.allow_threads(|| {
+        crate::do_all_websites(&websites, algorithm)?
+            .map(|result| result.map(|(name, matches, _)| (name, PyMatches::from(&matches))))
+            .collect::<crate::result::Result<Vec<_>>>()
+    });
+    let dict = PyDict::new_bound(py);
+    for (name, matches) in results.map_err(runtime_error)? {
+        dict.set_item(name, to_python(py, &matches)?)?;
+    }
+    Ok(dict.into_py(py))
+}
+
+/// The `corpus-stats` of the websites directory `websites`, as a dict
+#[pyfunction]
+fn corpus_stats(py: Python<'_>, websites: PathBuf) -> PyResult<PyObject> {
+    let stats = py.allow_threads(|| {
+        let options = ParseOptions::default();
+        CorpusStats::new(parse::get_websites_dirs(&websites)?, &options)
+    });
+    to_python(py, &stats.map_err(runtime_error)?)
+}
+
+#[pymodule]
+fn mach_6(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(match_strings, module)?)?;
+    module.add_function(wrap_pyfunction!(do_all_websites, module)?)?;
+    module.add_function(wrap_pyfunction!(corpus_stats, module)?)?;
+    Ok(())
+}
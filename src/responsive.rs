@@ -0,0 +1,297 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Matching a website at several viewport widths, to see which results
+//! depend on media queries. The document and stylesheets are parsed once;
+//! only the selector maps are rebuilt for each width.
+//!
+//! The same goes for the light and dark color schemes: stylo evaluates
+//! `prefers-color-scheme` itself, so `match_color_schemes` can match the
+//! same parse twice and keep only what differs, to audit a website's
+//! theming.
+//!
+//! `media_breakpoints` finds the widths worth matching at: those the
+//! website's `@media` conditions compare the viewport width with.
+
+use std::collections::BTreeSet;
+
+use cssparser::{ParseError, Parser, ParserInput, Token};
+use serde::Serialize;
+use style::shared_lock::ToCssWithGuard as _;
+use style::stylesheets::{AllRules, CssRule, StylesheetInDocument as _};
+
+use crate::device::{ColorScheme, DeviceOptions, MediaFeatures, Viewport};
+use crate::parse::ParsedWebsite;
+use crate::structs::diff::SelectorsDiff;
+use crate::structs::set::SetDocumentMatches;
+use crate::{Algorithm, do_website};
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Breakpoint {
+    pub width: f32,
+    /// Selectors that matched at least one element
+    pub matched_selectors: usize,
+    /// Selectors that match somewhere at this width but nowhere at the
+    /// previous one. Empty for the first breakpoint.
+    pub selectors_added: BTreeSet<String>,
+    /// Selectors that matched somewhere at the previous width but nowhere at
+    /// this one
+    pub selectors_removed: BTreeSet<String>,
+    /// Elements whose matched selectors differ from the previous width
+    pub elements_changed: usize,
+}
+
+/// Matches `website` with `algorithm` at each of `widths`, narrowest first,
+/// keeping the rest of the website's device. The website is left with its
+/// original device.
+pub fn match_at_breakpoints(website: &mut ParsedWebsite, algorithm: Algorithm, widths: &[f32]) -> Vec<Breakpoint> {
+    let original = website.device().clone();
+    let mut widths = widths.to_vec();
+    widths.sort_by(f32::total_cmp);
+    widths.dedup();
+    let mut breakpoints = Vec::with_capacity(widths.len());
+    let mut previous: Option<(SetDocumentMatches, BTreeSet<String>)> = None;
+    for width in widths {
+        website.set_device(DeviceOptions {
+            viewport: Viewport { width, ..original.viewport },
+            ..original.clone()
+        });
+        let (_, matches, _) = do_website(website, algorithm, None);
+        let selectors = matched_selectors(&matches);
+        let (selectors_added, selectors_removed, elements_changed) = match &previous {
+            Some((previous_matches, previous_selectors)) => (
+                selectors.difference(previous_selectors).cloned().collect(),
+                previous_selectors.difference(&selectors).cloned().collect(),
+                previous_matches.diff(&matches).selectors_differ.len(),
+            ),
+            None => Default::default(),
+        };
+        breakpoints.push(Breakpoint {
+            width,
+            matched_selectors: selectors.len(),
+            selectors_added,
+            selectors_removed,
+            elements_changed,
+        });
+        previous = Some((matches, selectors));
+    }
+    website.set_device(original);
+    breakpoints
+}
+
+/// How a website's matches differ between `prefers-color-scheme: light`
+/// (the left side of each `SelectorsDiff`) and `dark`
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ColorSchemeDiff {
+    /// Selectors that match somewhere in the light scheme but nowhere in the
+    /// dark one
+    pub light_only_selectors: BTreeSet<String>,
+    pub dark_only_selectors: BTreeSet<String>,
+    /// The elements whose matched selectors differ, in document order
+    pub elements: Vec<SelectorsDiff>,
+}
+
+impl ColorSchemeDiff {
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+}
+
+/// Matches `website` with `algorithm` in the light and the dark color
+/// scheme, keeping the rest of the website's device. The website is left
+/// with its original device.
+pub fn match_color_schemes(website: &mut ParsedWebsite, algorithm: Algorithm) -> ColorSchemeDiff {
+    let original = website.device().clone();
+    let mut match_in = |prefers_color_scheme| {
+        website.set_device(DeviceOptions {
+            media_features: MediaFeatures { prefers_color_scheme, ..original.media_features },
+            ..original.clone()
+        });
+        do_website(website, algorithm, None).1
+    };
+    let light = match_in(ColorScheme::Light);
+    let dark = match_in(ColorScheme::Dark);
+    website.set_device(original);
+    let (light_selectors, dark_selectors) = (matched_selectors(&light), matched_selectors(&dark));
+    let mut elements = light.diff(&dark).selectors_differ;
+    elements.sort_by(|a, b| a.element.cmp(&b.element));
+    ColorSchemeDiff {
+        light_only_selectors: light_selectors.difference(&dark_selectors).cloned().collect(),
+        dark_only_selectors: dark_selectors.difference(&light_selectors).cloned().collect(),
+        elements,
+    }
+}
+
+/// CSS pixels per `em` or `rem` in media queries, the initial font size
+const EM: f32 = 16.0;
+
+/// The distinct viewport widths, in CSS pixels and narrowest first, that the
+/// conditions of `website`'s `@media` rules compare with `width`, `min-width`
+/// or `max-width`. Rules in `@media` rules that don't apply count too.
+pub fn media_breakpoints(website: &ParsedWebsite) -> Vec<f32> {
+    // `AllRules` doesn't evaluate media queries, so the device only has to
+    // be there
+    let device = website.device().stylo_device();
+    let guard = website.stylesheet_lock().read();
+    let mut widths = Vec::new();
+    for stylesheet in website.stylesheets() {
+        for rule in stylesheet.iter_rules::<AllRules>(&device, &guard) {
+            if let CssRule::Media(media_rule) = rule {
+                // Stylo's parsed media features don't expose their values, so
+                // the prelude is tokenized back from the rule's serialization
+                let css = media_rule.to_css_string(&guard);
+                let mut input = ParserInput::new(&css);
+                let mut parser = Parser::new(&mut input);
+                if let Ok(Token::AtKeyword(_)) = parser.next() {
+                    media_widths(&mut parser, &mut widths);
+                }
+            }
+        }
+    }
+    widths.sort_by(f32::total_cmp);
+    widths.dedup();
+    widths
+}
+
+/// Adds the widths of the width features of the media query list `parser` is
+/// at, up to the rule's block, to `widths`
+fn media_widths(parser: &mut Parser, widths: &mut Vec<f32>) {
+    loop {
+        match parser.next().cloned() {
+            Ok(Token::ParenthesisBlock) => {
+                let _ = parser.parse_nested_block(|block| {
+                    condition_widths(block, widths);
+                    Ok::<_, ParseError<()>>(())
+                });
+            }
+            Ok(Token::CurlyBracketBlock) | Err(_) => return,
+            Ok(_) => (),
+        }
+    }
+}
+
+/// Adds the widths of the parenthesized condition `parser` is inside to
+/// `widths`: those it compares with if it's a width feature, as in
+/// `min-width: 40em` or `400px < width <= 800px`, or else those of the
+/// conditions nested in it
+fn condition_widths(parser: &mut Parser, widths: &mut Vec<f32>) {
+    let mut width_feature = false;
+    let mut lengths = Vec::new();
+    loop {
+        match parser.next().cloned() {
+            Ok(Token::ParenthesisBlock) => {
+                let _ = parser.parse_nested_block(|block| {
+                    condition_widths(block, widths);
+                    Ok::<_, ParseError<()>>(())
+                });
+            }
+            Ok(Token::Ident(name)) => {
+                let name = name.to_ascii_lowercase();
+                width_feature |= matches!(name.as_str(), "width" | "min-width" | "max-width");
+            }
+            Ok(Token::Dimension { value, unit, .. }) => lengths.extend(length(value, &unit)),
+            Ok(_) => (),
+            Err(_) => break,
+        }
+    }
+    if width_feature {
+        widths.extend(lengths);
+    }
+}
+
+/// A positive length in an absolute unit, `em` or `rem`, in CSS pixels
+fn length(value: f32, unit: &str) -> Option<f32> {
+    let pixels_per_unit = match unit.to_ascii_lowercase().as_str() {
+        "px" => 1.0,
+        "em" | "rem" => EM,
+        "in" => 96.0,
+        "cm" => 96.0 / 2.54,
+        "mm" => 96.0 / 25.4,
+        "q" => 96.0 / 101.6,
+        "pt" => 96.0 / 72.0,
+        "pc" => 16.0,
+        _ => return None,
+    };
+    let pixels = value * pixels_per_unit;
+    // `resolve_media_features` writes `(min-width: 0px)` for conditions
+    // that are always true
+    (pixels.is_finite() && pixels > 0.0).then_some(pixels)
+}
+
+fn matched_selectors(matches: &SetDocumentMatches) -> BTreeSet<String> {
+    matches
+        .0
+        .keys()
+        .flat_map(|&id| matches.find_selectors(id))
+        .map(|selector| selector.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{match_at_breakpoints, match_color_schemes, media_breakpoints};
+    use crate::device::ColorScheme;
+    use crate::Algorithm;
+    use crate::parse::{ParsedWebsite, parse_stylesheet};
+    use scraper::Html;
+    use style::shared_lock::SharedRwLock;
+    use style::stylesheets::UrlExtraData;
+    use test_log::test;
+
+    #[test]
+    fn reports_changes_between_widths() {
+        let lock = SharedRwLock::new();
+        let css = "p {} @media (min-width: 700px) { .wide {} } @media (max-width: 400px) { .narrow {} }";
+        let url_data = UrlExtraData::from(url::Url::parse("about:blank").unwrap());
+        let stylesheet = parse_stylesheet(css, url_data, &lock).unwrap();
+        let document = Html::parse_document(r#"<p class="wide narrow"></p><p class="wide"></p>"#);
+        let mut website = ParsedWebsite::new("test".to_owned(), document, vec![stylesheet], lock);
+        let breakpoints = match_at_breakpoints(&mut website, Algorithm::WithStyleSharing, &[1200.0, 360.0, 768.0]);
+        let widths: Vec<_> = breakpoints.iter().map(|b| b.width).collect();
+        assert_eq!(widths, [360.0, 768.0, 1200.0]);
+        assert_eq!(breakpoints[0].matched_selectors, 2);
+        assert!(breakpoints[0].selectors_added.is_empty());
+        assert_eq!(breakpoints[1].selectors_added, [".wide".to_owned()].into());
+        assert_eq!(breakpoints[1].selectors_removed, [".narrow".to_owned()].into());
+        assert_eq!(breakpoints[1].elements_changed, 2);
+        assert_eq!(breakpoints[2].elements_changed, 0);
+        assert_eq!(website.device().viewport.width, 1200.0);
+    }
+
+    #[test]
+    fn reports_differences_between_color_schemes() {
+        let lock = SharedRwLock::new();
+        let css = "p {} @media (prefers-color-scheme: dark) { .themed, .unused {} } \
+                   @media (prefers-color-scheme: light) { .light {} }";
+        let url_data = UrlExtraData::from(url::Url::parse("about:blank").unwrap());
+        let stylesheet = parse_stylesheet(css, url_data, &lock).unwrap();
+        let document = Html::parse_document(r#"<p class="themed"></p><p></p><p class="light"></p>"#);
+        let mut website = ParsedWebsite::new("test".to_owned(), document, vec![stylesheet], lock);
+        let diff = match_color_schemes(&mut website, Algorithm::WithStyleSharing);
+        assert_eq!(diff.light_only_selectors, [".light".to_owned()].into());
+        assert_eq!(diff.dark_only_selectors, [".themed".to_owned()].into());
+        let elements: Vec<_> = diff.elements.iter().map(|element| element.element.to_string()).collect();
+        assert_eq!(elements, ["html[0]/body[1]/p[0]", "html[0]/body[1]/p[2]"]);
+        assert_eq!(diff.elements[0].only_in_right, [".themed".to_owned()].into());
+        assert!(diff.elements[0].only_in_left.is_empty());
+        assert_eq!(website.device().media_features.prefers_color_scheme, ColorScheme::Light);
+    }
+
+    #[test]
+    fn extracts_breakpoints_from_media_queries() {
+        let lock = SharedRwLock::new();
+        let css = "@media (min-width: 768px) { a {} } \
+                   @media screen and (max-width: 40em), print { @media (400px < width <= 1200px) { b {} } } \
+                   @media (min-width: 768px) and (orientation: landscape) { c {} } \
+                   @media (hover: hover) and (min-resolution: 2dppx) { d {} } \
+                   @media not ((min-width: 1e3px) or (max-width: 0.5in)) { e {} }";
+        let url_data = UrlExtraData::from(url::Url::parse("about:blank").unwrap());
+        let css = crate::device::resolve_media_features(css, &Default::default());
+        let stylesheet = parse_stylesheet(&css, url_data, &lock).unwrap();
+        let website = ParsedWebsite::new("test".to_owned(), Html::parse_document(""), vec![stylesheet], lock);
+        assert_eq!(media_breakpoints(&website), [48.0, 400.0, 640.0, 768.0, 1000.0, 1200.0]);
+    }
+}
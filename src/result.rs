@@ -5,6 +5,8 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 use crate::parse::HtmlFile;
+use derive_more::Display;
+use serde::Serialize;
 use std::path::PathBuf;
 use std::io;
 use thiserror::Error;
@@ -19,7 +21,44 @@ pub struct Error {
 pub enum ErrorKind {
     Io(io::Error),
     MultipleHtmlFiles(Vec<HtmlFile>),
+    InvalidConfig(toml::de::Error),
+    /// Rules or declarations a stylesheet had that were dropped because they
+    /// didn't parse. The rest of the stylesheet is still used.
+    CssParse {
+        /// The stylesheet's name, as in `ParsedWebsite::stylesheet_names`
+        file: String,
+        errors: Vec<CssParseError>,
+        /// The CSS the errors are in, for showing them in context
+        source: std::sync::Arc<str>,
+    },
+    /// A selector given on the command line or to `parse_selector_list`
+    /// that didn't parse
+    InvalidSelector {
+        selector: String,
+        /// 1-based
+        line: u32,
+        /// 1-based
+        column: u32,
+        message: String,
+    },
+    #[cfg(feature = "sqlite")]
+    Sqlite(rusqlite::Error),
+    #[cfg(feature = "parquet")]
+    Parquet(parquet::errors::ParquetError),
+    #[cfg(feature = "msgpack")]
+    MsgpackEncode(rmp_serde::encode::Error),
+    #[cfg(feature = "msgpack")]
+    MsgpackDecode(rmp_serde::decode::Error),
+    #[cfg(feature = "browser_oracle")]
+    WebDriverSession(fantoccini::error::NewSessionError),
+    #[cfg(feature = "browser_oracle")]
+    WebDriver(fantoccini::error::CmdError),
+    /// A website went over a limit of its `WebsiteBudget`
+    BudgetExceeded(crate::budget::BudgetExceeded),
     Other(String),
+    /// Several errors, e.g. from different websites of a run that kept going
+    /// after the first one
+    Multiple(Vec<Error>),
 }
 
 impl std::fmt::Display for Error {
@@ -33,19 +72,163 @@ impl std::fmt::Display for Error {
                 Ok(())
             },
             ErrorKind::MultipleHtmlFiles(v) => {
-                writeln!(f, "website {} has more than one html file:", self.path.as_ref().unwrap().display())?;
+                match &self.path {
+                    Some(path) => writeln!(f, "website {} has more than one html file:", path.display())?,
+                    None => writeln!(f, "website has more than one html file:")?,
+                }
                 for HtmlFile(h) in v {
                     writeln!(f, "{}", h.display())?;
                 }
                 Ok(())
             }
+            ErrorKind::InvalidConfig(e) => {
+                write!(f, "invalid website config: {e}")?;
+                if let Some(path) = &self.path {
+                    write!(f, " path: {}", path.display())?;
+                }
+                Ok(())
+            }
+            ErrorKind::CssParse { file, errors, source: _ } => {
+                write!(f, "{} css parse errors in {file}", errors.len())?;
+                if let Some(path) = &self.path {
+                    write!(f, " path: {}", path.display())?;
+                }
+                for error in errors {
+                    write!(f, "\n  {error}")?;
+                }
+                Ok(())
+            }
+            ErrorKind::InvalidSelector { selector, line, column, message } => {
+                write!(f, "invalid selector {selector:?} at {line}:{column}: {message}")
+            }
+            #[cfg(feature = "sqlite")]
+            ErrorKind::Sqlite(e) => {
+                write!(f, "sqlite error: {e}")?;
+                if let Some(path) = &self.path {
+                    write!(f, " path: {}", path.display())?;
+                }
+                Ok(())
+            }
+            #[cfg(feature = "parquet")]
+            ErrorKind::Parquet(e) => {
+                write!(f, "parquet error: {e}")?;
+                if let Some(path) = &self.path {
+                    write!(f, " path: {}", path.display())?;
+                }
+                Ok(())
+            }
+            #[cfg(feature = "msgpack")]
+            ErrorKind::MsgpackEncode(e) => {
+                write!(f, "messagepack encode error: {e}")?;
+                if let Some(path) = &self.path {
+                    write!(f, " path: {}", path.display())?;
+                }
+                Ok(())
+            }
+            #[cfg(feature = "msgpack")]
+            ErrorKind::MsgpackDecode(e) => {
+                write!(f, "messagepack decode error: {e}")?;
+                if let Some(path) = &self.path {
+                    write!(f, " path: {}", path.display())?;
+                }
+                Ok(())
+            }
+            #[cfg(feature = "browser_oracle")]
+            ErrorKind::WebDriverSession(e) => {
+                write!(f, "could not start a webdriver session: {e}")
+            }
+            #[cfg(feature = "browser_oracle")]
+            ErrorKind::WebDriver(e) => {
+                write!(f, "webdriver error: {e}")?;
+                if let Some(path) = &self.path {
+                    write!(f, " path: {}", path.display())?;
+                }
+                Ok(())
+            }
+            ErrorKind::BudgetExceeded(budget) => {
+                match &self.path {
+                    Some(path) => write!(f, "website {} {budget}", path.display()),
+                    None => write!(f, "website {budget}"),
+                }
+            }
             ErrorKind::Other(s) => {
                 writeln!(f, "{s}")
             }
+            ErrorKind::Multiple(errors) => {
+                write!(f, "{} errors:", errors.len())?;
+                for error in errors {
+                    let error = error.to_string();
+                    write!(f, "\n- {}", error.trim_end().replace('\n', "\n  "))?;
+                }
+                Ok(())
+            }
         }
     }
 }
 
+/// A stable name for each kind of error, for scripts to match on. Printed
+/// with the error, and the process exits with its `exit_code`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    #[display("io")]
+    Io,
+    #[display("multiple_html_files")]
+    MultipleHtmlFiles,
+    #[display("invalid_config")]
+    InvalidConfig,
+    #[display("css_parse")]
+    CssParse,
+    #[display("invalid_selector")]
+    InvalidSelector,
+    /// Writing or reading SQLite, Parquet or MessagePack
+    #[display("export")]
+    Export,
+    #[display("webdriver")]
+    WebDriver,
+    #[display("budget_exceeded")]
+    BudgetExceeded,
+    #[display("other")]
+    Other,
+    /// Several errors with different codes
+    #[display("multiple")]
+    Multiple,
+}
+
+impl ErrorCode {
+    /// Never 0, for success
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ErrorCode::Other => 1,
+            ErrorCode::Io => 2,
+            ErrorCode::MultipleHtmlFiles => 3,
+            ErrorCode::InvalidConfig => 4,
+            ErrorCode::CssParse => 5,
+            ErrorCode::InvalidSelector => 6,
+            ErrorCode::Export => 7,
+            ErrorCode::WebDriver => 8,
+            ErrorCode::Multiple => 9,
+            ErrorCode::BudgetExceeded => 10,
+        }
+    }
+}
+
+/// A CSS parse error, as reported by stylo.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CssParseError {
+    /// 1-based
+    pub line: u32,
+    /// 1-based
+    pub column: u32,
+    pub message: String,
+}
+
+impl std::fmt::Display for CssParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
 impl Error {
     pub fn is_io_and(&self, f: impl FnOnce(&io::Error) -> bool) -> bool {
         match &self.error {
@@ -61,12 +244,66 @@ impl Error {
         }
     }
 
+    pub fn is_css_parse_and(&self, f: impl FnOnce(&str, &[CssParseError]) -> bool) -> bool {
+        match &self.error {
+            ErrorKind::CssParse { file, errors, .. } => f(file, errors),
+            _ => false,
+        }
+    }
+
     pub fn other(message: String) -> Self {
         Self {
             path: None,
             error: ErrorKind::Other(message),
         }
     }
+
+    pub fn code(&self) -> ErrorCode {
+        match &self.error {
+            ErrorKind::Io(_) => ErrorCode::Io,
+            ErrorKind::MultipleHtmlFiles(_) => ErrorCode::MultipleHtmlFiles,
+            ErrorKind::InvalidConfig(_) => ErrorCode::InvalidConfig,
+            ErrorKind::CssParse { .. } => ErrorCode::CssParse,
+            ErrorKind::InvalidSelector { .. } => ErrorCode::InvalidSelector,
+            #[cfg(feature = "sqlite")]
+            ErrorKind::Sqlite(_) => ErrorCode::Export,
+            #[cfg(feature = "parquet")]
+            ErrorKind::Parquet(_) => ErrorCode::Export,
+            #[cfg(feature = "msgpack")]
+            ErrorKind::MsgpackEncode(_) | ErrorKind::MsgpackDecode(_) => ErrorCode::Export,
+            #[cfg(feature = "browser_oracle")]
+            ErrorKind::WebDriverSession(_) | ErrorKind::WebDriver(_) => ErrorCode::WebDriver,
+            ErrorKind::BudgetExceeded(_) => ErrorCode::BudgetExceeded,
+            ErrorKind::Other(_) => ErrorCode::Other,
+            // The errors' code if they all have the same one
+            ErrorKind::Multiple(errors) => {
+                let mut codes = errors.iter().map(Error::code);
+                let first = codes.next().unwrap_or(ErrorCode::Multiple);
+                if codes.all(|code| code == first) { first } else { ErrorCode::Multiple }
+            }
+        }
+    }
+
+    /// Combines `errors` into one `ErrorKind::Multiple`, or returns `None` if
+    /// there are none. A single error is returned as is.
+    pub fn from_errors(mut errors: Vec<Error>) -> Option<Self> {
+        match errors.len() {
+            0 => None,
+            1 => errors.pop(),
+            _ => Some(Self {
+                path: None,
+                error: ErrorKind::Multiple(errors),
+            }),
+        }
+    }
+
+    /// The errors this one is made of: itself, or each of a `Multiple`'s.
+    pub fn errors(&self) -> Vec<&Error> {
+        match &self.error {
+            ErrorKind::Multiple(errors) => errors.iter().flat_map(Error::errors).collect(),
+            _ => vec![self],
+        }
+    }
 }
 
 pub trait IntoErrorExt<T> {
@@ -93,3 +330,54 @@ impl<T> IntoResultExt<T> for io::Result<T> {
         self.map_err(|e| <io::Error as IntoErrorExt<T>>::into_error(e, path))
     }
 }
+
+/// Collects every success and every failure of `results`, e.g. the websites
+/// of `do_all_websites`, instead of stopping at the first failure.
+pub fn partition_results<T>(results: impl IntoIterator<Item = Result<T>>) -> (Vec<T>, Vec<Error>) {
+    let mut successes = Vec::new();
+    let mut failures = Vec::new();
+    for result in results {
+        match result {
+            Ok(success) => successes.push(success),
+            Err(failure) => failures.push(failure),
+        }
+    }
+    (successes, failures)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, ErrorCode, ErrorKind, partition_results};
+    use crate::parse::HtmlFile;
+    use test_log::test;
+
+    #[test]
+    fn combines_errors() {
+        let results = vec![Ok(1), Err(Error::other("a".to_owned())), Ok(2), Err(Error::other("b\nc".to_owned()))];
+        let (successes, failures) = partition_results(results);
+        assert_eq!(successes, [1, 2]);
+        let error = Error::from_errors(failures).unwrap();
+        assert_eq!(error.errors().len(), 2);
+        assert_eq!(error.to_string(), "2 errors:\n- a\n- b\n  c");
+        assert!(Error::from_errors(Vec::new()).is_none());
+        let single = Error::from_errors(vec![Error::other("a".to_owned())]).unwrap();
+        assert_eq!(single.to_string(), "a\n");
+    }
+
+    #[test]
+    fn codes_and_display_are_total() {
+        let no_path = Error {
+            path: None,
+            error: ErrorKind::MultipleHtmlFiles(vec![HtmlFile("a.html".into()), HtmlFile("b.html".into())]),
+        };
+        assert_eq!(no_path.to_string(), "website has more than one html file:\na.html\nb.html\n");
+        assert_eq!(no_path.code(), ErrorCode::MultipleHtmlFiles);
+        assert_eq!(no_path.code().to_string(), "multiple_html_files");
+
+        let io = || Error { path: None, error: ErrorKind::Io(std::io::ErrorKind::NotFound.into()) };
+        assert_eq!(Error::from_errors(vec![io(), io()]).unwrap().code(), ErrorCode::Io);
+        let mixed = Error::from_errors(vec![io(), no_path]).unwrap();
+        assert_eq!(mixed.code(), ErrorCode::Multiple);
+        assert_ne!(mixed.code().exit_code(), 0);
+    }
+}
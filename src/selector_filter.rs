@@ -0,0 +1,211 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Narrowing the selectors a website is matched with, by predicates over
+//! their simple selectors:
+//!
+//! ```ignore
+//! let filter = SelectorFilter::new().drop_if(|c| matches!(c, Component::AttributeInNoNamespace { .. }));
+//! let website = website.with_selector_filter(&filter);
+//! ```
+//!
+//! Predicates see every simple selector, including those under `:not()`,
+//! `:is()` and the like, unless the filter is `shallow`. They never see
+//! combinators.
+
+use std::sync::Arc;
+
+use selectors::parser::Component;
+use selectors::visitor::SelectorVisitor;
+use style::selector_parser::SelectorImpl;
+
+use crate::structs::Selector;
+
+type Predicate = Arc<dyn Fn(&Component<SelectorImpl>) -> bool + Send + Sync>;
+
+#[derive(Clone)]
+enum Condition {
+    /// Drops selectors with any simple selector it holds for
+    DropIfAny(Predicate),
+    /// Keeps only selectors with some simple selector it holds for
+    KeepIfAny(Predicate),
+    /// Keeps only selectors it holds for every simple selector of
+    KeepIfAll(Predicate),
+}
+
+/// Which selectors to keep. A selector is kept when it passes every
+/// condition, so an empty filter keeps everything.
+#[derive(Clone, Default)]
+pub struct SelectorFilter {
+    conditions: Vec<Condition>,
+    shallow: bool,
+}
+
+impl std::fmt::Debug for SelectorFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SelectorFilter")
+            .field("conditions", &self.conditions.len())
+            .field("shallow", &self.shallow)
+            .finish()
+    }
+}
+
+impl SelectorFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drops selectors with a simple selector `predicate` holds for
+    pub fn drop_if(mut self, predicate: impl Fn(&Component<SelectorImpl>) -> bool + Send + Sync + 'static) -> Self {
+        self.conditions.push(Condition::DropIfAny(Arc::new(predicate)));
+        self
+    }
+
+    /// Keeps only selectors with a simple selector `predicate` holds for
+    pub fn keep_if(mut self, predicate: impl Fn(&Component<SelectorImpl>) -> bool + Send + Sync + 'static) -> Self {
+        self.conditions.push(Condition::KeepIfAny(Arc::new(predicate)));
+        self
+    }
+
+    /// Keeps only selectors whose simple selectors `predicate` holds for,
+    /// all of them
+    pub fn keep_only(mut self, predicate: impl Fn(&Component<SelectorImpl>) -> bool + Send + Sync + 'static) -> Self {
+        self.conditions.push(Condition::KeepIfAll(Arc::new(predicate)));
+        self
+    }
+
+    /// Shows the predicates only the simple selectors of a selector's own
+    /// compounds, not those in the selector lists of `:not()`, `:is()` etc.
+    /// Applies to every condition.
+    pub fn shallow(self) -> Self {
+        Self { shallow: true, ..self }
+    }
+
+    /// Drops selectors with attribute selectors, e.g. `[href]`
+    pub fn without_attribute_selectors() -> Self {
+        Self::new().drop_if(|component| {
+            matches!(
+                component,
+                Component::AttributeInNoNamespaceExists { .. }
+                    | Component::AttributeInNoNamespace { .. }
+                    | Component::AttributeOther(_)
+            )
+        })
+    }
+
+    /// Keeps only selectors made of class selectors, e.g. `.a .b.c`
+    pub fn only_class_selectors() -> Self {
+        Self::new().keep_only(|component| matches!(component, Component::Class(_)))
+    }
+
+    /// Drops the selectors `analysis::excluded` counts: those with a
+    /// pseudo-class in their own compounds that never matches, e.g. `:hover`
+    pub fn without_excluded_pseudo_classes() -> Self {
+        Self::new()
+            .drop_if(|component| {
+                matches!(component, Component::NonTSPseudoClass(pc) if !scraper::element_ref::can_match_non_ts_pseudo_class(pc))
+            })
+            .shallow()
+    }
+
+    /// Whether `selector` passes every condition
+    pub fn accepts(&self, selector: &Selector) -> bool {
+        self.conditions.iter().all(|condition| match condition {
+            Condition::DropIfAny(predicate) => !self.any_component(selector, &**predicate),
+            Condition::KeepIfAny(predicate) => self.any_component(selector, &**predicate),
+            Condition::KeepIfAll(predicate) => !self.any_component(selector, |component| !predicate(component)),
+        })
+    }
+
+    /// The selectors of `selectors` that `accepts` them, in order
+    pub fn retain(&self, selectors: impl IntoIterator<Item = Selector>) -> Vec<Selector> {
+        selectors.into_iter().filter(|selector| self.accepts(selector)).collect()
+    }
+
+    fn any_component(&self, selector: &Selector, predicate: impl Fn(&Component<SelectorImpl>) -> bool) -> bool {
+        if self.shallow {
+            selector.iter_raw_match_order().filter(|component| !component.is_combinator()).any(predicate)
+        } else {
+            let mut visitor = AnyComponent { predicate, found: false };
+            selector.visit(&mut visitor);
+            visitor.found
+        }
+    }
+}
+
+/// Looks for a simple selector `predicate` holds for. `SelectorVisitor`'s
+/// `visit_selector_list` walks nested selector lists too.
+struct AnyComponent<F> {
+    predicate: F,
+    found: bool,
+}
+
+impl<F: Fn(&Component<SelectorImpl>) -> bool> SelectorVisitor for AnyComponent<F> {
+    type Impl = SelectorImpl;
+
+    fn visit_simple_selector(&mut self, component: &Component<Self::Impl>) -> bool {
+        self.found = (self.predicate)(component);
+        // Returning `false` stops the walk
+        !self.found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use selectors::parser::Component;
+
+    use scraper::Html;
+    use style::shared_lock::SharedRwLock;
+    use style::stylesheets::UrlExtraData;
+
+    use super::SelectorFilter;
+    use crate::parse::{ParsedWebsite, parse_stylesheet};
+    use crate::parse_selector_list;
+    use test_log::test;
+
+    fn kept(filter: &SelectorFilter, selectors: &str) -> Vec<String> {
+        use cssparser::ToCss as _;
+        let list = parse_selector_list(selectors).unwrap();
+        filter.retain(list.slice().iter().cloned()).iter().map(|selector| selector.to_css_string()).collect()
+    }
+
+    #[test]
+    fn filters_by_predicates() {
+        let selectors = "a, .a .b.c, [href], p:not([title]), .a:hover, :is(.a:hover), .a > .b";
+        assert_eq!(
+            kept(&SelectorFilter::without_attribute_selectors(), selectors),
+            ["a", ".a .b.c", ".a:hover", ":is(.a:hover)", ".a > .b"],
+        );
+        assert_eq!(
+            kept(&SelectorFilter::without_attribute_selectors().shallow(), selectors),
+            ["a", ".a .b.c", "p:not([title])", ".a:hover", ":is(.a:hover)", ".a > .b"],
+        );
+        assert_eq!(kept(&SelectorFilter::only_class_selectors(), selectors), [".a .b.c", ".a > .b"]);
+        assert_eq!(
+            kept(&SelectorFilter::without_excluded_pseudo_classes(), selectors),
+            ["a", ".a .b.c", "[href]", "p:not([title])", ":is(.a:hover)", ".a > .b"],
+        );
+        let has_class = SelectorFilter::new().keep_if(|component| matches!(component, Component::Class(_)));
+        assert_eq!(
+            kept(&has_class.drop_if(|component| matches!(component, Component::NonTSPseudoClass(_))), selectors),
+            [".a .b.c", ".a > .b"],
+        );
+        assert_eq!(kept(&SelectorFilter::new(), "a, b"), ["a", "b"]);
+    }
+
+    #[test]
+    fn filters_a_websites_selectors() {
+        use cssparser::ToCss as _;
+        let lock = SharedRwLock::new();
+        let url_data = UrlExtraData::from(url::Url::parse("about:blank").unwrap());
+        let stylesheet = parse_stylesheet(".a {} [href] {} .b:focus {}", url_data, &lock).unwrap();
+        let website = ParsedWebsite::new("test".to_owned(), Html::parse_document(""), vec![stylesheet], lock)
+            .with_selector_filter(&SelectorFilter::without_attribute_selectors());
+        let selectors: Vec<_> = website.get_matcher().get_selectors().iter().map(|selector| selector.to_css_string()).collect();
+        assert_eq!(selectors, [".a", ".b:focus"]);
+        assert_eq!(website.stylesheets().len(), 1);
+    }
+}
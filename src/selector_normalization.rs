@@ -0,0 +1,94 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Canonical selector strings, for output that doesn't change with how a
+//! stylesheet happens to be written. Stylo's serialization already makes
+//! whitespace and quoting consistent; on top of that, type selectors are
+//! lowercased and the attribute selectors of each compound are sorted, also
+//! in the selector lists of `:is()`, `:where()` and `:not()`.
+//!
+//! This is opt-in (`ParseOptions::normalize_selectors`): `do_website` names
+//! matched selectors by their canonical string, and `analysis::provenance`
+//! keeps the string as written. Lowercasing type selectors only changes what
+//! they match in documents with non-HTML (e.g. SVG) elements, which is why
+//! the canonical selectors are only used for naming.
+
+use cssparser::ToCss as _;
+use selectors::SelectorList;
+use selectors::parser::{Component, LocalName};
+use style::selector_parser::SelectorImpl;
+
+use crate::structs::Selector;
+
+/// `selector`, serialized canonically
+pub fn canonical_css(selector: &Selector) -> String {
+    normalize(selector).to_css_string()
+}
+
+/// `selector` with lowercased type selectors and sorted attribute selectors
+pub fn normalize(selector: &Selector) -> Selector {
+    let mut components: Vec<_> = selector.iter_raw_parse_order_from(0).map(normalize_component).collect();
+    // Sort the attribute selectors of each compound among themselves, in the
+    // places attribute selectors were
+    for compound in components.split_mut(Component::is_combinator) {
+        let places: Vec<_> = (0..compound.len()).filter(|&i| is_attribute(&compound[i])).collect();
+        let mut attributes: Vec<_> = places.iter().map(|&i| compound[i].clone()).collect();
+        attributes.sort_by_cached_key(|attribute| attribute.to_css_string());
+        for (i, attribute) in places.into_iter().zip(attributes) {
+            compound[i] = attribute;
+        }
+    }
+    crate::preprocessing::selector_from_iter(components.into_iter())
+}
+
+fn normalize_list(list: &SelectorList<SelectorImpl>) -> SelectorList<SelectorImpl> {
+    SelectorList::from_iter(list.slice().iter().map(normalize))
+}
+
+fn normalize_component(component: &Component<SelectorImpl>) -> Component<SelectorImpl> {
+    match component {
+        Component::LocalName(local_name) => Component::LocalName(LocalName {
+            name: local_name.lower_name.clone(),
+            lower_name: local_name.lower_name.clone(),
+        }),
+        Component::Is(list) => Component::Is(normalize_list(list)),
+        Component::Where(list) => Component::Where(normalize_list(list)),
+        Component::Negation(list) => Component::Negation(normalize_list(list)),
+        component => component.clone(),
+    }
+}
+
+fn is_attribute(component: &Component<SelectorImpl>) -> bool {
+    matches!(
+        component,
+        Component::AttributeInNoNamespaceExists { .. }
+            | Component::AttributeInNoNamespace { .. }
+            | Component::AttributeOther(_)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::canonical_css;
+    use crate::parse_selector_list;
+    use test_log::test;
+
+    fn canonical(selectors: &str) -> Vec<String> {
+        parse_selector_list(selectors).unwrap().slice().iter().map(canonical_css).collect()
+    }
+
+    #[test]
+    fn canonicalizes_differently_written_selectors() {
+        assert_eq!(
+            canonical("DIV.a[title][href ='x' i] >  P, div.a[href=\"x\" i][title]>p, :is(Ul[b][a]) LI::before"),
+            [
+                "div.a[href=\"x\" i][title] > p",
+                "div.a[href=\"x\" i][title] > p",
+                ":is(ul[a][b]) li::before",
+            ],
+        );
+    }
+}
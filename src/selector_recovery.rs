@@ -0,0 +1,197 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Keeping the valid selectors of a style rule whose selector list doesn't
+//! parse, as `:is()` and `:where()` do with their arguments.
+//!
+//! Browsers drop the whole rule when any selector in its list is invalid, so
+//! this is opt-in (`ParseOptions::forgiving_selector_lists`). Each selector
+//! is checked by parsing it alone in an empty rule, after the stylesheet's
+//! `@namespace` rules, so that the result is whatever Stylo would accept.
+//! Only rules at the top level and in grouping at-rules (`@media`,
+//! `@supports`, ...) are recovered, not rules nested in style rules.
+
+use std::borrow::Cow;
+use std::ops::Range;
+
+use cssparser::{ParseError, Parser, ParserInput, Token};
+use style::shared_lock::SharedRwLock;
+use style::stylesheets::UrlExtraData;
+
+use crate::result::CssParseError;
+
+/// At-rules whose blocks hold rules
+const GROUPING_RULES: [&str; 8] =
+    ["media", "supports", "layer", "container", "document", "-moz-document", "scope", "starting-style"];
+
+/// Rewrites the selector lists in `css` that don't parse to the selectors in
+/// them that do, returning the rewritten CSS and an error for each dropped
+/// selector. Lists with no valid selector are left alone.
+pub(crate) fn recover_selector_lists(css: &str) -> (Cow<'_, str>, Vec<CssParseError>) {
+    let mut input = ParserInput::new(css);
+    let mut parser = Parser::new(&mut input);
+    let mut preludes = Vec::new();
+    let mut namespaces = String::new();
+    collect_preludes(&mut parser, true, &mut preludes, &mut namespaces);
+
+    let parses = |selectors: &str| {
+        let lock = SharedRwLock::new();
+        let url_data = UrlExtraData::from(url::Url::parse("about:blank").unwrap());
+        crate::parse::parse_stylesheet_with_errors(&format!("{namespaces}{selectors} {{}}"), url_data, &lock)
+            .1
+            .is_empty()
+    };
+    let mut recovered = String::new();
+    let mut copied = 0;
+    let mut dropped = Vec::new();
+    for prelude in preludes {
+        let text = &css[prelude.clone()];
+        let parts = split_selector_list(text);
+        if parts.len() < 2 || parses(text) {
+            continue;
+        }
+        let (valid, invalid): (Vec<_>, Vec<_>) = parts.into_iter().partition(|part| parses(&text[part.clone()]));
+        if valid.is_empty() {
+            continue;
+        }
+        recovered.push_str(&css[copied..prelude.start]);
+        let valid: Vec<&str> = valid.into_iter().map(|part| &text[part]).collect();
+        recovered.push_str(&valid.join(", "));
+        copied = prelude.end;
+        for part in invalid {
+            let (line, column) = line_and_column(css, prelude.start + part.start);
+            dropped.push(CssParseError {
+                line,
+                column,
+                message: format!("Dropped invalid selector from its selector list: {}", &text[part]),
+            });
+        }
+    }
+    if copied == 0 {
+        return (Cow::Borrowed(css), dropped);
+    }
+    recovered.push_str(&css[copied..]);
+    (Cow::Owned(recovered), dropped)
+}
+
+/// Pushes the byte ranges of the style rule preludes in `parser`'s rules,
+/// without surrounding whitespace, and the text of the top-level
+/// `@namespace` rules.
+fn collect_preludes(parser: &mut Parser, top_level: bool, preludes: &mut Vec<Range<usize>>, namespaces: &mut String) {
+    loop {
+        parser.skip_whitespace();
+        let start = parser.position();
+        let token = match parser.next() {
+            Ok(token) => token.clone(),
+            Err(_) => return,
+        };
+        match token {
+            Token::CDO | Token::CDC => (),
+            Token::AtKeyword(name) => loop {
+                match parser.next().cloned() {
+                    Ok(Token::Semicolon) | Err(_) => {
+                        if top_level && name.eq_ignore_ascii_case("namespace") {
+                            namespaces.push_str(parser.slice_from(start));
+                            namespaces.push('\n');
+                        }
+                        break;
+                    }
+                    Ok(Token::CurlyBracketBlock) => {
+                        if GROUPING_RULES.iter().any(|rule| name.eq_ignore_ascii_case(rule)) {
+                            let _ = parser.parse_nested_block(|block| {
+                                collect_preludes(block, false, preludes, namespaces);
+                                Ok::<_, ParseError<()>>(())
+                            });
+                        }
+                        break;
+                    }
+                    Ok(_) => (),
+                }
+            },
+            _ => loop {
+                let end = parser.position();
+                match parser.next_including_whitespace_and_comments().cloned() {
+                    Ok(Token::CurlyBracketBlock) => {
+                        let prelude = parser.slice(start..end);
+                        let trimmed = prelude.trim_end();
+                        preludes.push(start.byte_index()..start.byte_index() + trimmed.len());
+                        break;
+                    }
+                    Ok(_) => (),
+                    Err(_) => return,
+                }
+            },
+        }
+    }
+}
+
+/// The ranges of the comma-separated selectors in `selectors`, trimmed
+fn split_selector_list(selectors: &str) -> Vec<Range<usize>> {
+    let mut input = ParserInput::new(selectors);
+    let mut parser = Parser::new(&mut input);
+    let mut parts = Vec::new();
+    let mut part_start = 0;
+    let mut push = |start: usize, end: usize| {
+        let part = &selectors[start..end];
+        let trimmed_start = start + (part.len() - part.trim_start().len());
+        parts.push(trimmed_start..start + part.trim_end().len());
+    };
+    loop {
+        let before = parser.position().byte_index();
+        match parser.next_including_whitespace_and_comments().cloned() {
+            Ok(Token::Comma) => {
+                push(part_start, before);
+                part_start = parser.position().byte_index();
+            }
+            Ok(_) => (),
+            Err(_) => break,
+        }
+    }
+    push(part_start, selectors.len());
+    parts
+}
+
+/// 1-based, counting columns in characters
+fn line_and_column(css: &str, offset: usize) -> (u32, u32) {
+    let before = &css[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or(before).chars().count() + 1;
+    (line as u32, column as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{recover_selector_lists, split_selector_list};
+    use test_log::test;
+
+    #[test]
+    fn splits_at_top_level_commas() {
+        let selectors = " a , :is(b, c) ,[d=','] ";
+        let parts: Vec<_> = split_selector_list(selectors).into_iter().map(|part| &selectors[part]).collect();
+        assert_eq!(parts, ["a", ":is(b, c)", "[d=',']"]);
+    }
+
+    #[test]
+    fn keeps_valid_selectors() {
+        let css = "@namespace svg url(http://www.w3.org/2000/svg);
+a, b:bogus, svg|rect { color: red }
+@media screen {
+  p::nope, .c {}
+}
+.x:bogus, .y:bogus {}
+.ok, .fine {}";
+        let (recovered, dropped) = recover_selector_lists(css);
+        assert_eq!(recovered, "@namespace svg url(http://www.w3.org/2000/svg);
+a, svg|rect { color: red }
+@media screen {
+  .c {}
+}
+.x:bogus, .y:bogus {}
+.ok, .fine {}");
+        let dropped: Vec<_> = dropped.iter().map(|error| (error.line, error.column)).collect();
+        assert_eq!(dropped, [(2, 4), (4, 3)]);
+    }
+}
@@ -0,0 +1,231 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! An experimental matching algorithm over a trie of rightmost compounds.
+//!
+//! The type, id and class selectors of each selector's rightmost compound are
+//! sorted into a path from the root, so selectors whose compounds share a
+//! prefix (e.g. `.btn.primary` and `.btn.large`) share the nodes for it, and
+//! each node's test is done once per element. A failed test prunes every
+//! selector below it. Selectors that reach a node's end are matched in full
+//! by Stylo, which repeats the tests on the way but no longer has to reject
+//! most of them.
+
+use scraper::{ElementRef, Html};
+use selectors::context::SelectorCaches;
+use selectors::matching;
+use selectors::parser::Component;
+
+use crate::ElementOrder;
+use crate::structs::Selector;
+use crate::structs::borrowed::{DocumentMatches, ElementMatches, SelectorsOrSharedStyles};
+
+/// A simple selector the trie can test without Stylo. Ordered so that type
+/// selectors come first, since they are the most shared.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum TrieKey {
+    /// ASCII-lowercased
+    LocalName(String),
+    Id(String),
+    Class(String),
+}
+
+impl TrieKey {
+    /// Like Stylo's tests, in no-quirks mode, except that type selectors
+    /// ignore case for every element rather than only HTML ones. This can
+    /// only let through more elements, which the full match then rejects.
+    fn matches(&self, element: ElementRef) -> bool {
+        match self {
+            TrieKey::LocalName(name) => element.value().name().eq_ignore_ascii_case(name),
+            TrieKey::Id(id) => element.value().id() == Some(id.as_str()),
+            TrieKey::Class(class) => element
+                .value()
+                .has_class(class, selectors::attr::CaseSensitivity::CaseSensitive),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct TrieNode {
+    children: Vec<(TrieKey, TrieNode)>,
+    /// Indices of the selectors whose keys end here
+    selectors: Vec<usize>,
+}
+
+impl TrieNode {
+    fn insert(&mut self, keys: &[TrieKey], selector: usize) {
+        let Some((key, rest)) = keys.split_first() else {
+            self.selectors.push(selector);
+            return;
+        };
+        let child = match self.children.iter().position(|(k, _)| k == key) {
+            Some(i) => &mut self.children[i].1,
+            None => {
+                self.children.push((key.clone(), TrieNode::default()));
+                &mut self.children.last_mut().unwrap().1
+            }
+        };
+        child.insert(rest, selector);
+    }
+
+    /// Pushes the selectors below this node whose keys all match `element`.
+    fn candidates(&self, element: ElementRef, candidates: &mut Vec<usize>) {
+        candidates.extend_from_slice(&self.selectors);
+        for (key, child) in &self.children {
+            if key.matches(element) {
+                child.candidates(element, candidates);
+            }
+        }
+    }
+
+    fn count_nodes(&self) -> usize {
+        1 + self.children.iter().map(|(_, child)| child.count_nodes()).sum::<usize>()
+    }
+}
+
+/// A compiled selector list.
+#[derive(Debug)]
+pub struct SelectorTrie<'a> {
+    selectors: &'a [Selector],
+    root: TrieNode,
+}
+
+impl<'a> SelectorTrie<'a> {
+    pub fn new(selectors: &'a [Selector]) -> Self {
+        let mut root = TrieNode::default();
+        for (i, selector) in selectors.iter().enumerate() {
+            root.insert(&keys(selector), i);
+        }
+        Self { selectors, root }
+    }
+
+    /// Nodes in the trie, including the root. Fewer nodes than the selectors
+    /// have keys means some were shared.
+    pub fn node_count(&self) -> usize {
+        self.root.count_nodes()
+    }
+
+    /// The selectors `element` might match, in their order in the list.
+    fn candidates(&self, element: ElementRef) -> Vec<usize> {
+        let mut candidates = Vec::new();
+        self.root.candidates(element, &mut candidates);
+        candidates.sort_unstable();
+        candidates
+    }
+}
+
+/// The trie keys of `selector`'s rightmost compound, sorted.
+fn keys(selector: &Selector) -> Vec<TrieKey> {
+    // `iter()` stops at the first combinator
+    let mut keys: Vec<TrieKey> = selector
+        .iter()
+        .filter_map(|component| match component {
+            Component::LocalName(local_name) => Some(TrieKey::LocalName(local_name.lower_name.to_string())),
+            Component::ID(id) => Some(TrieKey::Id(id.0.to_string())),
+            Component::Class(class) => Some(TrieKey::Class(class.0.to_string())),
+            _ => None,
+        })
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+/// Like `match_selectors`, with `selectors` compiled into a `SelectorTrie`.
+pub fn match_selectors_with_trie<'a>(document: &'a Html, selectors: &'a [Selector]) -> DocumentMatches<'a> {
+    let trie = SelectorTrie::new(selectors);
+    log::debug!("selector trie has {} nodes for {} selectors", trie.node_count(), selectors.len());
+    let mut caches: SelectorCaches = Default::default();
+    let mut result = Vec::new();
+    trie_preorder_traversal(document.root_element(), &trie, &mut result, &mut caches);
+    DocumentMatches(result)
+}
+
+/// Like `match_selectors_with_trie`, matching the elements in `order`.
+pub fn match_selectors_with_trie_in_order<'a>(
+    document: &'a Html,
+    selectors: &'a [Selector],
+    order: ElementOrder,
+) -> DocumentMatches<'a> {
+    if order == ElementOrder::Document {
+        return match_selectors_with_trie(document, selectors);
+    }
+    let trie = SelectorTrie::new(selectors);
+    crate::match_in_element_order(document, order, |element, caches| trie_match_element(element, &trie, caches))
+}
+
+fn trie_preorder_traversal<'a>(
+    element: ElementRef<'a>,
+    trie: &SelectorTrie<'a>,
+    matches: &mut Vec<ElementMatches<'a>>,
+    caches: &mut SelectorCaches,
+) {
+    matches.push(trie_match_element(element, trie, caches));
+    for child in element.child_elements() {
+        trie_preorder_traversal(child, trie, matches, caches);
+    }
+}
+
+fn trie_match_element<'a>(element: ElementRef<'a>, trie: &SelectorTrie<'a>, caches: &mut SelectorCaches) -> ElementMatches<'a> {
+    crate::budget::stop_if_cancelled();
+    let mut context = matching::MatchingContext::new(
+        matching::MatchingMode::Normal,
+        None,
+        caches,
+        matching::QuirksMode::NoQuirks,
+        matching::NeedsSelectorFlags::No,
+        matching::MatchingForInvalidation::No,
+    );
+    let matched_selectors = trie
+        .candidates(element)
+        .into_iter()
+        .map(|i| &trie.selectors[i])
+        .filter(|s| matching::matches_selector(s, 0, None, &element, &mut context).0)
+        .collect();
+    ElementMatches{ element, selectors: SelectorsOrSharedStyles::Selectors(matched_selectors) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SelectorTrie, match_selectors_with_trie};
+    use crate::match_selectors;
+    use crate::structs::Selector;
+    use crate::structs::borrowed::{DocumentMatches, SelectorsOrSharedStyles};
+    use cssparser::ToCss as _;
+    use style::selector_parser::SelectorParser;
+    use style::stylesheets::UrlExtraData;
+    use test_log::test;
+
+    fn parse(selectors: &str) -> Vec<Selector> {
+        let url_data = UrlExtraData::from(url::Url::parse("about:blank").unwrap());
+        SelectorParser::parse_author_origin_no_namespace(selectors, &url_data).unwrap().slice().to_vec()
+    }
+
+    fn to_css(matches: &DocumentMatches) -> Vec<Vec<String>> {
+        matches.0.iter().map(|element_matches| match &element_matches.selectors {
+            SelectorsOrSharedStyles::Selectors(selectors) => selectors.iter().map(|s| s.to_css_string()).collect(),
+            SelectorsOrSharedStyles::SharedWithElement(_) => unreachable!(),
+        }).collect()
+    }
+
+    #[test]
+    fn shares_prefixes() {
+        let selectors = parse(".btn.primary, .primary.btn, .btn.large, div .btn, #x");
+        // root, .btn, .btn.primary, .btn.large, #x
+        assert_eq!(SelectorTrie::new(&selectors).node_count(), 5);
+    }
+
+    #[test]
+    fn matches_like_naive_in_order() {
+        let document = scraper::Html::parse_document(
+            "<div id='x'><button class='btn large'></button><BUTTON class='primary btn'></BUTTON></div>",
+        );
+        let selectors = parse(".btn.primary, *, button.btn, div .btn.large, #x, .primary:not(.large)");
+        let trie = match_selectors_with_trie(&document, &selectors);
+        assert_eq!(to_css(&trie), to_css(&match_selectors(&document, &selectors)));
+        assert_eq!(to_css(&trie).last().unwrap(), &[".btn.primary", "*", "button.btn", ".primary:not(.large)"]);
+    }
+}
@@ -0,0 +1,293 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `mach-6 serve`: matching documents sent over HTTP. `POST /match` takes a
+//! document and the stylesheets it links to, as JSON
+//!
+//! ```json
+//! {"html": "<link rel=stylesheet href=a.css><p class=a></p>",
+//!  "stylesheets": {"a.css": ".a {}"},
+//!  "algorithm": "with-style-sharing",
+//!  "device": {"viewport": "375x667", "dpr": 2, "prefers-color-scheme": "dark"}}
+//! ```
+//!
+//! or as `multipart/form-data`, with the document in an `html` field, each
+//! stylesheet in a `stylesheet` field whose filename is its href, and the
+//! algorithm and device options in fields of their own names. Device options
+//! are named and written as on the command line, and default to the server's.
+//! The response is `{"matches": ..., "stats": ...}`, or `{"error": ...}` with
+//! status 400.
+//!
+//! Requests are handled one at a time.
+
+use std::collections::BTreeMap;
+use std::io::Read as _;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::Algorithm;
+use crate::device::{ColorScheme, DeviceOptions, ForcedColors, Hover, MediaType, Pointer, ReducedMotion};
+use crate::parse::ParseOptions;
+use crate::result::{Error, Result};
+use crate::structs::{ser::SerDocumentMatches, stats::DocumentStats};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MatchRequest {
+    pub html: String,
+    /// By href
+    pub stylesheets: BTreeMap<String, String>,
+    pub algorithm: Option<String>,
+    /// Command-line device options, by name without the `--`
+    pub device: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonMatchRequest {
+    html: String,
+    #[serde(default)]
+    stylesheets: BTreeMap<String, String>,
+    algorithm: Option<String>,
+    #[serde(default)]
+    device: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MatchResponse {
+    pub matches: SerDocumentMatches,
+    pub stats: DocumentStats,
+}
+
+impl MatchRequest {
+    pub fn from_json(body: &[u8]) -> Result<Self> {
+        let request: JsonMatchRequest =
+            serde_json::from_slice(body).map_err(|e| Error::other(format!("invalid request: {e}")))?;
+        let device = request
+            .device
+            .into_iter()
+            .map(|(name, value)| match value {
+                serde_json::Value::String(value) => (name, value),
+                value => (name, value.to_string()),
+            })
+            .collect();
+        Ok(Self { html: request.html, stylesheets: request.stylesheets, algorithm: request.algorithm, device })
+    }
+
+    /// The body of a `multipart/form-data` request whose `boundary` parameter
+    /// is `boundary`
+    pub fn from_multipart(body: &[u8], boundary: &str) -> Result<Self> {
+        let mut request = Self::default();
+        let mut html = None;
+        for part in multipart_parts(body, boundary)? {
+            let contents = String::from_utf8_lossy(part.contents).into_owned();
+            match part.name.as_str() {
+                "html" => html = Some(contents),
+                "stylesheet" => {
+                    let href = part
+                        .filename
+                        .ok_or_else(|| Error::other("a stylesheet field has no filename to use as its href".to_owned()))?;
+                    request.stylesheets.insert(href, contents);
+                }
+                "algorithm" => request.algorithm = Some(contents),
+                _ => {
+                    request.device.insert(part.name, contents);
+                }
+            }
+        }
+        request.html = html.ok_or_else(|| Error::other("the request has no html field".to_owned()))?;
+        Ok(request)
+    }
+
+    /// Matches the document with `algorithm` and `options` unless the request
+    /// gives its own.
+    pub fn respond(&self, algorithm: Algorithm, options: &ParseOptions) -> Result<MatchResponse> {
+        let algorithm = match &self.algorithm {
+            Some(name) => parse_value(name, "algorithm")?,
+            None => algorithm,
+        };
+        // Every request is its own website, so none are duplicates
+        let mut options = ParseOptions { duplicate_documents: None, ..options.clone() };
+        for (name, value) in &self.device {
+            set_device_option(&mut options.device, name, value)?;
+        }
+        let files: Vec<_> = self.stylesheets.iter().map(|(href, css)| (href.as_str(), css.as_str())).collect();
+        let matches = crate::match_in_memory(&self.html, &files, algorithm, &options)?;
+        Ok(MatchResponse { matches: (&matches).into(), stats: (&matches).into() })
+    }
+}
+
+fn parse_value<T: ValueEnum>(value: &str, name: &str) -> Result<T> {
+    T::from_str(value, true).map_err(|e| Error::other(format!("invalid {name} {value:?}: {e}")))
+}
+
+fn set_device_option(device: &mut DeviceOptions, name: &str, value: &str) -> Result<()> {
+    let features = &mut device.media_features;
+    match name {
+        "viewport" => device.viewport = value.parse().map_err(|e| Error::other(format!("invalid viewport: {e}")))?,
+        "dpr" => {
+            device.device_pixel_ratio = value
+                .trim()
+                .parse()
+                .ok()
+                .filter(|dpr: &f32| dpr.is_finite() && *dpr > 0.0)
+                .ok_or_else(|| Error::other(format!("dpr must be positive, got {value:?}")))?;
+        }
+        "media" => device.media_type = parse_value::<MediaType>(value, name)?,
+        "prefers-color-scheme" => features.prefers_color_scheme = parse_value::<ColorScheme>(value, name)?,
+        "prefers-reduced-motion" => features.prefers_reduced_motion = parse_value::<ReducedMotion>(value, name)?,
+        "forced-colors" => features.forced_colors = parse_value::<ForcedColors>(value, name)?,
+        "hover" => features.hover = parse_value::<Hover>(value, name)?,
+        "pointer" => features.pointer = parse_value::<Pointer>(value, name)?,
+        _ => return Err(Error::other(format!("unknown device option {name}"))),
+    }
+    Ok(())
+}
+
+struct Part<'a> {
+    name: String,
+    filename: Option<String>,
+    contents: &'a [u8],
+}
+
+fn multipart_parts<'a>(body: &'a [u8], boundary: &str) -> Result<Vec<Part<'a>>> {
+    let invalid = || Error::other("invalid multipart body".to_owned());
+    let delimiter = format!("--{boundary}").into_bytes();
+    let mut parts = Vec::new();
+    let mut rest = &body[find(body, &delimiter).ok_or_else(invalid)? + delimiter.len()..];
+    // Each part follows a delimiter line, and the last delimiter ends in `--`
+    while !rest.starts_with(b"--") {
+        rest = rest.strip_prefix(b"\r\n").ok_or_else(invalid)?;
+        let end = find(rest, &[b"\r\n".as_slice(), &delimiter].concat()).ok_or_else(invalid)?;
+        let (part, after) = (&rest[..end], &rest[end + 2 + delimiter.len()..]);
+        let headers_end = find(part, b"\r\n\r\n").ok_or_else(invalid)?;
+        let headers = String::from_utf8_lossy(&part[..headers_end]);
+        let disposition = headers
+            .lines()
+            .find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                name.trim().eq_ignore_ascii_case("content-disposition").then_some(value)
+            })
+            .ok_or_else(invalid)?;
+        let parameter = |wanted: &str| {
+            disposition.split(';').find_map(|parameter| {
+                let (name, value) = parameter.split_once('=')?;
+                (name.trim() == wanted).then(|| value.trim().trim_matches('"').to_owned())
+            })
+        };
+        parts.push(Part {
+            name: parameter("name").ok_or_else(invalid)?,
+            filename: parameter("filename"),
+            contents: &part[headers_end + 4..],
+        });
+        rest = after;
+    }
+    Ok(parts)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// Serves `POST /match` on `address` (e.g. `127.0.0.1:8080`) until the
+/// process is stopped.
+pub fn serve(address: &str, algorithm: Algorithm, options: &ParseOptions) -> Result<()> {
+    let server = tiny_http::Server::http(address).map_err(|e| Error::other(format!("cannot listen on {address}: {e}")))?;
+    log::info!("serving on http://{address}/match");
+    for mut request in server.incoming_requests() {
+        let (status, body) = match (request.method(), request.url()) {
+            (tiny_http::Method::Post, "/match") => {
+                let content_type = request
+                    .headers()
+                    .iter()
+                    .find(|header| header.field.equiv("Content-Type"))
+                    .map(|header| header.value.as_str().to_owned())
+                    .unwrap_or_default();
+                let mut body = Vec::new();
+                let response = request
+                    .as_reader()
+                    .read_to_end(&mut body)
+                    .map_err(|e| Error::other(format!("error reading the request: {e}")))
+                    .and_then(|_| parse_request(&content_type, &body))
+                    .and_then(|request| request.respond(algorithm, options));
+                match response {
+                    Ok(response) => (200, serde_json::to_string(&response).unwrap()),
+                    Err(e) => (400, serde_json::json!({ "error": e.to_string() }).to_string()),
+                }
+            }
+            (_, "/match") => (405, serde_json::json!({ "error": "use POST" }).to_string()),
+            _ => (404, serde_json::json!({ "error": "not found" }).to_string()),
+        };
+        let header = tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap();
+        let response = tiny_http::Response::from_string(body).with_status_code(status).with_header(header);
+        if let Err(e) = request.respond(response) {
+            log::warn!("error sending a response: {e}");
+        }
+    }
+    Ok(())
+}
+
+fn parse_request(content_type: &str, body: &[u8]) -> Result<MatchRequest> {
+    let mut parameters = content_type.split(';').map(str::trim);
+    match parameters.next() {
+        Some(media_type) if media_type.eq_ignore_ascii_case("multipart/form-data") => {
+            let boundary = parameters
+                .find_map(|parameter| parameter.strip_prefix("boundary="))
+                .ok_or_else(|| Error::other("multipart request without a boundary".to_owned()))?;
+            MatchRequest::from_multipart(body, boundary.trim_matches('"'))
+        }
+        _ => MatchRequest::from_json(body),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MatchRequest, parse_request};
+    use crate::Algorithm;
+    use crate::parse::ParseOptions;
+    use test_log::test;
+
+    #[test]
+    fn parses_json_and_multipart_requests() -> crate::result::Result<()> {
+        let json = br#"{"html": "<link rel=stylesheet href=a.css><p class=a></p>", "stylesheets": {"a.css": ".a {}"}, "algorithm": "naive", "device": {"dpr": 2, "media": "print"}}"#;
+        let from_json = parse_request("application/json", json)?;
+        let multipart = "--XYZ\r\n\
+            Content-Disposition: form-data; name=\"html\"\r\n\r\n\
+            <link rel=stylesheet href=a.css><p class=a></p>\r\n\
+            --XYZ\r\n\
+            Content-Disposition: form-data; name=\"stylesheet\"; filename=\"a.css\"\r\n\
+            Content-Type: text/css\r\n\r\n\
+            .a {}\r\n\
+            --XYZ\r\n\
+            Content-Disposition: form-data; name=\"algorithm\"\r\n\r\n\
+            naive\r\n\
+            --XYZ\r\n\
+            Content-Disposition: form-data; name=\"dpr\"\r\n\r\n\
+            2\r\n\
+            --XYZ\r\n\
+            Content-Disposition: form-data; name=\"media\"\r\n\r\n\
+            print\r\n\
+            --XYZ--\r\n";
+        let from_multipart = parse_request("multipart/form-data; boundary=\"XYZ\"", multipart.as_bytes())?;
+        assert_eq!(from_json, from_multipart);
+        assert_eq!(from_json.stylesheets["a.css"], ".a {}");
+        assert_eq!(from_json.device["dpr"], "2");
+        Ok(())
+    }
+
+    #[test]
+    fn responds_with_matches() -> crate::result::Result<()> {
+        let request = MatchRequest::from_json(
+            br#"{"html": "<style>@media print { p {} }</style><link rel=stylesheet href=a.css><p class=a></p>", "stylesheets": {"a.css": ".a {}"}, "device": {"media": "print"}}"#,
+        )?;
+        let response = request.respond(Algorithm::Naive, &ParseOptions::default())?;
+        let p = response.matches.0.values().find(|element| element.html.starts_with("<p")).unwrap();
+        assert_eq!(p.selectors, [".a", "p"].map(str::to_owned).into());
+        let bad_device = MatchRequest { device: [("hover".to_owned(), "sometimes".to_owned())].into(), ..request };
+        assert!(bad_device.respond(Algorithm::Naive, &ParseOptions::default()).is_err());
+        Ok(())
+    }
+}
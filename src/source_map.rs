@@ -0,0 +1,221 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Reading version 3 source maps, to find where a rule of a compiled
+//! stylesheet came from in its SCSS or LESS sources.
+//!
+//! Only what provenance needs is supported: `sources`, `sourceRoot` and
+//! `mappings`. Index maps (with `sections`) and inline `data:` maps are not.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::result::{Error, IntoResultExt as _, Result};
+
+/// A position in one of the map's sources. Lines and columns are 1-based.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct OriginalLocation {
+    /// As listed in the map, after `sourceRoot`
+    pub source: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Mapping {
+    generated_column: u32,
+    source: u32,
+    line: u32,
+    column: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceMap {
+    sources: Vec<String>,
+    /// Mappings by generated line, each line's sorted by generated column.
+    /// Segments without a source are left out.
+    lines: Vec<Vec<Mapping>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawSourceMap {
+    version: u32,
+    #[serde(default)]
+    source_root: Option<String>,
+    sources: Vec<Option<String>>,
+    mappings: String,
+}
+
+impl SourceMap {
+    pub fn parse(json: &str) -> Result<Self> {
+        let raw: RawSourceMap = serde_json::from_str(json).map_err(std::io::Error::from).into_result(None)?;
+        if raw.version != 3 {
+            return Err(Error::other(format!("unsupported source map version {}", raw.version)));
+        }
+        let root = raw.source_root.filter(|root| !root.is_empty());
+        let sources = raw
+            .sources
+            .into_iter()
+            .map(|source| {
+                let source = source.unwrap_or_default();
+                match &root {
+                    Some(root) => format!("{}/{source}", root.trim_end_matches('/')),
+                    None => source,
+                }
+            })
+            .collect();
+        let lines = decode_mappings(&raw.mappings)?;
+        Ok(Self { sources, lines })
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let json = std::fs::read_to_string(path).into_result(Some(path.to_path_buf()))?;
+        Self::parse(&json).map_err(|e| Error { path: Some(path.to_path_buf()), ..e })
+    }
+
+    /// Where the generated position came from: the closest mapping at or
+    /// before it on its line, or `None` if its line has none before it.
+    /// `line` and `column` are 1-based.
+    pub fn lookup(&self, line: u32, column: u32) -> Option<OriginalLocation> {
+        let mappings = self.lines.get(line.checked_sub(1)? as usize)?;
+        let column = column.saturating_sub(1);
+        let index = mappings.partition_point(|mapping| mapping.generated_column <= column);
+        let mapping = mappings[..index].last()?;
+        Some(OriginalLocation {
+            source: self.sources.get(mapping.source as usize)?.clone(),
+            line: mapping.line + 1,
+            column: mapping.column + 1,
+        })
+    }
+}
+
+fn decode_mappings(mappings: &str) -> Result<Vec<Vec<Mapping>>> {
+    // Everything but the generated column is relative to the previous
+    // segment, across lines
+    let (mut source, mut line, mut column) = (0i64, 0i64, 0i64);
+    let mut lines = Vec::new();
+    for encoded_line in mappings.split(';') {
+        let mut generated_column = 0i64;
+        let mut decoded = Vec::new();
+        for segment in encoded_line.split(',').filter(|segment| !segment.is_empty()) {
+            let fields = decode_vlqs(segment)
+                .ok_or_else(|| Error::other(format!("invalid source map segment {segment:?}")))?;
+            generated_column += fields[0];
+            if fields.len() < 4 {
+                continue;
+            }
+            source += fields[1];
+            line += fields[2];
+            column += fields[3];
+            let field = |value: i64| u32::try_from(value).map_err(|_| Error::other(format!("negative position in source map segment {segment:?}")));
+            decoded.push(Mapping {
+                generated_column: field(generated_column)?,
+                source: field(source)?,
+                line: field(line)?,
+                column: field(column)?,
+            });
+        }
+        decoded.sort_by_key(|mapping| mapping.generated_column);
+        lines.push(decoded);
+    }
+    Ok(lines)
+}
+
+/// The base64 VLQ values of a segment
+fn decode_vlqs(segment: &str) -> Option<Vec<i64>> {
+    let mut values = Vec::new();
+    let (mut value, mut shift) = (0i64, 0);
+    for c in segment.bytes() {
+        let digit = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        } as i64;
+        value |= (digit & 31) << shift;
+        if digit & 32 != 0 {
+            shift += 5;
+            if shift > 60 {
+                return None;
+            }
+            continue;
+        }
+        values.push(if value & 1 == 1 { -(value >> 1) } else { value >> 1 });
+        (value, shift) = (0, 0);
+    }
+    (shift == 0 && !values.is_empty()).then_some(values)
+}
+
+/// The URL in a stylesheet's last `/*# sourceMappingURL=... */` comment
+pub fn source_mapping_url(css: &str) -> Option<&str> {
+    let start = css.rfind("sourceMappingURL=")? + "sourceMappingURL=".len();
+    let comment_start = css[..start].rfind("/*")?;
+    if !matches!(css[comment_start + 2..start].trim_start().as_bytes().first(), Some(b'#' | b'@')) {
+        return None;
+    }
+    let url = css[start..].split("*/").next()?.trim();
+    (!url.is_empty()).then_some(url)
+}
+
+/// The local file a stylesheet's source map URL refers to, relative to the
+/// stylesheet's directory. `None` for other schemes and `data:` URLs.
+pub fn local_source_map_path(stylesheet_dir: &Path, url: &str) -> Option<PathBuf> {
+    if url.contains(':') || url.starts_with('/') {
+        return None;
+    }
+    let path = url.split(['?', '#']).next()?;
+    Some(stylesheet_dir.join(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OriginalLocation, SourceMap, decode_vlqs, source_mapping_url};
+    use test_log::test;
+
+    #[test]
+    fn decodes_vlqs() {
+        assert_eq!(decode_vlqs("AAAA"), Some(vec![0, 0, 0, 0]));
+        assert_eq!(decode_vlqs("AACA"), Some(vec![0, 0, 1, 0]));
+        assert_eq!(decode_vlqs("D"), Some(vec![-1]));
+        assert_eq!(decode_vlqs("gB"), Some(vec![16]));
+        assert_eq!(decode_vlqs("g"), None);
+    }
+
+    #[test]
+    fn looks_up_original_positions() -> crate::result::Result<()> {
+        // line 1 from a.scss 1:1, column 6 from 3:3; line 2 from b.scss 10:1
+        let map = SourceMap::parse(r#"{
+            "version": 3,
+            "sourceRoot": "src/",
+            "sources": ["a.scss", "b.scss"],
+            "mappings": "AAAA,KAEE;ACQF"
+        }"#)?;
+        let location = |source: &str, line, column| Some(OriginalLocation { source: source.to_owned(), line, column });
+        assert_eq!(map.lookup(1, 1), location("src/a.scss", 1, 1));
+        assert_eq!(map.lookup(1, 5), location("src/a.scss", 1, 1));
+        assert_eq!(map.lookup(1, 9), location("src/a.scss", 3, 3));
+        assert_eq!(map.lookup(2, 4), location("src/b.scss", 10, 1));
+        assert_eq!(map.lookup(3, 1), None);
+        // Line 1's only mapping starts at column 3
+        let map = SourceMap::parse(r#"{"version": 3, "sources": ["a.scss"], "mappings": "EAAA"}"#)?;
+        assert_eq!(map.lookup(1, 2), None);
+        assert_eq!(map.lookup(1, 3), location("a.scss", 1, 1));
+        assert!(SourceMap::parse(r#"{"version": 2, "sources": [], "mappings": ""}"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn finds_source_mapping_urls() {
+        assert_eq!(source_mapping_url("a{}\n/*# sourceMappingURL=main.css.map */\n"), Some("main.css.map"));
+        assert_eq!(source_mapping_url("a{}/*@ sourceMappingURL=x.map*/"), Some("x.map"));
+        assert_eq!(source_mapping_url("a{} /* sourceMappingURL=x.map */"), None);
+        assert_eq!(source_mapping_url("a{}"), None);
+    }
+}
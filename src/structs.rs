@@ -4,12 +4,13 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fmt::Write as _;
 use std::hash::DefaultHasher;
 use std::hash::Hash;
 use std::hash::Hasher as _;
-
-use crate::element_to_string;
+use std::sync::OnceLock;
 
 pub fn element_id(value: scraper::ElementRef<'_>) -> u64 {
     let mut hasher = DefaultHasher::new();
@@ -17,26 +18,278 @@ pub fn element_id(value: scraper::ElementRef<'_>) -> u64 {
     hasher.finish()
 }
 
-#[derive(Debug, Clone, Eq, Ord, Serialize)]
+/// A stable identifier for an element: its local name and index among its
+/// element siblings, for each element from the root down, e.g.
+/// `html[0]/body[1]/div[3]`. Unlike `Element::id`, this does not depend on the
+/// parse, so it can be compared across runs and against other tools. Paths
+/// order the same way as a preorder traversal.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ElementPath(pub Vec<ElementPathStep>);
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ElementPathStep {
+    /// The index of the element among its parent's element children
+    pub index: usize,
+    pub name: String,
+    /// The index of the element among its parent's element children with the
+    /// same local name
+    pub type_index: usize,
+}
+
+impl From<scraper::ElementRef<'_>> for ElementPath {
+    fn from(value: scraper::ElementRef<'_>) -> Self {
+        let mut steps: Vec<_> = std::iter::once(value)
+            .chain(value.ancestors().filter_map(scraper::ElementRef::wrap))
+            .map(|element| {
+                let name = element.value().name();
+                let prev_siblings = element.prev_siblings().filter_map(scraper::ElementRef::wrap);
+                let mut index = 0;
+                let mut type_index = 0;
+                for sibling in prev_siblings {
+                    index += 1;
+                    if sibling.value().name() == name {
+                        type_index += 1;
+                    }
+                }
+                ElementPathStep {
+                    index,
+                    name: name.to_owned(),
+                    type_index,
+                }
+            })
+            .collect();
+        steps.reverse();
+        ElementPath(steps)
+    }
+}
+
+impl ElementPath {
+    /// Returns a CSS selector matching only this element, e.g.
+    /// `html:nth-child(1) > body:nth-child(2) > div:nth-child(4)`.
+    pub fn to_css_selector(&self) -> String {
+        let mut out = String::new();
+        for (i, step) in self.0.iter().enumerate() {
+            if i != 0 {
+                out.push_str(" > ");
+            }
+            cssparser::serialize_identifier(&step.name, &mut out).unwrap();
+            write!(&mut out, ":nth-child({})", step.index + 1).unwrap();
+        }
+        out
+    }
+
+    /// Parses a path from its `Display` form. That form leaves out each
+    /// step's `type_index`, so it is taken from the matching step of `xpath`
+    /// (as returned by `to_xpath`). Returns `None` if either is malformed or
+    /// they don't describe the same element.
+    pub fn parse(display: &str, xpath: &str) -> Option<Self> {
+        fn split_step(step: &str) -> Option<(&str, usize)> {
+            let (name, index) = step.strip_suffix(']')?.rsplit_once('[')?;
+            Some((name, index.parse().ok()?))
+        }
+        let display_steps: Vec<_> = display.split('/').map(split_step).collect::<Option<_>>()?;
+        let xpath_steps: Vec<_> = xpath.strip_prefix('/')?.split('/').map(split_step).collect::<Option<_>>()?;
+        if display_steps.len() != xpath_steps.len() {
+            return None;
+        }
+        display_steps
+            .into_iter()
+            .zip(xpath_steps)
+            .map(|((name, index), (xpath_name, xpath_index))| {
+                (name == xpath_name && xpath_index > 0).then(|| ElementPathStep {
+                    index,
+                    name: name.to_owned(),
+                    type_index: xpath_index - 1,
+                })
+            })
+            .collect::<Option<_>>()
+            .map(ElementPath)
+    }
+
+    /// Returns an XPath matching only this element, e.g.
+    /// `/html[1]/body[1]/div[3]`.
+    pub fn to_xpath(&self) -> String {
+        let mut out = String::new();
+        for step in &self.0 {
+            write!(&mut out, "/{}[{}]", step.name, step.type_index + 1).unwrap();
+        }
+        out
+    }
+}
+
+impl fmt::Display for ElementPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, step) in self.0.iter().enumerate() {
+            if i != 0 {
+                f.write_str("/")?;
+            }
+            write!(f, "{}[{}]", step.name, step.index)?;
+        }
+        Ok(())
+    }
+}
+
+/// Human-readable formats get the `Display` form. Binary formats get every
+/// step, since the `Display` form leaves out `type_index`.
+impl Serialize for ElementPath {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer
+    {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            self.0.serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ElementPath {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>
+    {
+        if deserializer.is_human_readable() {
+            Err(serde::de::Error::custom(
+                "element paths can only be deserialized from binary formats",
+            ))
+        } else {
+            Vec::deserialize(deserializer).map(ElementPath)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, Serialize, Deserialize)]
+#[serde(into = "ElementRepr", from = "ElementRepr")]
 pub struct Element {
     pub id: u64,
-    pub html: String,
+    pub path: ElementPath,
+    /// The line of the element's start tag in the html file
+    pub source_line: Option<u64>,
+    /// The column of the `<` of the element's start tag, in characters
+    pub source_column: Option<u64>,
+    /// How many declarations its `style` attribute has, if it has one.
+    /// These apply with no selector, above every author rule.
+    pub inline_style: Option<usize>,
+    /// Cheap to clone (atoms), unlike formatting the start tag, which most
+    /// callers never need. Empty for elements that were deserialized.
+    attrs: scraper::node::Attributes,
+    html: OnceLock<String>,
+}
+
+impl Element {
+    /// Returns the element's start tag, e.g. `<div class="a">`, formatting it
+    /// on first use.
+    pub fn html(&self) -> &str {
+        self.html.get_or_init(|| {
+            let name = self.path.0.last().map_or("", |step| step.name.as_str());
+            let mut out = String::new();
+            write!(&mut out, "<{name}").unwrap();
+            for (k, v) in &self.attrs {
+                write!(&mut out, " {}=\"{}\"", k.local, &**v).unwrap();
+            }
+            out.push('>');
+            out
+        })
+    }
+
+    /// The value of the element's attribute `name`. Always `None` for
+    /// elements that were deserialized, which only have their start tag.
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.iter().find(|(k, _)| &*k.local == name).map(|(_, v)| &**v)
+    }
+
+    /// Builds an element whose start tag is already known.
+    pub fn with_html(id: u64, path: ElementPath, html: String, source_line: Option<u64>) -> Self {
+        Self {
+            id,
+            path,
+            source_line,
+            source_column: None,
+            inline_style: None,
+            attrs: Default::default(),
+            html: OnceLock::from(html),
+        }
+    }
+}
+
+/// How `Element` is serialized: the same as before start tags were made lazy.
+#[derive(Serialize, Deserialize)]
+struct ElementRepr {
+    id: u64,
+    /// Always every step, so this round-trips through any format
+    #[serde(with = "path_steps")]
+    path: ElementPath,
+    html: String,
+    source_line: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source_column: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    inline_style: Option<usize>,
+}
+
+impl From<Element> for ElementRepr {
+    fn from(value: Element) -> Self {
+        let html = value.html().to_owned();
+        Self {
+            id: value.id,
+            path: value.path,
+            html,
+            source_line: value.source_line,
+            source_column: value.source_column,
+            inline_style: value.inline_style,
+        }
+    }
+}
+
+impl From<ElementRepr> for Element {
+    fn from(value: ElementRepr) -> Self {
+        let mut element = Element::with_html(value.id, value.path, value.html, value.source_line);
+        element.source_column = value.source_column;
+        element.inline_style = value.inline_style;
+        element
+    }
 }
 
 pub type Selector = selectors::parser::Selector<style::selector_parser::SelectorImpl>;
 
+mod path_steps {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::ElementPath;
+
+    pub fn serialize<S: Serializer>(path: &ElementPath, serializer: S) -> Result<S::Ok, S::Error> {
+        path.0.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ElementPath, D::Error> {
+        Vec::deserialize(deserializer).map(ElementPath)
+    }
+}
+
 impl From<scraper::ElementRef<'_>> for Element {
     fn from(value: scraper::ElementRef) -> Self {
         Self{
             id: element_id(value),
-            html: element_to_string(value),
+            path: ElementPath::from(value),
+            source_line: value.value().source_line(),
+            source_column: value.value().source_column(),
+            inline_style: value.value().inline_style_declarations(),
+            attrs: value.value().attrs.clone(),
+            html: OnceLock::new(),
         }
     }
 }
 
 impl PartialOrd for Element {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.id.partial_cmp(&other.id)
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Element {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.id.cmp(&other.id)
     }
 }
 
@@ -52,6 +305,19 @@ impl Hash for Element {
     }
 }
 
+/// Where a selector's rule comes in the cascade, for listing matched
+/// selectors in cascade order: by origin (user-agent before author), cascade
+/// layer, specificity and source order, lowest priority first. From
+/// `MatchingContext::cascade_priorities`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CascadePriority {
+    /// 0 for the user-agent origin, 1 for the author origin
+    pub origin: u8,
+    pub layer: style::stylesheets::layer_rule::LayerOrder,
+    pub specificity: u32,
+    pub source_order: u32,
+}
+
 /// Borrowed forms of result structs
 pub mod borrowed {
     use super::Selector;
@@ -128,43 +394,241 @@ pub mod owned {
     }
 }
 
+/// Result structs allocated in a bump arena, so that matching a large
+/// document doesn't allocate once per element, and the results are freed all
+/// at once. Only the borrowed form has a counterpart here: the owned form
+/// owns its strings, so would still allocate them.
+#[cfg(feature = "arena")]
+pub mod arena {
+    use super::Selector;
+    use super::borrowed::{DocumentMatches, ElementMatches, SelectorsOrSharedStyles};
+    use super::owned::{OwnedDocumentMatches, OwnedElementMatches, OwnedSelectorsOrSharedStyles};
+    use bumpalo::Bump;
+    use bumpalo::collections::Vec as BumpVec;
+    use scraper::ElementRef;
+
+    #[derive(Debug)]
+    pub struct ArenaDocumentMatches<'bump, 'a>(pub BumpVec<'bump, ArenaElementMatches<'bump, 'a>>);
+
+    #[derive(Debug)]
+    pub struct ArenaElementMatches<'bump, 'a> {
+        pub element: ElementRef<'a>,
+        pub selectors: BumpVec<'bump, &'a Selector>,
+    }
+
+    impl<'bump, 'a> ArenaDocumentMatches<'bump, 'a> {
+        pub fn new_in(bump: &'bump Bump) -> Self {
+            Self(BumpVec::new_in(bump))
+        }
+
+        pub fn to_borrowed(&self) -> DocumentMatches<'a> {
+            DocumentMatches(self.0.iter().map(|element_matches| ElementMatches {
+                element: element_matches.element,
+                selectors: SelectorsOrSharedStyles::Selectors(element_matches.selectors.iter().copied().collect()),
+            }).collect())
+        }
+    }
+
+    impl From<&ArenaDocumentMatches<'_, '_>> for OwnedDocumentMatches {
+        fn from(value: &ArenaDocumentMatches<'_, '_>) -> Self {
+            Self(value.0.iter().map(|element_matches| OwnedElementMatches {
+                element: element_matches.element.into(),
+                selectors: OwnedSelectorsOrSharedStyles::Selectors(
+                    element_matches.selectors.iter().map(|&selector| selector.clone()).collect(),
+                ),
+            }).collect())
+        }
+    }
+}
+
 pub mod set {
     use std::collections::{HashMap, HashSet};
+    use std::hash::{DefaultHasher, Hash as _, Hasher as _};
+
+    use std::sync::Arc;
 
     use ::cssparser::ToCss as _;
-    use selectors::parser::Selector;
-    use serde::Serialize;
+    use selectors::parser::Component;
+    use serde::{Deserialize, Serialize};
+    use style::selector_parser::SelectorImpl;
 
-    use super::Element;
+    use super::{CascadePriority, Element, Selector};
+    use super::diff::DocumentMatchesDiff;
+    use super::ser::{SerDocumentMatches, SerElementKey};
     use super::owned::{OwnedDocumentMatches, OwnedElementMatches, OwnedSelectorsOrSharedStyles};
+    use super::borrowed::{DocumentMatches, SelectorsOrSharedStyles};
 
-    #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
     pub struct SetDocumentMatches(pub HashMap<u64, SetElementMatches>);
 
     impl From<OwnedDocumentMatches> for SetDocumentMatches {
         fn from(OwnedDocumentMatches(v): OwnedDocumentMatches) -> Self {
             let num_elements = v.len();
+            let mut strings = SelectorStrings::default();
             let map: HashMap<_, _> = v.into_iter().map(|oem| {
-                (oem.element.id, oem.into())
+                let element_matches = SetElementMatches {
+                    element: oem.element,
+                    selectors: strings.convert(oem.selectors),
+                    ua_selectors: HashSet::new(),
+                    cascade_order: Vec::new(),
+                };
+                (element_matches.element.id, element_matches)
             }).collect();
             debug_assert_eq!(map.len(), num_elements);
             SetDocumentMatches(map)
         }
     }
 
+    /// Rebuilds the set form of previously serialized results. Which elements
+    /// shared styles is not kept by `SerDocumentMatches`, so every element gets
+    /// its selectors directly, and since the original ids are not kept either,
+    /// ids are derived from element paths instead.
+    impl From<SerDocumentMatches> for SetDocumentMatches {
+        fn from(SerDocumentMatches(map): SerDocumentMatches) -> Self {
+            let map = map
+                .into_iter()
+                .map(|(SerElementKey(path), element_matches)| {
+                    let mut hasher = DefaultHasher::new();
+                    path.hash(&mut hasher);
+                    let mut element = Element::with_html(
+                        hasher.finish(),
+                        path,
+                        element_matches.html,
+                        element_matches.line,
+                    );
+                    element.source_column = element_matches.column;
+                    element.inline_style = element_matches.inline_style;
+                    let selectors = SetSelectorsOrSharedStyles::Selectors(
+                        element_matches.selectors.into_iter().map(Arc::from).collect()
+                    );
+                    let ua_selectors = element_matches.ua_selectors.into_iter().map(Arc::from).collect();
+                    let cascade_order = element_matches.cascade_order.into_iter().map(Arc::from).collect();
+                    (element.id, SetElementMatches { element, selectors, ua_selectors, cascade_order })
+                })
+                .collect();
+            SetDocumentMatches(map)
+        }
+    }
+
+    /// Serializes each selector once per document and shares the string
+    /// between every element it matched, since popular selectors like `div`
+    /// can match thousands of elements. Selectors are keyed by their shared
+    /// heap allocation, which all clones of a `Selector` point to; a clone is
+    /// kept so the allocation can't be freed and reused while converting.
+    #[derive(Default)]
+    struct SelectorStrings(HashMap<*const Component<SelectorImpl>, (Selector, Arc<str>)>);
+
+    impl SelectorStrings {
+        fn get(&mut self, selector: &Selector) -> Arc<str> {
+            let key = selector.iter_raw_match_order().as_slice().as_ptr();
+            self.0
+                .entry(key)
+                .or_insert_with(|| (selector.clone(), Arc::from(selector.to_css_string())))
+                .1
+                .clone()
+        }
+
+        fn convert(&mut self, value: OwnedSelectorsOrSharedStyles) -> SetSelectorsOrSharedStyles {
+            match value {
+                OwnedSelectorsOrSharedStyles::Selectors(selectors) => {
+                    SetSelectorsOrSharedStyles::Selectors(selectors.iter().map(|s| self.get(s)).collect())
+                }
+                OwnedSelectorsOrSharedStyles::SharedWithElement(id) =>
+                    SetSelectorsOrSharedStyles::SharedWithElement(id),
+            }
+        }
+    }
+
     impl SetDocumentMatches {
-        pub fn find_selectors(&self, id: u64) -> &HashSet<String> {
+        pub fn find_selectors(&self, id: u64) -> &HashSet<Arc<str>> {
             match &self.0.get(&id).unwrap().selectors {
                 SetSelectorsOrSharedStyles::Selectors(hash_set) => hash_set,
                 SetSelectorsOrSharedStyles::SharedWithElement(id) => self.find_selectors(*id),
             }
         }
+
+        /// Returns the element this one shares styles with, if any.
+        pub fn shared_target(&self, element_matches: &SetElementMatches) -> Option<&Element> {
+            match &element_matches.selectors {
+                SetSelectorsOrSharedStyles::Selectors(_) => None,
+                SetSelectorsOrSharedStyles::SharedWithElement(id) => Some(&self.0.get(id).unwrap().element),
+            }
+        }
+
+        /// Records the user-agent stylesheet's matches, from matching its
+        /// selectors against the same document.
+        pub fn add_ua_matches(&mut self, matches: &DocumentMatches) {
+            let mut strings = SelectorStrings::default();
+            for element_matches in &matches.0 {
+                let SelectorsOrSharedStyles::Selectors(selectors) = &element_matches.selectors else {
+                    unreachable!("user-agent selectors are matched without style sharing");
+                };
+                if let Some(set_matches) = self.0.get_mut(&super::element_id(element_matches.element)) {
+                    set_matches.ua_selectors = selectors.iter().map(|s| strings.get(s)).collect();
+                }
+            }
+        }
+
+        /// Renames every matched selector, author and user-agent, to
+        /// `rename` of it. Selectors renamed to the same string are merged.
+        pub fn rename_selectors(&mut self, mut rename: impl FnMut(&Arc<str>) -> Arc<str>) {
+            let mut rename_all = |selectors: &mut HashSet<Arc<str>>| {
+                *selectors = selectors.iter().map(&mut rename).collect();
+            };
+            for element_matches in self.0.values_mut() {
+                if let SetSelectorsOrSharedStyles::Selectors(selectors) = &mut element_matches.selectors {
+                    rename_all(selectors);
+                }
+                rename_all(&mut element_matches.ua_selectors);
+                // A merged selector keeps its last, highest place
+                let mut renamed: Vec<_> = element_matches.cascade_order.iter().rev().map(&mut rename).collect();
+                let mut seen = HashSet::new();
+                renamed.retain(|selector| seen.insert(selector.clone()));
+                renamed.reverse();
+                element_matches.cascade_order = renamed;
+            }
+        }
+
+        /// Lists each element's matched selectors, author and user-agent, in
+        /// `cascade_order`, by their `priorities`. Selectors without one
+        /// come first.
+        pub fn order_by_cascade(&mut self, priorities: &HashMap<String, CascadePriority>) {
+            let orders: Vec<_> = self
+                .0
+                .iter()
+                .map(|(&id, element_matches)| {
+                    let mut order: Vec<_> =
+                        self.find_selectors(id).union(&element_matches.ua_selectors).cloned().collect();
+                    order.sort_by_cached_key(|selector| (priorities.get(&**selector).copied(), selector.clone()));
+                    (id, order)
+                })
+                .collect();
+            for (id, order) in orders {
+                self.0.get_mut(&id).unwrap().cascade_order = order;
+            }
+        }
+
+        /// Compares `self` (the "left" side) with `other` (the "right" side).
+        /// Elements are paired up by their `ElementPath`, so the two sides may
+        /// come from different parses of the same document.
+        pub fn diff(&self, other: &SetDocumentMatches) -> DocumentMatchesDiff {
+            DocumentMatchesDiff::new(self, other)
+        }
     }
 
-    #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
     pub struct SetElementMatches {
         pub element: Element,
         pub selectors: SetSelectorsOrSharedStyles,
+        /// Selectors of the user-agent stylesheet that matched, if it was
+        /// included. Never shared.
+        #[serde(default)]
+        pub ua_selectors: HashSet<Arc<str>>,
+        /// With `ParsedWebsite::with_cascade_order`, the matched author and
+        /// user-agent selectors (shared or not) in cascade order, lowest
+        /// priority first. Empty otherwise.
+        #[serde(default)]
+        pub cascade_order: Vec<Arc<str>>,
     }
 
     impl From<OwnedElementMatches> for SetElementMatches {
@@ -172,42 +636,296 @@ pub mod set {
             SetElementMatches {
                 element: value.element,
                 selectors: value.selectors.into(),
+                ua_selectors: HashSet::new(),
+                cascade_order: Vec::new(),
             }
         }
     }
 
-    #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
     pub enum SetSelectorsOrSharedStyles {
-        Selectors(HashSet<String>),
+        Selectors(HashSet<Arc<str>>),
         SharedWithElement(u64),
     }
 
     impl From<OwnedSelectorsOrSharedStyles> for SetSelectorsOrSharedStyles {
         fn from(value: OwnedSelectorsOrSharedStyles) -> Self {
-            match value {
-                OwnedSelectorsOrSharedStyles::Selectors(selectors) => {
-                    let selectors = selectors.iter().map(Selector::to_css_string).collect();
-                    SetSelectorsOrSharedStyles::Selectors(selectors)
-                },
-                OwnedSelectorsOrSharedStyles::SharedWithElement(id) =>
-                    SetSelectorsOrSharedStyles::SharedWithElement(id),
+            SelectorStrings::default().convert(value)
+        }
+    }
+}
+
+/// Differences between two `SetDocumentMatches`
+pub mod diff {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use serde::Serialize;
+
+    use super::ElementPath;
+    use super::set::{SetDocumentMatches, SetElementMatches};
+
+    #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+    pub struct DocumentMatchesDiff {
+        /// Elements that only the left side has results for
+        pub only_in_left: Vec<ElementPath>,
+        /// Elements that only the right side has results for
+        pub only_in_right: Vec<ElementPath>,
+        /// Elements whose (resolved) matched selectors differ
+        pub selectors_differ: Vec<SelectorsDiff>,
+        /// Elements that matched the same selectors, but shared styles with
+        /// different elements (or only shared styles on one side)
+        pub shared_target_differs: Vec<SharedTargetDiff>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+    pub struct SelectorsDiff {
+        pub element: ElementPath,
+        pub html: String,
+        pub only_in_left: BTreeSet<String>,
+        pub only_in_right: BTreeSet<String>,
+    }
+
+    /// `None` means the element was matched directly instead of sharing styles.
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+    pub struct SharedTargetDiff {
+        pub element: ElementPath,
+        pub left: Option<ElementPath>,
+        pub right: Option<ElementPath>,
+    }
+
+    impl DocumentMatchesDiff {
+        pub(super) fn new(left: &SetDocumentMatches, right: &SetDocumentMatches) -> Self {
+            fn by_path(matches: &SetDocumentMatches) -> BTreeMap<&ElementPath, &SetElementMatches> {
+                matches.0.values().map(|element_matches| (&element_matches.element.path, element_matches)).collect()
+            }
+            let left_by_path = by_path(left);
+            let right_by_path = by_path(right);
+            let mut diff = Self::default();
+            for (path, left_matches) in &left_by_path {
+                let Some(right_matches) = right_by_path.get(path) else {
+                    diff.only_in_left.push((*path).clone());
+                    continue;
+                };
+                let left_selectors = left.find_selectors(left_matches.element.id);
+                let right_selectors = right.find_selectors(right_matches.element.id);
+                if left_selectors != right_selectors {
+                    diff.selectors_differ.push(SelectorsDiff {
+                        element: (*path).clone(),
+                        html: left_matches.element.html().to_owned(),
+                        only_in_left: left_selectors.difference(right_selectors).map(|s| s.to_string()).collect(),
+                        only_in_right: right_selectors.difference(left_selectors).map(|s| s.to_string()).collect(),
+                    });
+                    continue;
+                }
+                let left_target = left.shared_target(left_matches).map(|element| &element.path);
+                let right_target = right.shared_target(right_matches).map(|element| &element.path);
+                if left_target != right_target {
+                    diff.shared_target_differs.push(SharedTargetDiff {
+                        element: (*path).clone(),
+                        left: left_target.cloned(),
+                        right: right_target.cloned(),
+                    });
+                }
+            }
+            diff.only_in_right = right_by_path
+                .keys()
+                .filter(|path| !left_by_path.contains_key(*path))
+                .map(|path| (*path).clone())
+                .collect();
+            diff
+        }
+
+        /// Returns true if both sides matched the same selectors for the same
+        /// elements. Differences in style sharing targets are ignored.
+        pub fn selectors_match(&self) -> bool {
+            self.only_in_left.is_empty() && self.only_in_right.is_empty() && self.selectors_differ.is_empty()
+        }
+
+        /// Returns true if there are no differences at all.
+        pub fn is_empty(&self) -> bool {
+            self.selectors_match() && self.shared_target_differs.is_empty()
+        }
+    }
+}
+
+/// Summary statistics about a document's matches
+pub mod stats {
+    use std::collections::{BTreeMap, HashMap, HashSet};
+
+    use serde::{Deserialize, Serialize};
+
+    use super::set::{SetDocumentMatches, SetSelectorsOrSharedStyles};
+
+    #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+    pub struct DocumentStats {
+        /// Number of elements in the document
+        pub element_count: usize,
+        /// Number of distinct selectors that matched at least one element
+        pub selector_count: usize,
+        /// Number of (element, selector) pairs that matched
+        pub matched_pairs: usize,
+        pub elements_with_zero_matches: usize,
+        pub max_selectors_on_one_element: usize,
+        pub mean_selectors_per_element: f64,
+        /// Number of elements that shared styles instead of being matched directly
+        pub elements_sharing_styles: usize,
+        #[serde(default)]
+        pub histograms: MatchHistograms,
+        #[serde(default)]
+        pub shape: DocumentShape,
+    }
+
+    /// The structure of the document, for relating matching time to it
+    #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+    pub struct DocumentShape {
+        /// Of the deepest element, the root being at depth 1
+        pub max_depth: usize,
+        /// Element children per element that has any
+        pub mean_children_per_parent: f64,
+        /// Class names used by any element. From attributes, so 0 for
+        /// deserialized matches.
+        pub distinct_classes: usize,
+        /// Ids used by any element. From attributes, as above.
+        pub distinct_ids: usize,
+    }
+
+    impl From<&SetDocumentMatches> for DocumentShape {
+        fn from(value: &SetDocumentMatches) -> Self {
+            let mut shape = DocumentShape::default();
+            let mut parents = HashSet::new();
+            let mut children = 0;
+            let mut classes = HashSet::new();
+            let mut ids = HashSet::new();
+            for element_matches in value.0.values() {
+                let element = &element_matches.element;
+                let steps = &element.path.0;
+                shape.max_depth = shape.max_depth.max(steps.len());
+                if let Some((_, parent)) = steps.split_last().filter(|(_, parent)| !parent.is_empty()) {
+                    parents.insert(parent);
+                    children += 1;
+                }
+                classes.extend(element.attr("class").into_iter().flat_map(str::split_ascii_whitespace));
+                ids.extend(element.attr("id"));
+            }
+            if !parents.is_empty() {
+                shape.mean_children_per_parent = children as f64 / parents.len() as f64;
+            }
+            shape.distinct_classes = classes.len();
+            shape.distinct_ids = ids.len();
+            shape
+        }
+    }
+
+    /// Each histogram maps a count to how many elements (or selectors) had
+    /// it.
+    #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct MatchHistograms {
+        /// Selectors matched per element, to how many elements matched that
+        /// many
+        pub selectors_per_element: BTreeMap<usize, usize>,
+        /// Elements matched per selector (that matched any), to how many
+        /// selectors matched that many
+        pub elements_per_selector: BTreeMap<usize, usize>,
+    }
+
+    impl MatchHistograms {
+        /// Adds `other`'s counts to these. A selector text that appears in
+        /// two documents counts once for each.
+        pub fn merge(&mut self, other: &MatchHistograms) {
+            for (count, frequency) in &other.selectors_per_element {
+                *self.selectors_per_element.entry(*count).or_default() += frequency;
+            }
+            for (count, frequency) in &other.elements_per_selector {
+                *self.elements_per_selector.entry(*count).or_default() += frequency;
             }
         }
     }
+
+    impl From<&SetDocumentMatches> for MatchHistograms {
+        fn from(value: &SetDocumentMatches) -> Self {
+            let mut histograms = MatchHistograms::default();
+            let mut elements_per_selector: HashMap<&str, usize> = HashMap::new();
+            for id in value.0.keys() {
+                let selectors = value.find_selectors(*id);
+                *histograms.selectors_per_element.entry(selectors.len()).or_default() += 1;
+                for selector in selectors {
+                    *elements_per_selector.entry(selector).or_default() += 1;
+                }
+            }
+            for count in elements_per_selector.into_values() {
+                *histograms.elements_per_selector.entry(count).or_default() += 1;
+            }
+            histograms
+        }
+    }
+
+    impl From<&SetDocumentMatches> for DocumentStats {
+        fn from(value: &SetDocumentMatches) -> Self {
+            let mut stats = DocumentStats::default();
+            let mut distinct_selectors = HashSet::new();
+            for (id, element_matches) in &value.0 {
+                let selectors = value.find_selectors(*id);
+                stats.element_count += 1;
+                stats.matched_pairs += selectors.len();
+                if selectors.is_empty() {
+                    stats.elements_with_zero_matches += 1;
+                }
+                stats.max_selectors_on_one_element = stats.max_selectors_on_one_element.max(selectors.len());
+                if matches!(element_matches.selectors, SetSelectorsOrSharedStyles::SharedWithElement(_)) {
+                    stats.elements_sharing_styles += 1;
+                }
+                distinct_selectors.extend(selectors.iter().map(|s| &**s));
+            }
+            stats.selector_count = distinct_selectors.len();
+            stats.histograms = MatchHistograms::from(value);
+            stats.shape = DocumentShape::from(value);
+            if stats.element_count > 0 {
+                stats.mean_selectors_per_element = stats.matched_pairs as f64 / stats.element_count as f64;
+            }
+            stats
+        }
+    }
 }
 
 pub mod ser {
     use std::collections::{BTreeMap, BTreeSet};
 
-    use serde::Serialize;
+    use serde::{Deserialize, Deserializer, Serialize};
 
     use crate::structs::set::SetSelectorsOrSharedStyles;
 
+    use super::ElementPath;
     use super::set::SetDocumentMatches;
 
     #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
     pub struct SerDocumentMatches(pub BTreeMap<SerElementKey, SerElementMatches>);
 
+    /// In human-readable formats, keys are in `ElementPath`'s `Display` form,
+    /// which is parsed back with the help of each element's `xpath`.
+    impl<'de> Deserialize<'de> for SerDocumentMatches {
+        fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+        where
+            D: Deserializer<'de>
+        {
+            if !deserializer.is_human_readable() {
+                return BTreeMap::deserialize(deserializer).map(SerDocumentMatches);
+            }
+            let map = BTreeMap::<String, SerElementMatches>::deserialize(deserializer)?;
+            map.into_iter()
+                .map(|(key, element_matches)| {
+                    let path = ElementPath::parse(&key, &element_matches.xpath).ok_or_else(|| {
+                        serde::de::Error::custom(format!(
+                            "element key {key:?} does not agree with xpath {:?}",
+                            element_matches.xpath
+                        ))
+                    })?;
+                    Ok((SerElementKey(path), element_matches))
+                })
+                .collect::<std::result::Result<_, _>>()
+                .map(SerDocumentMatches)
+        }
+    }
+
     impl From<&SetDocumentMatches> for SerDocumentMatches {
         fn from(value: &SetDocumentMatches) -> Self {
             let new_map: BTreeMap<_, _> = value.0
@@ -215,32 +933,61 @@ pub mod ser {
                 .map(|(k, v)| {
                     debug_assert_eq!(*k, v.element.id);
                     let selectors = value.find_selectors(v.element.id)
-                        .clone()
-                        .into_iter()
+                        .iter()
+                        .map(|s| s.to_string())
                         .collect();
-                    (SerElementKey(*k), SerElementMatches { html: v.element.html.clone(), selectors })
+                    (
+                        SerElementKey(v.element.path.clone()),
+                        SerElementMatches {
+                            html: v.element.html().to_owned(),
+                            css_path: v.element.path.to_css_selector(),
+                            xpath: v.element.path.to_xpath(),
+                            line: v.element.source_line,
+                            column: v.element.source_column,
+                            inline_style: v.element.inline_style,
+                            selectors,
+                            ua_selectors: v.ua_selectors.iter().map(|s| s.to_string()).collect(),
+                            cascade_order: v.cascade_order.iter().map(|s| s.to_string()).collect(),
+                        },
+                    )
                 }).collect();
             SerDocumentMatches(new_map)
         }
     }
 
-    #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
     pub struct SerElementMatches {
         pub html: String,
+        /// A CSS selector that matches only this element
+        pub css_path: String,
+        /// An XPath that matches only this element
+        pub xpath: String,
+        /// The line of the element's start tag in the html file
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub line: Option<u64>,
+        /// The column of the `<` of the element's start tag, in characters
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub column: Option<u64>,
+        /// How many declarations the element's `style` attribute has, if it
+        /// has one
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub inline_style: Option<usize>,
         pub selectors: BTreeSet<String>,
+        /// Selectors of the user-agent stylesheet that matched, with
+        /// `--include-ua-styles`
+        #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+        pub ua_selectors: BTreeSet<String>,
+        /// `selectors` and `ua_selectors` in cascade order, lowest priority
+        /// first, with `--cascade-order`
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub cascade_order: Vec<String>,
     }
 
-    #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
-    pub struct SerElementKey(pub u64);
-
-    impl Serialize for SerElementKey {
-        fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
-        where
-            S: serde::Serializer
-        {
-            serializer.serialize_str(&format!("element_{}", self.0))
-        }
-    }
+    /// Keys serialized elements by their `ElementPath`, so output is stable
+    /// across runs and in document order.
+    #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+    #[serde(transparent)]
+    pub struct SerElementKey(pub ElementPath);
 
     #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
     pub struct DebugSerDocumentMatches(pub BTreeMap<SerElementKey, DebugSerElementMatches>);
@@ -251,7 +998,15 @@ pub mod ser {
                 .iter()
                 .map(|(k, v)| {
                     debug_assert_eq!(*k, v.element.id);
-                    (SerElementKey(*k), DebugSerElementMatches { html: v.element.html.clone(), selectors: (&v.selectors).into() })
+                    let selectors = match &v.selectors {
+                        SetSelectorsOrSharedStyles::Selectors(selectors) =>
+                            DebugSerSelectorsOrSharedStyles::Selectors(selectors.iter().map(|s| s.to_string()).collect()),
+                        SetSelectorsOrSharedStyles::SharedWithElement(id) =>
+                            DebugSerSelectorsOrSharedStyles::SharedWithElement(
+                                SerElementKey(value.0.get(id).unwrap().element.path.clone())
+                            ),
+                    };
+                    (SerElementKey(v.element.path.clone()), DebugSerElementMatches { html: v.element.html().to_owned(), selectors })
                 }).collect();
             DebugSerDocumentMatches(new_map)
         }
@@ -266,16 +1021,235 @@ pub mod ser {
     #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
     pub enum DebugSerSelectorsOrSharedStyles {
         Selectors(BTreeSet<String>),
-        SharedWithElement(u64),
+        SharedWithElement(SerElementKey),
     }
+}
 
-    impl From<&SetSelectorsOrSharedStyles> for DebugSerSelectorsOrSharedStyles {
-        fn from(value: &SetSelectorsOrSharedStyles) -> Self {
-            match value {
-                SetSelectorsOrSharedStyles::Selectors(selectors) =>
-                    DebugSerSelectorsOrSharedStyles::Selectors(selectors.iter().cloned().collect()),
-                SetSelectorsOrSharedStyles::SharedWithElement(id) => DebugSerSelectorsOrSharedStyles::SharedWithElement(*id),
-            }
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use std::sync::Arc;
+
+    use super::{Element, ElementPath};
+    use super::set::{SetDocumentMatches, SetElementMatches, SetSelectorsOrSharedStyles};
+    use super::ser::SerDocumentMatches;
+    use super::stats::DocumentStats;
+
+    #[test]
+    fn element_paths_are_stable_and_in_document_order() {
+        let html = "<html><body><p>1</p><div><span>2</span></div><p>3</p></body></html>";
+        let paths = |document: &scraper::Html| -> Vec<ElementPath> {
+            document.root_element().descendent_elements().map(ElementPath::from).collect()
+        };
+        let first = paths(&scraper::Html::parse_document(html));
+        let second = paths(&scraper::Html::parse_document(html));
+        assert_eq!(first, second);
+        let strings: Vec<_> = first.iter().map(ElementPath::to_string).collect();
+        assert_eq!(strings, [
+            "html[0]",
+            "html[0]/head[0]",
+            "html[0]/body[1]",
+            "html[0]/body[1]/p[0]",
+            "html[0]/body[1]/div[1]",
+            "html[0]/body[1]/div[1]/span[0]",
+            "html[0]/body[1]/p[2]",
+        ]);
+        let mut sorted = first.clone();
+        sorted.sort();
+        assert_eq!(sorted, first);
+    }
+
+    #[test]
+    fn css_and_xpath_paths_locate_the_element() {
+        let html = "<html><body><p>1</p><div>2</div><p>3</p></body></html>";
+        let document = scraper::Html::parse_document(html);
+        let last_p = document
+            .select(&scraper::Selector::parse("p").unwrap())
+            .last()
+            .unwrap();
+        let path = ElementPath::from(last_p);
+        assert_eq!(path.to_xpath(), "/html[1]/body[1]/p[2]");
+        let css_path = path.to_css_selector();
+        assert_eq!(css_path, "html:nth-child(1) > body:nth-child(2) > p:nth-child(3)");
+        let selector = scraper::Selector::parse(&css_path).unwrap();
+        let found: Vec<_> = document.select(&selector).collect();
+        assert_eq!(found, [last_p]);
+    }
+
+    #[test]
+    fn elements_record_their_inline_styles() {
+        let html = "<p style='color: red; margin: 0'></p><p style=''></p><p></p>";
+        let document = scraper::Html::parse_document(html);
+        let inline_styles: Vec<_> = document
+            .select(&scraper::Selector::parse("p").unwrap())
+            .map(|p| Element::from(p).inline_style)
+            .collect();
+        // `margin` is four longhands
+        assert_eq!(inline_styles, [Some(5), Some(0), None]);
+        let matches = set_matches(html, &[("p", selectors(&[]))]);
+        let round_tripped = SetDocumentMatches::from(SerDocumentMatches::from(&matches));
+        let element = &round_tripped.0.values().next().unwrap().element;
+        assert_eq!(element.inline_style, Some(5));
+    }
+
+    fn set_matches(html: &str, selectors: &[(&str, SetSelectorsOrSharedStyles)]) -> SetDocumentMatches {
+        let document = scraper::Html::parse_document(html);
+        let mut map = HashMap::new();
+        for (selector, matched) in selectors {
+            let element = document
+                .select(&scraper::Selector::parse(selector).unwrap())
+                .next()
+                .unwrap();
+            let element = Element::from(element);
+            map.insert(element.id, SetElementMatches {
+                element,
+                selectors: matched.clone(),
+                ua_selectors: HashSet::new(),
+                cascade_order: Vec::new(),
+            });
         }
+        SetDocumentMatches(map)
+    }
+
+    fn selectors(selectors: &[&str]) -> SetSelectorsOrSharedStyles {
+        SetSelectorsOrSharedStyles::Selectors(selectors.iter().map(|s| Arc::from(*s)).collect::<HashSet<_>>())
+    }
+
+    #[test]
+    fn diff_classifies_differences() {
+        let html = "<html><body><p id='a'></p><p id='b'></p><p id='c'></p></body></html>";
+        let left = set_matches(html, &[
+            ("#a", selectors(&["p", "#a"])),
+            ("#b", selectors(&["p"])),
+            ("#c", selectors(&["p"])),
+        ]);
+        let right = {
+            let mut right = set_matches(html, &[
+                ("#a", selectors(&["p"])),
+                ("#b", selectors(&["p"])),
+            ]);
+            let b_id = *right.0.keys().find(|id| right.0[*id].element.html().contains("\"b\"")).unwrap();
+            let c = set_matches(html, &[("#c", SetSelectorsOrSharedStyles::SharedWithElement(b_id))]);
+            right.0.extend(c.0);
+            right
+        };
+        let diff = left.diff(&right);
+        assert!(diff.only_in_left.is_empty());
+        assert!(diff.only_in_right.is_empty());
+        assert_eq!(diff.selectors_differ.len(), 1);
+        assert_eq!(diff.selectors_differ[0].element.to_string(), "html[0]/body[1]/p[0]");
+        assert_eq!(diff.selectors_differ[0].only_in_left.iter().collect::<Vec<_>>(), ["#a"]);
+        assert!(diff.selectors_differ[0].only_in_right.is_empty());
+        assert_eq!(diff.shared_target_differs.len(), 1);
+        assert_eq!(diff.shared_target_differs[0].left, None);
+        assert_eq!(diff.shared_target_differs[0].right.as_ref().map(ElementPath::to_string).as_deref(), Some("html[0]/body[1]/p[1]"));
+        assert!(!diff.selectors_match());
+
+        let only_left = set_matches(html, &[("#a", selectors(&["p"]))]);
+        let diff = only_left.diff(&set_matches(html, &[]));
+        assert_eq!(diff.only_in_left.len(), 1);
+        assert!(left.diff(&left).is_empty());
+    }
+
+    #[test]
+    fn document_stats() {
+        let html = "<html><body><p id='a'></p><p id='b'></p></body></html>";
+        let mut matches = set_matches(html, &[
+            ("html", selectors(&[])),
+            ("body", selectors(&["body"])),
+            ("#a", selectors(&["p", "#a", "body > p"])),
+        ]);
+        let a_id = *matches.0.keys().find(|id| matches.0[*id].element.html().contains("\"a\"")).unwrap();
+        matches.0.extend(set_matches(html, &[("#b", SetSelectorsOrSharedStyles::SharedWithElement(a_id))]).0);
+        let stats = DocumentStats::from(&matches);
+        assert_eq!(stats.element_count, 4);
+        assert_eq!(stats.selector_count, 4);
+        assert_eq!(stats.matched_pairs, 7);
+        assert_eq!(stats.elements_with_zero_matches, 1);
+        assert_eq!(stats.max_selectors_on_one_element, 3);
+        assert_eq!(stats.elements_sharing_styles, 1);
+        assert_eq!(stats.mean_selectors_per_element, 1.75);
+        assert_eq!(stats.histograms.selectors_per_element, [(0, 1), (1, 1), (3, 2)].into());
+        assert_eq!(stats.histograms.elements_per_selector, [(1, 1), (2, 3)].into());
+        let mut merged = stats.histograms.clone();
+        merged.merge(&stats.histograms);
+        assert_eq!(merged.elements_per_selector, [(1, 2), (2, 6)].into());
+        // html has body, and body both ps
+        assert_eq!(stats.shape.max_depth, 3);
+        assert_eq!(stats.shape.mean_children_per_parent, 1.5);
+        assert_eq!((stats.shape.distinct_classes, stats.shape.distinct_ids), (0, 2));
+        assert_eq!(DocumentStats::from(&SetDocumentMatches(Default::default())), DocumentStats::default());
+    }
+
+    #[test]
+    fn element_paths_parse_from_display_and_xpath() {
+        let document = scraper::Html::parse_document("<html><body><p></p><div></div><p id='a'></p></body></html>");
+        let a = document.select(&scraper::Selector::parse("#a").unwrap()).next().unwrap();
+        let path = ElementPath::from(a);
+        assert_eq!(ElementPath::parse(&path.to_string(), &path.to_xpath()), Some(path));
+        assert_eq!(ElementPath::parse("html[0]/body[1]", "/html[1]/div[1]"), None);
+        assert_eq!(ElementPath::parse("html[0]/body[1]", "/html[1]"), None);
+        assert_eq!(ElementPath::parse("html[0]/body", "/html[1]/body[1]"), None);
+    }
+
+    #[test]
+    fn ser_matches_round_trip_through_yaml() {
+        let html = "<html><body><p id='a'></p><p id='b'></p></body></html>";
+        let mut matches = set_matches(html, &[
+            ("html", selectors(&[])),
+            ("body", selectors(&["body"])),
+            ("#a", selectors(&["p", "#a"])),
+        ]);
+        let a_id = *matches.0.keys().find(|id| matches.0[*id].element.html().contains("\"a\"")).unwrap();
+        matches.0.extend(set_matches(html, &[("#b", SetSelectorsOrSharedStyles::SharedWithElement(a_id))]).0);
+        let ser = SerDocumentMatches::from(&matches);
+        let yaml = serde_yml::to_string(&ser).unwrap();
+        let read: SerDocumentMatches = serde_yml::from_str(&yaml).unwrap();
+        assert_eq!(read, ser);
+        let set = SetDocumentMatches::from(read);
+        let diff = matches.diff(&set);
+        assert!(diff.selectors_match());
+        // Style sharing isn't kept
+        assert_eq!(diff.shared_target_differs.len(), 1);
+
+        // The set form round-trips directly too
+        let yaml = serde_yml::to_string(&matches).unwrap();
+        let read: SetDocumentMatches = serde_yml::from_str(&yaml).unwrap();
+        assert_eq!(read, matches);
+    }
+
+    #[test]
+    fn selector_strings_are_shared_between_elements() {
+        let document = scraper::Html::parse_document("<p>a</p><p>b</p><p>c</p>");
+        let selector = style::selector_parser::SelectorParser::parse_author_origin_no_namespace(
+            "p",
+            &style::stylesheets::UrlExtraData::from(url::Url::parse("about:blank").unwrap()),
+        ).unwrap().slice()[0].clone();
+        let selectors = [selector];
+        let result = crate::match_selectors(&document, &selectors);
+        let matches = SetDocumentMatches::from(super::owned::OwnedDocumentMatches::from(&result));
+        let strings: Vec<&Arc<str>> = matches
+            .0
+            .values()
+            .filter_map(|element_matches| match &element_matches.selectors {
+                SetSelectorsOrSharedStyles::Selectors(selectors) => selectors.iter().next(),
+                SetSelectorsOrSharedStyles::SharedWithElement(_) => None,
+            })
+            .collect();
+        assert_eq!(strings.len(), 3);
+        assert!(strings.iter().all(|s| Arc::ptr_eq(s, strings[0])));
+        assert_eq!(&*strings[0] as &str, "p");
+    }
+
+    #[test]
+    fn element_html_is_built_on_demand() {
+        let document = scraper::Html::parse_document("<p class='x' id='a'>text</p>");
+        let p = document.select(&scraper::Selector::parse("p").unwrap()).next().unwrap();
+        let element = Element::from(p);
+        assert_eq!(element.html(), "<p class=\"x\" id=\"a\">");
+        let yaml = serde_yml::to_string(&element).unwrap();
+        let read: Element = serde_yml::from_str(&yaml).unwrap();
+        assert_eq!(read.html(), element.html());
+        assert_eq!(read.path, element.path);
     }
 }
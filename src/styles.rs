@@ -0,0 +1,224 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The declared value of chosen properties (color, display, font-size, ...)
+//! for each element: the declaration that wins the cascade among the rules
+//! the Stylist matched and the element's `style` attribute, as written.
+//!
+//! The winner is chosen by importance, origin (user-agent, author, then the
+//! `style` attribute), cascade layer, specificity and source order. Values
+//! are not computed: relative units, percentages, `var()` and keywords like
+//! `inherit` are printed as they are, and a property nothing declares is
+//! left out rather than inherited. Computing values needs Stylo's cascade
+//! proper, which needs a `TElement` that scraper's elements don't implement.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use scraper::ElementRef;
+use selectors::context::SelectorCaches;
+use selectors::matching;
+use serde::Serialize;
+use style::properties::{PropertyDeclarationBlock, PropertyId};
+use style::shared_lock::SharedRwLockReadGuard;
+use style::stylesheets::Origin;
+use style::stylesheets::layer_rule::LayerOrder;
+use style::stylist::{CascadeData, Rule};
+
+use crate::parse::ParsedWebsite;
+use crate::result::{Error, Result};
+use crate::structs::ElementPath;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ElementStyle {
+    /// As returned by `ElementPath::to_xpath`
+    pub element: String,
+    /// Properties nothing declares are left out
+    pub declared: BTreeMap<String, String>,
+}
+
+/// Returns the declared value of each of `properties` for every element of
+/// `website`, in document order.
+pub fn declared_styles(website: &ParsedWebsite, properties: &[String]) -> Result<Vec<ElementStyle>> {
+    let properties = properties
+        .iter()
+        .map(|name| match PropertyId::parse_enabled_for_all_content(name) {
+            Ok(id) => Ok((name.as_str(), id)),
+            Err(()) => Err(Error::other(format!("unknown property {name:?}"))),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let matching_context = website.get_matcher();
+    let guard = matching_context.stylesheet_lock().read();
+    let cascade_data = matching_context.stylist().cascade_data();
+    let mut declared = Declared {
+        properties: &properties,
+        origins: [Origin::UserAgent, Origin::Author].map(|origin| cascade_data.borrow_for_origin(origin)),
+        guard: &guard,
+        caches: SelectorCaches::default(),
+    };
+    let mut styles = Vec::new();
+    for element in std::iter::once(website.document().root_element()).chain(website.document().root_element().descendent_elements()) {
+        styles.push(ElementStyle {
+            element: ElementPath::from(element).to_xpath(),
+            declared: declared.element_styles(element),
+        });
+    }
+    Ok(styles)
+}
+
+struct Declared<'a> {
+    properties: &'a [(&'a str, PropertyId)],
+    /// The user-agent and author origins
+    origins: [&'a CascadeData; 2],
+    guard: &'a SharedRwLockReadGuard<'a>,
+    caches: SelectorCaches,
+}
+
+/// Where a declaration comes in the cascade
+#[derive(Clone, Copy)]
+struct Precedence {
+    important: bool,
+    /// 0 for the user-agent origin, 1 for the author origin, 2 for the
+    /// `style` attribute
+    origin: u8,
+    /// `None` for the `style` attribute
+    layer: Option<LayerOrder>,
+    specificity: u32,
+    source_order: u32,
+}
+
+impl Precedence {
+    fn compare(&self, other: &Self) -> Ordering {
+        // Importance reverses the order of origins and of layers, but the
+        // `style` attribute still beats the author's rules
+        let origin = |precedence: &Self| match (precedence.important, precedence.origin) {
+            (true, 0) => 3,
+            (_, origin) => origin,
+        };
+        let layers = match (self.layer, other.layer) {
+            (Some(a), Some(b)) if self.important => b.cmp(&a),
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => Ordering::Equal,
+        };
+        self.important
+            .cmp(&other.important)
+            .then(origin(self).cmp(&origin(other)))
+            .then(layers)
+            .then(self.specificity.cmp(&other.specificity))
+            .then(self.source_order.cmp(&other.source_order))
+    }
+}
+
+impl Declared<'_> {
+    fn element_styles(&mut self, element: ElementRef<'_>) -> BTreeMap<String, String> {
+        let mut context = matching::MatchingContext::new(
+            matching::MatchingMode::Normal,
+            None,
+            &mut self.caches,
+            matching::QuirksMode::NoQuirks,
+            matching::NeedsSelectorFlags::No,
+            matching::MatchingForInvalidation::No,
+        );
+        let mut rules: Vec<(u8, &Rule, Option<LayerOrder>)> = Vec::new();
+        for (origin, cascade_data) in self.origins.iter().enumerate() {
+            let Some(selector_map) = cascade_data.normal_rules(&[]) else {
+                continue;
+            };
+            selector_map.lookup(element, matching::QuirksMode::NoQuirks, None, |rule| {
+                if matching::matches_selector(&rule.selector, 0, None, &element, &mut context).0 {
+                    rules.push((origin as u8, rule, Some(cascade_data.layer_order_for(rule.layer_id))));
+                }
+                true
+            });
+        }
+        let inline_guard = element.value().style_block_lock.read();
+        let inline = element.value().attr("style").map(|_| element.value().style_block.read_with(&inline_guard));
+
+        let mut styles = BTreeMap::new();
+        for (name, id) in self.properties {
+            let mut winner: Option<(Precedence, String)> = None;
+            let mut consider = |block: &PropertyDeclarationBlock, precedence: Precedence| {
+                let mut value = String::new();
+                if block.property_value_to_css(id, &mut value).is_err() || value.is_empty() {
+                    return;
+                }
+                let precedence = Precedence { important: block.property_priority(id).important(), ..precedence };
+                if winner.as_ref().is_none_or(|(winner, _)| precedence.compare(winner) != Ordering::Less) {
+                    winner = Some((precedence, value));
+                }
+            };
+            for &(origin, rule, layer) in &rules {
+                let precedence = Precedence {
+                    important: false,
+                    origin,
+                    layer,
+                    specificity: rule.selector.specificity(),
+                    source_order: rule.source_order,
+                };
+                consider(rule.style_source.get().read_with(self.guard), precedence);
+            }
+            if let Some(inline) = inline {
+                let precedence = Precedence { important: false, origin: 2, layer: None, specificity: 0, source_order: 0 };
+                consider(inline, precedence);
+            }
+            if let Some((_, value)) = winner {
+                styles.insert(name.to_string(), value);
+            }
+        }
+        styles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{ElementStyle, declared_styles};
+    use crate::parse::{ParsedWebsite, parse_stylesheet};
+    use scraper::Html;
+    use style::shared_lock::SharedRwLock;
+    use style::stylesheets::UrlExtraData;
+    use test_log::test;
+
+    #[test]
+    fn picks_the_winning_declarations() {
+        let lock = SharedRwLock::new();
+        let css = "div { color: green; font-size: 20px } \
+                   p { color: red; display: inline } \
+                   #y { color: purple } \
+                   .a { color: blue !important; display: block } \
+                   #x { color: inherit; display: initial } \
+                   @layer base { #x { display: grid } p { font-size: 1em !important } } \
+                   p { font-size: 2em !important }";
+        let url_data = UrlExtraData::from(url::Url::parse("about:blank").unwrap());
+        let stylesheet = parse_stylesheet(css, url_data, &lock).unwrap();
+        let document = Html::parse_document(r#"<div><p id="y" class="a"></p><p style="color: orange"></p><span id="x"></span></div>"#);
+        let website = ParsedWebsite::new("test".to_owned(), document, vec![stylesheet], lock);
+        let properties = ["color", "display", "font-size"].map(str::to_owned);
+        let styles = declared_styles(&website, &properties).unwrap();
+        let style = |element: &str, declared: &[(&str, &str)]| ElementStyle {
+            element: element.to_owned(),
+            declared: declared.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<BTreeMap<_, _>>(),
+        };
+        assert_eq!(styles, [
+            style("/html[1]", &[]),
+            style("/html[1]/head[1]", &[]),
+            style("/html[1]/body[1]", &[]),
+            style("/html[1]/body[1]/div[1]", &[("color", "green"), ("font-size", "20px")]),
+            // An important declaration in a layer beats an unlayered one
+            style("/html[1]/body[1]/div[1]/p[1]", &[("color", "blue"), ("display", "block"), ("font-size", "1em")]),
+            style("/html[1]/body[1]/div[1]/p[2]", &[("color", "orange"), ("display", "inline"), ("font-size", "1em")]),
+            // A normal declaration in a layer loses to an unlayered one
+            style("/html[1]/body[1]/div[1]/span[1]", &[("color", "inherit"), ("display", "initial")]),
+        ]);
+    }
+
+    #[test]
+    fn rejects_unknown_properties() {
+        let website = ParsedWebsite::new("test".to_owned(), Html::parse_document(""), Vec::new(), SharedRwLock::new());
+        assert!(declared_styles(&website, &["colour".to_owned()]).is_err());
+    }
+}
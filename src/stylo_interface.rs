@@ -1,5 +1,6 @@
 /// Contains structures/functions which are insignificant other than to interface with Stylo
 use selectors::matching;
+use crate::device::{ColorScheme, DeviceOptions};
 use style::media_queries::Device;
 use style::media_queries::MediaType;
 use style::properties::ComputedValues;
@@ -7,13 +8,16 @@ use style::properties::style_structs::Font;
 use style::queries::values::PrefersColorScheme;
 use style::style_resolver::{PrimaryStyle, ResolvedStyle};
 use style::servo::media_queries::FontMetricsProvider;
+#[cfg(not(feature = "font_metrics"))]
 use style::values::computed::{CSSPixelLength, font::GenericFontFamily, font::QueryFontMetricsFlags, Length};
 use style::Atom;
 use style::context::{RegisteredSpeculativePainter, RegisteredSpeculativePainters};
 
+#[cfg(not(feature = "font_metrics"))]
 #[derive(Debug)]
 struct TestFontMetricsProvider;
 
+#[cfg(not(feature = "font_metrics"))]
 impl FontMetricsProvider for TestFontMetricsProvider {
     fn query_font_metrics(
         &self,
@@ -38,16 +42,33 @@ impl FontMetricsProvider for TestFontMetricsProvider {
     }
 }
 
-pub fn mock_device() -> Device {
+#[cfg(feature = "font_metrics")]
+fn font_metrics_provider() -> Box<dyn FontMetricsProvider> {
+    Box::new(crate::font_metrics::SystemFontMetricsProvider::new())
+}
+
+#[cfg(not(feature = "font_metrics"))]
+fn font_metrics_provider() -> Box<dyn FontMetricsProvider> {
+    Box::new(TestFontMetricsProvider)
+}
+
+pub fn device(options: &DeviceOptions) -> Device {
     let default_font = Font::initial_values();
+    let media_type = match options.media_type {
+        crate::device::MediaType::Screen => MediaType::screen(),
+        crate::device::MediaType::Print => MediaType::print(),
+    };
     Device::new(
-        MediaType::screen(),
+        media_type,
         matching::QuirksMode::NoQuirks,
-        euclid::Size2D::new(1200.0, 800.0),
-        euclid::Scale::new(1.0),
-        Box::new(TestFontMetricsProvider),
+        euclid::Size2D::new(options.viewport.width, options.viewport.height),
+        euclid::Scale::new(options.device_pixel_ratio),
+        font_metrics_provider(),
         ComputedValues::initial_values_with_font_override(default_font),
-        PrefersColorScheme::Light,
+        match options.media_features.prefers_color_scheme {
+            ColorScheme::Light => PrefersColorScheme::Light,
+            ColorScheme::Dark => PrefersColorScheme::Dark,
+        },
     )
 }
 
@@ -0,0 +1,207 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `timings.json`: each website's wall time per run and algorithm, kept
+//! across runs for tracking regressions in CI, e.g.
+//!
+//! ```json
+//! {"runs": [{"unix_time": 1760000000, "algorithm": "Naive", "websites": {"example.com": 0.0123}}]}
+//! ```
+//!
+//! A website's time covers discovery through matching (`instrument`
+//! phases), but not serializing its results, which depends on the output
+//! format rather than on the algorithm.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Algorithm;
+use crate::instrument::{Phase, PhaseReport};
+use crate::result::{Error, IntoResultExt as _, Result};
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TimingsFile {
+    /// Oldest first
+    pub runs: Vec<TimingsRun>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TimingsRun {
+    /// When the run finished, in seconds since the Unix epoch
+    pub unix_time: u64,
+    pub algorithm: Algorithm,
+    /// Seconds per website
+    pub websites: BTreeMap<String, f64>,
+}
+
+impl TimingsRun {
+    pub fn new(algorithm: Algorithm, reports: &BTreeMap<String, PhaseReport>) -> Self {
+        let websites = reports
+            .iter()
+            .map(|(name, report)| {
+                let time: Duration = report
+                    .timings
+                    .0
+                    .iter()
+                    .filter(|(phase, _)| **phase != Phase::Serialize)
+                    .map(|(_, time)| *time)
+                    .sum();
+                (name.clone(), time.as_secs_f64())
+            })
+            .collect();
+        let unix_time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        Self { unix_time, algorithm, websites }
+    }
+}
+
+impl TimingsFile {
+    /// Reads the file at `path`, or returns an empty one if there is none.
+    pub fn read(path: &Path) -> Result<Self> {
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(path).into_result(Some(path.to_path_buf()))?;
+        serde_json::from_str(&contents)
+            .map_err(|e| Error::other(format!("invalid timings file {}: {e}", path.display())))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).unwrap();
+        fs::write(path, json).into_result(Some(path.to_path_buf()))
+    }
+
+    /// Adds `run` to the file at `path`, creating it if it doesn't exist.
+    pub fn append(path: &Path, run: TimingsRun) -> Result<()> {
+        let mut file = Self::read(path)?;
+        file.runs.push(run);
+        file.write(path)
+    }
+
+    /// The algorithms with runs, in order of their last run.
+    fn algorithms(&self) -> Vec<Algorithm> {
+        let mut algorithms = Vec::new();
+        for run in self.runs.iter().rev() {
+            if !algorithms.contains(&run.algorithm) {
+                algorithms.insert(0, run.algorithm);
+            }
+        }
+        algorithms
+    }
+
+    /// The last run of `algorithm`, and the one before it.
+    fn last_runs(&self, algorithm: Algorithm) -> (Option<&TimingsRun>, Option<&TimingsRun>) {
+        let mut runs = self.runs.iter().rev().filter(|run| run.algorithm == algorithm);
+        (runs.next(), runs.next())
+    }
+}
+
+/// A website that took longer than its baseline by more than the threshold.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Regression {
+    pub website: String,
+    pub algorithm: Algorithm,
+    pub baseline_seconds: f64,
+    pub current_seconds: f64,
+    /// `current / baseline - 1`, e.g. 0.25 for 25% slower
+    pub slowdown: f64,
+}
+
+/// Compares the last run of each algorithm in `current` with the last run of
+/// the same algorithm in `baseline`, or, without a baseline, with the run of
+/// that algorithm before it in `current`. Flags websites in both runs whose
+/// time grew by more than `threshold` (a fraction, e.g. 0.1 for 10%).
+pub fn compare(current: &TimingsFile, baseline: Option<&TimingsFile>, threshold: f64) -> Vec<Regression> {
+    let mut regressions = Vec::new();
+    for algorithm in current.algorithms() {
+        let (Some(current_run), previous) = current.last_runs(algorithm) else {
+            continue;
+        };
+        let baseline_run = match baseline {
+            Some(baseline) => baseline.last_runs(algorithm).0,
+            None => previous,
+        };
+        let Some(baseline_run) = baseline_run else {
+            continue;
+        };
+        for (website, &current_seconds) in &current_run.websites {
+            let Some(&baseline_seconds) = baseline_run.websites.get(website) else {
+                continue;
+            };
+            if baseline_seconds <= 0.0 {
+                continue;
+            }
+            let slowdown = current_seconds / baseline_seconds - 1.0;
+            if slowdown > threshold {
+                regressions.push(Regression {
+                    website: website.clone(),
+                    algorithm,
+                    baseline_seconds,
+                    current_seconds,
+                    slowdown,
+                });
+            }
+        }
+    }
+    regressions
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{TimingsFile, TimingsRun, compare};
+    use crate::Algorithm;
+    use crate::result::{IntoResultExt as _, Result};
+    use test_log::test;
+
+    fn run(algorithm: Algorithm, websites: &[(&str, f64)]) -> TimingsRun {
+        TimingsRun {
+            unix_time: 0,
+            algorithm,
+            websites: websites.iter().map(|&(name, seconds)| (name.to_owned(), seconds)).collect(),
+        }
+    }
+
+    #[test]
+    fn appends_runs() -> Result<()> {
+        let dir = tempfile::tempdir().into_result(None)?;
+        let path = dir.path().join("timings.json");
+        TimingsFile::append(&path, run(Algorithm::Naive, &[("a", 1.0)]))?;
+        TimingsFile::append(&path, TimingsRun::new(Algorithm::Mach7, &BTreeMap::new()))?;
+        let file = TimingsFile::read(&path)?;
+        assert_eq!(file.runs.len(), 2);
+        assert_eq!(file.runs[0], run(Algorithm::Naive, &[("a", 1.0)]));
+        assert_eq!(file.runs[1].algorithm, Algorithm::Mach7);
+        Ok(())
+    }
+
+    #[test]
+    fn flags_regressions_above_the_threshold() {
+        let current = TimingsFile {
+            runs: vec![
+                run(Algorithm::Naive, &[("a", 1.0), ("b", 1.0)]),
+                run(Algorithm::Mach7, &[("a", 1.0)]),
+                run(Algorithm::Naive, &[("a", 1.05), ("b", 1.5), ("new", 9.0)]),
+            ],
+        };
+        let regressions = compare(&current, None, 0.1);
+        assert_eq!(regressions.len(), 1);
+        assert_eq!(regressions[0].website, "b");
+        assert_eq!(regressions[0].algorithm, Algorithm::Naive);
+        assert!((regressions[0].slowdown - 0.5).abs() < 1e-9);
+
+        let baseline = TimingsFile { runs: vec![run(Algorithm::Mach7, &[("a", 0.5)])] };
+        let regressions = compare(&current, Some(&baseline), 0.1);
+        assert_eq!(regressions.len(), 1);
+        assert_eq!(regressions[0].algorithm, Algorithm::Mach7);
+    }
+}
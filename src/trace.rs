@@ -0,0 +1,214 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! A log of every selector and element pair an algorithm decided on, one
+//! JSON object per line, for finding where two algorithms part ways.
+//!
+//! Each pair records what the algorithm decided, what naive matching decides,
+//! and, if the selector doesn't match, the compound that rejected it (as in
+//! `explain`). Stylo doesn't say which of its shortcuts it took, so two are
+//! reconstructed: an element whose styles were shared records the element it
+//! shared with, and a rejection notes whether the ancestor Bloom filter would
+//! have rejected the selector without matching it. Tracing is slow, since
+//! each pair is explained separately.
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write as _};
+use std::path::Path;
+
+use cssparser::ToCss as _;
+use scraper::ElementRef;
+use selectors::parser::{Combinator, Component};
+use serde::Serialize;
+
+use crate::explain::{self, find_element};
+use crate::parse::ParsedWebsite;
+use crate::result::{IntoResultExt as _, Result};
+use crate::structs::set::{SetDocumentMatches, SetSelectorsOrSharedStyles};
+use crate::structs::{ElementPath, Selector, element_id};
+
+#[derive(Clone, Debug, Default)]
+pub struct TraceFilter {
+    /// Only this element, as accepted by `explain::find_element`
+    pub element: Option<String>,
+    /// Only pairs the algorithm decided differently from naive matching
+    pub discrepancies_only: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TraceEvent<'a> {
+    pub website: &'a str,
+    /// XPath
+    pub element: String,
+    pub selector: String,
+    /// What the algorithm decided
+    pub matched: bool,
+    pub naive_matched: bool,
+    /// The compound that didn't match, if the selector didn't
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rejected_by: Option<String>,
+    /// Whether the Bloom filter's ancestor check rejects the selector. This
+    /// checks every id, class and type selector the filter would, so it can
+    /// reject more than Stylo's filter, which keeps at most four.
+    pub bloom_rejects: bool,
+    /// The XPath of the element whose styles this one reused, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shared_with: Option<String>,
+}
+
+pub struct TraceWriter {
+    out: BufWriter<File>,
+    filter: TraceFilter,
+    events: usize,
+}
+
+impl TraceWriter {
+    pub fn create(path: &Path, filter: TraceFilter) -> Result<Self> {
+        let file = File::create(path).into_result(Some(path.to_path_buf()))?;
+        Ok(Self { out: BufWriter::new(file), filter, events: 0 })
+    }
+
+    /// Writes the trace of `matches`, which an algorithm found for `website`.
+    pub fn trace_website(&mut self, website: &ParsedWebsite, matches: &SetDocumentMatches) -> Result<()> {
+        let selectors = website.get_matcher().get_selectors();
+        let document = website.document();
+        let root = document.root_element();
+        let elements: Vec<ElementRef> = match &self.filter.element {
+            Some(key) => find_element(document, key).into_iter().collect(),
+            None => std::iter::once(root).chain(root.descendent_elements()).collect(),
+        };
+        for element in elements {
+            let id = element_id(element);
+            let matched = matches.find_selectors(id);
+            let shared_with = matches.0.get(&id).and_then(|element_matches| match element_matches.selectors {
+                SetSelectorsOrSharedStyles::SharedWithElement(_) => {
+                    matches.shared_target(element_matches).map(|target| target.path.to_xpath())
+                }
+                SetSelectorsOrSharedStyles::Selectors(_) => None,
+            });
+            let ancestors = AncestorKeys::new(element);
+            let xpath = ElementPath::from(element).to_xpath();
+            for selector in &selectors {
+                let css = selector.to_css_string();
+                let explanation = explain::explain_selector(element, selector);
+                let event = TraceEvent {
+                    website: &website.name,
+                    element: xpath.clone(),
+                    matched: matched.contains(css.as_str()),
+                    naive_matched: explanation.matched,
+                    rejected_by: (!explanation.matched)
+                        .then(|| explanation.steps.last().map(|step| step.compound.clone()))
+                        .flatten(),
+                    bloom_rejects: !explanation.matched && ancestors.rejects(selector),
+                    shared_with: shared_with.clone(),
+                    selector: css,
+                };
+                if self.filter.discrepancies_only && event.matched == event.naive_matched {
+                    continue;
+                }
+                serde_json::to_writer(&mut self.out, &event).map_err(std::io::Error::from).into_result(None)?;
+                self.out.write_all(b"\n").into_result(None)?;
+                self.events += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flushes the trace, returning how many events were written.
+    pub fn finish(mut self) -> Result<usize> {
+        self.out.flush().into_result(None)?;
+        Ok(self.events)
+    }
+}
+
+/// The type, id and class names of an element's ancestors, which is what the
+/// Bloom filter holds.
+struct AncestorKeys(HashSet<String>);
+
+impl AncestorKeys {
+    fn new(element: ElementRef) -> Self {
+        let mut keys = HashSet::new();
+        for ancestor in element.ancestors().filter_map(ElementRef::wrap) {
+            let value = ancestor.value();
+            keys.insert(format!("type {}", value.name().to_ascii_lowercase()));
+            keys.extend(value.id().map(|id| format!("id {id}")));
+            keys.extend(value.classes().map(|class| format!("class {class}")));
+        }
+        Self(keys)
+    }
+
+    /// Like the Bloom filter, only compounds reached through `>` and ` `
+    /// from the rightmost one are checked, up to the first sibling
+    /// combinator.
+    fn rejects(&self, selector: &Selector) -> bool {
+        let mut iter = selector.iter();
+        for _ in iter.by_ref() {}
+        while let Some(combinator) = iter.next_sequence() {
+            if !matches!(combinator, Combinator::Child | Combinator::Descendant) {
+                return false;
+            }
+            for component in iter.by_ref() {
+                let key = match component {
+                    Component::LocalName(local_name) => format!("type {}", local_name.lower_name),
+                    Component::ID(id) => format!("id {}", id.0),
+                    Component::Class(class) => format!("class {}", class.0),
+                    _ => continue,
+                };
+                if !self.0.contains(&key) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead as _;
+
+    use super::{TraceFilter, TraceWriter};
+    use crate::parse::ParsedWebsite;
+    use crate::{Algorithm, do_website};
+    use style::shared_lock::SharedRwLock;
+    use style::stylesheets::UrlExtraData;
+    use test_log::test;
+
+    #[test]
+    fn traces_decisions() -> crate::result::Result<()> {
+        let lock = SharedRwLock::new();
+        let stylesheet = crate::parse::parse_stylesheet(
+            "section p {} div > p {} .x {}",
+            UrlExtraData::from(url::Url::parse("about:blank").unwrap()),
+            &lock,
+        ).unwrap();
+        let document = scraper::Html::parse_document("<div><p class='x'></p></div>");
+        let website = ParsedWebsite::new("test".to_owned(), document, vec![stylesheet], lock);
+        let (_, matches, _) = do_website(&website, Algorithm::Naive, None);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.jsonl");
+        let filter = TraceFilter { element: Some("/html[1]/body[1]/div[1]/p[1]".to_owned()), discrepancies_only: false };
+        let mut writer = TraceWriter::create(&path, filter)?;
+        writer.trace_website(&website, &matches)?;
+        assert_eq!(writer.finish()?, 3);
+        let events: Vec<serde_json::Value> = std::io::BufReader::new(std::fs::File::open(&path).unwrap())
+            .lines()
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect();
+        let event = |selector: &str| events.iter().find(|event| event["selector"] == selector).unwrap();
+        assert_eq!(event("div > p")["matched"], true);
+        assert_eq!(event("section p")["matched"], false);
+        assert_eq!(event("section p")["rejected_by"], "section");
+        assert_eq!(event("section p")["bloom_rejects"], true);
+        assert_eq!(event(".x")["naive_matched"], true);
+
+        let mut writer = TraceWriter::create(&path, TraceFilter { element: None, discrepancies_only: true })?;
+        writer.trace_website(&website, &matches)?;
+        assert_eq!(writer.finish()?, 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,16 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The user-agent stylesheet, for `ParsedWebsite::with_ua_stylesheet`.
+//!
+//! Websites are written against a browser's default styles, so a lot of real
+//! matching work (every `div` is `display: block`) happens at the user-agent
+//! origin before any author rule is looked at. These rules go into the
+//! Stylist at `CascadeLevel::UANormal`, and their matches are reported next to
+//! the author ones rather than mixed in.
+
+/// `resources/html.css`
+pub const HTML_CSS: &str = include_str!("../resources/html.css");
@@ -0,0 +1,237 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Comparing matches with a real browser's `document.querySelectorAll`,
+//! through a WebDriver server such as geckodriver or chromedriver.
+//!
+//! Elements are compared by XPath (`ElementPath::to_xpath`), so a divergence
+//! can also mean the browser's DOM differs from ours, e.g. because a script
+//! on the page changed it after loading.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use cssparser::ToCss as _;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::parse::{self, HtmlFile, ParseOptions};
+use crate::result::{Error, ErrorKind, IntoResultExt as _, Result};
+use crate::structs::set::SetDocumentMatches;
+use crate::{Algorithm, do_website};
+
+/// Returns the XPath of every element each selector matches, or `null` for a
+/// selector the browser can't parse.
+const QUERY_SCRIPT: &str = r#"
+const xpath = (element) => {
+    let path = "";
+    for (let e = element; e; e = e.parentElement) {
+        let index = 1;
+        for (let s = e.previousElementSibling; s; s = s.previousElementSibling) {
+            if (s.localName === e.localName) {
+                index++;
+            }
+        }
+        path = `/${e.localName}[${index}]` + path;
+    }
+    return path;
+};
+return arguments[0].map((selector) => {
+    try {
+        return Array.from(document.querySelectorAll(selector), xpath);
+    } catch (e) {
+        return null;
+    }
+});
+"#;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct BrowserReport {
+    /// Selectors the browser failed to parse
+    pub unsupported_by_browser: Vec<String>,
+    pub divergences: Vec<SelectorDivergence>,
+}
+
+impl BrowserReport {
+    /// Returns true if the browser agreed on every selector it could parse.
+    pub fn is_empty(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+/// A selector the browser and mach-6 matched against different elements.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SelectorDivergence {
+    pub selector: String,
+    /// XPaths of elements only the browser matched
+    pub only_in_browser: BTreeSet<String>,
+    /// XPaths of elements only mach-6 matched
+    pub only_in_mach6: BTreeSet<String>,
+}
+
+/// A session with a headless browser. The WebDriver client is async, so this
+/// owns a runtime to drive it from synchronous code.
+pub struct BrowserOracle {
+    runtime: tokio::runtime::Runtime,
+    client: fantoccini::Client,
+}
+
+impl BrowserOracle {
+    /// Starts a headless browser session on the WebDriver server at
+    /// `webdriver_url`, e.g. `http://localhost:4444`.
+    pub fn connect(webdriver_url: &str) -> Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .into_result(None)?;
+        let capabilities = json!({
+            "moz:firefoxOptions": { "args": ["-headless"] },
+            "goog:chromeOptions": { "args": ["--headless=new"] },
+        });
+        let serde_json::Value::Object(capabilities) = capabilities else {
+            unreachable!()
+        };
+        let client = runtime
+            .block_on(
+                fantoccini::ClientBuilder::native()
+                    .capabilities(capabilities)
+                    .connect(webdriver_url),
+            )
+            .map_err(|e| Error {
+                path: None,
+                error: ErrorKind::WebDriverSession(e),
+            })?;
+        Ok(Self { runtime, client })
+    }
+
+    /// Loads `html_file` and runs `querySelectorAll` for each selector. Returns
+    /// the XPaths of the matched elements, in the order of `selectors`, or
+    /// `None` for selectors the browser can't parse.
+    pub fn query_selector_all(&self, html_file: &HtmlFile, selectors: &[String]) -> Result<Vec<Option<BTreeSet<String>>>> {
+        let HtmlFile(path) = html_file;
+        let path_error = |message: String| Error {
+            path: Some(path.clone()),
+            error: ErrorKind::Other(message),
+        };
+        let absolute = path.canonicalize().into_result(Some(path.clone()))?;
+        let url = url::Url::from_file_path(&absolute)
+            .map_err(|()| path_error("cannot be turned into a file:// url".to_owned()))?;
+        let webdriver_error = |e: fantoccini::error::CmdError| Error {
+            path: Some(path.clone()),
+            error: ErrorKind::WebDriver(e),
+        };
+        let value = self.runtime.block_on(async {
+            self.client.goto(url.as_str()).await?;
+            self.client.execute(QUERY_SCRIPT, vec![json!(selectors)]).await
+        }).map_err(webdriver_error)?;
+        serde_json::from_value(value)
+            .map_err(|e| path_error(format!("unexpected querySelectorAll results: {e}")))
+    }
+
+    /// Compares `matches`, for the website whose main document is `html_file`,
+    /// against the browser for each of `selectors`.
+    pub fn compare(&self, html_file: &HtmlFile, selectors: &[String], matches: &SetDocumentMatches) -> Result<BrowserReport> {
+        let browser = self.query_selector_all(html_file, selectors)?;
+        Ok(report(selectors, browser, matches))
+    }
+
+    /// Parses and matches the website at `website_path` with `algorithm`, and
+    /// compares the result with the browser for every selector in its
+    /// stylesheets. Returns `None` if the path is not a website.
+    pub fn verify_website(
+        &self,
+        website_path: &Path,
+        algorithm: Algorithm,
+        options: &ParseOptions,
+    ) -> Result<Option<(String, BrowserReport)>> {
+        let Some(website) = parse::get_document_and_selectors_with_options(website_path, options)? else {
+            return Ok(None);
+        };
+        let Some(html_file) = parse::main_html_file(website_path, options)? else {
+            return Ok(None);
+        };
+        let selectors: BTreeSet<String> = website
+            .get_matcher()
+            .get_selectors()
+            .iter()
+            .map(|selector| selector.to_css_string())
+            .collect();
+        let selectors: Vec<String> = selectors.into_iter().collect();
+        let (name, matches, _stats) = do_website(&website, algorithm, None);
+        let report = self.compare(&html_file, &selectors, &matches)?;
+        Ok(Some((name, report)))
+    }
+
+    /// Ends the browser session.
+    pub fn close(self) -> Result<()> {
+        self.runtime.block_on(self.client.close()).map_err(|e| Error {
+            path: None,
+            error: ErrorKind::WebDriver(e),
+        })
+    }
+}
+
+/// Compares the browser's results for `selectors` (as returned by
+/// `query_selector_all`) with `matches`.
+fn report(selectors: &[String], browser: Vec<Option<BTreeSet<String>>>, matches: &SetDocumentMatches) -> BrowserReport {
+    let mut mach6: BTreeMap<&str, BTreeSet<String>> = BTreeMap::new();
+    for (&id, element_matches) in &matches.0 {
+        for selector in matches.find_selectors(id) {
+            mach6.entry(&**selector).or_default().insert(element_matches.element.path.to_xpath());
+        }
+    }
+    let mut report = BrowserReport::default();
+    for (selector, browser) in selectors.iter().zip(browser) {
+        let Some(browser) = browser else {
+            report.unsupported_by_browser.push(selector.clone());
+            continue;
+        };
+        let mach6 = mach6.remove(selector.as_str()).unwrap_or_default();
+        if browser != mach6 {
+            report.divergences.push(SelectorDivergence {
+                selector: selector.clone(),
+                only_in_browser: browser.difference(&mach6).cloned().collect(),
+                only_in_mach6: mach6.difference(&browser).cloned().collect(),
+            });
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::{BrowserReport, SelectorDivergence, report};
+    use crate::test_matches as matches;
+    use test_log::test;
+
+    fn xpaths(xpaths: &[&str]) -> Option<BTreeSet<String>> {
+        Some(xpaths.iter().map(|xpath| xpath.to_string()).collect())
+    }
+
+    #[test]
+    fn reports_divergent_and_unsupported_selectors() {
+        let matches = matches("<p class=\"x\">a</p><p>b</p>", &["p", ".x"]);
+        let selectors = ["p", ".x", "p:unsupported"].map(str::to_owned);
+        let browser = vec![
+            xpaths(&["/html[1]/body[1]/p[1]", "/html[1]/body[1]/p[2]"]),
+            xpaths(&["/html[1]/body[1]/p[2]"]),
+            None,
+        ];
+        assert_eq!(
+            report(&selectors, browser, &matches),
+            BrowserReport {
+                unsupported_by_browser: vec!["p:unsupported".to_owned()],
+                divergences: vec![SelectorDivergence {
+                    selector: ".x".to_owned(),
+                    only_in_browser: xpaths(&["/html[1]/body[1]/p[2]"]).unwrap(),
+                    only_in_mach6: xpaths(&["/html[1]/body[1]/p[1]"]).unwrap(),
+                }],
+            },
+        );
+    }
+}
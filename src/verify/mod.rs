@@ -0,0 +1,149 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Checking mach-6's results against independent sources of truth.
+
+#[cfg(feature = "browser_oracle")]
+pub mod browser;
+pub mod wpt;
+
+use serde::Serialize;
+
+use crate::parse::ParsedWebsite;
+use crate::structs::diff::DocumentMatchesDiff;
+use crate::structs::ser::SerDocumentMatches;
+use crate::{Algorithm, do_website};
+
+/// Two algorithms matching a website differently.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Divergence {
+    pub website: String,
+    pub left: Algorithm,
+    pub right: Algorithm,
+    /// `left`'s results compared with `right`'s
+    pub diff: DocumentMatchesDiff,
+}
+
+/// Runs each of `algorithms` on each website and compares every pair of
+/// results. A pair diverges if the algorithms matched different selectors for
+/// any element; sharing styles with different elements is not a divergence.
+pub fn verify_algorithms<'a>(
+    algorithms: &[Algorithm],
+    websites: impl IntoIterator<Item = &'a ParsedWebsite>,
+) -> Vec<Divergence> {
+    let mut divergences = Vec::new();
+    for website in websites {
+        let results: Vec<_> = algorithms
+            .iter()
+            .map(|&algorithm| (algorithm, do_website(website, algorithm, None).1))
+            .collect();
+        for (i, (left, left_matches)) in results.iter().enumerate() {
+            for (right, right_matches) in &results[i + 1..] {
+                let diff = left_matches.diff(right_matches);
+                if !diff.selectors_match() {
+                    divergences.push(Divergence {
+                        website: website.name.clone(),
+                        left: *left,
+                        right: *right,
+                        diff,
+                    });
+                }
+            }
+        }
+    }
+    divergences
+}
+
+/// An algorithm matching a website differently in two runs.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Nondeterminism {
+    pub website: String,
+    pub algorithm: Algorithm,
+    /// The first line of the serialized matches where the runs differ,
+    /// 1-based
+    pub line: usize,
+    /// That line in the first run's output; empty past the end
+    pub first: String,
+    /// That line in the second run's output; empty past the end
+    pub second: String,
+}
+
+/// Runs `algorithm` on each website twice, in this process, and compares the
+/// results as `--emit matches` would print them. The second run starts with
+/// the atoms and caches the first left behind, so hash iteration order,
+/// attribute order and interning can all show up as differences.
+pub fn verify_determinism<'a>(
+    algorithm: Algorithm,
+    websites: impl IntoIterator<Item = &'a ParsedWebsite>,
+) -> Vec<Nondeterminism> {
+    let serialize = |website: &ParsedWebsite| {
+        let (_, matches, _) = do_website(website, algorithm, None);
+        serde_yml::to_string(&SerDocumentMatches::from(&matches)).expect("matches serialize")
+    };
+    let mut nondeterminism = Vec::new();
+    for website in websites {
+        let (first, second) = (serialize(website), serialize(website));
+        if first == second {
+            continue;
+        }
+        let (mut first_lines, mut second_lines) = (first.lines(), second.lines());
+        for line in 1.. {
+            let (a, b) = (first_lines.next(), second_lines.next());
+            if a != b {
+                nondeterminism.push(Nondeterminism {
+                    website: website.name.clone(),
+                    algorithm,
+                    line,
+                    first: a.unwrap_or_default().to_owned(),
+                    second: b.unwrap_or_default().to_owned(),
+                });
+                break;
+            }
+            if a.is_none() {
+                break;
+            }
+        }
+    }
+    nondeterminism
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Divergence, verify_algorithms, verify_determinism};
+    use crate::Algorithm;
+    use crate::parse::{get_document_and_selectors, websites_path};
+    use crate::result::Result;
+    use test_log::test;
+
+    #[test]
+    fn all_algorithms_agree() -> Result<()> {
+        let websites = ["ten_divs_style_sharing", "is_conversion_test"]
+            .into_iter()
+            .map(|name| Ok(get_document_and_selectors(&websites_path().join(name))?.unwrap()))
+            .collect::<Result<Vec<_>>>()?;
+        let algorithms = [
+            Algorithm::Naive,
+            Algorithm::TagIndex,
+            Algorithm::SelectorTrie,
+            Algorithm::Bytecode,
+            Algorithm::WithStyleSharing,
+            Algorithm::WithIsConversion,
+            Algorithm::WithDistribution,
+            Algorithm::Mach7,
+        ];
+        assert_eq!(verify_algorithms(&algorithms, &websites), Vec::<Divergence>::new());
+        Ok(())
+    }
+
+    #[test]
+    fn algorithms_are_deterministic() -> Result<()> {
+        let website = get_document_and_selectors(&websites_path().join("ten_divs_style_sharing"))?.unwrap();
+        for algorithm in [Algorithm::Naive, Algorithm::WithStyleSharing, Algorithm::Mach7] {
+            assert_eq!(verify_determinism(algorithm, [&website]), []);
+        }
+        Ok(())
+    }
+}
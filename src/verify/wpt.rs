@@ -0,0 +1,352 @@
+/* Copyright 2026 Andrew Riachi
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Running Web Platform Tests `css/selectors/` cases through naive matching.
+//!
+//! Only testharness.js assertions of the form
+//! `assert_true(document.getElementById("id").matches("selector"))` (or
+//! `assert_false`) are understood; they are run against the test file's own
+//! markup as parsed, without running its scripts. Reftests and anything else
+//! are skipped, so a feature missing from the report was not tested rather
+//! than passed.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use cssparser::{ParseError, Parser, ParserInput, ToCss as _, Token};
+use scraper::{ElementRef, Html};
+use selectors::parser::Component;
+use selectors::visitor::SelectorVisitor;
+use serde::Serialize;
+use style::selector_parser::{SelectorImpl, SelectorParser};
+use style::stylesheets::UrlExtraData;
+
+use crate::match_selectors;
+use crate::result::{IntoResultExt as _, Result};
+use crate::structs::borrowed::SelectorsOrSharedStyles;
+
+/// One `matches()` assertion from a test file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct WptCase {
+    pub element_id: String,
+    pub selector: String,
+    pub expected: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Pass,
+    Fail,
+    /// The selector failed to parse
+    Unsupported,
+    /// No element has the case's id
+    MissingElement,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CaseResult {
+    pub file: PathBuf,
+    #[serde(flatten)]
+    pub case: WptCase,
+    pub outcome: Outcome,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct FeatureSummary {
+    pub pass: usize,
+    pub fail: usize,
+    pub unsupported: usize,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct WptReport {
+    /// Cases by the pseudo-classes and pseudo-elements their selector uses,
+    /// or `basic` if it uses none. A case counts toward each one it uses.
+    pub features: BTreeMap<String, FeatureSummary>,
+    /// Cases that did not pass
+    pub failures: Vec<CaseResult>,
+    /// Files with no cases that could be extracted
+    pub skipped_files: Vec<PathBuf>,
+}
+
+/// Runs every `.html` and `.xhtml` test file under `dir` (usually a WPT
+/// checkout's `css/selectors`), recursively.
+pub fn run_directory(dir: &Path) -> Result<WptReport> {
+    let mut files = Vec::new();
+    collect_test_files(dir, &mut files)?;
+    files.sort();
+    let mut report = WptReport::default();
+    for file in files {
+        let contents = fs::read_to_string(&file).into_result(Some(file.clone()))?;
+        let cases = extract_cases(&contents);
+        if cases.is_empty() {
+            report.skipped_files.push(file);
+            continue;
+        }
+        let document = Html::parse_document(&contents);
+        for case in cases {
+            let outcome = run_case(&document, &case);
+            for feature in features(&case.selector) {
+                let summary = report.features.entry(feature).or_default();
+                match outcome {
+                    Outcome::Pass => summary.pass += 1,
+                    Outcome::Fail | Outcome::MissingElement => summary.fail += 1,
+                    Outcome::Unsupported => summary.unsupported += 1,
+                }
+            }
+            if outcome != Outcome::Pass {
+                report.failures.push(CaseResult { file: file.clone(), case, outcome });
+            }
+        }
+    }
+    Ok(report)
+}
+
+fn collect_test_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir).into_result(Some(dir.to_path_buf()))? {
+        let path = entry.into_result(Some(dir.to_path_buf()))?.path();
+        if path.is_dir() {
+            // Not tests: support files and reftest references
+            if !matches!(path.file_name().and_then(|n| n.to_str()), Some("support" | "reference" | "resources")) {
+                collect_test_files(&path, files)?;
+            }
+        } else if matches!(path.extension().and_then(|e| e.to_str()), Some("html" | "xhtml"))
+            && !path.file_stem().and_then(|s| s.to_str()).is_some_and(|s| s.ends_with("-ref"))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+pub fn run_case(document: &Html, case: &WptCase) -> Outcome {
+    let url_data = UrlExtraData::from(url::Url::parse("about:blank").unwrap());
+    let Ok(selectors) = SelectorParser::parse_author_origin_no_namespace(&case.selector, &url_data) else {
+        return Outcome::Unsupported;
+    };
+    let Some(target) = document
+        .tree
+        .nodes()
+        .filter_map(ElementRef::wrap)
+        .find(|element| element.value().id() == Some(case.element_id.as_str()))
+    else {
+        return Outcome::MissingElement;
+    };
+    // `matches()` takes a selector list, and matches if any selector does
+    let matched = match_selectors(document, selectors.slice())
+        .0
+        .iter()
+        .find(|element_matches| element_matches.element == target)
+        .is_some_and(|element_matches| match &element_matches.selectors {
+            SelectorsOrSharedStyles::Selectors(selectors) => !selectors.is_empty(),
+            SelectorsOrSharedStyles::SharedWithElement(_) => unreachable!("naive matching does not share styles"),
+        });
+    if matched == case.expected { Outcome::Pass } else { Outcome::Fail }
+}
+
+/// The pseudo-classes and pseudo-elements in `selector`, e.g. `:nth-child`
+/// and `::before`, or just `basic`.
+fn features(selector: &str) -> Vec<String> {
+    let url_data = UrlExtraData::from(url::Url::parse("about:blank").unwrap());
+    let mut features = Features::default();
+    match SelectorParser::parse_author_origin_no_namespace(selector, &url_data) {
+        Ok(list) => {
+            for selector in list.slice() {
+                selector.visit(&mut features);
+            }
+        }
+        // A selector stylo can't parse is still grouped by the pseudo-classes
+        // it was testing
+        Err(_) => {
+            let mut input = ParserInput::new(selector);
+            features.add_unparsed(&mut Parser::new(&mut input));
+        }
+    }
+    if features.0.is_empty() {
+        return vec!["basic".to_owned()];
+    }
+    features.0.into_iter().collect()
+}
+
+/// Names of pseudo-classes and pseudo-elements. `SelectorVisitor`'s
+/// `visit_selector_list` walks the lists of `:is()`, `:not()` and the like
+/// too.
+#[derive(Default)]
+struct Features(BTreeSet<String>);
+
+impl SelectorVisitor for Features {
+    type Impl = SelectorImpl;
+
+    fn visit_simple_selector(&mut self, component: &Component<Self::Impl>) -> bool {
+        if matches!(
+            component,
+            Component::NonTSPseudoClass(_)
+                | Component::PseudoElement(_)
+                | Component::Nth(_)
+                | Component::NthOf(_)
+                | Component::Is(_)
+                | Component::Where(_)
+                | Component::Negation(_)
+                | Component::Has(_)
+                | Component::Root
+                | Component::Empty
+                | Component::Scope
+                | Component::Host(_)
+                | Component::Slotted(_)
+                | Component::Part(_)
+        ) {
+            // The component alone, without arguments like `2n` or a selector
+            // list
+            let css = component.to_css_string();
+            self.0.insert(css.split('(').next().unwrap_or_default().to_owned());
+        }
+        true
+    }
+}
+
+impl Features {
+    /// Adds the names of the pseudo-classes and pseudo-elements in the
+    /// selector `parser` is at, which stylo couldn't parse, from its tokens
+    fn add_unparsed(&mut self, parser: &mut Parser) {
+        let mut colons = 0;
+        loop {
+            match parser.next_including_whitespace().cloned() {
+                Ok(Token::Colon) => {
+                    colons += 1;
+                    continue;
+                }
+                Ok(Token::Ident(name)) if colons > 0 => {
+                    self.0.insert(format!("{}{}", ":".repeat(colons), name.to_ascii_lowercase()));
+                }
+                Ok(Token::Function(name)) => {
+                    if colons > 0 {
+                        self.0.insert(format!("{}{}", ":".repeat(colons), name.to_ascii_lowercase()));
+                    }
+                    let _ = parser.parse_nested_block(|block| {
+                        self.add_unparsed(block);
+                        Ok::<_, ParseError<()>>(())
+                    });
+                }
+                Ok(_) => (),
+                Err(_) => return,
+            }
+            colons = 0;
+        }
+    }
+}
+
+/// Finds `assert_true(document.getElementById(...).matches(...))` and
+/// `assert_false(...)` calls, in source order.
+pub fn extract_cases(source: &str) -> Vec<WptCase> {
+    let mut cases = Vec::new();
+    let mut rest = source;
+    while let Some(start) = rest.find("assert_") {
+        rest = &rest[start + "assert_".len()..];
+        let (expected, arguments) = if let Some(arguments) = rest.strip_prefix("true(") {
+            (true, arguments)
+        } else if let Some(arguments) = rest.strip_prefix("false(") {
+            (false, arguments)
+        } else {
+            continue;
+        };
+        if let Some(case) = parse_matches_call(arguments, expected) {
+            cases.push(case);
+        }
+    }
+    cases
+}
+
+fn parse_matches_call(s: &str, expected: bool) -> Option<WptCase> {
+    let s = s.trim_start().strip_prefix("document.getElementById(")?;
+    let (element_id, s) = parse_js_string(s.trim_start())?;
+    let s = s.trim_start().strip_prefix(')')?.trim_start().strip_prefix(".matches(")?;
+    let (selector, s) = parse_js_string(s.trim_start())?;
+    s.trim_start().strip_prefix(')')?;
+    Some(WptCase { element_id, selector, expected })
+}
+
+/// Parses a single- or double-quoted JS string literal at the start of `s`,
+/// returning its value and what follows it.
+fn parse_js_string(s: &str) -> Option<(String, &str)> {
+    let mut chars = s.char_indices();
+    let (_, quote) = chars.next().filter(|&(_, c)| c == '"' || c == '\'')?;
+    let mut value = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                let (_, escaped) = chars.next()?;
+                value.push(match escaped {
+                    'n' => '\n',
+                    't' => '\t',
+                    other => other,
+                });
+            }
+            c if c == quote => return Some((value, &s[i + c.len_utf8()..])),
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Outcome, WptCase, extract_cases, features, run_case};
+    use scraper::Html;
+    use test_log::test;
+
+    const TEST_FILE: &str = r#"<!DOCTYPE html>
+<div id="parent"><p id="child" class="a">x</p><p id="second"></p></div>
+<script>
+test(() => {
+  assert_true(document.getElementById("child").matches(".a:first-child"));
+  assert_false(document.getElementById('second').matches('p:first-child'));
+  assert_true(document.getElementById("second").matches("div > \"p\""));
+  assert_true(document.getElementById("parent").matches(":has(> .a)"));
+  assert_true(someOtherElement.matches("p"));
+});
+</script>
+"#;
+
+    #[test]
+    fn extracts_matches_assertions() {
+        let cases = extract_cases(TEST_FILE);
+        let case = |element_id: &str, selector: &str, expected| WptCase {
+            element_id: element_id.to_owned(),
+            selector: selector.to_owned(),
+            expected,
+        };
+        assert_eq!(cases, [
+            case("child", ".a:first-child", true),
+            case("second", "p:first-child", false),
+            case("second", "div > \"p\"", true),
+            case("parent", ":has(> .a)", true),
+        ]);
+    }
+
+    #[test]
+    fn runs_cases() {
+        let document = Html::parse_document(TEST_FILE);
+        let outcomes: Vec<_> = extract_cases(TEST_FILE).iter().map(|case| run_case(&document, case)).collect();
+        assert_eq!(outcomes[0], Outcome::Pass);
+        assert_eq!(outcomes[1], Outcome::Pass);
+        assert_eq!(outcomes[2], Outcome::Unsupported);
+        let missing = WptCase { element_id: "nope".to_owned(), selector: "p".to_owned(), expected: true };
+        assert_eq!(run_case(&document, &missing), Outcome::MissingElement);
+    }
+
+    #[test]
+    fn groups_by_pseudo_classes() {
+        assert_eq!(features("p.a"), ["basic"]);
+        assert_eq!(features("a:HOVER::before"), ["::before", ":hover"]);
+        assert_eq!(features("[data-x=':not']:nth-child(2n)"), [":nth-child"]);
+        assert_eq!(features("#a\\:hover"), ["basic"]);
+        assert_eq!(features(":not(:is(.a, :first-child))"), [":first-child", ":is", ":not"]);
+        // Unparsable, so grouped from its tokens
+        assert_eq!(features(":hover:unknown(\"a:b\")"), [":hover", ":unknown"]);
+    }
+}
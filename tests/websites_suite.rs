@@ -6,7 +6,7 @@
  */
 use std::{fmt::Write as _, path::{Path, PathBuf}, sync::atomic::{AtomicBool, Ordering}};
 use html5ever::{LocalName, QualName, ns};
-use mach_6::{Algorithm, match_selectors, parse::{ParsedWebsite, get_document_and_selectors, get_websites_dirs, websites_path}, result::{Error, IntoResultExt, Result}, structs::{borrowed::DocumentMatches, element_id, owned::OwnedDocumentMatches, ser::{DebugSerDocumentMatches, SerDocumentMatches}, set::SetDocumentMatches}};
+use mach_6::{Algorithm, match_selectors, parse::{ParsedWebsite, get_document_and_selectors, get_websites_dirs, websites_path}, result::{Error, IntoResultExt, Result}, structs::{ElementPath, borrowed::DocumentMatches, owned::OwnedDocumentMatches, ser::{DebugSerDocumentMatches, SerDocumentMatches}, set::SetDocumentMatches}};
 use insta;
 use rayon::prelude::*;
 use scraper::{ElementRef, Html, Node};
@@ -33,19 +33,19 @@ fn website_paths_for_tests() -> Result<Vec<Result<PathBuf>>> {
 
 fn annotated_html(document: &Html) -> String {
     let mut debug_document = Html::parse_document(&document.html());
-    let attr_name = QualName::new(None, ns!(), LocalName::from("data-mach6-id"));
-    let element_ids: Vec<_> = debug_document
+    let attr_name = QualName::new(None, ns!(), LocalName::from("data-mach6-path"));
+    let element_paths: Vec<_> = debug_document
         .tree
         .nodes()
         .filter_map(ElementRef::wrap)
-        .map(|element| (element.id(), element_id(element)))
+        .map(|element| (element.id(), ElementPath::from(element)))
         .collect();
-    for (node_id, id) in element_ids {
+    for (node_id, path) in element_paths {
         let mut node = debug_document.tree.get_mut(node_id).expect("node should still exist");
         let Node::Element(element) = node.value() else {
             continue;
         };
-        element.attrs.push((attr_name.clone(), Atom::from(id.to_string())));
+        element.attrs.push((attr_name.clone(), Atom::from(path.to_string())));
     }
     debug_document.html()
 }
@@ -54,6 +54,7 @@ fn compare_with_naive(
     website_name: &str,
     input: &ParsedWebsite,
     naive_result: &DocumentMatches,
+    set_naive_result: &SetDocumentMatches,
     ser_naive_result: &SerDocumentMatches,
     debug_naive_result: &DebugSerDocumentMatches,
     algorithm: Algorithm,
@@ -67,6 +68,18 @@ fn compare_with_naive(
         let annotated_html_path = website_folder.join(format!("{website_name}.debug.html"));
         std::fs::write(&annotated_html_path, annotated_html(input.document()))
             .into_result(Some(annotated_html_path))?;
+        // Lead with a summary of what differs, so nobody has to diff the full dumps by eye.
+        let diff = set_naive_result.diff(&SetDocumentMatches::from(OwnedDocumentMatches::from(&result)));
+        let diff_path = website_folder.join(format!("{website_name}.{}-vs-{algorithm}.diff.yaml", Algorithm::Naive));
+        let f_diff = std::fs::File::create(&diff_path).into_result(Some(diff_path.clone()))?;
+        serde_yml::to_writer(f_diff, &diff).unwrap();
+        log::error!(
+            "{website_name}: {algorithm} differs from naive on {} element(s) ({} only in naive, {} only in {algorithm}). See {}",
+            diff.selectors_differ.len(),
+            diff.only_in_left.len(),
+            diff.only_in_right.len(),
+            diff_path.display(),
+        );
         for (algorithm, ser_result, debug_result) in [(Algorithm::Naive, ser_naive_result, debug_naive_result), (algorithm, &ser_result, &DebugSerDocumentMatches::from(&result))] {
             let yaml_path = website_folder.join(format!("{website_name}.{algorithm}.yaml"));
             let debug_yaml_path = website_folder.join(format!("{website_name}.{algorithm}.debug.yaml"));
@@ -127,6 +140,7 @@ fn all_algorithms_correct() -> Result<()> {
                     &website.name,
                     &website,
                     &naive_result,
+                    &set_naive_result,
                     &ser_naive_result,
                     &debug_naive_result,
                     *algorithm,
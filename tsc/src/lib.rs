@@ -66,9 +66,16 @@
 //! [so_time_stamp]: https://stackoverflow.com/a/42490374/1422197
 #![cfg_attr(not(any(test, feature = "std")), no_std)]
 
-#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+// On wasm32-unknown-unknown there is no TSC: every reading is 0, so
+// durations are 0 cycles, and `has_invariant_tsc` is false.
+#[cfg(not(any(
+    target_arch = "x86",
+    target_arch = "x86_64",
+    all(target_arch = "wasm32", target_os = "unknown")
+)))]
 compile_error!(
-    "The TSC crate only supports the \"x86\" and \"x86_64\" architectures"
+    "The TSC crate only supports the \"x86\" and \"x86_64\" architectures, \
+     and wasm32-unknown-unknown without a TSC"
 );
 
 // extern crate test; // NIGHTLY
@@ -87,6 +94,16 @@ use std::convert::TryFrom as _;
 ///
 /// Without an invariant TSC, the timings reported by this library might be
 /// unreliable.
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub fn has_invariant_tsc() -> bool {
+    false
+}
+
+/// Returns true if the CPU has an invariant TSC.
+///
+/// Without an invariant TSC, the timings reported by this library might be
+/// unreliable.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
 pub fn has_invariant_tsc() -> bool {
     use self::arch::{/*has_cpuid, */ CpuidResult, __cpuid};
 
@@ -155,6 +172,7 @@ pub struct Start(u64);
 
 impl Start {
     /// Start measurement
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     pub fn now() -> Self {
         unsafe {
             // let _ = arch::__cpuid(0); // too slow
@@ -162,6 +180,12 @@ impl Start {
         }
     }
 
+    /// Start measurement
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    pub fn now() -> Self {
+        Start(0)
+    }
+
     /// Convenience method to get cycles since a `Start`.
     pub fn elapsed(&self) -> Duration {
         Stop::now() - *self
@@ -174,6 +198,7 @@ pub struct Stop(u64);
 
 impl Stop {
     /// Stop measurement
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     pub fn now() -> Self {
         unsafe {
             let mut core: u32 = 0;
@@ -182,6 +207,12 @@ impl Stop {
             Stop(r)
         }
     }
+
+    /// Stop measurement
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    pub fn now() -> Self {
+        Stop(0)
+    }
 }
 
 /// Duration between two time instants